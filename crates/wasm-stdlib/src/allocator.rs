@@ -0,0 +1,115 @@
+// A simple allocator for the guest, which carves power-of-two sized blocks out of
+// linear memory, growing the memory (using `memory.grow`) whenever it runs out.
+// Freed blocks are kept in a free list per size, and re-used by later allocations
+// of the same size class. Wasm is single-threaded, so no locking is needed.
+
+use core::alloc::GlobalAlloc;
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+
+/// The size of a Wasm memory page.
+const PAGE_SIZE: usize = 64 * 1024;
+
+/// The number of size classes, where blocks of class `n` are `2^n` bytes long.
+const SIZE_CLASSES: usize = usize::BITS as usize;
+
+/// The smallest size class, whose blocks can hold the free list's next pointer.
+const MIN_SIZE_CLASS: u32 = 3;
+
+pub struct SimpleAllocator {
+    /// The address of the next free byte of the memory claimed from `memory.grow`.
+    head: UnsafeCell<usize>,
+    /// The end of the memory claimed from `memory.grow`.
+    end: UnsafeCell<usize>,
+    /// The first free block of each size class, each of which starts with a pointer
+    /// to the next one (or null).
+    free_lists: UnsafeCell<[*mut u8; SIZE_CLASSES]>,
+}
+
+impl SimpleAllocator {
+    pub const fn new() -> Self {
+        SimpleAllocator {
+            head: UnsafeCell::new(0),
+            end: UnsafeCell::new(0),
+            free_lists: UnsafeCell::new([core::ptr::null_mut(); SIZE_CLASSES]),
+        }
+    }
+
+    /// Grows the memory by enough pages to fit a block of the given size, returning
+    /// `false` if the memory can't be grown.
+    unsafe fn grow(&self, block_size: usize) -> bool {
+        // The block may need to be aligned past the start of the new pages, unless
+        // they continue the memory claimed so far.
+        let pages = (block_size * 2).div_ceil(PAGE_SIZE);
+        let previous_pages = core::arch::wasm32::memory_grow::<0>(pages);
+        if previous_pages == usize::MAX {
+            return false;
+        }
+
+        // The host (or another module) may have grown the memory in the meantime, in
+        // which case the rest of the claimed memory is abandoned.
+        let start = previous_pages * PAGE_SIZE;
+        if start != *self.end.get() {
+            *self.head.get() = start;
+        }
+        *self.end.get() = start + pages * PAGE_SIZE;
+        true
+    }
+}
+
+unsafe impl Sync for SimpleAllocator {}
+
+unsafe impl GlobalAlloc for SimpleAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = match size_class(layout) {
+            Some(class) => class,
+            None => return core::ptr::null_mut(),
+        };
+
+        // Re-use a freed block of the same size class if there is one.
+        let free_list = &mut (*self.free_lists.get())[class];
+        if !free_list.is_null() {
+            let block = *free_list;
+            *free_list = *(block as *mut *mut u8);
+            return block;
+        }
+
+        // Otherwise, carve a new block out of the memory. Blocks are aligned to their
+        // size, which is at least the requested alignment.
+        let block_size = 1 << class;
+        let mut start = (*self.head.get()).next_multiple_of(block_size);
+        if start + block_size > *self.end.get() {
+            // If the memory can't be grown, we return a null pointer, which
+            // signals a failed allocation.
+            if !self.grow(block_size) {
+                return core::ptr::null_mut();
+            }
+            start = (*self.head.get()).next_multiple_of(block_size);
+        }
+        // Bump the head to the next free byte
+        *self.head.get() = start + block_size;
+        start as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = match size_class(layout) {
+            Some(class) => class,
+            None => return,
+        };
+
+        let free_list = &mut (*self.free_lists.get())[class];
+        *(ptr as *mut *mut u8) = *free_list;
+        *free_list = ptr;
+    }
+}
+
+/// Gets the size class of the blocks which can hold the given layout, or `None` if
+/// it's too large to allocate.
+#[inline]
+fn size_class(layout: Layout) -> Option<usize> {
+    let size = layout
+        .size()
+        .max(layout.align())
+        .checked_next_power_of_two()?;
+    Some(size.trailing_zeros().max(MIN_SIZE_CLASS) as usize)
+}
@@ -3,108 +3,794 @@
 
 use core::alloc::GlobalAlloc;
 use core::alloc::Layout;
-use core::cell::UnsafeCell;
 
-#[panic_handler]
-fn panic(_panic: &core::panic::PanicInfo<'_>) -> ! {
-    core::arch::wasm32::unreachable()
-}
+mod allocator;
+mod panic;
+mod serialization;
+mod sha256;
+mod wide;
+
+use allocator::SimpleAllocator;
+use serialization::{memory_slice, read_int, read_uint};
+use wide::{U256, U512};
 
-#[alloc_error_handler]
-fn alloc_error(_: core::alloc::Layout) -> ! {
-    core::arch::wasm32::unreachable()
+/// Exports a function under its Clarity-style (kebab-case) name, e.g. `add-int128`,
+/// along with an alias under its snake_case Rust name, e.g. `add_int128`, for
+/// toolchains which can't import names containing dashes. Functions whose names
+/// don't contain dashes are exported using `#[export_name]` directly.
+macro_rules! export {
+    (
+        $name:literal
+        pub extern "C" fn $func:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block
+    ) => {
+        #[export_name = $name]
+        pub extern "C" fn $func($($arg: $ty),*) $(-> $ret)? $body
+
+        // The alias lives in a module of the same name, as a function can't be
+        // defined twice in the same scope.
+        mod $func {
+            #[no_mangle]
+            pub extern "C" fn $func($($arg: $ty),*) $(-> $ret)? {
+                super::$func($($arg),*)
+            }
+        }
+    };
 }
 
 #[global_allocator]
 static ALLOCATOR: SimpleAllocator = SimpleAllocator::new();
 
+// Guest-side memory management, e.g. for generated modules building serialized
+// values in linear memory.
+
 #[no_mangle]
-#[export_name = "add-int128"]
-pub extern "C" fn add_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
-    let a = ((a_lo as u64) as u128) | ((a_hi as u64) as u128) << 64;
-    let b = ((b_lo as u64) as u128) | ((b_hi as u64) as u128) << 64;
+#[export_name = "alloc"]
+pub extern "C" fn alloc_memory(size: i32) -> i32 {
+    // Returns `0` (a null pointer) if the allocation failed.
+    match Layout::from_size_align(size as usize, 1) {
+        Ok(layout) => unsafe { ALLOCATOR.alloc(layout) as usize as i32 },
+        _ => 0,
+    }
+}
 
-    match a.checked_add(b) {
-        Some(result) => {
-            let result: i128 = result
-                .try_into()
-                .unwrap_or_else(|_| core::arch::wasm32::unreachable());
-            (
-                (result & 0xFFFFFFFFFFFFFFFF) as i64,
-                ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
-            )
+#[no_mangle]
+#[export_name = "free"]
+pub extern "C" fn free_memory(offset: i32, size: i32) {
+    // The size must be the one the region was allocated with.
+    if offset == 0 {
+        return;
+    }
+    if let Ok(layout) = Layout::from_size_align(size as usize, 1) {
+        unsafe { ALLOCATOR.dealloc(offset as usize as *mut u8, layout) }
+    }
+}
+
+// Primitives for manipulating serialized buffers in linear memory (e.g. to concat or
+// slice them), shared by generated modules instead of each emitting its own byte
+// loops. They are exported under their own names, as the `memcpy`, `memcmp` and
+// `memset` symbols are provided by the compiler's builtins.
+
+export! {
+    "mem-copy"
+    pub extern "C" fn mem_copy(dst: i32, src: i32, len: i32) {
+        // The regions may overlap, e.g. when shifting a buffer's contents in place.
+        if len > 0 {
+            unsafe {
+                core::ptr::copy(
+                    src as usize as *const u8,
+                    dst as usize as *mut u8,
+                    len as usize,
+                )
+            }
         }
-        _ => core::arch::wasm32::unreachable(),
     }
 }
 
-#[no_mangle]
-#[export_name = "add-unt128"]
-pub extern "C" fn add_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
-    let a = ((a_lo as u64) as u128) | ((a_hi as u64) as u128) << 64;
-    let b = ((b_lo as u64) as u128) | ((b_hi as u64) as u128) << 64;
+export! {
+    "mem-compare"
+    pub extern "C" fn mem_compare(a: i32, b: i32, len: i32) -> i32 {
+        // Returns `-1`, `0` or `1` as the first region is less than, equal to or greater
+        // than the second, comparing bytes as unsigned.
+        if len <= 0 {
+            return 0;
+        }
+        let (a, b) = unsafe { (memory_slice(a, len), memory_slice(b, len)) };
+        a.cmp(b) as i32
+    }
+}
 
-    match a.checked_add(b) {
-        Some(result) => (
-            (result & 0xFFFFFFFFFFFFFFFF) as i64,
-            ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
-        ),
-        _ => core::arch::wasm32::unreachable(),
+export! {
+    "mem-set"
+    pub extern "C" fn mem_set(dst: i32, value: i32, len: i32) {
+        // Only the low byte of `value` is written.
+        if len > 0 {
+            unsafe { core::ptr::write_bytes(dst as usize as *mut u8, value as u8, len as usize) }
+        }
     }
 }
 
 #[no_mangle]
-#[export_name = "mul-uint128"]
-pub extern "C" fn mul_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
-    let a = ((a_lo as u64) as u128) | ((a_hi as u64) as u128) << 64;
-    let b = ((b_lo as u64) as u128) | ((b_hi as u64) as u128) << 64;
+#[export_name = "sha256"]
+pub extern "C" fn sha256(offset: i32, len: i32, out_offset: i32) {
+    // Writes the 32-byte digest of the buffer to `out_offset`, which may overlap
+    // with the buffer.
+    let digest = sha256::sha256(unsafe { memory_slice(offset, len.max(0)) });
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            digest.as_ptr(),
+            out_offset as usize as *mut u8,
+            sha256::DIGEST_SIZE,
+        )
+    }
+}
 
-    match a.checked_mul(b) {
-        Some(result) => (
-            (result & 0xFFFFFFFFFFFFFFFF) as i64,
-            ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
-        ),
-        _ => core::arch::wasm32::unreachable(),
+// Functions which can fail return an error code along with the low/high 64 bits of
+// their result, rather than trapping, so that the host can map failures to Clarity
+// runtime errors. The codes match those of the host's `RuntimeError`, and the result
+// is zero unless the code is `ERR_NONE`.
+
+const ERR_NONE: i32 = 0;
+const ERR_MALFORMED_OPERAND: i32 = 2;
+const ERR_OVERFLOW: i32 = 6;
+const ERR_UNDERFLOW: i32 = 11;
+const ERR_DIVISION_BY_ZERO: i32 = 12;
+const ERR_INVALID_ARGUMENT: i32 = 21;
+
+export! {
+    "add-int128"
+    pub extern "C" fn add_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        checked_add_int(a, b)
     }
 }
 
-const ARENA_SIZE: usize = 128 * 1024;
-#[repr(C, align(32))]
-struct SimpleAllocator {
-    arena: UnsafeCell<[u8; ARENA_SIZE]>,
-    head: UnsafeCell<usize>,
+export! {
+    "add-uint128"
+    pub extern "C" fn add_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        match a.checked_add(b) {
+            Some(result) => ok_128(result),
+            _ => err_128(ERR_OVERFLOW),
+        }
+    }
 }
 
-impl SimpleAllocator {
-    const fn new() -> Self {
-        SimpleAllocator {
-            arena: UnsafeCell::new([0; ARENA_SIZE]),
-            head: UnsafeCell::new(0),
+export! {
+    "mul-uint128"
+    pub extern "C" fn mul_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        checked_mul_uint(a, b)
+    }
+}
+
+export! {
+    "sub-int128"
+    pub extern "C" fn sub_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        match a.checked_sub(b) {
+            Some(result) => ok_128(result as u128),
+            _ if b < 0 => err_128(ERR_OVERFLOW),
+            _ => err_128(ERR_UNDERFLOW),
+        }
+    }
+}
+
+export! {
+    "sub-uint128"
+    pub extern "C" fn sub_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        match a.checked_sub(b) {
+            Some(result) => ok_128(result),
+            _ => err_128(ERR_UNDERFLOW),
+        }
+    }
+}
+
+export! {
+    "div-int128"
+    pub extern "C" fn div_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        // The quotient is truncated toward zero, as in Clarity. Besides division by
+        // zero, `i128::MIN / -1` overflows.
+        match a.checked_div(b) {
+            Some(result) => ok_128(result as u128),
+            _ if b == 0 => err_128(ERR_DIVISION_BY_ZERO),
+            _ => err_128(ERR_OVERFLOW),
         }
     }
 }
 
-unsafe impl Sync for SimpleAllocator {}
+export! {
+    "div-uint128"
+    pub extern "C" fn div_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        match a.checked_div(b) {
+            Some(result) => ok_128(result),
+            _ => err_128(ERR_DIVISION_BY_ZERO),
+        }
+    }
+}
+
+export! {
+    "mod-int128"
+    pub extern "C" fn mod_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        // Like Clarity's `mod`, the result has the sign of the dividend, and like the
+        // division, `i128::MIN % -1` fails rather than wrapping around to zero.
+        match a.checked_rem(b) {
+            Some(result) => ok_128(result as u128),
+            _ if b == 0 => err_128(ERR_DIVISION_BY_ZERO),
+            _ => err_128(ERR_OVERFLOW),
+        }
+    }
+}
 
-unsafe impl GlobalAlloc for SimpleAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let size = layout.size();
-        let align = layout.align();
+export! {
+    "mod-uint128"
+    pub extern "C" fn mod_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
 
-        // Find the next address that has the right alignment.
-        let idx = (*self.head.get()).next_multiple_of(align);
-        // Bump the head to the next free byte
-        *self.head.get() = idx + size;
-        let arena: &mut [u8; ARENA_SIZE] = &mut (*self.arena.get());
-        // If we ran out of arena space, we return a null pointer, which
-        // signals a failed allocation.
-        match arena.get_mut(idx) {
-            Some(item) => item as *mut u8,
-            _ => core::ptr::null_mut(),
+        match a.checked_rem(b) {
+            Some(result) => ok_128(result),
+            _ => err_128(ERR_DIVISION_BY_ZERO),
         }
     }
+}
 
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
-        /* lol */
+// Like in Clarity, the exponent of `pow` must fit in a `u32`, and `sqrti` and `log2`
+// fail on negative (and for `log2`, zero) operands, with `ERR_INVALID_ARGUMENT`.
+
+export! {
+    "pow-int128"
+    pub extern "C" fn pow_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        let exponent = match u32::try_from(b) {
+            Ok(exponent) => exponent,
+            _ => return err_128(ERR_INVALID_ARGUMENT),
+        };
+        match a.checked_pow(exponent) {
+            Some(result) => ok_128(result as u128),
+            // The result is only negative for a negative base and an odd exponent.
+            _ if a < 0 && exponent % 2 == 1 => err_128(ERR_UNDERFLOW),
+            _ => err_128(ERR_OVERFLOW),
+        }
     }
 }
+
+export! {
+    "pow-uint128"
+    pub extern "C" fn pow_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        let exponent = match u32::try_from(b) {
+            Ok(exponent) => exponent,
+            _ => return err_128(ERR_INVALID_ARGUMENT),
+        };
+        match a.checked_pow(exponent) {
+            Some(result) => ok_128(result),
+            _ => err_128(ERR_OVERFLOW),
+        }
+    }
+}
+
+export! {
+    "sqrti-int128"
+    pub extern "C" fn sqrti_int128(a_lo: i64, a_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+
+        match a.checked_isqrt() {
+            Some(result) => ok_128(result as u128),
+            _ => err_128(ERR_INVALID_ARGUMENT),
+        }
+    }
+}
+
+export! {
+    "sqrti-uint128"
+    pub extern "C" fn sqrti_uint128(a_lo: i64, a_hi: i64) -> (i32, i64, i64) {
+        ok_128(join_128(a_lo, a_hi).isqrt())
+    }
+}
+
+export! {
+    "log2-int128"
+    pub extern "C" fn log2_int128(a_lo: i64, a_hi: i64) -> (i32, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+
+        match a.checked_ilog2() {
+            Some(result) => ok_128(result as u128),
+            _ => err_128(ERR_INVALID_ARGUMENT),
+        }
+    }
+}
+
+export! {
+    "log2-uint128"
+    pub extern "C" fn log2_uint128(a_lo: i64, a_hi: i64) -> (i32, i64, i64) {
+        match join_128(a_lo, a_hi).checked_ilog2() {
+            Some(result) => ok_128(result as u128),
+            _ => err_128(ERR_INVALID_ARGUMENT),
+        }
+    }
+}
+
+export! {
+    "lt-int128"
+    pub extern "C" fn lt_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        (a < b) as i32
+    }
+}
+
+export! {
+    "lt-uint128"
+    pub extern "C" fn lt_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        (a < b) as i32
+    }
+}
+
+export! {
+    "le-int128"
+    pub extern "C" fn le_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        (a <= b) as i32
+    }
+}
+
+export! {
+    "le-uint128"
+    pub extern "C" fn le_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        (a <= b) as i32
+    }
+}
+
+export! {
+    "gt-int128"
+    pub extern "C" fn gt_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        (a > b) as i32
+    }
+}
+
+export! {
+    "gt-uint128"
+    pub extern "C" fn gt_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        (a > b) as i32
+    }
+}
+
+export! {
+    "ge-int128"
+    pub extern "C" fn ge_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        (a >= b) as i32
+    }
+}
+
+export! {
+    "ge-uint128"
+    pub extern "C" fn ge_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        (a >= b) as i32
+    }
+}
+
+export! {
+    "eq-int128"
+    pub extern "C" fn eq_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        (a == b) as i32
+    }
+}
+
+export! {
+    "eq-uint128"
+    pub extern "C" fn eq_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> i32 {
+        let a = join_128(a_lo, a_hi);
+        let b = join_128(b_lo, b_hi);
+
+        (a == b) as i32
+    }
+}
+
+export! {
+    "bit-and-int128"
+    pub extern "C" fn bit_and_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
+        (a_lo & b_lo, a_hi & b_hi)
+    }
+}
+
+export! {
+    "bit-and-uint128"
+    pub extern "C" fn bit_and_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
+        (a_lo & b_lo, a_hi & b_hi)
+    }
+}
+
+export! {
+    "bit-or-int128"
+    pub extern "C" fn bit_or_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
+        (a_lo | b_lo, a_hi | b_hi)
+    }
+}
+
+export! {
+    "bit-or-uint128"
+    pub extern "C" fn bit_or_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
+        (a_lo | b_lo, a_hi | b_hi)
+    }
+}
+
+export! {
+    "bit-xor-int128"
+    pub extern "C" fn bit_xor_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
+        (a_lo ^ b_lo, a_hi ^ b_hi)
+    }
+}
+
+export! {
+    "bit-xor-uint128"
+    pub extern "C" fn bit_xor_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64) {
+        (a_lo ^ b_lo, a_hi ^ b_hi)
+    }
+}
+
+export! {
+    "bit-not-int128"
+    pub extern "C" fn bit_not_int128(a_lo: i64, a_hi: i64) -> (i64, i64) {
+        (!a_lo, !a_hi)
+    }
+}
+
+export! {
+    "bit-not-uint128"
+    pub extern "C" fn bit_not_uint128(a_lo: i64, a_hi: i64) -> (i64, i64) {
+        (!a_lo, !a_hi)
+    }
+}
+
+// As of Clarity 2, the shift amount (a `uint`) is taken modulo 128, and bits shifted
+// out are discarded rather than causing an overflow.
+
+export! {
+    "bit-shift-left-int128"
+    pub extern "C" fn bit_shift_left_int128(a_lo: i64, a_hi: i64, b_lo: i64, _b_hi: i64) -> (i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        split_128((a << shift_amount(b_lo)) as u128)
+    }
+}
+
+export! {
+    "bit-shift-left-uint128"
+    pub extern "C" fn bit_shift_left_uint128(
+        a_lo: i64,
+        a_hi: i64,
+        b_lo: i64,
+        _b_hi: i64,
+    ) -> (i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        split_128(a << shift_amount(b_lo))
+    }
+}
+
+export! {
+    "bit-shift-right-int128"
+    pub extern "C" fn bit_shift_right_int128(
+        a_lo: i64,
+        a_hi: i64,
+        b_lo: i64,
+        _b_hi: i64,
+    ) -> (i64, i64) {
+        // Shifting a signed integer right is arithmetic, i.e. it preserves the sign.
+        let a = join_128(a_lo, a_hi) as i128;
+        split_128((a >> shift_amount(b_lo)) as u128)
+    }
+}
+
+export! {
+    "bit-shift-right-uint128"
+    pub extern "C" fn bit_shift_right_uint128(
+        a_lo: i64,
+        a_hi: i64,
+        b_lo: i64,
+        _b_hi: i64,
+    ) -> (i64, i64) {
+        let a = join_128(a_lo, a_hi);
+        split_128(a >> shift_amount(b_lo))
+    }
+}
+
+// The following functions are building blocks for integers wider than 128 bits, which
+// take and return 256-bit values as four 64-bit limbs (least significant first), and
+// 512-bit values as eight. Rather than failing, additions and subtractions return
+// their carry (or borrow) as the first result.
+
+export! {
+    "add-uint256"
+    pub extern "C" fn add_uint256(
+        a0: i64,
+        a1: i64,
+        a2: i64,
+        a3: i64,
+        b0: i64,
+        b1: i64,
+        b2: i64,
+        b3: i64,
+    ) -> (i32, i64, i64, i64, i64) {
+        let (sum, carry) = wide::add_u256(&join_256(a0, a1, a2, a3), &join_256(b0, b1, b2, b3));
+        let [s0, s1, s2, s3] = split_256(sum);
+        (carry as i32, s0, s1, s2, s3)
+    }
+}
+
+export! {
+    "sub-uint256"
+    pub extern "C" fn sub_uint256(
+        a0: i64,
+        a1: i64,
+        a2: i64,
+        a3: i64,
+        b0: i64,
+        b1: i64,
+        b2: i64,
+        b3: i64,
+    ) -> (i32, i64, i64, i64, i64) {
+        let (diff, borrow) = wide::sub_u256(&join_256(a0, a1, a2, a3), &join_256(b0, b1, b2, b3));
+        let [d0, d1, d2, d3] = split_256(diff);
+        (borrow as i32, d0, d1, d2, d3)
+    }
+}
+
+export! {
+    "mul-wide-uint128"
+    pub extern "C" fn mul_wide_uint128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64, i64, i64) {
+        let [p0, p1, p2, p3] = split_256(wide::mul_wide_u128(join_128(a_lo, a_hi), join_128(b_lo, b_hi)));
+        (p0, p1, p2, p3)
+    }
+}
+
+export! {
+    "mul-wide-int128"
+    pub extern "C" fn mul_wide_int128(a_lo: i64, a_hi: i64, b_lo: i64, b_hi: i64) -> (i64, i64, i64, i64) {
+        let a = join_128(a_lo, a_hi) as i128;
+        let b = join_128(b_lo, b_hi) as i128;
+
+        let [p0, p1, p2, p3] = split_256(wide::mul_wide_i128(a, b));
+        (p0, p1, p2, p3)
+    }
+}
+
+export! {
+    "mul-wide-uint256"
+    pub extern "C" fn mul_wide_uint256(
+        a0: i64,
+        a1: i64,
+        a2: i64,
+        a3: i64,
+        b0: i64,
+        b1: i64,
+        b2: i64,
+        b3: i64,
+    ) -> (i64, i64, i64, i64, i64, i64, i64, i64) {
+        split_512(wide::mul_wide_u256(&join_256(a0, a1, a2, a3), &join_256(b0, b1, b2, b3)))
+    }
+}
+
+export! {
+    "mul-wide-int256"
+    pub extern "C" fn mul_wide_int256(
+        a0: i64,
+        a1: i64,
+        a2: i64,
+        a3: i64,
+        b0: i64,
+        b1: i64,
+        b2: i64,
+        b3: i64,
+    ) -> (i64, i64, i64, i64, i64, i64, i64, i64) {
+        split_512(wide::mul_wide_i256(&join_256(a0, a1, a2, a3), &join_256(b0, b1, b2, b3)))
+    }
+}
+
+// The following functions take their operands as serialized values in linear memory
+// (the memory calling convention), so that arithmetic on them doesn't require a call
+// to the host per operation. Malformed operands fail with `ERR_MALFORMED_OPERAND`.
+
+export! {
+    "read-int128"
+    pub extern "C" fn read_int128(offset: i32, len: i32) -> (i32, i64, i64) {
+        match read_int_operand(offset, len) {
+            Some(value) => ok_128(value as u128),
+            _ => err_128(ERR_MALFORMED_OPERAND),
+        }
+    }
+}
+
+export! {
+    "read-uint128"
+    pub extern "C" fn read_uint128(offset: i32, len: i32) -> (i32, i64, i64) {
+        match read_uint_operand(offset, len) {
+            Some(value) => ok_128(value),
+            _ => err_128(ERR_MALFORMED_OPERAND),
+        }
+    }
+}
+
+export! {
+    "add-int128-memory"
+    pub extern "C" fn add_int128_memory(
+        a_offset: i32,
+        a_len: i32,
+        b_offset: i32,
+        b_len: i32,
+    ) -> (i32, i64, i64) {
+        match (
+            read_int_operand(a_offset, a_len),
+            read_int_operand(b_offset, b_len),
+        ) {
+            (Some(a), Some(b)) => checked_add_int(a, b),
+            _ => err_128(ERR_MALFORMED_OPERAND),
+        }
+    }
+}
+
+export! {
+    "add-uint128-memory"
+    pub extern "C" fn add_uint128_memory(
+        a_offset: i32,
+        a_len: i32,
+        b_offset: i32,
+        b_len: i32,
+    ) -> (i32, i64, i64) {
+        match (
+            read_uint_operand(a_offset, a_len),
+            read_uint_operand(b_offset, b_len),
+        ) {
+            (Some(a), Some(b)) => match a.checked_add(b) {
+                Some(result) => ok_128(result),
+                _ => err_128(ERR_OVERFLOW),
+            },
+            _ => err_128(ERR_MALFORMED_OPERAND),
+        }
+    }
+}
+
+export! {
+    "mul-uint128-memory"
+    pub extern "C" fn mul_uint128_memory(
+        a_offset: i32,
+        a_len: i32,
+        b_offset: i32,
+        b_len: i32,
+    ) -> (i32, i64, i64) {
+        match (
+            read_uint_operand(a_offset, a_len),
+            read_uint_operand(b_offset, b_len),
+        ) {
+            (Some(a), Some(b)) => checked_mul_uint(a, b),
+            _ => err_128(ERR_MALFORMED_OPERAND),
+        }
+    }
+}
+
+#[inline]
+fn read_int_operand(offset: i32, len: i32) -> Option<i128> {
+    read_int(unsafe { memory_slice(offset, len) })
+}
+
+#[inline]
+fn read_uint_operand(offset: i32, len: i32) -> Option<u128> {
+    read_uint(unsafe { memory_slice(offset, len) })
+}
+
+#[inline]
+fn checked_add_int(a: i128, b: i128) -> (i32, i64, i64) {
+    match a.checked_add(b) {
+        Some(result) => ok_128(result as u128),
+        _ if b < 0 => err_128(ERR_UNDERFLOW),
+        _ => err_128(ERR_OVERFLOW),
+    }
+}
+
+#[inline]
+fn checked_mul_uint(a: u128, b: u128) -> (i32, i64, i64) {
+    match a.checked_mul(b) {
+        Some(result) => ok_128(result),
+        _ => err_128(ERR_OVERFLOW),
+    }
+}
+
+/// Creates a successful result holding the given 128-bit value.
+#[inline]
+fn ok_128(value: u128) -> (i32, i64, i64) {
+    let (lo, hi) = split_128(value);
+    (ERR_NONE, lo, hi)
+}
+
+/// Creates a failed result with the given error code.
+#[inline]
+fn err_128(code: i32) -> (i32, i64, i64) {
+    (code, 0, 0)
+}
+
+/// Joins the low and high 64 bits of a 128-bit value.
+#[inline]
+fn join_128(lo: i64, hi: i64) -> u128 {
+    ((lo as u64) as u128) | ((hi as u64) as u128) << 64
+}
+
+/// Joins the limbs of a 256-bit value.
+#[inline]
+fn join_256(l0: i64, l1: i64, l2: i64, l3: i64) -> U256 {
+    [l0 as u64, l1 as u64, l2 as u64, l3 as u64]
+}
+
+/// Splits a 256-bit value into its limbs.
+#[inline]
+fn split_256(value: U256) -> [i64; 4] {
+    value.map(|limb| limb as i64)
+}
+
+/// Splits a 512-bit value into its limbs.
+#[inline]
+fn split_512(value: U512) -> (i64, i64, i64, i64, i64, i64, i64, i64) {
+    let [l0, l1, l2, l3, l4, l5, l6, l7] = value.map(|limb| limb as i64);
+    (l0, l1, l2, l3, l4, l5, l6, l7)
+}
+
+/// Gets the shift amount for a `uint` operand, i.e. the operand modulo 128, which
+/// only depends on its low 64 bits.
+#[inline]
+fn shift_amount(lo: i64) -> u32 {
+    (lo as u64 % 128) as u32
+}
+
+/// Splits a 128-bit value into its low and high 64 bits.
+#[inline]
+fn split_128(value: u128) -> (i64, i64) {
+    (
+        (value & 0xFFFFFFFFFFFFFFFF) as i64,
+        ((value >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
+    )
+}
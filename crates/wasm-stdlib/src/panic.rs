@@ -0,0 +1,95 @@
+// Reporting of guest failures to the host. Rather than trapping without a trace, the
+// panic handlers record why the guest failed in the `PANIC_RECORD`, whose address is
+// exported as the `panic-info` global, before trapping. The host can then read it
+// back from linear memory after the trap (see `read_guest_panic()` in the
+// `wasm-test` crate). The record is laid out as:
+// `[reason: i32 LE][message length: i32 LE][message: u8; MAX_MESSAGE_LEN]`.
+
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+
+/// The maximum length of a recorded message, beyond which it's truncated.
+const MAX_MESSAGE_LEN: usize = 256;
+
+/// The guest panicked, e.g. because of a failed assertion.
+const REASON_PANIC: i32 = 1;
+
+/// An allocation failed, e.g. because the memory couldn't be grown.
+const REASON_ALLOCATION_FAILED: i32 = 2;
+
+#[repr(C)]
+pub struct PanicRecord {
+    /// The reason the guest failed, which is zero until it does.
+    reason: UnsafeCell<i32>,
+    message_len: UnsafeCell<i32>,
+    message: UnsafeCell<[u8; MAX_MESSAGE_LEN]>,
+}
+
+unsafe impl Sync for PanicRecord {}
+
+#[no_mangle]
+#[export_name = "panic-info"]
+pub static PANIC_RECORD: PanicRecord = PanicRecord {
+    reason: UnsafeCell::new(0),
+    message_len: UnsafeCell::new(0),
+    message: UnsafeCell::new([0; MAX_MESSAGE_LEN]),
+};
+
+impl PanicRecord {
+    /// Records the reason of a failure, formatting the message into the record.
+    fn record(&self, reason: i32, message: core::fmt::Arguments<'_>) {
+        unsafe {
+            *self.reason.get() = reason;
+            *self.message_len.get() = 0;
+        }
+        // Messages which don't fit are truncated, which `write_str` signals with an
+        // error that we can ignore.
+        let _ = MessageWriter(self).write_fmt(message);
+    }
+}
+
+/// Appends formatted text to the message of a `PanicRecord`.
+struct MessageWriter<'a>(&'a PanicRecord);
+
+impl Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let (len, message) =
+            unsafe { (&mut *self.0.message_len.get(), &mut *self.0.message.get()) };
+        let start = *len as usize;
+        let count = s.len().min(MAX_MESSAGE_LEN - start);
+        message[start..start + count].copy_from_slice(&s.as_bytes()[..count]);
+        *len += count as i32;
+
+        if count < s.len() {
+            Err(core::fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    match info.location() {
+        Some(location) => PANIC_RECORD.record(
+            REASON_PANIC,
+            format_args!(
+                "{} at {}:{}",
+                info.message(),
+                location.file(),
+                location.line()
+            ),
+        ),
+        None => PANIC_RECORD.record(REASON_PANIC, format_args!("{}", info.message())),
+    }
+    core::arch::wasm32::unreachable()
+}
+
+#[alloc_error_handler]
+fn alloc_error(layout: core::alloc::Layout) -> ! {
+    PANIC_RECORD.record(
+        REASON_ALLOCATION_FAILED,
+        format_args!("failed to allocate {} bytes", layout.size()),
+    );
+    core::arch::wasm32::unreachable()
+}
@@ -0,0 +1,62 @@
+// Helpers for parsing values serialized by the host (see `serialization.rs` in the
+// `wasm-test` crate) directly from linear memory. Each value is a header
+// (`[type indicator: u8][length: u16 LE]`, where a length of `LENGTH_ESCAPE` is
+// followed by the actual length as a `u32 LE`) followed by the value's bytes.
+
+pub const TYPE_UINT: u8 = 1;
+pub const TYPE_INT: u8 = 2;
+
+/// The `u16` length which signals that the actual length follows as a `u32`.
+const LENGTH_ESCAPE: u16 = u16::MAX;
+
+/// Gets the `len` bytes of linear memory starting at `offset`.
+///
+/// # Safety
+/// The range must lie within linear memory, which is the case for any `Ptr`
+/// handed out by the host.
+#[inline]
+pub unsafe fn memory_slice(offset: i32, len: i32) -> &'static [u8] {
+    core::slice::from_raw_parts(offset as usize as *const u8, len as usize)
+}
+
+/// Reads a length (or element count) indicator from the start of the provided
+/// bytes, returning the length and the number of bytes the indicator occupied.
+#[inline]
+pub fn read_length(bytes: &[u8]) -> Option<(usize, usize)> {
+    let short = u16::from_le_bytes(bytes.get(0..2)?.try_into().ok()?);
+    if short == LENGTH_ESCAPE {
+        let long = u32::from_le_bytes(bytes.get(2..6)?.try_into().ok()?);
+        Some((long as usize, 6))
+    } else {
+        Some((short as usize, 2))
+    }
+}
+
+/// Reads the serialized value at the start of the provided bytes, returning its
+/// type indicator and its bytes (excluding the header), or `None` if the bytes are
+/// too short to hold it.
+#[inline]
+pub fn read_value(bytes: &[u8]) -> Option<(u8, &[u8])> {
+    let (type_indicator, rest) = bytes.split_first()?;
+    let (len, len_size) = read_length(rest)?;
+    let body = rest.get(len_size..len_size.checked_add(len)?)?;
+    Some((*type_indicator, body))
+}
+
+/// Reads a serialized `int` from the start of the provided bytes.
+#[inline]
+pub fn read_int(bytes: &[u8]) -> Option<i128> {
+    match read_value(bytes)? {
+        (TYPE_INT, body) => Some(i128::from_le_bytes(body.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Reads a serialized `uint` from the start of the provided bytes.
+#[inline]
+pub fn read_uint(bytes: &[u8]) -> Option<u128> {
+    match read_value(bytes)? {
+        (TYPE_UINT, body) => Some(u128::from_le_bytes(body.try_into().ok()?)),
+        _ => None,
+    }
+}
@@ -0,0 +1,130 @@
+// Building blocks for integers wider than 128 bits, e.g. to detect the overflow of a
+// 128-bit multiplication from its full product. Values are arrays of 64-bit limbs,
+// least significant first, and signed values are in two's complement.
+
+/// A 256-bit integer.
+pub type U256 = [u64; 4];
+
+/// A 512-bit integer.
+pub type U512 = [u64; 8];
+
+/// Adds `a`, `b` and the incoming carry, returning the sum and the outgoing carry.
+#[inline]
+pub fn add_with_carry(a: u64, b: u64, carry: bool) -> (u64, bool) {
+    let (sum, c1) = a.overflowing_add(b);
+    let (sum, c2) = sum.overflowing_add(carry as u64);
+    (sum, c1 | c2)
+}
+
+/// Subtracts `b` and the incoming borrow from `a`, returning the difference and the
+/// outgoing borrow.
+#[inline]
+pub fn sub_with_borrow(a: u64, b: u64, borrow: bool) -> (u64, bool) {
+    let (diff, b1) = a.overflowing_sub(b);
+    let (diff, b2) = diff.overflowing_sub(borrow as u64);
+    (diff, b1 | b2)
+}
+
+/// Computes `a * b + add + carry`, returning its low and high limbs. This can't
+/// overflow, as `(2^64 - 1)^2 + 2 * (2^64 - 1) = 2^128 - 1`.
+#[inline]
+pub fn mul_add(a: u64, b: u64, add: u64, carry: u64) -> (u64, u64) {
+    let wide = (a as u128) * (b as u128) + (add as u128) + (carry as u128);
+    (wide as u64, (wide >> 64) as u64)
+}
+
+/// Adds two 256-bit integers, returning the (wrapped) sum and whether it overflowed.
+pub fn add_u256(a: &U256, b: &U256) -> (U256, bool) {
+    let mut sum = [0; 4];
+    let mut carry = false;
+    for (limb, (a, b)) in sum.iter_mut().zip(a.iter().zip(b)) {
+        (*limb, carry) = add_with_carry(*a, *b, carry);
+    }
+    (sum, carry)
+}
+
+/// Subtracts `b` from `a`, returning the (wrapped) difference and whether it
+/// underflowed.
+pub fn sub_u256(a: &U256, b: &U256) -> (U256, bool) {
+    let mut diff = [0; 4];
+    let mut borrow = false;
+    for (limb, (a, b)) in diff.iter_mut().zip(a.iter().zip(b)) {
+        (*limb, borrow) = sub_with_borrow(*a, *b, borrow);
+    }
+    (diff, borrow)
+}
+
+/// Computes the full product of two 128-bit integers.
+pub fn mul_wide_u128(a: u128, b: u128) -> U256 {
+    let mut product = [0; 4];
+    mul_limbs(&split(a), &split(b), &mut product);
+    product
+}
+
+/// Computes the full (256-bit, two's complement) product of two signed 128-bit
+/// integers.
+pub fn mul_wide_i128(a: i128, b: i128) -> U256 {
+    let mut product = mul_wide_u128(a.unsigned_abs(), b.unsigned_abs());
+    if (a < 0) != (b < 0) {
+        negate(&mut product);
+    }
+    product
+}
+
+/// Computes the full product of two 256-bit integers.
+pub fn mul_wide_u256(a: &U256, b: &U256) -> U512 {
+    let mut product = [0; 8];
+    mul_limbs(a, b, &mut product);
+    product
+}
+
+/// Computes the full (512-bit, two's complement) product of two signed 256-bit
+/// integers.
+pub fn mul_wide_i256(a: &U256, b: &U256) -> U512 {
+    let (a_negative, a) = unsigned_abs(a);
+    let (b_negative, b) = unsigned_abs(b);
+
+    let mut product = mul_wide_u256(&a, &b);
+    if a_negative != b_negative {
+        negate(&mut product);
+    }
+    product
+}
+
+/// Gets whether the signed `value` is negative, along with its magnitude, which fits
+/// even for the minimum value.
+fn unsigned_abs(value: &U256) -> (bool, U256) {
+    let negative = (value[3] as i64) < 0;
+    let mut magnitude = *value;
+    if negative {
+        negate(&mut magnitude);
+    }
+    (negative, magnitude)
+}
+
+/// Multiplies `a` by `b` (schoolbook), writing the full product to `product`, which
+/// must hold `a.len() + b.len()` limbs.
+fn mul_limbs(a: &[u64], b: &[u64], product: &mut [u64]) {
+    product.fill(0);
+    for (i, &a_limb) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &b_limb) in b.iter().enumerate() {
+            (product[i + j], carry) = mul_add(a_limb, b_limb, product[i + j], carry);
+        }
+        product[i + b.len()] = carry;
+    }
+}
+
+/// Negates the two's complement integer held by `limbs`, in place.
+fn negate(limbs: &mut [u64]) {
+    let mut carry = true;
+    for limb in limbs.iter_mut() {
+        (*limb, carry) = add_with_carry(!*limb, 0, carry);
+    }
+}
+
+/// Splits a 128-bit integer into its limbs.
+#[inline]
+fn split(value: u128) -> [u64; 2] {
+    [value as u64, (value >> 64) as u64]
+}
@@ -1,7 +1,7 @@
 use clarity::vm::{
     types::{
         ASCIIData, BuffData, CharType, ListData, ListTypeData, OptionalData, ResponseData,
-        SequenceData, TypeSignature, UTF8Data,
+        SequenceData, TypeSignature,
     },
     Value,
 };
@@ -66,12 +66,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     // `string-utf8` serialization
     // ================================================================================
     c.bench_function("string-utf8", |b| {
-        let str = "hello world!";
-        let mut data = Vec::<Vec<u8>>::new();
-        for chunk in str.as_bytes().chunks(4) {
-            data.push(vec![chunk[0], chunk[1], chunk[2], chunk[3]]);
-        }
-        let value = Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data { data })));
+        let value = Value::string_utf8_from_bytes("héllo wörld! 👋".as_bytes().to_vec())
+            .expect("Failed to create utf8 string");
 
         b.iter(|| {
             let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
@@ -183,5 +179,66 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         })
     });
 
+    // ================================================================================
+    // `list` serialization into a re-used buffer, containing five unsigned integers
+    // ================================================================================
+    group.bench_function("list-5-uints-into", |b| {
+        let value = Value::Sequence(SequenceData::List(ListData {
+            data: vec![
+                Value::UInt(1),
+                Value::UInt(2),
+                Value::UInt(3),
+                Value::UInt(4),
+                Value::UInt(5),
+            ],
+            type_signature: ListTypeData::new_list(TypeSignature::UIntType, 5)
+                .expect("Could not construct list"),
+        }));
+        let mut buffer = vec![0u8; 256];
+
+        b.iter(|| {
+            let len = serialize_clarity_value_into(&value, &mut buffer)
+                .expect("Failed to serialize value");
+            deserialize_clarity_value(&buffer[..len]).expect("Failed to deserialize value");
+        })
+    });
+
+    // ================================================================================
+    // `list` serialization, containing 8192 integers (as folded over in the
+    // `fold-add-square` benchmark)
+    // ================================================================================
+    let value =
+        Value::list_from((1..8193).map(Value::Int).collect()).expect("Failed to create list");
+
+    group.bench_function("list-8192-ints", |b| {
+        b.iter(|| {
+            let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+            deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+        })
+    });
+
+    // ================================================================================
+    // Packed `list` serialization, containing 8192 integers
+    // ================================================================================
+    group.bench_function("list-8192-ints-packed", |b| {
+        b.iter(|| {
+            let serialized = serialize_packed_int_list(&value).expect("Failed to serialize value");
+            deserialize_packed_int_list(&serialized).expect("Failed to deserialize value");
+        })
+    });
+
+    // ================================================================================
+    // Packed `list` decoding, containing 8192 integers, without constructing `Value`s
+    // ================================================================================
+    group.bench_function("list-8192-ints-packed-unpack", |b| {
+        let serialized = serialize_packed_int_list(&value).expect("Failed to serialize value");
+        let mut ints = Vec::with_capacity(8192);
+
+        b.iter(|| {
+            ints.clear();
+            unpack_int_list(&serialized, &mut ints).expect("Failed to unpack ints");
+        })
+    });
+
     group.finish();
 }
@@ -0,0 +1,146 @@
+use clarity::vm::Value;
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasm_test::{
+    runtime::{
+        write_value_to_memory, FrameAllocator, RuntimeBuilder, WasmAllocator, WasmMemoryAllocator,
+        HOST_MODULE_NAME,
+    },
+    stdlib::STDLIB_WASM,
+    ClarityWasmContext,
+};
+use wasmtime::{Engine, Instance, Module, Store, Val};
+
+/// The size of the buffers which are hashed.
+const BUFFER_SIZE: usize = 1024;
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let data = vec![0xABu8; BUFFER_SIZE];
+
+    let mut sha256_group = c.benchmark_group("Sha256");
+
+    // ================================================================================
+    // SHA256 using a host call (memory convention) benchmark
+    // ================================================================================
+    sha256_group.bench_function("sha256_host", |b| {
+        // The digests are allocated in a frame per call, so that memory doesn't grow
+        // across iterations.
+        let context =
+            ClarityWasmContext::new().with_allocator(FrameAllocator::new(WasmAllocator::new()));
+        let (instance, mut store) = RuntimeBuilder::new()
+            .with_context(context)
+            .instantiate(&generate_sha256_memory_test())
+            .expect("Couldn't create new module instance");
+        let instance_fn = instance
+            .get_func(&mut store, "sha256_memory_test")
+            .expect("Failed to get fn");
+        let results = &mut [Val::I32(0), Val::I32(0), Val::I32(0)];
+
+        // Serialize the buffer into Wasm memory, as the host function reads Clarity
+        // values.
+        let mem = instance
+            .get_memory(&mut store, "vm_mem")
+            .expect("Failed to find 'vm_mem'.");
+        let buffer = Value::buff_from(data.clone()).expect("Failed to create buffer");
+        let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+        let ptr = write_value_to_memory(&mut store, mem, alloc.as_mut(), &buffer)
+            .expect("Failed to write buffer to memory");
+        store.data_mut().alloc = alloc;
+
+        b.iter(|| {
+            store.data_mut().alloc.push_frame();
+            instance_fn
+                .call(
+                    &mut store,
+                    &[Val::I32(ptr.offset), Val::I32(ptr.len)],
+                    results,
+                )
+                .expect("Failed to call function");
+            store.data_mut().alloc.drop_frame();
+        });
+    });
+
+    // ================================================================================
+    // SHA256 inside of the guest (wasm-stdlib) benchmark
+    // ================================================================================
+    let (instance, mut store) = instantiate_stdlib();
+    sha256_group.bench_function("sha256_guest", |b| {
+        let alloc_fn = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .expect("Failed to get fn");
+        let sha256_fn = instance
+            .get_typed_func::<(i32, i32, i32), ()>(&mut store, "sha256")
+            .expect("Failed to get fn");
+
+        // Copy the (raw) buffer and reserve room for the digest in the guest's
+        // memory.
+        let offset = alloc_fn
+            .call(&mut store, BUFFER_SIZE as i32)
+            .expect("Failed to allocate buffer");
+        let out_offset = alloc_fn
+            .call(&mut store, 32)
+            .expect("Failed to allocate digest");
+        instance
+            .get_memory(&mut store, "memory")
+            .expect("Failed to find 'memory'.")
+            .write(&mut store, offset as usize, &data)
+            .expect("Failed to write buffer to memory");
+
+        b.iter(|| {
+            sha256_fn
+                .call(&mut store, (offset, BUFFER_SIZE as i32, out_offset))
+                .expect("Failed to call function")
+        });
+    });
+
+    sha256_group.finish();
+}
+
+/// Instantiates the embedded `wasm-stdlib` module.
+fn instantiate_stdlib() -> (Instance, Store<()>) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, STDLIB_WASM).expect("Failed to compile wasm-stdlib");
+    let mut store = Store::new(&engine, ());
+    let instance =
+        Instance::new(&mut store, &module, &[]).expect("Failed to instantiate wasm-stdlib");
+    (instance, store)
+}
+
+/// Generates a module exporting a `sha256_memory_test` function, which calls the
+/// `sha256_memory` host function, along with its `vm_mem` memory.
+fn generate_sha256_memory_test() -> Vec<u8> {
+    use walrus::{ExportItem, FunctionBuilder, Module, ModuleConfig, ValType};
+
+    let mut module = Module::with_config(ModuleConfig::new());
+
+    let sha256_memory_ty = module.types.add(
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let (sha256_memory_id, _) =
+        module.add_import_func(HOST_MODULE_NAME, "sha256_memory", sha256_memory_ty);
+
+    let mut sha256_memory = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let ptr = module.locals.add(ValType::I32);
+    let len = module.locals.add(ValType::I32);
+    sha256_memory
+        .func_body()
+        .local_get(ptr)
+        .local_get(len)
+        .call(sha256_memory_id);
+    let sha256_memory_test_id = sha256_memory.finish(vec![ptr, len], &mut module.funcs);
+    module
+        .exports
+        .add("sha256_memory_test", sha256_memory_test_id);
+
+    let memory_id = module.memories.add_local(false, 1, None);
+    module.exports.add("vm_mem", ExportItem::Memory(memory_id));
+
+    module.emit_wasm()
+}
@@ -1,8 +1,23 @@
-use clarity::vm::Value;
+use clarity::types::StacksEpochId;
+use clarity::vm::{
+    costs::LimitedCostTracker, database::MemoryBackingStore, types::QualifiedContractIdentifier,
+    ClarityVersion, Value,
+};
 use criterion::{criterion_group, criterion_main, Criterion};
 use walrus::FunctionId;
-use wasm_test::{get_all_functions, serialization::serialize_clarity_value, ClarityWasmContext};
-use wasmtime::{Config, Engine, Extern, ExternRef, Instance, Module, Store, Val};
+use wasm_test::compiler::{
+    analyze_contract, compile_with_options, CodegenOptions, SequenceLowering,
+};
+use wasm_test::runtime::{
+    alloc_in_memory, get_all_functions, link_host_functions, write_value_to_memory, RuntimeBuilder,
+    HOST_MODULE_NAME,
+};
+use wasm_test::serialization::serialize_packed_int_list;
+use wasm_test::ClarityWasmContext;
+use wasmtime::{
+    Config, Engine, ExternRef, Global, GlobalType, Instance, Linker, Module, Mutability, Store,
+    Table, TableType, Val, ValType,
+};
 
 /// Helper struct to store mappings between a function name andits module import id and function id.
 #[derive(Debug, Clone)]
@@ -53,40 +68,12 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     // which in turn calls the below defined wrapped function `func`.
     let wasm_bytes = generate_wasm();
 
-    // Initialize config which allows for reference types.
-    let mut config = Config::new();
-    config.wasm_reference_types(true);
-
     Engine::tls_eager_initialize();
 
-    // Initialize the wasmtime engine.
-    let engine = Engine::new(&config).expect("Failed to initialize engine");
-
-    // Pre-compile the module.
-    let precompiled = engine
-        .precompile_module(&wasm_bytes)
-        .expect("Failed to precompile module");
-
-    // Initialize the wasmtime store (using a custom state type).
-    let state = ClarityWasmContext::new();
-    let mut store = Store::new(&engine, state);
-
-    // Load the module generated above.
-    //let module = Module::from_binary(store.engine(), &wasm_bytes).expect("Failed to load module");
-    let module =
-        unsafe { Module::deserialize(&engine, &precompiled).expect("Failed to load module") };
-
-    // Get our list of host functions to be included in the instance.
-    let native_fns = get_all_functions(&mut store);
-    // Convert the (name, func) pairs to a vec of `Export`s (needed for the Instance).
-    let imports = native_fns
-        .iter()
-        .map(|f| Extern::Func(f.func))
-        .collect::<Vec<Extern>>();
-
-    // We create a new instance and pass in any imported (host) functions.
-    let instance =
-        Instance::new(&mut store, &module, &imports).expect("Couldn't create new module instance");
+    // Create the engine and store, define the host functions and instantiate the module.
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&wasm_bytes)
+        .expect("Couldn't create new module instance");
 
     // ================================================================================
     // FOLD-ADD-SQUARE benchmark
@@ -124,6 +111,106 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         })
     });
 
+    // ================================================================================
+    // FOLD-ADD-SQUARE and MAP-SQUARE benchmarks, using the module compiled from
+    // `SEQUENCE_CONTRACT` with `fold` and `map` lowered to loops in Wasm rather than
+    // calls to `fold_extref` and `map_extref` (see `SequenceLowering`)
+    // ================================================================================
+    let (loop_instance, mut loop_store) = instantiate_compiled(SequenceLowering::Loop);
+    let sequence =
+        Value::list_from((1..8193).map(Value::Int).collect()).expect("Failed to create list");
+
+    c.bench_function("fold-add-square-loop", |b| {
+        let instance_fn = loop_instance
+            .get_func(&mut loop_store, "fold-add-square")
+            .expect("Failed to get fn");
+        let results = &mut [Val::ExternRef(None)];
+
+        b.iter(|| {
+            instance_fn
+                .call(
+                    &mut loop_store,
+                    &[
+                        Val::ExternRef(Some(ExternRef::new(sequence.clone()))),
+                        Val::ExternRef(Some(ExternRef::new(Value::Int(1)))),
+                    ],
+                    results,
+                )
+                .expect("Failed to call function")
+        })
+    });
+
+    c.bench_function("map-square-loop", |b| {
+        let instance_fn = loop_instance
+            .get_func(&mut loop_store, "map-square")
+            .expect("Failed to get fn");
+        let results = &mut [Val::ExternRef(None)];
+
+        b.iter(|| {
+            instance_fn
+                .call(
+                    &mut loop_store,
+                    &[Val::ExternRef(Some(ExternRef::new(sequence.clone())))],
+                    results,
+                )
+                .expect("Failed to call function")
+        })
+    });
+
+    // ================================================================================
+    // FOLD-ADD benchmarks using memory + serialization, with the sequence serialized
+    // using the regular encoding and the packed encoding for integer lists
+    // ================================================================================
+    for (name, packed) in [("fold-add-memory", false), ("fold-add-memory-packed", true)] {
+        c.bench_function(name, |b| {
+            let instance_fn = instance
+                .get_func(&mut store, "fold_add_memory_test")
+                .expect("Failed to get fn");
+            let mem = instance
+                .get_memory(&mut store, "vm_mem")
+                .expect("Failed to find 'vm_mem'.");
+
+            let results = &mut [Val::I32(0), Val::I32(0), Val::I32(0)];
+            let sequence = Value::list_from((1..8193).map(Value::Int).collect())
+                .expect("Failed to create list");
+            let init = Value::Int(1);
+
+            b.iter(|| {
+                // Start from an empty memory each time, so that it doesn't keep growing.
+                let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+                alloc.reset();
+                let seq_ptr = if packed {
+                    let bytes =
+                        serialize_packed_int_list(&sequence).expect("Failed to serialize list");
+                    let ptr = alloc_in_memory(&mut store, mem, &mut alloc, bytes.len())
+                        .expect("Failed to allocate memory");
+                    mem.write(&mut store, ptr.offset as usize, &bytes)
+                        .expect("Failed to write list to memory");
+                    ptr
+                } else {
+                    write_value_to_memory(&mut store, mem, &mut alloc, &sequence)
+                        .expect("Failed to write list to memory")
+                };
+                let init_ptr = write_value_to_memory(&mut store, mem, &mut alloc, &init)
+                    .expect("Failed to write 'init' to memory");
+                store.data_mut().alloc = alloc;
+
+                instance_fn
+                    .call(
+                        &mut store,
+                        &[
+                            Val::I32(seq_ptr.offset),
+                            Val::I32(seq_ptr.len),
+                            Val::I32(init_ptr.offset),
+                            Val::I32(init_ptr.len),
+                        ],
+                        results,
+                    )
+                    .expect("Failed to call function")
+            })
+        });
+    }
+
     let mut add_group = c.benchmark_group("Add");
 
     // ================================================================================
@@ -202,18 +289,13 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         let a_val = Value::Int(5);
         let b_val = Value::Int(11);
 
-        // Serialize the two values we want to add to their byte-representations.
-        let a_bytes = serialize_clarity_value(&a_val).expect("Failed to serialize 'a'");
-        let b_bytes = serialize_clarity_value(&b_val).expect("Failed to serialize 'b'");
-
-        // Get pointers to both a and b slices.
-        let a_ptr = store.data_mut().alloc.alloc_for_buffer(&a_bytes);
-        let b_ptr = store.data_mut().alloc.alloc_for_buffer(&b_bytes);
-
-        mem.write(&mut store, a_ptr.offset as usize, &a_bytes)
-            .expect("Failed to write buffer for 'a'");
-        mem.write(&mut store, b_ptr.offset as usize, &b_bytes)
-            .expect("Failed to write buffer for 'b'");
+        // Serialize the two values we want to add directly into Wasm memory.
+        let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+        let a_ptr = write_value_to_memory(&mut store, mem, &mut alloc, &a_val)
+            .expect("Failed to write 'a' to memory");
+        let b_ptr = write_value_to_memory(&mut store, mem, &mut alloc, &b_val)
+            .expect("Failed to write 'b' to memory");
+        store.data_mut().alloc = alloc;
 
         b.iter(|| {
             instance_fn
@@ -250,9 +332,14 @@ pub fn generate_wasm() -> Vec<u8> {
         define_add_extref(&mut module),
         define_add_native(&mut module),
         define_add_memory(&mut module),
+        define_sub_extref(&mut module),
+        define_sub_native(&mut module),
+        define_sub_memory(&mut module),
         define_mul_extref(&mut module),
         define_fold_extref(&mut module),
         define_fold_memory(&mut module),
+        define_print_extref(&mut module),
+        define_print_memory(&mut module),
     ];
 
     funcs.push(define_add_native_test(&mut module, &funcs));
@@ -260,6 +347,7 @@ pub fn generate_wasm() -> Vec<u8> {
     funcs.push(define_add_extref_test(&mut module, &funcs));
     funcs.push(define_add_square_extref_test(&mut module, &funcs));
     funcs.push(define_fold_add_square_extref_test(&mut module, &funcs));
+    funcs.push(define_fold_add_memory_test(&mut module, &funcs));
 
     // Create and export a Wasm memory
     let memory_id = module.memories.add_local(false, 1, None);
@@ -274,6 +362,89 @@ pub fn generate_wasm() -> Vec<u8> {
     wasm_bytes
 }
 
+/// The contract whose compiled module is used by the `*-loop` benchmarks.
+const SEQUENCE_CONTRACT: &str = "
+    (define-private (add-square (x int) (acc int)) (+ acc (* x x)))
+    (define-read-only (fold-add-square (l (list 8192 int)) (init int))
+      (fold add-square l init))
+    (define-private (square (x int)) (* x x))
+    (define-read-only (map-square (l (list 8192 int))) (map square l))
+";
+
+/// Analyzes and compiles `SEQUENCE_CONTRACT` with the given `SequenceLowering`, and
+/// instantiates the resulting module along with the cost tracker and constants table
+/// which it imports.
+fn instantiate_compiled(
+    sequence_lowering: SequenceLowering,
+) -> (Instance, Store<ClarityWasmContext>) {
+    let contract_id = QualifiedContractIdentifier::transient();
+    let contract_analysis = analyze_contract(
+        SEQUENCE_CONTRACT,
+        &contract_id,
+        LimitedCostTracker::new_free(),
+        ClarityVersion::Clarity2,
+        StacksEpochId::Epoch24,
+        &mut MemoryBackingStore::new(),
+    )
+    .expect("Failed to analyze contract")
+    .contract_analysis;
+    let options = CodegenOptions {
+        sequence_lowering,
+        ..Default::default()
+    };
+    let compile_result =
+        compile_with_options(&contract_analysis, options).expect("Failed to compile contract");
+
+    let mut config = Config::default();
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config).expect("Failed to create engine");
+    let module =
+        Module::new(&engine, compile_result.module_bytes()).expect("Failed to load module");
+    let mut context = ClarityWasmContext::new();
+    context.set_contract_id(Some(contract_id.clone()));
+    compile_result
+        .prepare_context(&mut context, &contract_id)
+        .expect("Failed to prepare context");
+    let mut store = Store::new(&engine, context);
+
+    let mut linker = Linker::new(&engine);
+    let host_functions = get_all_functions(&mut store);
+    link_host_functions(&mut linker, &store, &host_functions)
+        .expect("Failed to link host functions");
+
+    let cost_tracker = Global::new(
+        &mut store,
+        GlobalType::new(ValType::ExternRef, Mutability::Const),
+        Val::ExternRef(None),
+    )
+    .expect("Failed to create cost tracker");
+    linker
+        .define(&store, HOST_MODULE_NAME, "__cost_tracker_ref", cost_tracker)
+        .expect("Failed to define cost tracker");
+
+    let constants = compile_result.constants();
+    let consts = Table::new(
+        &mut store,
+        TableType::new(ValType::ExternRef, constants.len() as u32, None),
+        Val::ExternRef(None),
+    )
+    .expect("Failed to create constants table");
+    for (index, constant) in constants.iter().enumerate() {
+        let constant = Val::ExternRef(Some(ExternRef::new(constant.clone())));
+        consts
+            .set(&mut store, index as u32, constant)
+            .expect("Failed to set constant");
+    }
+    linker
+        .define(&store, HOST_MODULE_NAME, "__consts", consts)
+        .expect("Failed to define constants table");
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .expect("Couldn't create new module instance");
+    (instance, store)
+}
+
 /// ================================================================================
 /// `fold_memory` function.
 /// ================================================================================
@@ -296,6 +467,37 @@ fn define_fold_memory(module: &mut walrus::Module) -> WasmFunctionMapping {
     WasmFunctionMapping::new_import("fold_memory", function_id)
 }
 
+/// ================================================================================
+/// `print_extref` function.
+/// ================================================================================
+fn define_print_extref(module: &mut walrus::Module) -> WasmFunctionMapping {
+    use walrus::ValType;
+
+    // Import the API definition for `print_extref`.
+    let print_extref_ty = module
+        .types
+        .add(&[ValType::Externref], &[ValType::Externref]);
+
+    let (function_id, _) = module.add_import_func("clarity", "print_extref", print_extref_ty);
+    WasmFunctionMapping::new_import("print_extref", function_id)
+}
+
+/// ================================================================================
+/// `print_memory` function.
+/// ================================================================================
+fn define_print_memory(module: &mut walrus::Module) -> WasmFunctionMapping {
+    use walrus::ValType;
+
+    // Import the API definition for `print_memory`.
+    let print_memory_ty = module.types.add(
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+
+    let (function_id, _) = module.add_import_func("clarity", "print_memory", print_memory_ty);
+    WasmFunctionMapping::new_import("print_memory", function_id)
+}
+
 /// ================================================================================
 /// `mul_externref` function.
 /// ================================================================================
@@ -376,6 +578,54 @@ fn define_add_memory(module: &mut walrus::Module) -> WasmFunctionMapping {
     WasmFunctionMapping::new_import("add_memory", function_id)
 }
 
+/// ================================================================================
+/// `sub_externref` function.
+/// ================================================================================
+fn define_sub_extref(module: &mut walrus::Module) -> WasmFunctionMapping {
+    use walrus::ValType;
+
+    // Import the API definition for `sub_extref`.
+    let sub_extref_ty = module.types.add(
+        &[ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+
+    let (function_id, _) = module.add_import_func("clarity", "sub_extref", sub_extref_ty);
+    WasmFunctionMapping::new_import("sub_extref", function_id)
+}
+
+/// ================================================================================
+/// `sub_native` function.
+/// ================================================================================
+fn define_sub_native(module: &mut walrus::Module) -> WasmFunctionMapping {
+    use walrus::ValType;
+
+    // Import the API definition for `sub_native`.
+    let sub_native_ty = module.types.add(
+        &[ValType::I64, ValType::I64, ValType::I64, ValType::I64],
+        &[ValType::I64, ValType::I64],
+    );
+
+    let (function_id, _) = module.add_import_func("clarity", "sub_native", sub_native_ty);
+    WasmFunctionMapping::new_import("sub_native", function_id)
+}
+
+/// ================================================================================
+/// `sub_memory` function.
+/// ================================================================================
+fn define_sub_memory(module: &mut walrus::Module) -> WasmFunctionMapping {
+    use walrus::ValType;
+
+    // Import the API definition for `sub_memory`.
+    let sub_memory_ty = module.types.add(
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+
+    let (function_id, _) = module.add_import_func("clarity", "sub_memory", sub_memory_ty);
+    WasmFunctionMapping::new_import("sub_memory", function_id)
+}
+
 /// ================================================================================
 /// `add_native_test` function.
 /// ================================================================================
@@ -559,3 +809,46 @@ fn define_fold_add_square_extref_test(
         .add("fold_add_square_extref_test", fold_add_square_extref_id);
     WasmFunctionMapping::new_export("fold_add_square_extref_test", fold_add_square_extref_id)
 }
+
+/// ================================================================================
+/// `fold_add_memory_test` function, folding `add_memory_test` over a sequence in
+/// memory using `fold_memory`.
+/// ================================================================================
+fn define_fold_add_memory_test(
+    module: &mut walrus::Module,
+    funcs: &[WasmFunctionMapping],
+) -> WasmFunctionMapping {
+    use walrus::{FunctionBuilder, ValType};
+
+    let fold_memory_id = funcs.get_by_name("fold_memory").unwrap().function_id;
+    let add_memory_test_id = funcs.get_by_name("add_memory_test").unwrap().function_id;
+
+    let mut fold_add = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32], // list + init
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+
+    let seq_ptr = module.locals.add(ValType::I32);
+    let seq_len = module.locals.add(ValType::I32);
+    let init_ptr = module.locals.add(ValType::I32);
+    let init_len = module.locals.add(ValType::I32);
+
+    fold_add
+        .func_body()
+        .ref_func(add_memory_test_id)
+        .local_get(seq_ptr)
+        .local_get(seq_len)
+        .local_get(init_ptr)
+        .local_get(init_len)
+        .call(fold_memory_id);
+
+    let fold_add_memory_id = fold_add.finish(
+        vec![seq_ptr, seq_len, init_ptr, init_len],
+        &mut module.funcs,
+    );
+    module
+        .exports
+        .add("fold_add_memory_test", fold_add_memory_id);
+    WasmFunctionMapping::new_export("fold_add_memory_test", fold_add_memory_id)
+}
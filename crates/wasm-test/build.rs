@@ -0,0 +1,34 @@
+use std::{env, fs, path::PathBuf};
+
+/// The location of the `wasm-stdlib` module, as built by `cargo make build` in its
+/// crate, relative to this crate.
+const STDLIB_PATH: &str = "../../target/wasm32-unknown-unknown/release/wasm_stdlib.wasm";
+
+/// Copies the `wasm-stdlib` module into `OUT_DIR`, from where it's embedded by
+/// `stdlib::STDLIB_WASM`. Building it requires the nightly toolchain and the `wasm32`
+/// target, so it's only embedded when the `stdlib` feature is enabled, in which case
+/// the build fails if it hasn't been built. Otherwise, an empty file is embedded.
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    let bytes = if env::var_os("CARGO_FEATURE_STDLIB").is_some() {
+        let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let stdlib_path = manifest_dir.join(STDLIB_PATH);
+        if !stdlib_path.exists() {
+            panic!(
+                "The `stdlib` feature is enabled, but the wasm-stdlib module hasn't been \
+                 built at {}: run `cargo make build` in crates/wasm-stdlib first",
+                stdlib_path.display()
+            );
+        }
+
+        println!("cargo:rerun-if-changed={}", stdlib_path.display());
+        fs::read(&stdlib_path).expect("Failed to read the wasm-stdlib module")
+    } else {
+        println!("cargo:rerun-if-changed=build.rs");
+        Vec::new()
+    };
+
+    fs::write(out_dir.join("wasm_stdlib.wasm"), bytes)
+        .expect("Failed to write the wasm-stdlib module");
+}
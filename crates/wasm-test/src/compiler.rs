@@ -1,4 +1,12 @@
+use std::{collections::BTreeMap, fmt};
+
+#[cfg(feature = "serde")]
+pub use self::cache::DiskCacheBackend;
+pub use self::cache::{CacheBackend, CacheKey, CacheStats, CompilationCache, MemoryCacheBackend};
+pub use self::optimizations::OptimizationReport;
+pub use self::source_map::{SourceMap, SOURCE_MAP_SECTION};
 use self::wasm_generator::WasmGenerator;
+use crate::{stdlib::StdlibLinkError, ClarityWasmContext};
 use clarity::{
     types::StacksEpochId,
     vm::{
@@ -7,22 +15,275 @@ use clarity::{
         costs::LimitedCostTracker,
         database::ClarityBackingStore,
         diagnostic::{DiagnosableError, Diagnostic},
-        types::QualifiedContractIdentifier,
-        ClarityVersion, Value,
+        representations::Span,
+        types::{
+            FunctionArg, FunctionSignature, QualifiedContractIdentifier, TraitIdentifier,
+            TypeSignature,
+        },
+        ClarityName, ClarityVersion, Value,
     },
 };
 use walrus::{ir::InstrSeqId, FunctionBuilder, GlobalId, ImportId, LocalId, TableId, ValType};
 
 // Sub-module definitions
+mod cache;
+mod host_functions;
+mod optimizations;
+mod source_map;
 mod traversals;
 mod visitors;
-mod wasm_generator;
+pub(crate) mod wasm_generator;
 
 type WasmGenerationResult = Result<(), WasmGenerationError>;
 
-#[derive(Debug)]
+/// The name under which the function initializing the contract (e.g. storing the
+/// initial values of its data variables) is exported. It must be called once after the
+/// module has been instantiated, before any of the contract's functions. The leading
+/// `.` ensures that it can't clash with a Clarity function name.
+pub const TOP_LEVEL_FUNCTION: &str = ".top-level";
+
+/// The convention by which generated code passes Clarity values to, and receives them
+/// from, the host functions. Each convention uses its own set of host functions,
+/// distinguished by their suffix (e.g. `add_extref`, `add_native` and `add_memory`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CodegenConvention {
+    /// Values are passed as `ExternRef`s to the `*_extref` host functions.
+    #[default]
+    Externref,
+    /// Integers are passed on the Wasm stack, as low/high `i64` pairs, to the
+    /// `*_native` host functions. Only `int` values, and the `bool`s (as `i32`s)
+    /// returned by comparisons, are supported.
+    RustRefStack,
+    /// Values are serialized to the module's `vm_mem` memory and passed as
+    /// offset/length `i32` pairs to the `*_memory` host functions.
+    Memory,
+}
+
+impl CodegenConvention {
+    /// Gets the suffix of the names of the host functions used by this convention.
+    pub fn host_function_suffix(&self) -> &'static str {
+        match self {
+            CodegenConvention::Externref => "_extref",
+            CodegenConvention::RustRefStack => "_native",
+            CodegenConvention::Memory => "_memory",
+        }
+    }
+}
+
+/// How `fold` and `map` over a sequence are lowered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SequenceLowering {
+    /// The `fold_extref` and `map_extref` host functions are called with a `funcref`
+    /// to the function to apply, which the host calls back into for each element.
+    #[default]
+    HostCall,
+    /// A loop is generated which gets each element using the `seq_len_extref` and
+    /// `seq_get_extref` host functions and calls the function to apply directly,
+    /// avoiding a dynamic call from the host per element.
+    Loop,
+}
+
+/// The options which affect the code generated for a contract.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodegenOptions {
+    pub convention: CodegenConvention,
+    pub sequence_lowering: SequenceLowering,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompileResult {
+    /// Stored separately from the rest of the result by the `DiskCacheBackend`.
+    #[cfg_attr(feature = "serde", serde(skip))]
     module_bytes: Vec<u8>,
+    names: Vec<ClarityName>,
+    constants: Vec<Value>,
+    maps: Vec<DataMapMetadata>,
+    data_vars: Vec<DataVarMetadata>,
+    traits: Vec<TraitMetadata>,
+    implemented_traits: Vec<TraitIdentifier>,
+    interfaces: Vec<TraitIdentifier>,
+    called_contracts: Vec<QualifiedContractIdentifier>,
+    contract_interface: ContractInterface,
+    source_map: SourceMap,
+}
+
+impl CompileResult {
+    /// Gets the bytes of the compiled Wasm module.
+    pub fn module_bytes(&self) -> &[u8] {
+        &self.module_bytes
+    }
+
+    /// Gets the names (e.g. of data maps) which the module references by id, in the
+    /// order of their ids. See `prepare_context`.
+    pub fn names(&self) -> &[ClarityName] {
+        &self.names
+    }
+
+    /// Gets the constants (e.g. literal values) which the module's `__consts` table
+    /// must be filled with, in order, before the module is instantiated.
+    pub fn constants(&self) -> &[Value] {
+        &self.constants
+    }
+
+    /// Gets the data maps defined by the contract.
+    pub fn maps(&self) -> &[DataMapMetadata] {
+        &self.maps
+    }
+
+    /// Gets the data variables defined by the contract.
+    pub fn data_vars(&self) -> &[DataVarMetadata] {
+        &self.data_vars
+    }
+
+    /// Gets the traits defined by the contract.
+    pub fn traits(&self) -> &[TraitMetadata] {
+        &self.traits
+    }
+
+    /// Gets the traits which the contract declares that it implements.
+    pub fn implemented_traits(&self) -> &[TraitIdentifier] {
+        &self.implemented_traits
+    }
+
+    /// Gets the traits of the contract's trait-typed parameters, in the order of the
+    /// interface ids which they're referenced by.
+    pub fn interfaces(&self) -> &[TraitIdentifier] {
+        &self.interfaces
+    }
+
+    /// Gets the contracts called by the contract using `contract-call?`, in the order
+    /// of the ids which they're referenced by. See `prepare_context`.
+    pub fn called_contracts(&self) -> &[QualifiedContractIdentifier] {
+        &self.called_contracts
+    }
+
+    /// Gets the functions defined by the contract. The public and read-only functions
+    /// are exported from the module under their names.
+    pub fn contract_interface(&self) -> &ContractInterface {
+        &self.contract_interface
+    }
+
+    /// Gets the map from offsets of instructions in the module (e.g. those reported in
+    /// the backtrace of a trap) to the spans of the Clarity expressions which they were
+    /// generated from. It's also stored in the module's `SOURCE_MAP_SECTION`.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// Interns the names and called contracts referenced by the module in the given
+    /// context and declares the contract's data variables, so that the ids used by the
+    /// module match. Ids are per contract, so any number of contracts can be prepared
+    /// in the same context. The contract's names and called contracts must not have
+    /// been interned otherwise, as an error is returned at the first id which doesn't
+    /// match, leaving the context partially prepared.
+    pub fn prepare_context(
+        &self,
+        context: &mut ClarityWasmContext,
+        contract_id: &QualifiedContractIdentifier,
+    ) -> Result<(), PrepareContextError> {
+        for (id, name) in self.names.iter().enumerate() {
+            let data_var = self.data_vars.iter().find(|var| var.name == *name);
+            let name_id = match data_var {
+                Some(var) => context.declare_data_var(
+                    contract_id.clone(),
+                    name.clone(),
+                    var.value_type.clone(),
+                ),
+                None => context.contract_names_mut(contract_id).intern(name.clone()),
+            };
+            if name_id != id as i32 {
+                return Err(PrepareContextError::NameIdMismatch {
+                    name: name.clone(),
+                    expected: id as i32,
+                    actual: name_id,
+                });
+            }
+        }
+
+        for (id, called_contract) in self.called_contracts.iter().enumerate() {
+            let called_id = context
+                .contracts_mut()
+                .intern(contract_id, called_contract.clone());
+            if called_id != id as i32 {
+                return Err(PrepareContextError::ContractIdMismatch {
+                    contract_id: called_contract.clone(),
+                    expected: id as i32,
+                    actual: called_id,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes the functions defined by a contract.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContractInterface {
+    pub functions: Vec<FunctionInterface>,
+}
+
+impl ContractInterface {
+    /// Gets the function with the given name.
+    pub fn function(&self, name: &str) -> Option<&FunctionInterface> {
+        self.functions
+            .iter()
+            .find(|function| function.name.as_str() == name)
+    }
+}
+
+/// Describes a function defined using `define-public`, `define-read-only` or
+/// `define-private`, with its types as inferred by the type checker.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionInterface {
+    pub name: ClarityName,
+    pub access: FunctionAccess,
+    pub args: Vec<FunctionArg>,
+    pub returns: TypeSignature,
+}
+
+/// How a function defined by a contract may be called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FunctionAccess {
+    Public,
+    ReadOnly,
+    Private,
+}
+
+impl FunctionAccess {
+    /// Gets whether functions with this access are exported from the module.
+    pub fn is_exported(&self) -> bool {
+        !matches!(self, FunctionAccess::Private)
+    }
+}
+
+/// Describes a trait defined using `define-trait`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraitMetadata {
+    pub name: ClarityName,
+    pub functions: BTreeMap<ClarityName, FunctionSignature>,
+}
+
+/// Describes a data variable defined using `define-data-var`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataVarMetadata {
+    pub name: ClarityName,
+    pub value_type: TypeSignature,
+}
+
+/// Describes a data map defined using `define-map`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataMapMetadata {
+    pub name: ClarityName,
+    pub key_type: TypeSignature,
+    pub value_type: TypeSignature,
 }
 
 #[derive(Debug)]
@@ -31,9 +292,59 @@ pub struct AnalyzeResult {
     pub contract_analysis: ContractAnalysis,
 }
 
+/// An error preparing a context for a compiled module (see
+/// `CompileResult::prepare_context`), as an id interned in the context doesn't match
+/// the one which the module references it by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrepareContextError {
+    NameIdMismatch {
+        name: ClarityName,
+        expected: i32,
+        actual: i32,
+    },
+    ContractIdMismatch {
+        contract_id: QualifiedContractIdentifier,
+        expected: i32,
+        actual: i32,
+    },
+}
+
+impl fmt::Display for PrepareContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrepareContextError::NameIdMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "name `{name}` was interned as {actual}, but the module references it as \
+                 {expected}"
+            ),
+            PrepareContextError::ContractIdMismatch {
+                contract_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "contract `{contract_id}` was interned as {actual}, but the module \
+                 references it as {expected}"
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CompileError {
-    Wasm(WasmGenerationError),
+    Wasm(GenerationError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Wasm(err) => write!(f, "{}", err),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -48,6 +359,10 @@ pub enum WasmGenerationError {
     UnknownFunction(String),
     InternalError(String),
     EmptyListTraversal,
+    UnknownVariable(String),
+    NotInFunction(String),
+    UnsupportedByConvention(String, CodegenConvention),
+    StdlibLink(StdlibLinkError),
 }
 
 impl DiagnosableError for WasmGenerationError {
@@ -59,11 +374,142 @@ impl DiagnosableError for WasmGenerationError {
                 "Attempted to traverse an empty list".to_string()
             }
             WasmGenerationError::UnknownFunction(name) => format!("Unknown function: {}", name),
+            WasmGenerationError::UnknownVariable(name) => format!("Unknown variable: {}", name),
+            WasmGenerationError::NotInFunction(expr) => {
+                format!("Expression must be in a function body: {}", expr)
+            }
+            WasmGenerationError::UnsupportedByConvention(what, convention) => {
+                format!(
+                    "{} is not supported by the {:?} convention",
+                    what, convention
+                )
+            }
+            WasmGenerationError::StdlibLink(err) => format!("Failed to link wasm-stdlib: {}", err),
         }
     }
 
     fn suggestion(&self) -> Option<String> {
-        None
+        match self {
+            WasmGenerationError::UnknownFunction(name) => Some(format!(
+                "Check that `{}` is a native function or defined by the contract",
+                name
+            )),
+            WasmGenerationError::UnknownVariable(name) => Some(format!(
+                "Check that `{}` is bound by a `let`, a `match` or a function parameter",
+                name
+            )),
+            WasmGenerationError::NotInFunction(expr) => {
+                Some(format!("Move the `{}` into a function body", expr))
+            }
+            WasmGenerationError::UnsupportedByConvention(_, CodegenConvention::Externref) => None,
+            WasmGenerationError::UnsupportedByConvention(..) => {
+                Some("Use the Externref convention, which supports all values".to_string())
+            }
+            WasmGenerationError::StdlibLink(StdlibLinkError::NotBuilt) => {
+                Some("Build wasm-stdlib and enable the `stdlib` feature to embed it".to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An error generating the module for a contract, located at the innermost
+/// expression whose code couldn't be generated.
+#[derive(Debug)]
+pub struct GenerationError {
+    pub kind: WasmGenerationError,
+    /// The span of the offending expression, if the error was caused by one.
+    pub span: Option<Span>,
+    /// The name of the offending construct, e.g. the function called or defined by
+    /// the expression.
+    pub construct: Option<String>,
+    /// How the contract could be changed to avoid the error, if known.
+    pub suggestion: Option<String>,
+    /// The line of the contract's source which the span starts on. See `with_source`.
+    pub source_line: Option<String>,
+}
+
+impl GenerationError {
+    /// Creates an error located at the expression with the given span, which is an
+    /// instance of the given construct.
+    pub fn at(kind: WasmGenerationError, span: Span, construct: String) -> Self {
+        GenerationError {
+            span: Some(span),
+            construct: Some(construct),
+            ..GenerationError::from(kind)
+        }
+    }
+
+    /// Attaches the line of the contract's source which the error's span starts on,
+    /// so that it's printed along with the error.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source_line = self
+            .span
+            .as_ref()
+            .and_then(|span| source.lines().nth(span.start_line.checked_sub(1)? as usize))
+            .map(str::to_string);
+        self
+    }
+}
+
+impl From<WasmGenerationError> for GenerationError {
+    fn from(kind: WasmGenerationError) -> Self {
+        GenerationError {
+            suggestion: kind.suggestion(),
+            kind,
+            span: None,
+            construct: None,
+            source_line: None,
+        }
+    }
+}
+
+/// Prints the error along with its location and, if attached, the offending source
+/// line with the expression underlined:
+///
+/// ```text
+/// error: `mul` is not supported by the Memory convention (in `*`)
+///  --> 2:3
+///   |
+/// 2 |   (* a a))
+///   |   ^^^^^^^
+///  = suggestion: Use the Externref convention, which supports all values
+/// ```
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {}", self.kind.message())?;
+        if let Some(construct) = &self.construct {
+            write!(f, " (in `{}`)", construct)?;
+        }
+
+        if let Some(span) = &self.span {
+            write!(f, "\n --> {}:{}", span.start_line, span.start_column)?;
+            if let Some(line) = &self.source_line {
+                // Spans are 1-based and inclusive. An expression spanning several lines
+                // is underlined up to the end of its first line.
+                let start = span.start_column.saturating_sub(1) as usize;
+                let end = if span.end_line == span.start_line {
+                    span.end_column as usize
+                } else {
+                    line.chars().count()
+                };
+                let gutter = " ".repeat(span.start_line.to_string().len());
+                write!(
+                    f,
+                    "\n{gutter} |\n{} | {}\n{gutter} | {}{}",
+                    span.start_line,
+                    line,
+                    " ".repeat(start),
+                    "^".repeat(end.saturating_sub(start).max(1)),
+                )?;
+            }
+        }
+
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n = suggestion: {}", suggestion)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -74,21 +520,52 @@ pub(crate) struct WasmFunctionContext {
     pub name: String,
     pub locals: Vec<LocalId>,
     pub params: Vec<ParameterDefinition>,
+    /// The names bound by the enclosing `let` expressions, innermost last, along
+    /// with the locals holding their values.
+    pub bindings: Vec<(String, Vec<LocalId>)>,
+}
+
+impl WasmFunctionContext {
+    /// Resolves a variable referenced by name in the function body to the locals
+    /// holding its value. `let` bindings shadow outer bindings and parameters.
+    pub fn resolve(&self, name: &str) -> Option<&[LocalId]> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(binding, _)| binding == name)
+            .map(|(_, local_ids)| local_ids.as_slice())
+            .or_else(|| {
+                self.params
+                    .iter()
+                    .find(|param| param.name == name)
+                    .map(|param| param.local_ids.as_slice())
+            })
+    }
 }
 
 #[derive(Debug)]
 pub struct ParameterDefinition {
     pub name: String,
-    pub val_type: ValType,
-    pub local_id: LocalId,
+    /// The Clarity type of the parameter, as inferred by the type checker.
+    pub type_signature: TypeSignature,
+    /// The Wasm types which the parameter's value is passed as, which depend on the
+    /// `CodegenConvention`, along with the locals holding them.
+    pub val_types: Vec<ValType>,
+    pub local_ids: Vec<LocalId>,
 }
 
 impl ParameterDefinition {
-    pub fn new(name: &str, val_type: ValType, local_id: LocalId) -> Self {
+    pub fn new(
+        name: &str,
+        type_signature: TypeSignature,
+        val_types: Vec<ValType>,
+        local_ids: Vec<LocalId>,
+    ) -> Self {
         ParameterDefinition {
             name: name.to_string(),
-            val_type,
-            local_id,
+            type_signature,
+            val_types,
+            local_ids,
         }
     }
 }
@@ -184,15 +661,36 @@ pub fn analyze_contract(
 
 /// Compile a WASM binary from the provided `ContractAnalysis`.
 pub fn compile(contract_analysis: &ContractAnalysis) -> Result<CompileResult, CompileError> {
-    let mut generator = WasmGenerator::new();
+    compile_with_convention(contract_analysis, CodegenConvention::default())
+}
+
+/// Compile a WASM binary from the provided `ContractAnalysis`, passing values to the
+/// host functions using the given `CodegenConvention`.
+pub fn compile_with_convention(
+    contract_analysis: &ContractAnalysis,
+    convention: CodegenConvention,
+) -> Result<CompileResult, CompileError> {
+    compile_with_options(
+        contract_analysis,
+        CodegenOptions {
+            convention,
+            ..Default::default()
+        },
+    )
+}
+
+/// Compile a WASM binary from the provided `ContractAnalysis` with the given
+/// `CodegenOptions`.
+pub fn compile_with_options(
+    contract_analysis: &ContractAnalysis,
+    options: CodegenOptions,
+) -> Result<CompileResult, CompileError> {
+    let mut generator = WasmGenerator::with_convention(options.convention)
+        .with_sequence_lowering(options.sequence_lowering);
 
     generator
         .generate(contract_analysis.clone())
         .map_err(CompileError::Wasm)?;
 
-    let module_bytes = generator.finalize();
-
-    Ok(CompileResult {
-        module_bytes: module_bytes.clone(),
-    })
+    Ok(generator.into_compile_result())
 }
@@ -0,0 +1,368 @@
+use std::{collections::HashMap, io, sync::OnceLock};
+
+#[cfg(feature = "serde")]
+use std::{fs, path::PathBuf};
+
+use clarity::{util::hash::Sha256Sum, vm::analysis::ContractAnalysis};
+use wasmtime::{Engine, Module};
+
+use super::{compile_with_options, CodegenOptions, CompileError, CompileResult};
+use crate::stdlib::STDLIB_WASM;
+
+/// A SHA-256 hash identifying an entry of a `CompilationCache`.
+///
+/// The result of compiling a contract is identified by the hash of its source along
+/// with everything else which affects the generated module: the contract's
+/// identifier (against which relative contract references are resolved), the
+/// Clarity version and epoch it was analyzed with, the `CodegenOptions`, the version
+/// of this crate and the embedded `wasm-stdlib` module (which is linked into the
+/// generated modules). The artifact precompiled from a module is identified by the
+/// hash of the module itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    /// Computes the key of compiling the given source, which the `ContractAnalysis`
+    /// was produced from, with the given options.
+    pub fn new(
+        source: &str,
+        contract_analysis: &ContractAnalysis,
+        options: CodegenOptions,
+    ) -> Self {
+        let fields = [
+            env!("CARGO_PKG_VERSION").to_string(),
+            stdlib_hash().to_string(),
+            contract_analysis.contract_identifier.to_string(),
+            contract_analysis.clarity_version.to_string(),
+            contract_analysis.epoch.to_string(),
+            format!("{:?}", options.convention),
+            format!("{:?}", options.sequence_lowering),
+        ];
+
+        let mut data = Vec::with_capacity(source.len() + 128);
+        for field in fields.iter().map(String::as_str).chain([source]) {
+            // Each field is length-prefixed, so that different fields can't produce
+            // the same data.
+            data.extend((field.len() as u64).to_le_bytes());
+            data.extend(field.as_bytes());
+        }
+
+        CacheKey::of(&data)
+    }
+
+    /// Computes the key of the artifact precompiled from the given module.
+    pub fn for_module(module_bytes: &[u8]) -> Self {
+        CacheKey::of(module_bytes)
+    }
+
+    fn of(data: &[u8]) -> Self {
+        CacheKey(sha256(data))
+    }
+
+    /// Gets the hash as a lowercase hex string, e.g. to name the files of an entry.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut digest = [0; 32];
+    digest.copy_from_slice(Sha256Sum::from_data(data).as_bytes());
+    digest
+}
+
+/// Gets the SHA-256 hash of the embedded `wasm-stdlib` module, as a hex string.
+fn stdlib_hash() -> &'static str {
+    static STDLIB_HASH: OnceLock<String> = OnceLock::new();
+    STDLIB_HASH.get_or_init(|| Sha256Sum::from_data(STDLIB_WASM).to_hex())
+}
+
+/// Storage for the entries of a `CompilationCache`: the results of compiling
+/// contracts and the artifacts precompiled from their modules by
+/// `Engine::precompile_module`.
+///
+/// # Safety
+///
+/// As the artifacts are loaded as native code, `get_precompiled` must only return an
+/// artifact stored by `put_precompiled` under the same key, unmodified. A backend
+/// storing them where they may be tampered with or corrupted must detect it, as
+/// `DiskCacheBackend` does.
+pub unsafe trait CacheBackend {
+    /// Gets the cached result of the compilation with the given key.
+    fn get_compile_result(&self, key: &CacheKey) -> Option<CompileResult>;
+
+    /// Stores the result of the compilation with the given key.
+    fn put_compile_result(
+        &mut self,
+        key: &CacheKey,
+        compile_result: &CompileResult,
+    ) -> io::Result<()>;
+
+    /// Gets the artifact precompiled from the module with the given key.
+    fn get_precompiled(&self, key: &CacheKey) -> Option<Vec<u8>>;
+
+    /// Stores the artifact precompiled from the module with the given key.
+    ///
+    /// # Safety
+    ///
+    /// The artifact must have been produced by `Engine::precompile_module`.
+    unsafe fn put_precompiled(&mut self, key: &CacheKey, artifact: &[u8]) -> io::Result<()>;
+}
+
+/// A `CacheBackend` which keeps its entries in memory, for the lifetime of the cache.
+#[derive(Debug, Default)]
+pub struct MemoryCacheBackend {
+    compile_results: HashMap<CacheKey, CompileResult>,
+    precompiled: HashMap<CacheKey, Vec<u8>>,
+}
+
+impl MemoryCacheBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// SAFETY: the artifacts are held in memory, and returned as they were stored.
+unsafe impl CacheBackend for MemoryCacheBackend {
+    fn get_compile_result(&self, key: &CacheKey) -> Option<CompileResult> {
+        self.compile_results.get(key).cloned()
+    }
+
+    fn put_compile_result(
+        &mut self,
+        key: &CacheKey,
+        compile_result: &CompileResult,
+    ) -> io::Result<()> {
+        self.compile_results.insert(*key, compile_result.clone());
+        Ok(())
+    }
+
+    fn get_precompiled(&self, key: &CacheKey) -> Option<Vec<u8>> {
+        self.precompiled.get(key).cloned()
+    }
+
+    unsafe fn put_precompiled(&mut self, key: &CacheKey, artifact: &[u8]) -> io::Result<()> {
+        self.precompiled.insert(*key, artifact.to_vec());
+        Ok(())
+    }
+}
+
+/// A `CacheBackend` which stores each entry as files named after its key in a
+/// directory, so that it persists across restarts: the module in `<key>.wasm`, the
+/// rest of the `CompileResult` as JSON in `<key>.json` and the precompiled artifact
+/// in `<key>.cwasm`.
+///
+/// Each file starts with an HMAC-SHA256 of its key, extension and contents under a
+/// secret key, and files whose MAC doesn't match (e.g. because they were tampered
+/// with or corrupted) are treated as missing. As the artifacts are loaded as native
+/// code, the secret key must be kept from anything which can write to the directory.
+#[cfg(feature = "serde")]
+pub struct DiskCacheBackend {
+    dir: PathBuf,
+    secret: [u8; 32],
+}
+
+#[cfg(feature = "serde")]
+impl DiskCacheBackend {
+    /// Creates a backend storing its entries in the given directory, creating it if
+    /// it doesn't exist, and authenticating them with the given secret key. The same
+    /// key must be used to read the entries back, e.g. once the node restarts.
+    pub fn new(dir: impl Into<PathBuf>, secret: [u8; 32]) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(DiskCacheBackend { dir, secret })
+    }
+
+    fn path(&self, key: &CacheKey, extension: &str) -> PathBuf {
+        self.dir.join(key.to_hex()).with_extension(extension)
+    }
+
+    /// Computes the HMAC-SHA256 of the file with the given key, extension and
+    /// contents, so that a file can't be passed off as another one either.
+    fn mac(&self, key: &CacheKey, extension: &str, contents: &[u8]) -> [u8; 32] {
+        const BLOCK_SIZE: usize = 64;
+        let mut padded_secret = [0; BLOCK_SIZE];
+        padded_secret[..self.secret.len()].copy_from_slice(&self.secret);
+
+        let mut inner = Vec::with_capacity(BLOCK_SIZE + 40 + contents.len());
+        inner.extend(padded_secret.iter().map(|byte| byte ^ 0x36));
+        inner.extend(key.0);
+        inner.extend(extension.as_bytes());
+        inner.push(0);
+        inner.extend(contents);
+
+        let mut outer = Vec::with_capacity(BLOCK_SIZE + 32);
+        outer.extend(padded_secret.iter().map(|byte| byte ^ 0x5c));
+        outer.extend(sha256(&inner));
+        sha256(&outer)
+    }
+
+    /// Reads the contents of the file, if it exists and its MAC matches.
+    fn read(&self, key: &CacheKey, extension: &str) -> Option<Vec<u8>> {
+        let mut contents = fs::read(self.path(key, extension)).ok()?;
+        if contents.len() < 32 {
+            return None;
+        }
+
+        let expected = self.mac(key, extension, &contents[32..]);
+        // The MACs are compared in constant time, so as not to leak how much of a
+        // forged one is right.
+        let diff = expected
+            .iter()
+            .zip(&contents[..32])
+            .fold(0, |diff, (a, b)| diff | (a ^ b));
+        if diff != 0 {
+            return None;
+        }
+
+        contents.drain(..32);
+        Some(contents)
+    }
+
+    /// Writes the file, preceded by its MAC, via a temporary file, so that a
+    /// partially written file is never read back.
+    fn write(&self, key: &CacheKey, extension: &str, contents: &[u8]) -> io::Result<()> {
+        let path = self.path(key, extension);
+        let tmp_path = path.with_extension(format!("{extension}.tmp"));
+
+        let mut data = Vec::with_capacity(32 + contents.len());
+        data.extend(self.mac(key, extension, contents));
+        data.extend(contents);
+        fs::write(&tmp_path, data)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Debug for DiskCacheBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The secret key is left out.
+        f.debug_struct("DiskCacheBackend")
+            .field("dir", &self.dir)
+            .finish_non_exhaustive()
+    }
+}
+
+// SAFETY: the artifacts are only returned if their MAC, which can't be forged
+// without the secret key, matches.
+#[cfg(feature = "serde")]
+unsafe impl CacheBackend for DiskCacheBackend {
+    fn get_compile_result(&self, key: &CacheKey) -> Option<CompileResult> {
+        let metadata = self.read(key, "json")?;
+        let mut compile_result: CompileResult = serde_json::from_slice(&metadata).ok()?;
+        compile_result.module_bytes = self.read(key, "wasm")?;
+        Some(compile_result)
+    }
+
+    fn put_compile_result(
+        &mut self,
+        key: &CacheKey,
+        compile_result: &CompileResult,
+    ) -> io::Result<()> {
+        let metadata = serde_json::to_vec(compile_result)?;
+        // The module is written first, as the entry is only read once its metadata
+        // exists.
+        self.write(key, "wasm", &compile_result.module_bytes)?;
+        self.write(key, "json", &metadata)
+    }
+
+    fn get_precompiled(&self, key: &CacheKey) -> Option<Vec<u8>> {
+        self.read(key, "cwasm")
+    }
+
+    unsafe fn put_precompiled(&mut self, key: &CacheKey, artifact: &[u8]) -> io::Result<()> {
+        self.write(key, "cwasm", artifact)
+    }
+}
+
+/// The number of lookups of a `CompilationCache` which found a cached entry, and
+/// which didn't.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub compile_hits: usize,
+    pub compile_misses: usize,
+    pub precompile_hits: usize,
+    pub precompile_misses: usize,
+}
+
+/// Caches the modules generated for contracts, along with their artifacts
+/// precompiled by Wasmtime, so that deploying a contract identical to one which was
+/// already compiled skips both code generation and `Engine::precompile_module`.
+///
+/// Writing to the backend is best-effort: if an entry can't be stored, the
+/// compilation still succeeds and is simply repeated next time.
+#[derive(Debug, Default)]
+pub struct CompilationCache<B: CacheBackend = MemoryCacheBackend> {
+    backend: B,
+    stats: CacheStats,
+}
+
+impl<B: CacheBackend> CompilationCache<B> {
+    pub fn new(backend: B) -> Self {
+        CompilationCache {
+            backend,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Gets the backend storing the cache's entries.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Gets the number of hits and misses of the cache so far.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Compiles the contract with the given source, which the `ContractAnalysis` was
+    /// produced from, returning the cached result if it was already compiled with
+    /// the same options.
+    pub fn compile(
+        &mut self,
+        source: &str,
+        contract_analysis: &ContractAnalysis,
+        options: CodegenOptions,
+    ) -> Result<CompileResult, CompileError> {
+        let key = CacheKey::new(source, contract_analysis, options);
+        if let Some(compile_result) = self.backend.get_compile_result(&key) {
+            self.stats.compile_hits += 1;
+            return Ok(compile_result);
+        }
+
+        self.stats.compile_misses += 1;
+        let compile_result = compile_with_options(contract_analysis, options)?;
+        let _ = self.backend.put_compile_result(&key, &compile_result);
+        Ok(compile_result)
+    }
+
+    /// Loads the compiled module into the engine, from the artifact precompiled from
+    /// it if one is cached. Otherwise, or if the artifact can't be loaded by the
+    /// engine (e.g. it was precompiled by another version of Wasmtime or with another
+    /// configuration), the module is precompiled and its artifact cached.
+    pub fn load_module(
+        &mut self,
+        engine: &Engine,
+        compile_result: &CompileResult,
+    ) -> wasmtime::Result<Module> {
+        let module_bytes = compile_result.module_bytes();
+        let key = CacheKey::for_module(module_bytes);
+        if let Some(artifact) = self.backend.get_precompiled(&key) {
+            // SAFETY: the backend only returns the artifacts stored by
+            // `put_precompiled` (see `CacheBackend`), which were produced by
+            // `Engine::precompile_module`. The engine checks that they're compatible
+            // with its own configuration.
+            if let Ok(module) = unsafe { Module::deserialize(engine, &artifact) } {
+                self.stats.precompile_hits += 1;
+                return Ok(module);
+            }
+        }
+
+        self.stats.precompile_misses += 1;
+        let artifact = engine.precompile_module(module_bytes)?;
+        // SAFETY: the artifact was just produced by `Engine::precompile_module`.
+        let _ = unsafe { self.backend.put_precompiled(&key, &artifact) };
+        // SAFETY: the artifact was just produced by the same engine.
+        unsafe { Module::deserialize(engine, &artifact) }
+    }
+}
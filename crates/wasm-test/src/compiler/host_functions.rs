@@ -0,0 +1,368 @@
+use walrus::{FunctionId, ValType};
+
+use super::{
+    wasm_generator::WasmGenerator, CodegenConvention, WasmGenerationError, WasmGenerationResult,
+};
+use crate::runtime::HOST_MODULE_NAME;
+
+/// Describes the Wasm signature of a host function (as defined in
+/// `runtime::native_functions`) so that it can be imported by generated modules.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HostFunctionSignature {
+    pub name: &'static str,
+    pub params: &'static [ValType],
+    pub results: &'static [ValType],
+}
+
+/// Signatures shared by host functions which take one or two `ExternRef`s and return one.
+const EXTREF_UNARY_PARAMS: &[ValType] = &[ValType::Externref];
+const EXTREF_BINARY_PARAMS: &[ValType] = &[ValType::Externref, ValType::Externref];
+const EXTREF_RESULT: &[ValType] = &[ValType::Externref];
+/// Signatures shared by the `RustRefStack` host functions which take two integers,
+/// as low/high `i64` pairs, and return one.
+const NATIVE_BINARY_PARAMS: &[ValType] = &[ValType::I64, ValType::I64, ValType::I64, ValType::I64];
+const NATIVE_RESULT: &[ValType] = &[ValType::I64, ValType::I64];
+/// The result of the `RustRefStack` comparison host functions, a `bool` as an `i32`.
+const NATIVE_BOOL_RESULT: &[ValType] = &[ValType::I32];
+/// Signatures shared by the `Memory` host functions which take two values, as
+/// offset/length pairs, and return a `FuncResult`.
+const MEMORY_BINARY_PARAMS: &[ValType] = &[ValType::I32, ValType::I32, ValType::I32, ValType::I32];
+const MEMORY_RESULT: &[ValType] = &[ValType::I32, ValType::I32, ValType::I32];
+/// Parameters shared by host functions which apply a function to a sequence.
+const FUNCREF_EXTREF_PARAMS: &[ValType] = &[ValType::Funcref, ValType::Externref];
+
+macro_rules! extref_unary {
+    ($name:literal) => {
+        HostFunctionSignature {
+            name: $name,
+            params: EXTREF_UNARY_PARAMS,
+            results: EXTREF_RESULT,
+        }
+    };
+}
+
+macro_rules! native_binary {
+    ($name:literal) => {
+        HostFunctionSignature {
+            name: $name,
+            params: NATIVE_BINARY_PARAMS,
+            results: NATIVE_RESULT,
+        }
+    };
+}
+
+macro_rules! native_comparison {
+    ($name:literal) => {
+        HostFunctionSignature {
+            name: $name,
+            params: NATIVE_BINARY_PARAMS,
+            results: NATIVE_BOOL_RESULT,
+        }
+    };
+}
+
+macro_rules! memory_binary {
+    ($name:literal) => {
+        HostFunctionSignature {
+            name: $name,
+            params: MEMORY_BINARY_PARAMS,
+            results: MEMORY_RESULT,
+        }
+    };
+}
+
+macro_rules! extref_binary {
+    ($name:literal) => {
+        HostFunctionSignature {
+            name: $name,
+            params: EXTREF_BINARY_PARAMS,
+            results: EXTREF_RESULT,
+        }
+    };
+}
+
+/// The host functions which may be imported by generated modules. These must match
+/// the signatures of the functions registered by `get_all_functions`.
+pub(crate) const HOST_FUNCTION_SIGNATURES: &[HostFunctionSignature] = &[
+    extref_binary!("add_extref"),
+    native_binary!("add_native"),
+    memory_binary!("add_memory"),
+    extref_binary!("sub_extref"),
+    native_binary!("sub_native"),
+    memory_binary!("sub_memory"),
+    extref_binary!("mul_extref"),
+    extref_binary!("div_extref"),
+    memory_binary!("div_memory"),
+    extref_binary!("mod_extref"),
+    memory_binary!("mod_memory"),
+    extref_binary!("gt_extref"),
+    extref_binary!("ge_extref"),
+    extref_binary!("lt_extref"),
+    extref_binary!("le_extref"),
+    extref_binary!("is_eq_extref"),
+    native_comparison!("gt_native"),
+    native_comparison!("ge_native"),
+    native_comparison!("lt_native"),
+    native_comparison!("le_native"),
+    native_comparison!("is_eq_native"),
+    extref_unary!("not_extref"),
+    extref_unary!("len_extref"),
+    extref_binary!("element_at_extref"),
+    HostFunctionSignature {
+        name: "seq_len_extref",
+        params: EXTREF_UNARY_PARAMS,
+        results: &[ValType::I32],
+    },
+    HostFunctionSignature {
+        name: "seq_get_extref",
+        params: &[ValType::Externref, ValType::I32],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "slice_extref",
+        params: &[ValType::Externref, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    extref_binary!("index_of_extref"),
+    extref_binary!("append_extref"),
+    extref_binary!("list_push_extref"),
+    extref_unary!("list_build_extref"),
+    extref_binary!("concat_extref"),
+    extref_unary!("to_int_extref"),
+    extref_unary!("to_uint_extref"),
+    extref_unary!("buff_to_int_le_extref"),
+    extref_unary!("buff_to_int_be_extref"),
+    extref_unary!("buff_to_uint_le_extref"),
+    extref_unary!("buff_to_uint_be_extref"),
+    extref_unary!("int_to_buff_le_extref"),
+    extref_unary!("int_to_buff_be_extref"),
+    extref_unary!("int_to_ascii_extref"),
+    extref_unary!("int_to_utf8_extref"),
+    extref_unary!("string_to_int_extref"),
+    extref_unary!("string_to_uint_extref"),
+    extref_unary!("sha256_extref"),
+    extref_unary!("sha512_extref"),
+    extref_unary!("sha512_256_extref"),
+    extref_unary!("hash160_extref"),
+    extref_unary!("keccak256_extref"),
+    extref_unary!("is_some_extref"),
+    extref_unary!("is_none_extref"),
+    extref_unary!("is_ok_extref"),
+    extref_unary!("is_err_extref"),
+    extref_unary!("unwrap_extref"),
+    extref_unary!("unwrap_err_extref"),
+    extref_unary!("ok_extref"),
+    extref_unary!("err_extref"),
+    extref_unary!("some_extref"),
+    HostFunctionSignature {
+        name: "to_bool_extref",
+        params: EXTREF_UNARY_PARAMS,
+        results: &[ValType::I32],
+    },
+    HostFunctionSignature {
+        name: "is_success_extref",
+        params: EXTREF_UNARY_PARAMS,
+        results: &[ValType::I32],
+    },
+    HostFunctionSignature {
+        name: "tuple_get_extref",
+        params: &[ValType::Externref, ValType::I32],
+        results: EXTREF_RESULT,
+    },
+    extref_binary!("merge_extref"),
+    extref_unary!("principal_of_extref"),
+    extref_unary!("principal_destruct_extref"),
+    HostFunctionSignature {
+        name: "principal_of_memory",
+        params: &[ValType::I32, ValType::I32],
+        results: MEMORY_RESULT,
+    },
+    HostFunctionSignature {
+        name: "principal_destruct_memory",
+        params: &[ValType::I32, ValType::I32],
+        results: MEMORY_RESULT,
+    },
+    HostFunctionSignature {
+        name: "principal_construct_extref",
+        params: &[ValType::Externref, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "principal_construct_memory",
+        params: &[
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+        ],
+        results: MEMORY_RESULT,
+    },
+    extref_binary!("secp256k1_recover_extref"),
+    memory_binary!("secp256k1_recover_memory"),
+    HostFunctionSignature {
+        name: "secp256k1_verify_extref",
+        params: &[ValType::Externref, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "secp256k1_verify_memory",
+        params: &[
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+        ],
+        results: MEMORY_RESULT,
+    },
+    HostFunctionSignature {
+        name: "map_get_extref",
+        params: &[ValType::I32, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "map_set_extref",
+        params: &[ValType::I32, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "map_insert_extref",
+        params: &[ValType::I32, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "map_delete_extref",
+        params: &[ValType::I32, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "var_get_extref",
+        params: &[ValType::I32],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "var_set_extref",
+        params: &[ValType::I32, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "get_block_info_extref",
+        params: &[ValType::I32, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "get_burn_block_info_extref",
+        params: &[ValType::I32, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "at_block_extref",
+        params: &[ValType::Externref, ValType::Funcref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "contract_call_extref",
+        params: &[ValType::Externref, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "contract_call_static_extref",
+        params: &[ValType::I32, ValType::I32, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    extref_binary!("push_arg_extref"),
+    HostFunctionSignature {
+        name: "map_extref",
+        params: FUNCREF_EXTREF_PARAMS,
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "filter_extref",
+        params: FUNCREF_EXTREF_PARAMS,
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "fold_extref",
+        params: &[ValType::Funcref, ValType::Externref, ValType::Externref],
+        results: EXTREF_RESULT,
+    },
+    HostFunctionSignature {
+        name: "drop_ptrs_memory",
+        params: &[ValType::I32, ValType::I32],
+        results: MEMORY_RESULT,
+    },
+];
+
+/// Looks up the signature of the host function with the given name.
+pub(crate) fn get_host_function_signature(name: &str) -> Option<&'static HostFunctionSignature> {
+    HOST_FUNCTION_SIGNATURES.iter().find(|sig| sig.name == name)
+}
+
+impl WasmGenerator {
+    /// Imports the host function with the given name into the module, returning its
+    /// `FunctionId`. Each host function is only imported once, regardless of how many
+    /// times it is requested. Only the host functions of the generator's
+    /// `CodegenConvention` may be imported.
+    pub(crate) fn import_host_function(
+        &mut self,
+        name: &str,
+    ) -> Result<FunctionId, WasmGenerationError> {
+        if let Some(function_id) = self.host_functions.get(name) {
+            return Ok(*function_id);
+        }
+
+        let signature = get_host_function_signature(name)
+            .ok_or_else(|| WasmGenerationError::UnknownFunction(name.to_string()))?;
+
+        if !name.ends_with(self.convention.host_function_suffix()) {
+            return Err(WasmGenerationError::UnsupportedByConvention(
+                format!("Host function {}", name),
+                self.convention,
+            ));
+        }
+
+        let ty = self.module.types.add(signature.params, signature.results);
+        let (function_id, _) = self.module.add_import_func(HOST_MODULE_NAME, name, ty);
+        self.module.funcs.get_mut(function_id).name = Some(name.to_string());
+        self.host_functions.insert(name.to_string(), function_id);
+
+        Ok(function_id)
+    }
+
+    /// Emits a call to the given host function (e.g. `add`) of the generator's
+    /// `CodegenConvention`, whose arguments are on the stack, leaving its result on the
+    /// stack. A `FuncResult` returned by a `Memory` host function is unpacked, trapping
+    /// if it holds an error.
+    pub(crate) fn call_convention_function(&mut self, name: &str) -> WasmGenerationResult {
+        let full_name = format!("{}{}", name, self.convention.host_function_suffix());
+        let function_id = match get_host_function_signature(&full_name) {
+            Some(_) => self.import_host_function(&full_name)?,
+            None => {
+                return Err(WasmGenerationError::UnsupportedByConvention(
+                    format!("`{}`", name),
+                    self.convention,
+                ))
+            }
+        };
+        self.get_function().call(function_id);
+
+        if self.convention == CodegenConvention::Memory {
+            let result_locals = self.add_value_locals(None)?;
+            self.set_value_locals(&result_locals);
+            // The error code is non-zero if the host function failed.
+            self.get_function().if_else(
+                None,
+                |failure| {
+                    failure.unreachable();
+                },
+                |_| {},
+            );
+            self.get_value_locals(&result_locals);
+        }
+
+        Ok(())
+    }
+}
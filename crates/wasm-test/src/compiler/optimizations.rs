@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+
+use walrus::{
+    ir::{
+        dfs_in_order, dfs_pre_order_mut, Instr, InstrLocId, InstrSeq, InstrSeqId, Visitor,
+        VisitorMut,
+    },
+    LocalFunction, LocalId, Module, ValType,
+};
+
+use super::wasm_generator::WasmGenerator;
+
+/// The sizes of a module before and after it was optimized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub size_before: usize,
+    pub size_after: usize,
+}
+
+impl OptimizationReport {
+    /// Gets the number of bytes saved by the optimizations.
+    pub fn bytes_saved(&self) -> usize {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+impl WasmGenerator {
+    /// Optimizes the module generated so far, returning its size before and after:
+    /// - instructions following an unconditional branch (e.g. a `return`), which
+    ///   can't be reached, are removed;
+    /// - locals whose values are never needed at the same time are coalesced;
+    /// - items which aren't used, such as the host functions the contract never calls
+    ///   and functions which are neither exported nor called, are removed.
+    ///
+    /// This must be called once the module is complete, as functions which aren't
+    /// exported yet may be removed, along with host functions which were imported
+    /// but aren't called yet.
+    pub fn optimize(&mut self) -> OptimizationReport {
+        let size_before = self.emit_wasm().len();
+
+        for (_, func) in self.module.funcs.iter_local_mut() {
+            remove_unreachable_code(func);
+        }
+        coalesce_locals(&mut self.module);
+        walrus::passes::gc::run(&mut self.module);
+
+        OptimizationReport {
+            size_before,
+            size_after: self.emit_wasm().len(),
+        }
+    }
+}
+
+/// Removes the instructions following an unconditional branch in each of the
+/// function's instruction sequences.
+fn remove_unreachable_code(func: &mut LocalFunction) {
+    let mut collect = CollectSeqIds::default();
+    dfs_in_order(&mut collect, func, func.entry_block());
+
+    for seq_id in collect.seq_ids {
+        let instrs = &mut func.block_mut(seq_id).instrs;
+        let end = instrs.iter().position(|(instr, _)| {
+            matches!(
+                instr,
+                Instr::Br(_) | Instr::BrTable(_) | Instr::Return(_) | Instr::Unreachable(_)
+            )
+        });
+        if let Some(end) = end {
+            instrs.truncate(end + 1);
+        }
+    }
+}
+
+/// Collects the ids of a function's instruction sequences.
+#[derive(Default)]
+struct CollectSeqIds {
+    seq_ids: Vec<InstrSeqId>,
+}
+
+impl<'a> Visitor<'a> for CollectSeqIds {
+    fn start_instr_seq(&mut self, seq: &'a InstrSeq) {
+        self.seq_ids.push(seq.id());
+    }
+}
+
+/// Coalesces the locals of each function whose live ranges don't overlap, so that
+/// fewer locals are declared. The coalesced locals take the name of the local which
+/// they're merged into.
+fn coalesce_locals(module: &mut Module) {
+    for (_, func) in module.funcs.iter_local_mut() {
+        let mut ranges = LiveRanges::default();
+        dfs_in_order(&mut ranges, func, func.entry_block());
+
+        let args = func.args.iter().copied().collect::<HashSet<_>>();
+        let mut candidates = ranges
+            .ranges()
+            .into_iter()
+            .filter(|(local_id, _)| !args.contains(local_id))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(local_id, range)| (range.start, *local_id));
+
+        // Each local is merged into the first local of the same type whose range has
+        // ended by the time its own begins.
+        let mut merged = HashMap::new();
+        let mut available: Vec<(LocalId, ValType, usize)> = Vec::new();
+        for (local_id, range) in candidates {
+            let ty = module.locals.get(local_id).ty();
+            match available
+                .iter_mut()
+                .find(|(_, available_ty, end)| *available_ty == ty && *end < range.start)
+            {
+                Some((target, _, end)) => {
+                    merged.insert(local_id, *target);
+                    *end = range.end;
+                }
+                None => available.push((local_id, ty, range.end)),
+            }
+        }
+
+        if !merged.is_empty() {
+            let entry = func.entry_block();
+            dfs_pre_order_mut(&mut MergeLocals { merged: &merged }, func, entry);
+        }
+    }
+}
+
+/// The range of positions (in the order of a `dfs_in_order` traversal) at which a
+/// local is accessed.
+#[derive(Debug, Clone, Copy)]
+struct LiveRange {
+    start: usize,
+    end: usize,
+    /// The sequence of the first access.
+    seq_id: InstrSeqId,
+    /// Whether the local can be coalesced, i.e. its first access sets it and its other
+    /// accesses follow in the same sequence (or a nested one), so that they can't
+    /// observe the value of another local which it's merged with.
+    coalescable: bool,
+}
+
+/// Collects the live ranges of a function's locals.
+#[derive(Default)]
+struct LiveRanges {
+    position: usize,
+    /// The sequences being traversed, innermost last, with the positions at which
+    /// they were entered.
+    seqs: Vec<(InstrSeqId, usize)>,
+    loop_seqs: HashSet<InstrSeqId>,
+    /// The ranges of positions covered by loop bodies.
+    loops: Vec<(usize, usize)>,
+    ranges: HashMap<LocalId, LiveRange>,
+}
+
+impl LiveRanges {
+    fn access(&mut self, local_id: LocalId, is_set: bool) {
+        let Some(&(seq_id, _)) = self.seqs.last() else {
+            return;
+        };
+        let position = self.position;
+
+        match self.ranges.get_mut(&local_id) {
+            Some(range) => {
+                range.end = position;
+                range.coalescable &= self.seqs.iter().any(|(id, _)| *id == range.seq_id);
+            }
+            None => {
+                self.ranges.insert(
+                    local_id,
+                    LiveRange {
+                        start: position,
+                        end: position,
+                        seq_id,
+                        coalescable: is_set,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Gets the live ranges of the locals which can be coalesced. A local accessed in
+    /// a loop is live throughout the loop, as its value may be needed in the next
+    /// iteration.
+    fn ranges(&self) -> Vec<(LocalId, LiveRange)> {
+        let mut ranges = self
+            .ranges
+            .iter()
+            .filter(|(_, range)| range.coalescable)
+            .map(|(local_id, range)| (*local_id, *range))
+            .collect::<Vec<_>>();
+
+        for (_, range) in ranges.iter_mut() {
+            loop {
+                let (start, end) = self
+                    .loops
+                    .iter()
+                    .filter(|(loop_start, loop_end)| {
+                        *loop_start <= range.end && range.start <= *loop_end
+                    })
+                    .fold(
+                        (range.start, range.end),
+                        |(start, end), (loop_start, loop_end)| {
+                            (start.min(*loop_start), end.max(*loop_end))
+                        },
+                    );
+                if (start, end) == (range.start, range.end) {
+                    break;
+                }
+                (range.start, range.end) = (start, end);
+            }
+        }
+
+        ranges
+    }
+}
+
+impl<'a> Visitor<'a> for LiveRanges {
+    fn start_instr_seq(&mut self, seq: &'a InstrSeq) {
+        self.seqs.push((seq.id(), self.position));
+    }
+
+    fn end_instr_seq(&mut self, seq: &'a InstrSeq) {
+        if let Some((_, start)) = self.seqs.pop() {
+            if self.loop_seqs.contains(&seq.id()) {
+                self.loops.push((start, self.position));
+            }
+        }
+    }
+
+    fn visit_instr(&mut self, instr: &'a Instr, _loc: &'a InstrLocId) {
+        self.position += 1;
+        match instr {
+            Instr::LocalGet(get) => self.access(get.local, false),
+            Instr::LocalSet(set) => self.access(set.local, true),
+            Instr::LocalTee(tee) => self.access(tee.local, true),
+            Instr::Loop(body) => {
+                self.loop_seqs.insert(body.seq);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replaces references to coalesced locals with the locals they're merged into.
+struct MergeLocals<'a> {
+    merged: &'a HashMap<LocalId, LocalId>,
+}
+
+impl VisitorMut for MergeLocals<'_> {
+    fn visit_local_id_mut(&mut self, local: &mut LocalId) {
+        if let Some(target) = self.merged.get(local) {
+            *local = *target;
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::borrow::Cow;
+
+use clarity::vm::representations::Span;
+use walrus::{CodeTransform, CustomSection, IdsToIndices, InstrLocId};
+
+/// The name of the custom section holding the `SourceMap` of a generated module.
+pub const SOURCE_MAP_SECTION: &str = "clarity-source-map";
+
+/// Maps offsets of instructions in a generated module (as reported in Wasm backtraces)
+/// back to the spans of the Clarity expressions which they were generated from.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceMap {
+    /// The offset of the first instruction of each run of instructions generated from
+    /// the same expression, along with its span, ordered by offset.
+    entries: Vec<(u32, Span)>,
+}
+
+impl SourceMap {
+    /// Gets the span of the expression which the instruction at the given offset
+    /// (from the start of the module) was generated from.
+    pub fn span_at(&self, offset: usize) -> Option<&Span> {
+        let index = self
+            .entries
+            .partition_point(|(entry_offset, _)| *entry_offset as usize <= offset);
+        index.checked_sub(1).map(|index| &self.entries[index].1)
+    }
+
+    /// Gets whether the source map doesn't map any instructions.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Decodes the contents of a module's `SOURCE_MAP_SECTION`, returning `None` if
+    /// they're malformed.
+    pub fn decode(data: &[u8]) -> Option<SourceMap> {
+        let mut words = data
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+
+        let count = words.next()? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let offset = words.next()?;
+            let span = Span {
+                start_line: words.next()?,
+                start_column: words.next()?,
+                end_line: words.next()?,
+                end_column: words.next()?,
+            };
+            entries.push((offset, span));
+        }
+
+        Some(SourceMap { entries })
+    }
+
+    /// Encodes the source map as the number of entries followed by the entries, each
+    /// as its offset and the start and end of its span, all as little-endian `u32`s.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(4 + self.entries.len() * 20);
+        data.extend((self.entries.len() as u32).to_le_bytes());
+        for (offset, span) in &self.entries {
+            for word in [
+                *offset,
+                span.start_line,
+                span.start_column,
+                span.end_line,
+                span.end_column,
+            ] {
+                data.extend(word.to_le_bytes());
+            }
+        }
+        data
+    }
+}
+
+/// The custom section holding the `SourceMap` of the module being generated. The
+/// generated instructions are tagged with the ids of the spans (see `add_span`), which
+/// are resolved to the instructions' offsets once the module is emitted.
+#[derive(Debug, Default)]
+pub(crate) struct SourceMapSection {
+    spans: Vec<Span>,
+    pub(crate) source_map: SourceMap,
+}
+
+impl SourceMapSection {
+    /// Adds a span which instructions can be tagged with, returning its id.
+    pub(crate) fn add_span(&mut self, span: &Span) -> InstrLocId {
+        self.spans.push(span.clone());
+        InstrLocId::new((self.spans.len() - 1) as u32)
+    }
+}
+
+impl CustomSection for SourceMapSection {
+    fn name(&self) -> &str {
+        SOURCE_MAP_SECTION
+    }
+
+    fn data(&self, _ids_to_indices: &IdsToIndices) -> Cow<[u8]> {
+        Cow::Owned(self.source_map.encode())
+    }
+
+    fn apply_code_transform(&mut self, transform: &CodeTransform) {
+        let mut entries = transform
+            .instruction_map
+            .iter()
+            .filter(|(loc, _)| !loc.is_default())
+            .map(|(loc, offset)| (*offset as u32, self.spans[loc.data() as usize].clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(offset, _)| *offset);
+        // Consecutive instructions generated from the same expression share an entry.
+        entries.dedup_by(|(_, span), (_, previous)| span == previous);
+
+        self.source_map = SourceMap { entries };
+    }
+}
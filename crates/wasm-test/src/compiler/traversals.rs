@@ -1,40 +1,50 @@
 use clarity::vm::{
     functions::{define::DefineFunctions, NativeFunctions},
-    ClarityVersion, SymbolicExpression, SymbolicExpressionType,
+    types::{PrincipalData, TraitDefinition},
+    ClarityName, SymbolicExpression, SymbolicExpressionType, Value,
+};
+use walrus::{
+    ir::{BinaryOp, IfElse, Loop},
+    FunctionId, LocalId, ValType,
 };
-use walrus::ValType;
 
 use super::{
-    wasm_generator::WasmGenerator, ParameterDefinition, WasmGenerationError, WasmGenerationResult,
+    wasm_generator::WasmGenerator, FunctionAccess, FunctionInterface, ParameterDefinition,
+    SequenceLowering, WasmGenerationError, WasmGenerationResult,
 };
 
 impl WasmGenerator {
-    /// Traverses the provided expression. recursively.
+    /// Traverses the provided expression. recursively. The instructions generated from
+    /// it are mapped to its span in the source map. If the traversal fails, the
+    /// innermost failing expression is recorded so that the error can be located.
     pub(crate) fn traverse_expr(&mut self, expr: &SymbolicExpression) -> WasmGenerationResult {
+        let start = self.instr_position();
+        if let Err(err) = self.traverse_expr_kind(expr) {
+            self.failed_expr
+                .get_or_insert_with(|| (expr.span.clone(), construct_name(expr)));
+            return Err(err);
+        }
+        self.map_to_span(start, &expr.span);
+        Ok(())
+    }
+
+    fn traverse_expr_kind(&mut self, expr: &SymbolicExpression) -> WasmGenerationResult {
         match &expr.expr {
             SymbolicExpressionType::List(expressions) => self.traverse_list(expr, expressions)?,
-            SymbolicExpressionType::Atom(clarity_name) => {
-                println!("==> traverse_expr(Atom): {}", clarity_name);
-                todo!()
-            }
-            SymbolicExpressionType::AtomValue(value) => {
-                println!("==> traverse_expr(AtomValue): {}", value);
-                todo!()
-            }
-            SymbolicExpressionType::Field(trait_id) => {
-                println!("==> traverse_expr(Field): {}", trait_id);
-                todo!()
-            }
-            SymbolicExpressionType::LiteralValue(value) => {
-                println!("==> traverse_expr(LiteralValue): {}", value);
-                self.visit_literal_value(expr, value)?;
-            }
-            SymbolicExpressionType::TraitReference(clarity_name, trait_def) => {
-                println!(
-                    "==> traverse_expr(TraitReference): {}, {:?}",
-                    clarity_name, trait_def
-                );
-                todo!()
+            SymbolicExpressionType::Atom(clarity_name) => self.visit_atom(clarity_name)?,
+            SymbolicExpressionType::AtomValue(value) => self.visit_literal_value(expr, value)?,
+            // Trait identifiers (`.contract.trait`) and references (`<trait>`) only
+            // appear in trait definitions and function signatures, which are handled by
+            // their define-functions rather than traversed.
+            SymbolicExpressionType::Field(trait_id) => Err(WasmGenerationError::InternalError(
+                format!("Unexpected trait identifier: {}", trait_id),
+            ))?,
+            SymbolicExpressionType::LiteralValue(value) => self.visit_literal_value(expr, value)?,
+            SymbolicExpressionType::TraitReference(clarity_name, _) => {
+                Err(WasmGenerationError::InternalError(format!(
+                    "Unexpected trait reference: {}",
+                    clarity_name
+                )))?
             }
         }
         Ok(())
@@ -67,11 +77,20 @@ impl WasmGenerator {
         // Or, if the current expression is a native-function call (as defined in the `NativeFunctions` enum),
         // traverse the function.
         } else if let Some(native_function) =
-            NativeFunctions::lookup_by_name_at_version(function_name, &ClarityVersion::latest())
+            NativeFunctions::lookup_by_name_at_version(function_name, &self.clarity_version)
         {
-            // traverse native
             self.traverse_native_function(native_function, expr, args)?;
-            todo!("Native functions")
+        // Otherwise, it's a call to a function defined by the contract, which the analysis
+        // has ordered before its callers.
+        } else {
+            let function_id = *self
+                .functions
+                .get(function_name.as_str())
+                .ok_or_else(|| WasmGenerationError::UnknownFunction(function_name.to_string()))?;
+            for arg in args {
+                self.traverse_expr(arg)?;
+            }
+            self.get_function().call(function_id);
         }
 
         Ok(())
@@ -80,17 +99,652 @@ impl WasmGenerator {
     pub(crate) fn traverse_native_function(
         &mut self,
         function: NativeFunctions,
-        expr: &SymbolicExpression,
+        _expr: &SymbolicExpression,
         operands: &[SymbolicExpression],
     ) -> WasmGenerationResult {
+        // Special forms control how (and whether) their operands are evaluated.
+        match function {
+            NativeFunctions::Let => return self.traverse_let(operands),
+            NativeFunctions::If => return self.traverse_if(operands),
+            NativeFunctions::Asserts => return self.traverse_asserts(operands),
+            NativeFunctions::TryRet => return self.traverse_try(operands),
+            NativeFunctions::Begin => return self.traverse_statements(operands),
+            NativeFunctions::Match => return self.traverse_match(operands),
+            // The first operand of `fold` and `map` is the name of the function to apply.
+            NativeFunctions::Fold => return self.traverse_fold(operands),
+            NativeFunctions::Map => return self.traverse_map(operands),
+            // The target of a `contract-call?` is resolved at compile time.
+            NativeFunctions::ContractCall => return self.traverse_contract_call(operands),
+            // The first operand of the data map and variable functions is a name.
+            NativeFunctions::FetchEntry => {
+                return self.traverse_named_op("map_get_extref", operands)
+            }
+            NativeFunctions::SetEntry => return self.traverse_named_op("map_set_extref", operands),
+            NativeFunctions::InsertEntry => {
+                return self.traverse_named_op("map_insert_extref", operands)
+            }
+            NativeFunctions::DeleteEntry => {
+                return self.traverse_named_op("map_delete_extref", operands)
+            }
+            NativeFunctions::FetchVar => return self.traverse_named_op("var_get_extref", operands),
+            NativeFunctions::SetVar => return self.traverse_named_op("var_set_extref", operands),
+            // `and` and `or` only evaluate operands until the result is known.
+            NativeFunctions::And => {
+                return self
+                    .short_circuit(operands.len(), true, &|g, i| g.traverse_expr(&operands[i]))
+            }
+            NativeFunctions::Or => {
+                return self
+                    .short_circuit(operands.len(), false, &|g, i| g.traverse_expr(&operands[i]))
+            }
+            // Arithmetic is folded from the left, so each operand is traversed as it's
+            // applied.
+            NativeFunctions::Add => return self.visit_add(operands),
+            NativeFunctions::Subtract => return self.visit_sub(operands),
+            NativeFunctions::Multiply => return self.visit_mul(operands),
+            NativeFunctions::Divide => return self.visit_div(operands),
+            NativeFunctions::Modulo => return self.visit_mod(operands),
+            _ => {}
+        }
+
         for op in operands {
             self.traverse_expr(op)?;
         }
 
         match function {
-            NativeFunctions::Add => self.visit_add(expr, operands),
-            _ => todo!("Function {} not implemented.", function),
+            NativeFunctions::CmpGreater => self.visit_comparison("gt", operands),
+            NativeFunctions::CmpGeq => self.visit_comparison("ge", operands),
+            NativeFunctions::CmpLess => self.visit_comparison("lt", operands),
+            NativeFunctions::CmpLeq => self.visit_comparison("le", operands),
+            NativeFunctions::Equals => self.visit_equals(operands),
+            NativeFunctions::Not => self.visit_not(),
+            NativeFunctions::ConsOkay => self.visit_wrap("ok_extref"),
+            NativeFunctions::ConsError => self.visit_wrap("err_extref"),
+            NativeFunctions::ConsSome => self.visit_wrap("some_extref"),
+            _ => Err(WasmGenerationError::NotImplemented),
+        }
+    }
+
+    /// Traverses a `let` expression. Each binding's initializer is evaluated in turn and
+    /// stored in a new local, and is in scope for the following bindings and the body.
+    /// The bindings go out of scope again once the body has been traversed.
+    pub(crate) fn traverse_let(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        let (bindings, body) = operands
+            .split_first()
+            .and_then(|(bindings, body)| Some((bindings.match_list()?, body)))
+            .ok_or_else(|| WasmGenerationError::InternalError("Malformed let".to_string()))?;
+
+        let scope = match &self.current_fn {
+            Some(func) => func.bindings.len(),
+            None => return Err(WasmGenerationError::NotInFunction("let".to_string())),
+        };
+
+        for binding in bindings {
+            let (name, initializer) = match binding.match_list() {
+                Some([name, initializer]) => (name, initializer),
+                _ => Err(WasmGenerationError::InternalError(format!(
+                    "Malformed let binding: {}",
+                    binding
+                )))?,
+            };
+            let name = name.match_atom().ok_or_else(|| {
+                WasmGenerationError::InternalError(format!("Malformed let binding: {}", binding))
+            })?;
+
+            self.traverse_expr(initializer)?;
+
+            let type_signature = self.expr_type(initializer).cloned();
+            let local_ids = self.add_value_locals(type_signature.as_ref())?;
+            self.name_value_locals(&local_ids, name);
+            self.set_value_locals(&local_ids);
+
+            // The binding is only added to the scope once its initializer has been
+            // traversed, so the initializer can't refer to it.
+            self.current_fn
+                .as_mut()
+                .unwrap()
+                .bindings
+                .push((name.to_string(), local_ids));
+        }
+
+        let result = self.traverse_statements(body);
+
+        self.current_fn.as_mut().unwrap().bindings.truncate(scope);
+
+        result
+    }
+
+    /// Traverses an `if` expression, evaluating only the branch selected by its
+    /// condition.
+    pub(crate) fn traverse_if(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        let (condition, then_expr, else_expr) = match operands {
+            [condition, then_expr, else_expr] => (condition, then_expr, else_expr),
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed if".to_string(),
+            ))?,
+        };
+
+        self.traverse_expr(condition)?;
+        let to_bool = self.import_host_function("to_bool_extref")?;
+        self.get_function().call(to_bool);
+
+        let consequent = self.build_block(ValType::Externref, |g| g.traverse_expr(then_expr))?;
+        let alternative = self.build_block(ValType::Externref, |g| g.traverse_expr(else_expr))?;
+        self.get_function().instr(IfElse {
+            consequent,
+            alternative,
+        });
+
+        Ok(())
+    }
+
+    /// Traverses an `asserts!` expression. If the condition holds, it's also the
+    /// expression's value (`true`); otherwise the thrown value is evaluated and
+    /// returned from the function.
+    pub(crate) fn traverse_asserts(
+        &mut self,
+        operands: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (condition, thrown) = match operands {
+            [condition, thrown] => (condition, thrown),
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed asserts!".to_string(),
+            ))?,
+        };
+
+        self.traverse_expr(condition)?;
+        let condition_local = self.add_local(ValType::Externref);
+        let to_bool = self.import_host_function("to_bool_extref")?;
+        self.get_function().local_tee(condition_local).call(to_bool);
+
+        let consequent = self.build_block(ValType::Externref, |g| {
+            g.get_function().local_get(condition_local);
+            Ok(())
+        })?;
+        let alternative = self.build_block(ValType::Externref, |g| {
+            g.traverse_expr(thrown)?;
+            g.get_function().return_();
+            Ok(())
+        })?;
+        self.get_function().instr(IfElse {
+            consequent,
+            alternative,
+        });
+
+        Ok(())
+    }
+
+    /// Traverses a `try!` expression. A `(some ...)` or `(ok ...)` is unwrapped, while
+    /// a `none` or `(err ...)` is returned from the function as-is.
+    pub(crate) fn traverse_try(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        let input = match operands {
+            [input] => input,
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed try!".to_string(),
+            ))?,
+        };
+
+        self.traverse_expr(input)?;
+        let input_local = self.add_local(ValType::Externref);
+        let is_success = self.import_host_function("is_success_extref")?;
+        let unwrap = self.import_host_function("unwrap_extref")?;
+        self.get_function().local_tee(input_local).call(is_success);
+
+        let consequent = self.build_block(ValType::Externref, |g| {
+            g.get_function().local_get(input_local).call(unwrap);
+            Ok(())
+        })?;
+        let alternative = self.build_block(ValType::Externref, |g| {
+            g.get_function().local_get(input_local).return_();
+            Ok(())
+        })?;
+        self.get_function().instr(IfElse {
+            consequent,
+            alternative,
+        });
+
+        Ok(())
+    }
+
+    /// Traverses a `match` over an optional, `(match opt name some-branch none-branch)`,
+    /// or a response, `(match res ok-name ok-branch err-name err-branch)`. The inner
+    /// value is unwrapped into a new local, bound to the given name for its branch.
+    pub(crate) fn traverse_match(
+        &mut self,
+        operands: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        fn malformed() -> WasmGenerationError {
+            WasmGenerationError::InternalError("Malformed match".to_string())
+        }
+        fn binding_name(expr: &SymbolicExpression) -> Result<&ClarityName, WasmGenerationError> {
+            expr.match_atom().ok_or_else(malformed)
+        }
+
+        let (input, success, failure) = match operands {
+            [input, some_name, some_branch, none_branch] => (
+                input,
+                (binding_name(some_name)?, some_branch),
+                (None, none_branch),
+            ),
+            [input, ok_name, ok_branch, err_name, err_branch] => (
+                input,
+                (binding_name(ok_name)?, ok_branch),
+                (Some(binding_name(err_name)?), err_branch),
+            ),
+            _ => return Err(malformed()),
+        };
+
+        self.traverse_expr(input)?;
+        let input_local = self.add_local(ValType::Externref);
+        let is_success = self.import_host_function("is_success_extref")?;
+        self.get_function().local_tee(input_local).call(is_success);
+
+        let consequent = self.build_block(ValType::Externref, |g| {
+            let (name, branch) = success;
+            g.traverse_match_branch(input_local, "unwrap_extref", name, branch)
+        })?;
+        let alternative = self.build_block(ValType::Externref, |g| match failure {
+            (Some(name), branch) => {
+                g.traverse_match_branch(input_local, "unwrap_err_extref", name, branch)
+            }
+            // A `none` has no inner value to bind.
+            (None, branch) => g.traverse_expr(branch),
+        })?;
+        self.get_function().instr(IfElse {
+            consequent,
+            alternative,
+        });
+
+        Ok(())
+    }
+
+    /// Traverses a branch of a `match`, with the value unwrapped from `input_local` by
+    /// the given host function bound to `name`.
+    fn traverse_match_branch(
+        &mut self,
+        input_local: LocalId,
+        unwrap_function: &str,
+        name: &str,
+        branch: &SymbolicExpression,
+    ) -> WasmGenerationResult {
+        let unwrap = self.import_host_function(unwrap_function)?;
+        let inner_local = self.add_local(ValType::Externref);
+        self.name_value_locals(&[inner_local], name);
+        self.get_function()
+            .local_get(input_local)
+            .call(unwrap)
+            .local_set(inner_local);
+
+        let func = self.current_fn.as_mut().unwrap();
+        let scope = func.bindings.len();
+        func.bindings.push((name.to_string(), vec![inner_local]));
+
+        let result = self.traverse_expr(branch);
+
+        self.current_fn.as_mut().unwrap().bindings.truncate(scope);
+        result
+    }
+
+    /// Traverses a `fold`, `(fold function sequence initial-value)`, lowered as per the
+    /// generator's `SequenceLowering`.
+    pub(crate) fn traverse_fold(
+        &mut self,
+        operands: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (function, sequence, initial_value) = match operands {
+            [function, sequence, initial_value] => (function, sequence, initial_value),
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed fold".to_string(),
+            ))?,
+        };
+        let function_id = self.applied_function(function)?;
+
+        match self.sequence_lowering {
+            SequenceLowering::HostCall => {
+                self.ref_func(function_id);
+                self.traverse_expr(sequence)?;
+                self.traverse_expr(initial_value)?;
+                let fold = self.import_host_function("fold_extref")?;
+                self.get_function().call(fold);
+            }
+            SequenceLowering::Loop => {
+                self.traverse_expr(sequence)?;
+                let sequence_local = self.add_local(ValType::Externref);
+                self.get_function().local_set(sequence_local);
+
+                self.traverse_expr(initial_value)?;
+                let acc_local = self.add_local(ValType::Externref);
+                self.get_function().local_set(acc_local);
+
+                self.sequence_loop(sequence_local, |g, element_local| {
+                    g.get_function()
+                        .local_get(element_local)
+                        .local_get(acc_local)
+                        .call(function_id)
+                        .local_set(acc_local);
+                    Ok(())
+                })?;
+                self.get_function().local_get(acc_local);
+            }
         }
+
+        Ok(())
+    }
+
+    /// Traverses a `map` over a single sequence, `(map function sequence)`, lowered as
+    /// per the generator's `SequenceLowering`.
+    pub(crate) fn traverse_map(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        let (function, sequence) = match operands {
+            [function, sequence] => (function, sequence),
+            // Mapping over several sequences at once isn't supported yet.
+            [_, _, ..] => return Err(WasmGenerationError::NotImplemented),
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed map".to_string(),
+            ))?,
+        };
+        let function_id = self.applied_function(function)?;
+
+        match self.sequence_lowering {
+            SequenceLowering::HostCall => {
+                self.ref_func(function_id);
+                self.traverse_expr(sequence)?;
+                let map = self.import_host_function("map_extref")?;
+                self.get_function().call(map);
+            }
+            SequenceLowering::Loop => {
+                self.traverse_expr(sequence)?;
+                let sequence_local = self.add_local(ValType::Externref);
+                self.get_function().local_set(sequence_local);
+
+                // The results are pushed onto a builder, starting from a null reference,
+                // and the list is built once all of them are known.
+                let builder_local = self.add_local(ValType::Externref);
+                self.get_function()
+                    .ref_null(ValType::Externref)
+                    .local_set(builder_local);
+
+                let list_push = self.import_host_function("list_push_extref")?;
+                self.sequence_loop(sequence_local, |g, element_local| {
+                    g.get_function()
+                        .local_get(builder_local)
+                        .local_get(element_local)
+                        .call(function_id)
+                        .call(list_push)
+                        .local_set(builder_local);
+                    Ok(())
+                })?;
+
+                let list_build = self.import_host_function("list_build_extref")?;
+                self.get_function()
+                    .local_get(builder_local)
+                    .call(list_build);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the function defined by the contract which is applied by a `fold` or `map`.
+    fn applied_function(
+        &self,
+        function: &SymbolicExpression,
+    ) -> Result<FunctionId, WasmGenerationError> {
+        let name = function
+            .match_atom()
+            .ok_or_else(|| WasmGenerationError::UnknownFunction(function.to_string()))?;
+        match self.functions.get(name.as_str()) {
+            Some(function_id) => Ok(*function_id),
+            // Native functions have no Wasm function to apply.
+            None if NativeFunctions::lookup_by_name_at_version(name, &self.clarity_version)
+                .is_some() =>
+            {
+                Err(WasmGenerationError::NotImplemented)
+            }
+            None => Err(WasmGenerationError::UnknownFunction(name.to_string())),
+        }
+    }
+
+    /// Emits a loop over the elements of the sequence in `sequence_local`, which calls
+    /// `emit_body` to emit the code run for each element with a local holding it.
+    fn sequence_loop(
+        &mut self,
+        sequence_local: LocalId,
+        emit_body: impl FnOnce(&mut Self, LocalId) -> WasmGenerationResult,
+    ) -> WasmGenerationResult {
+        let seq_len = self.import_host_function("seq_len_extref")?;
+        let seq_get = self.import_host_function("seq_get_extref")?;
+        let len_local = self.add_local(ValType::I32);
+        let index_local = self.add_local(ValType::I32);
+        let element_local = self.add_local(ValType::Externref);
+        self.get_function()
+            .local_get(sequence_local)
+            .call(seq_len)
+            .local_set(len_local)
+            .i32_const(0)
+            .local_set(index_local);
+
+        let seq = self.build_block(None::<ValType>, |g| {
+            let loop_id = g.current_fn.as_ref().unwrap().id;
+            g.get_function()
+                .local_get(index_local)
+                .local_get(len_local)
+                .binop(BinaryOp::I32LtS);
+
+            let consequent = g.build_block(None::<ValType>, |g| {
+                g.get_function()
+                    .local_get(sequence_local)
+                    .local_get(index_local)
+                    .call(seq_get)
+                    .local_set(element_local);
+                emit_body(g, element_local)?;
+                g.get_function()
+                    .local_get(index_local)
+                    .i32_const(1)
+                    .binop(BinaryOp::I32Add)
+                    .local_set(index_local)
+                    .br(loop_id);
+                Ok(())
+            })?;
+            let alternative = g.build_block(None::<ValType>, |_| Ok(()))?;
+            g.get_function().instr(IfElse {
+                consequent,
+                alternative,
+            });
+            Ok(())
+        })?;
+        self.get_function().instr(Loop { seq });
+
+        Ok(())
+    }
+
+    /// Traverses a `contract-call?`, `(contract-call? contract function args...)`. For a
+    /// static call, the contract and function are passed to the `contract_call_static`
+    /// host function by id, along with the arguments collected by `push_arg`. A call to
+    /// a trait-typed parameter passes the contract's principal and the function's name
+    /// to `contract_call` instead. Its value is the result of the called function, e.g.
+    /// the response of a public function.
+    pub(crate) fn traverse_contract_call(
+        &mut self,
+        operands: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (contract, function, args) = match operands {
+            [contract, function, args @ ..] => (contract, function, args),
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed contract-call?".to_string(),
+            ))?,
+        };
+
+        let function_name = function.match_atom().ok_or_else(|| {
+            WasmGenerationError::InternalError("Malformed contract-call?".to_string())
+        })?;
+
+        let contract_id = match &contract.expr {
+            SymbolicExpressionType::LiteralValue(Value::Principal(PrincipalData::Contract(
+                contract_id,
+            )))
+            | SymbolicExpressionType::AtomValue(Value::Principal(PrincipalData::Contract(
+                contract_id,
+            ))) => contract_id,
+            // The contract passed as a trait-typed parameter is only known at runtime,
+            // so it's called by its principal and the function's name.
+            _ => {
+                self.traverse_expr(contract)?;
+                let name = Value::string_ascii_from_bytes(function_name.as_bytes().to_vec())
+                    .map_err(|_| {
+                        WasmGenerationError::InternalError(format!(
+                            "Invalid function name: {}",
+                            function_name
+                        ))
+                    })?;
+                self.push_constant(&format!("\"{}\"", function_name), &name)?;
+                self.push_contract_call_args(args)?;
+
+                let contract_call = self.import_host_function("contract_call_extref")?;
+                self.get_function().call(contract_call);
+                return Ok(());
+            }
+        };
+
+        let contract_id = self.called_contract_id(contract_id);
+        let function_id = self.names.intern(function_name.clone());
+        self.get_function()
+            .i32_const(contract_id)
+            .i32_const(function_id);
+        self.push_contract_call_args(args)?;
+
+        let contract_call = self.import_host_function("contract_call_static_extref")?;
+        self.get_function().call(contract_call);
+
+        Ok(())
+    }
+
+    /// Pushes the arguments of a `contract-call?`, as collected by the `push_arg` host
+    /// function starting from a null reference.
+    fn push_contract_call_args(&mut self, args: &[SymbolicExpression]) -> WasmGenerationResult {
+        self.get_function().ref_null(ValType::Externref);
+
+        let push_arg = self.import_host_function("push_arg_extref")?;
+        for arg in args {
+            self.traverse_expr(arg)?;
+            self.get_function().call(push_arg);
+        }
+
+        Ok(())
+    }
+
+    /// Traverses a call to one of the data map functions (`map-get?`, `map-set`,
+    /// `map-insert`, `map-delete`) or data variable functions (`var-get`, `var-set`),
+    /// which take the map's or variable's name followed by their other arguments. The
+    /// name is passed to the host function as an interned id.
+    pub(crate) fn traverse_named_op(
+        &mut self,
+        host_function: &str,
+        operands: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (name, args) = operands
+            .split_first()
+            .and_then(|(name, args)| Some((name.match_atom()?, args)))
+            .ok_or_else(|| {
+                WasmGenerationError::InternalError(format!("Malformed {}", host_function))
+            })?;
+
+        let name_id = self.names.intern(name.clone());
+        self.get_function().i32_const(name_id);
+        for arg in args {
+            self.traverse_expr(arg)?;
+        }
+
+        let function_id = self.import_host_function(host_function)?;
+        self.get_function().call(function_id);
+
+        Ok(())
+    }
+
+    /// Emits `count` boolean steps, each evaluated only while the previous one's value
+    /// was `continue_on`, leaving the value of the last evaluated step on the stack.
+    /// This lowers `and` (which continues on `true`) and `or` (which continues on
+    /// `false`).
+    pub(crate) fn short_circuit(
+        &mut self,
+        count: usize,
+        continue_on: bool,
+        emit_step: &dyn Fn(&mut Self, usize) -> WasmGenerationResult,
+    ) -> WasmGenerationResult {
+        self.short_circuit_from(0, count, continue_on, emit_step)
+    }
+
+    fn short_circuit_from(
+        &mut self,
+        step: usize,
+        count: usize,
+        continue_on: bool,
+        emit_step: &dyn Fn(&mut Self, usize) -> WasmGenerationResult,
+    ) -> WasmGenerationResult {
+        if step >= count {
+            return Err(WasmGenerationError::EmptyListTraversal);
+        }
+
+        emit_step(self, step)?;
+        if step + 1 == count {
+            return Ok(());
+        }
+
+        // The value of the step which ended the evaluation is also the result.
+        let value_local = self.add_local(ValType::Externref);
+        let to_bool = self.import_host_function("to_bool_extref")?;
+        self.get_function().local_tee(value_local).call(to_bool);
+
+        let continue_block = self.build_block(ValType::Externref, |g| {
+            g.short_circuit_from(step + 1, count, continue_on, emit_step)
+        })?;
+        let stop_block = self.build_block(ValType::Externref, |g| {
+            g.get_function().local_get(value_local);
+            Ok(())
+        })?;
+
+        let (consequent, alternative) = if continue_on {
+            (continue_block, stop_block)
+        } else {
+            (stop_block, continue_block)
+        };
+        self.get_function().instr(IfElse {
+            consequent,
+            alternative,
+        });
+
+        Ok(())
+    }
+
+    /// Traverses a sequence of expressions, such as a `begin` or the body of a `let`,
+    /// where only the value of the last expression is kept. Values are `ExternRef`s,
+    /// which are released by the runtime once they're unreachable, so the others are
+    /// simply dropped.
+    pub(crate) fn traverse_statements(
+        &mut self,
+        statements: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (last, rest) = statements
+            .split_last()
+            .ok_or(WasmGenerationError::EmptyListTraversal)?;
+
+        for statement in rest {
+            self.traverse_statement(statement)?;
+        }
+
+        self.traverse_expr(last)
+    }
+
+    /// Traverses an expression which is only evaluated for its side effects, dropping
+    /// its value.
+    pub(crate) fn traverse_statement(
+        &mut self,
+        statement: &SymbolicExpression,
+    ) -> WasmGenerationResult {
+        self.traverse_expr(statement)?;
+        for _ in self.val_types(self.expr_type(statement))? {
+            self.get_function().drop();
+        }
+        Ok(())
     }
 
     /// Traverses the provided define-function and its arguments.
@@ -101,10 +755,16 @@ impl WasmGenerator {
     ) -> WasmGenerationResult {
         match function {
             //DefineFunctions::Constant => self.visit_define_constant(function, args),
+            DefineFunctions::Map => self.visit_define_map(expr),
+            DefineFunctions::PersistedVariable => self.visit_define_data_var(expr),
+            DefineFunctions::Trait => self.visit_define_trait(expr),
+            DefineFunctions::ImplTrait => self.visit_impl_trait(expr),
+            // `use-trait` only introduces an alias, which the analysis has resolved.
+            DefineFunctions::UseTrait => Ok(()),
             DefineFunctions::PublicFunction
             | DefineFunctions::PrivateFunction
             | DefineFunctions::ReadOnlyFunction => self.traverse_define_function(function, expr),
-            _ => todo!(),
+            _ => Err(WasmGenerationError::NotImplemented),
         }
     }
 
@@ -119,7 +779,6 @@ impl WasmGenerator {
         // In the signature expression list:
         // - The first expression includes the name of the defined function
         // - The remaining expressions describe the input parameters, in pairs.
-        // - TODO: Return type?
         // So here, we extract both the name and parameters from the signature expression.
         let (name, parameters) = expr[0].match_list().unwrap().split_first().unwrap();
 
@@ -130,36 +789,70 @@ impl WasmGenerator {
         // further traversal to generate the WASM function body.
         let body = &expr[1];
 
-        //println!("==> NAME: {:?}", name);
-        //println!("==> PARAMS: {:?}", parameters);
-        //println!("==> BODY: {:?}", body);
+        // The types of the parameters and the return value are those inferred by the type checker.
+        let function_type = self.fixed_function_type(name)?;
 
         let mut params = Vec::<ParameterDefinition>::new();
 
-        for arg in parameters {
-            arg.match_list().unwrap().chunks(2).for_each(|x| {
-                // Add a new local to the module for the input parameter.
-                let local_id = self.module.locals.add(ValType::Externref);
-                // Create a new `ParameterDefinition` for the input parameter.
-                let param_def = ParameterDefinition::new(
-                    x[0].match_atom().unwrap(),
-                    ValType::Externref,
-                    local_id,
-                );
-                // Add the parameter to the input parameter definitions.
-                params.push(param_def);
-            });
+        for (arg, arg_type) in parameters.iter().zip(function_type.args.iter()) {
+            let type_expr = match arg.match_list() {
+                Some([_, type_expr]) => type_expr,
+                _ => Err(WasmGenerationError::InternalError(format!(
+                    "Malformed parameter: {}",
+                    arg
+                )))?,
+            };
+
+            // Add new locals to the module for the input parameter.
+            let val_types = self.val_types(Some(&arg_type.signature))?;
+            let local_ids = val_types
+                .iter()
+                .map(|val_type| self.module.locals.add(*val_type))
+                .collect::<Vec<_>>();
+            self.name_value_locals(&local_ids, &arg_type.name);
+            // Create a new `ParameterDefinition` for the input parameter.
+            let param_def = ParameterDefinition::new(
+                &arg_type.name,
+                arg_type.signature.clone(),
+                val_types,
+                local_ids,
+            );
+            // A trait-typed parameter holds the contract's principal. Its trait is
+            // recorded among the module's interfaces.
+            if let SymbolicExpressionType::TraitReference(_, trait_definition) = &type_expr.expr {
+                let trait_id = match trait_definition {
+                    TraitDefinition::Defined(trait_id) | TraitDefinition::Imported(trait_id) => {
+                        trait_id
+                    }
+                };
+                self.interface_id(trait_id);
+            }
+            // Add the parameter to the input parameter definitions.
+            params.push(param_def);
         }
 
         // Begin the function.
-        self.begin_function(name, params, &[ValType::Externref]);
+        let results = self.val_types(Some(&function_type.returns))?;
+        self.begin_function(name, params, &results);
 
         // Traverse the function's body expression, building the function along the way.
         self.traverse_expr(body)?;
 
-        // Once the body traversal is finished, we can end the function. If this is a `public` function,
-        // then we also need to export it from the module.
-        self.end_function(function == DefineFunctions::PublicFunction);
+        let access = match function {
+            DefineFunctions::PublicFunction => FunctionAccess::Public,
+            DefineFunctions::ReadOnlyFunction => FunctionAccess::ReadOnly,
+            _ => FunctionAccess::Private,
+        };
+        self.contract_interface.functions.push(FunctionInterface {
+            name: name.clone(),
+            access,
+            args: function_type.args,
+            returns: function_type.returns,
+        });
+
+        // Once the body traversal is finished, we can end the function. If this is a `public` or
+        // `read-only` function, then we also need to export it from the module.
+        self.end_function(access.is_exported());
 
         Ok(())
     }
@@ -185,3 +878,17 @@ impl WasmGenerator {
     let add_square_fn = add_square.finish(vec![a, b], &mut module.funcs);
     module.exports.add("add-square", add_square_fn); */
 }
+
+/// Gets the name of the construct which an expression is an instance of, as reported
+/// in errors: the function called or defined by a list, or the expression itself.
+fn construct_name(expr: &SymbolicExpression) -> String {
+    match &expr.expr {
+        SymbolicExpressionType::List(expressions) => match expressions.first() {
+            Some(first) => first
+                .match_atom()
+                .map_or_else(|| first.to_string(), |name| name.to_string()),
+            None => "()".to_string(),
+        },
+        _ => expr.to_string(),
+    }
+}
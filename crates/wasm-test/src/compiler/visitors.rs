@@ -1,43 +1,336 @@
-use clarity::vm::{ClarityName, SymbolicExpression, Value};
+use clarity::vm::{types::TypeSignature, ClarityName, SymbolicExpression, Value};
+use walrus::ValType;
 
-use super::{wasm_generator::WasmGenerator, WasmGenerationResult};
+use super::{
+    wasm_generator::WasmGenerator, CodegenConvention, DataMapMetadata, DataVarMetadata,
+    TraitMetadata, WasmGenerationError, WasmGenerationResult,
+};
 
 impl WasmGenerator {
-    pub(crate) fn visit_add(
+    /// Lowers `+` to calls to the `add` host function.
+    pub(crate) fn visit_add(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        self.visit_arithmetic("add", operands)
+    }
+
+    /// Lowers `-` to calls to the `sub` host function. `(- a)` negates its operand, so
+    /// it's lowered as `(- 0 a)`, using a zero of the operand's type.
+    pub(crate) fn visit_sub(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        if let [operand] = operands {
+            let zero = match self.expr_type(operand) {
+                Some(TypeSignature::IntType) => Value::Int(0),
+                Some(TypeSignature::UIntType) => Value::UInt(0),
+                _ => {
+                    return Err(WasmGenerationError::InternalError(
+                        "Expected an integer operand for -".to_string(),
+                    ))
+                }
+            };
+            self.push_constant(&zero.to_string(), &zero)?;
+            self.traverse_expr(operand)?;
+            return self.call_convention_function("sub");
+        }
+        self.visit_arithmetic("sub", operands)
+    }
+
+    /// Lowers `*` to calls to the `mul` host function.
+    pub(crate) fn visit_mul(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        self.visit_arithmetic("mul", operands)
+    }
+
+    /// Lowers `/` to calls to the `div` host function.
+    pub(crate) fn visit_div(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        self.visit_arithmetic("div", operands)
+    }
+
+    /// Lowers `mod` to a call to the `mod` host function.
+    pub(crate) fn visit_mod(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        self.visit_arithmetic("mod", operands)
+    }
+
+    /// Lowers an n-ary arithmetic operation to calls to the given (binary) host
+    /// function of the generator's convention, folding the operands from the left,
+    /// e.g. `(- a b c)` is `(a - b) - c`. The operands are traversed here, as each call
+    /// has to be made before the next operand is pushed.
+    fn visit_arithmetic(
         &mut self,
-        expr: &SymbolicExpression,
+        host_function: &str,
+        operands: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (first, rest) = operands
+            .split_first()
+            .ok_or(WasmGenerationError::EmptyListTraversal)?;
+
+        self.traverse_expr(first)?;
+        for operand in rest {
+            self.traverse_expr(operand)?;
+            self.call_convention_function(host_function)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lowers a two-operand comparison (`>`, `>=`, `<`, `<=`) to a call to the given
+    /// host function of the generator's convention. The operands have already been
+    /// traversed, so their values are on the stack.
+    pub(crate) fn visit_comparison(
+        &mut self,
+        host_function: &str,
         operands: &[SymbolicExpression],
     ) -> WasmGenerationResult {
-        println!("==> visit_add()");
+        if operands.len() != 2 {
+            return Err(WasmGenerationError::InternalError(format!(
+                "Expected 2 operands for {}, got {}",
+                host_function,
+                operands.len()
+            )));
+        }
+
+        self.call_convention_function(host_function)
+    }
+
+    /// Lowers `is-eq`, which compares each of its operands to the first. The operands
+    /// have already been traversed, so their values are on the stack. All of them are
+    /// evaluated, but the comparisons stop at the first one which doesn't hold.
+    pub(crate) fn visit_equals(&mut self, operands: &[SymbolicExpression]) -> WasmGenerationResult {
+        // `(is-eq a)` has nothing to compare `a` to, so it's always `true`.
+        if let [operand] = operands {
+            for _ in self.val_types(self.expr_type(operand))? {
+                self.get_function().drop();
+            }
+            return self.push_constant("true", &Value::Bool(true));
+        }
+
+        if operands.len() == 2 {
+            return self.call_convention_function("is_eq");
+        }
+
+        let is_eq = self.import_host_function("is_eq_extref")?;
+
+        // Move the values from the stack to locals, so that each can be compared to the
+        // first.
+        let locals = operands
+            .iter()
+            .map(|_| self.add_local(ValType::Externref))
+            .collect::<Vec<_>>();
+        for local_id in locals.iter().rev() {
+            self.get_function().local_set(*local_id);
+        }
+
+        let (first, rest) = locals
+            .split_first()
+            .ok_or(WasmGenerationError::EmptyListTraversal)?;
+        self.short_circuit(rest.len(), true, &|g, i| {
+            g.get_function()
+                .local_get(*first)
+                .local_get(rest[i])
+                .call(is_eq);
+            Ok(())
+        })
+    }
+
+    /// Lowers `not` to a call to the `not_extref` host function.
+    pub(crate) fn visit_not(&mut self) -> WasmGenerationResult {
+        let function_id = self.import_host_function("not_extref")?;
+        self.get_function().call(function_id);
+        Ok(())
+    }
+
+    /// Lowers `ok`, `err` and `some`, which wrap their operand, to a call to the given
+    /// host function.
+    pub(crate) fn visit_wrap(&mut self, host_function: &str) -> WasmGenerationResult {
+        let function_id = self.import_host_function(host_function)?;
+        self.get_function().call(function_id);
+        Ok(())
+    }
+
+    /// Records the metadata of a data map defined using `define-map`, whose types are
+    /// taken from the contract analysis. Its name is interned, as the map is referenced
+    /// by id.
+    pub(crate) fn visit_define_map(&mut self, args: &[SymbolicExpression]) -> WasmGenerationResult {
+        let name = args
+            .first()
+            .and_then(|name| name.match_atom())
+            .ok_or_else(|| {
+                WasmGenerationError::InternalError("Malformed define-map".to_string())
+            })?;
+        let (key_type, value_type) = self
+            .contract_analysis
+            .as_ref()
+            .and_then(|analysis| analysis.map_types.get(name))
+            .cloned()
+            .ok_or_else(|| WasmGenerationError::InternalError(format!("Unknown map: {}", name)))?;
+
+        self.names.intern(name.clone());
+        self.maps.push(DataMapMetadata {
+            name: name.clone(),
+            key_type,
+            value_type,
+        });
+
+        Ok(())
+    }
+
+    /// Records the metadata of a trait defined using `define-trait`, whose function
+    /// signatures are taken from the contract analysis.
+    pub(crate) fn visit_define_trait(
+        &mut self,
+        args: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let name = args
+            .first()
+            .and_then(|name| name.match_atom())
+            .ok_or_else(|| {
+                WasmGenerationError::InternalError("Malformed define-trait".to_string())
+            })?;
+        let functions = self
+            .contract_analysis
+            .as_ref()
+            .and_then(|analysis| analysis.defined_traits.get(name))
+            .cloned()
+            .ok_or_else(|| {
+                WasmGenerationError::InternalError(format!("Unknown trait: {}", name))
+            })?;
+
+        self.traits.push(TraitMetadata {
+            name: name.clone(),
+            functions,
+        });
+
+        Ok(())
+    }
+
+    /// Records a trait which the contract declares that it implements using
+    /// `impl-trait`.
+    pub(crate) fn visit_impl_trait(&mut self, args: &[SymbolicExpression]) -> WasmGenerationResult {
+        let trait_id = args
+            .first()
+            .and_then(|trait_id| trait_id.match_field())
+            .ok_or_else(|| {
+                WasmGenerationError::InternalError("Malformed impl-trait".to_string())
+            })?;
+
+        self.implemented_traits.push(trait_id.clone());
+
         Ok(())
     }
 
+    /// Records the metadata of a data variable defined using `define-data-var`, whose
+    /// type is taken from the contract analysis. Its initial value is stored by the
+    /// top-level function.
+    pub(crate) fn visit_define_data_var(
+        &mut self,
+        args: &[SymbolicExpression],
+    ) -> WasmGenerationResult {
+        let (name, initial_value) = match args {
+            [name, _, initial_value] => name
+                .match_atom()
+                .map(|name| (name, initial_value))
+                .ok_or_else(|| {
+                    WasmGenerationError::InternalError("Malformed define-data-var".to_string())
+                })?,
+            _ => Err(WasmGenerationError::InternalError(
+                "Malformed define-data-var".to_string(),
+            ))?,
+        };
+        let value_type = self
+            .contract_analysis
+            .as_ref()
+            .and_then(|analysis| analysis.persisted_variable_types.get(name))
+            .cloned()
+            .ok_or_else(|| {
+                WasmGenerationError::InternalError(format!("Unknown data variable: {}", name))
+            })?;
+
+        let name_id = self.names.intern(name.clone());
+        self.data_vars.push(DataVarMetadata {
+            name: name.clone(),
+            value_type,
+        });
+
+        let var_set = self.import_host_function("var_set_extref")?;
+        self.with_top_level(|g| {
+            g.get_function().i32_const(name_id);
+            g.traverse_expr(initial_value)?;
+            g.get_function().call(var_set).drop();
+            Ok(())
+        })
+    }
+
+    /// Lowers a reference to a variable (a function parameter or `let` binding) to
+    /// `local.get`s of the locals holding its value. The `true`, `false` and `none`
+    /// keywords are lowered to constants.
+    pub(crate) fn visit_atom(&mut self, name: &ClarityName) -> WasmGenerationResult {
+        let local_ids = match &self.current_fn {
+            Some(func) => func.resolve(name).map(|local_ids| local_ids.to_vec()),
+            None => return Err(WasmGenerationError::NotInFunction(name.to_string())),
+        };
+
+        match (local_ids, name.as_str()) {
+            (Some(local_ids), _) => {
+                self.get_value_locals(&local_ids);
+                Ok(())
+            }
+            (None, "true") => self.push_constant(name, &Value::Bool(true)),
+            (None, "false") => self.push_constant(name, &Value::Bool(false)),
+            (None, "none") => self.push_constant(name, &Value::none()),
+            (None, _) => Err(WasmGenerationError::UnknownVariable(name.to_string())),
+        }
+    }
+
+    /// Lowers a literal value to a constant.
     pub(crate) fn visit_literal_value(
         &mut self,
         expr: &SymbolicExpression,
         value: &Value,
     ) -> WasmGenerationResult {
-        println!("===> visit_literal_value({}): {}", value, expr);
+        self.push_constant(&expr.to_string(), value)
+    }
+
+    /// Pushes a constant value. Under the `Externref` convention, this is a
+    /// `table.get` of the constants table: the value is added to the table's constants
+    /// (unless it's already one of them), which the host fills the table with. Under
+    /// the `RustRefStack` convention, integers are pushed as `i64.const`s.
+    pub(crate) fn push_constant(&mut self, name: &str, value: &Value) -> WasmGenerationResult {
+        match (self.convention, value) {
+            (CodegenConvention::Externref, _) => (),
+            (CodegenConvention::RustRefStack, Value::Int(value)) => {
+                self.get_function()
+                    .i64_const(*value as i64)
+                    .i64_const((*value >> 64) as i64);
+                return Ok(());
+            }
+            _ => {
+                return Err(WasmGenerationError::UnsupportedByConvention(
+                    format!("Constant {}", name),
+                    self.convention,
+                ))
+            }
+        }
 
-        /*let index = self.const_table.add_const("_", value);
-        let table = self.module.tables.get(self.const_table.table_id);
-        let func = &self.current_fn.unwrap().id;
+        let index = match self
+            .const_table
+            .constants
+            .iter()
+            .find(|constant| constant.value == *value)
+        {
+            Some(constant) => constant.index,
+            None => self.const_table.add_const(name, value),
+        };
 
-        let element = self.module.elements.add(
-            ElementKind::Active { table: self.const_table.table_id, offset: InitExpr::Value(walrus::ir::Value::I32(0)) },
-            ValType::Externref,
-            Vec::<_>::new()
-        );*/
+        let table_id = self.const_table.table_id;
+        self.get_function()
+            .i32_const(index as i32)
+            .table_get(table_id);
 
-        todo!()
+        Ok(())
     }
 
     pub(crate) fn visit_define_constant(
         &self,
-        name: &ClarityName,
-        expr: &[SymbolicExpression],
+        _name: &ClarityName,
+        _expr: &[SymbolicExpression],
     ) -> WasmGenerationResult {
-        todo!()
+        Err(WasmGenerationError::NotImplemented)
     }
 
     /*
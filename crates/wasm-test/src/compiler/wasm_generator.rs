@@ -1,24 +1,98 @@
-use clarity::vm::analysis::ContractAnalysis;
-use walrus::{FunctionBuilder, InstrSeqBuilder, LocalId, Module, ModuleConfig, ValType};
+use std::collections::HashMap;
+
+use clarity::vm::{
+    analysis::ContractAnalysis,
+    functions::define::DefineFunctions,
+    representations::Span,
+    types::{
+        FixedFunction, FunctionType, QualifiedContractIdentifier, TraitIdentifier, TypeSignature,
+    },
+    ClarityVersion, SymbolicExpression,
+};
+use walrus::{
+    ir::{InstrSeqId, InstrSeqType},
+    ElementId, ElementKind, FunctionBuilder, FunctionId, InstrSeqBuilder, LocalId, Module,
+    ModuleConfig, TypedCustomSectionId, ValType,
+};
 
 use super::{
-    GlobalImportReference, ParameterDefinition, TableImportReference, WasmFunctionContext,
-    WasmGenerationResult,
+    source_map::SourceMapSection, CodegenConvention, CompileResult, ContractInterface,
+    DataMapMetadata, DataVarMetadata, GenerationError, GlobalImportReference, ParameterDefinition,
+    SequenceLowering, TableImportReference, TraitMetadata, WasmFunctionContext,
+    WasmGenerationError, WasmGenerationResult,
+};
+use crate::{
+    compiler::TOP_LEVEL_FUNCTION,
+    runtime::NameInterner,
+    stdlib::{self, LinkedStdlib},
 };
 
 #[derive(Debug)]
 pub struct WasmGenerator {
     pub(crate) module: Module,
+    /// The convention by which values are passed to the host functions, which also
+    /// determines how they're represented in the generated code.
+    pub(crate) convention: CodegenConvention,
+    /// How `fold` and `map` are lowered.
+    pub(crate) sequence_lowering: SequenceLowering,
     pub(crate) current_fn: Option<WasmFunctionContext>,
     pub(crate) cost_tracker_ref: GlobalImportReference,
     pub(crate) const_table: TableImportReference,
+    pub(crate) clarity_version: ClarityVersion,
+    pub(crate) host_functions: HashMap<String, FunctionId>,
+    /// The functions defined by the contract, by name.
+    pub(crate) functions: HashMap<String, FunctionId>,
+    pub(crate) stdlib: Option<LinkedStdlib>,
+    /// The analysis of the contract being generated, without its expressions.
+    pub(crate) contract_analysis: Option<ContractAnalysis>,
+    /// The names referenced by id from the module, e.g. as arguments to the data map
+    /// host functions.
+    pub(crate) names: NameInterner,
+    pub(crate) maps: Vec<DataMapMetadata>,
+    pub(crate) data_vars: Vec<DataVarMetadata>,
+    pub(crate) traits: Vec<TraitMetadata>,
+    pub(crate) implemented_traits: Vec<TraitIdentifier>,
+    /// The traits of trait-typed parameters, indexed by their interface ids.
+    pub(crate) interfaces: Vec<TraitIdentifier>,
+    /// The contracts called using `contract-call?`, indexed by their ids.
+    pub(crate) called_contracts: Vec<QualifiedContractIdentifier>,
+    /// The functions defined by the contract.
+    pub(crate) contract_interface: ContractInterface,
+    /// The function initializing the contract, exported as `TOP_LEVEL_FUNCTION`. It's
+    /// only created once there's top-level code to run.
+    pub(crate) top_level: Option<WasmFunctionContext>,
+    /// The custom section mapping the generated instructions back to the spans of the
+    /// expressions which they were generated from.
+    pub(crate) source_map: TypedCustomSectionId<SourceMapSection>,
+    /// The element segment declaring the functions referenced by `ref.func`, created
+    /// once the first function is referenced.
+    pub(crate) declared_functions: Option<ElementId>,
+    /// The span and construct name of the innermost expression whose traversal
+    /// failed, which the error returned by `generate` is located at.
+    pub(crate) failed_expr: Option<(Span, String)>,
 }
 
 impl WasmGenerator {
     pub fn new() -> Self {
-        // Construct a new Walrus module.
-        let config = ModuleConfig::new();
+        Self::with_convention(CodegenConvention::default())
+    }
+
+    /// Creates a generator which passes values to the host functions using the given
+    /// `CodegenConvention`.
+    pub fn with_convention(convention: CodegenConvention) -> Self {
+        // Construct a new Walrus module. The code transform is needed to resolve the
+        // offsets of the instructions in the source map.
+        let mut config = ModuleConfig::new();
+        config.preserve_code_transform(true);
         let mut module = Module::with_config(config);
+        let source_map = module.customs.add(SourceMapSection::default());
+
+        // The memory host functions read their arguments from, and write their results
+        // to, the module's `vm_mem` memory.
+        if convention == CodegenConvention::Memory {
+            let memory_id = module.memories.add_local(false, 1, None);
+            module.exports.add("vm_mem", memory_id);
+        }
 
         // Add a global for the cost tracker, stored in a global called `__cost_tracker_ref`.
         let (cost_tracker_global_id, cost_tracker_import_id) =
@@ -30,20 +104,76 @@ impl WasmGenerator {
 
         WasmGenerator {
             module,
+            convention,
+            sequence_lowering: SequenceLowering::default(),
             current_fn: None,
             cost_tracker_ref: GlobalImportReference {
                 global_id: cost_tracker_global_id,
                 import_id: cost_tracker_import_id,
             },
             const_table: TableImportReference::new(const_table_id, const_table_import_id),
+            clarity_version: ClarityVersion::latest(),
+            host_functions: HashMap::new(),
+            functions: HashMap::new(),
+            stdlib: None,
+            contract_analysis: None,
+            names: NameInterner::new(),
+            maps: Vec::new(),
+            data_vars: Vec::new(),
+            traits: Vec::new(),
+            implemented_traits: Vec::new(),
+            interfaces: Vec::new(),
+            called_contracts: Vec::new(),
+            contract_interface: ContractInterface::default(),
+            top_level: None,
+            source_map,
+            declared_functions: None,
+            failed_expr: None,
         }
     }
 
-    /// Generate the
-    pub fn generate(&mut self, contract_analysis: ContractAnalysis) -> WasmGenerationResult {
-        // Traverse and visit all of the expressions from the provided `ContractAnalysis`.
-        for expr in contract_analysis.expressions.iter() {
-            self.traverse_expr(expr)?
+    /// Sets how `fold` and `map` are lowered. See `SequenceLowering`.
+    pub fn with_sequence_lowering(mut self, sequence_lowering: SequenceLowering) -> Self {
+        self.sequence_lowering = sequence_lowering;
+        self
+    }
+
+    /// Generate the module for the provided `ContractAnalysis`. Native function lookups
+    /// honor the Clarity version that the contract was analyzed with. An error is
+    /// located at the innermost expression whose code couldn't be generated.
+    pub fn generate(
+        &mut self,
+        mut contract_analysis: ContractAnalysis,
+    ) -> Result<(), GenerationError> {
+        self.clarity_version = contract_analysis.clarity_version;
+
+        // The rest of the analysis (e.g. the types of data maps) is kept for the visitors.
+        let expressions = std::mem::take(&mut contract_analysis.expressions);
+        self.contract_analysis = Some(contract_analysis);
+
+        self.failed_expr = None;
+        self.generate_expressions(&expressions)
+            .map_err(|kind| match self.failed_expr.take() {
+                Some((span, construct)) => GenerationError::at(kind, span, construct),
+                None => GenerationError::from(kind),
+            })
+    }
+
+    /// Traverses and visits all of the expressions from the provided `ContractAnalysis`.
+    /// Those which aren't definitions are evaluated, for their side effects, when the
+    /// contract is initialized.
+    fn generate_expressions(&mut self, expressions: &[SymbolicExpression]) -> WasmGenerationResult {
+        for expr in expressions.iter() {
+            if is_definition(expr) {
+                self.traverse_expr(expr)?
+            } else {
+                self.with_top_level(|g| g.traverse_statement(expr))?
+            }
+        }
+
+        if let Some(top_level) = self.top_level.take() {
+            self.current_fn = Some(top_level);
+            self.end_function(true);
         }
 
         Ok(())
@@ -51,9 +181,190 @@ impl WasmGenerator {
 
     /// Finalizes the module, consuming `self` and emitting the final WASM binary bytes.
     pub fn finalize(mut self) -> Vec<u8> {
+        self.emit_wasm()
+    }
+
+    /// Emits the WASM binary bytes of the module generated so far.
+    pub fn emit_wasm(&mut self) -> Vec<u8> {
         self.module.emit_wasm()
     }
 
+    /// Emits the module generated so far in the WebAssembly text format, e.g. to
+    /// inspect the code generated for a Clarity function in tests and bug reports.
+    pub fn emit_wat(&mut self) -> Result<String, WasmGenerationError> {
+        wasmprinter::print_bytes(self.emit_wasm()).map_err(|e| {
+            WasmGenerationError::InternalError(format!("Failed to print module: {}", e))
+        })
+    }
+
+    /// Finalizes the module, consuming `self` and returning it along with the metadata
+    /// which the host needs in order to run it.
+    pub(crate) fn into_compile_result(mut self) -> CompileResult {
+        let module_bytes = self.emit_wasm();
+        CompileResult {
+            module_bytes,
+            source_map: self
+                .module
+                .customs
+                .get(self.source_map)
+                .map(|section| section.source_map.clone())
+                .unwrap_or_default(),
+            names: self.names.iter().cloned().collect(),
+            constants: self
+                .const_table
+                .constants
+                .iter()
+                .map(|constant| constant.value.clone())
+                .collect(),
+            maps: std::mem::take(&mut self.maps),
+            data_vars: std::mem::take(&mut self.data_vars),
+            traits: std::mem::take(&mut self.traits),
+            implemented_traits: std::mem::take(&mut self.implemented_traits),
+            interfaces: std::mem::take(&mut self.interfaces),
+            called_contracts: std::mem::take(&mut self.called_contracts),
+            contract_interface: std::mem::take(&mut self.contract_interface),
+        }
+    }
+
+    /// Gets the Wasm types which values of the given Clarity type are represented by
+    /// under the generator's `CodegenConvention`. The type may be unknown (`None`),
+    /// which is only supported by conventions representing all values alike.
+    pub(crate) fn val_types(
+        &self,
+        type_signature: Option<&TypeSignature>,
+    ) -> Result<Vec<ValType>, WasmGenerationError> {
+        match (self.convention, type_signature) {
+            (CodegenConvention::Externref, _) => Ok(vec![ValType::Externref]),
+            (CodegenConvention::RustRefStack, Some(TypeSignature::IntType)) => {
+                Ok(vec![ValType::I64, ValType::I64])
+            }
+            (CodegenConvention::RustRefStack, Some(TypeSignature::BoolType)) => {
+                Ok(vec![ValType::I32])
+            }
+            (CodegenConvention::RustRefStack, _) => {
+                Err(WasmGenerationError::UnsupportedByConvention(
+                    format!(
+                        "Values of type {}",
+                        type_signature.map_or("unknown".to_string(), |ty| ty.to_string())
+                    ),
+                    self.convention,
+                ))
+            }
+            (CodegenConvention::Memory, _) => Ok(vec![ValType::I32, ValType::I32]),
+        }
+    }
+
+    /// Adds new locals to the function being built to hold a value of the given type.
+    pub(crate) fn add_value_locals(
+        &mut self,
+        type_signature: Option<&TypeSignature>,
+    ) -> Result<Vec<LocalId>, WasmGenerationError> {
+        Ok(self
+            .val_types(type_signature)?
+            .into_iter()
+            .map(|ty| self.add_local(ty))
+            .collect())
+    }
+
+    /// Moves the value on top of the stack to the given locals (see `add_value_locals`).
+    pub(crate) fn set_value_locals(&mut self, local_ids: &[LocalId]) {
+        let mut function = self.get_function();
+        for local_id in local_ids.iter().rev() {
+            function.local_set(*local_id);
+        }
+    }
+
+    /// Pushes the value held by the given locals (see `add_value_locals`).
+    pub(crate) fn get_value_locals(&mut self, local_ids: &[LocalId]) {
+        let mut function = self.get_function();
+        for local_id in local_ids {
+            function.local_get(*local_id);
+        }
+    }
+
+    /// Gets the type of the given expression, as inferred by the type checker.
+    pub(crate) fn expr_type(&self, expr: &SymbolicExpression) -> Option<&TypeSignature> {
+        self.contract_analysis
+            .as_ref()
+            .and_then(|analysis| analysis.type_map.as_ref())
+            .and_then(|type_map| type_map.get_type(expr))
+    }
+
+    /// Gets the type of the function defined by the contract with the given name, as
+    /// inferred by the type checker.
+    pub(crate) fn fixed_function_type(
+        &self,
+        name: &str,
+    ) -> Result<FixedFunction, WasmGenerationError> {
+        let function_type = self.contract_analysis.as_ref().and_then(|analysis| {
+            analysis
+                .get_public_function_type(name)
+                .or_else(|| analysis.get_read_only_function_type(name))
+                .or_else(|| analysis.get_private_function(name))
+        });
+
+        match function_type {
+            Some(FunctionType::Fixed(function_type)) => Ok(function_type.clone()),
+            _ => Err(WasmGenerationError::InternalError(format!(
+                "Unknown function type: {}",
+                name
+            ))),
+        }
+    }
+
+    /// Gets the interface id of the given trait, which identifies it among the traits
+    /// of trait-typed parameters.
+    pub(crate) fn interface_id(&mut self, trait_id: &TraitIdentifier) -> u32 {
+        match self.interfaces.iter().position(|id| id == trait_id) {
+            Some(index) => index as u32,
+            None => {
+                self.interfaces.push(trait_id.clone());
+                (self.interfaces.len() - 1) as u32
+            }
+        }
+    }
+
+    /// Gets the id of the given contract among the contracts called using
+    /// `contract-call?`, by which the `contract_call_static` host function resolves it.
+    pub(crate) fn called_contract_id(&mut self, contract_id: &QualifiedContractIdentifier) -> i32 {
+        match self
+            .called_contracts
+            .iter()
+            .position(|id| id == contract_id)
+        {
+            Some(index) => index as i32,
+            None => {
+                self.called_contracts.push(contract_id.clone());
+                (self.called_contracts.len() - 1) as i32
+            }
+        }
+    }
+
+    /// Runs `emit` with the top-level function, which initializes the contract, as the
+    /// function being built. This is used for code outside of any function, such as
+    /// storing the initial values of data variables.
+    pub(crate) fn with_top_level(
+        &mut self,
+        emit: impl FnOnce(&mut Self) -> WasmGenerationResult,
+    ) -> WasmGenerationResult {
+        if self.current_fn.is_some() {
+            return Err(WasmGenerationError::InternalError(
+                "Top-level code in a function".to_string(),
+            ));
+        }
+
+        if self.top_level.is_none() {
+            self.begin_function(TOP_LEVEL_FUNCTION, Vec::new(), &[]);
+            self.top_level = self.current_fn.take();
+        }
+
+        self.current_fn = self.top_level.take();
+        let result = emit(self);
+        self.top_level = self.current_fn.take();
+
+        result
+    }
+
     /// Puts the `WasmGenerator` in function-building mode.
     pub fn begin_function(
         &mut self,
@@ -62,10 +373,15 @@ impl WasmGenerator {
         results: &[ValType],
     ) -> InstrSeqBuilder {
         // Convert the parameters to a list of `ValType`s which is required for the Walrus `FunctionBuilder`.
-        let fn_params = params.iter().map(|x| x.val_type).collect::<Vec<ValType>>();
+        let fn_params = params
+            .iter()
+            .flat_map(|x| x.val_types.iter().copied())
+            .collect::<Vec<ValType>>();
 
         // Initialize a new function builder.
-        let function_builder = FunctionBuilder::new(&mut self.module.types, &fn_params, results);
+        let mut function_builder =
+            FunctionBuilder::new(&mut self.module.types, &fn_params, results);
+        function_builder.name(name.to_string());
 
         let current_fn = WasmFunctionContext {
             id: function_builder.func_body_id(),
@@ -73,6 +389,7 @@ impl WasmGenerator {
             name: name.to_string(),
             locals: Vec::<LocalId>::new(),
             params,
+            bindings: Vec::new(),
         };
 
         // Set the current WASM function context for this generator.
@@ -111,12 +428,114 @@ impl WasmGenerator {
         }
 
         let func = self.current_fn.take().unwrap();
-        let locals = func.locals;
-        let function_id = func.function_builder.finish(locals, &mut self.module.funcs);
+        // The parameters are passed in the locals which they're resolved to.
+        let args = func
+            .params
+            .iter()
+            .flat_map(|param| param.local_ids.iter().copied())
+            .collect();
+        let function_id = func.function_builder.finish(args, &mut self.module.funcs);
 
         if export {
             self.module.exports.add(&func.name, function_id);
         }
+        self.functions.insert(func.name, function_id);
+    }
+
+    /// Names the given locals, holding a value (see `add_value_locals`), after the
+    /// Clarity variable holding it. The names are emitted in the module's name section.
+    pub(crate) fn name_value_locals(&mut self, local_ids: &[LocalId], name: &str) {
+        for (index, local_id) in local_ids.iter().enumerate() {
+            self.module.locals.get_mut(*local_id).name = Some(match local_ids.len() {
+                1 => name.to_string(),
+                _ => format!("{}.{}", name, index),
+            });
+        }
+    }
+
+    /// Gets the position at which the next instruction will be emitted in the
+    /// function being built, if any. See `map_to_span`.
+    pub(crate) fn instr_position(&mut self) -> Option<(InstrSeqId, usize)> {
+        let func = self.current_fn.as_mut()?;
+        let len = func.function_builder.instr_seq(func.id).instrs().len();
+        Some((func.id, len))
+    }
+
+    /// Maps the instructions emitted since the given position (see `instr_position`)
+    /// which aren't mapped yet, i.e. those not generated from a nested expression, to
+    /// the given span in the source map.
+    pub(crate) fn map_to_span(&mut self, start: Option<(InstrSeqId, usize)>, span: &Span) {
+        let (Some((seq_id, start)), Some(func)) = (start, self.current_fn.as_mut()) else {
+            return;
+        };
+        if func.id != seq_id {
+            return;
+        }
+
+        let mut seq = func.function_builder.instr_seq(seq_id);
+        let instrs = &mut seq.instrs_mut()[start..];
+        if instrs.iter().all(|(_, loc)| !loc.is_default()) {
+            return;
+        }
+
+        let loc = self
+            .module
+            .customs
+            .get_mut(self.source_map)
+            .expect("The source map section is always present")
+            .add_span(span);
+        for (_, instr_loc) in instrs.iter_mut().filter(|(_, loc)| loc.is_default()) {
+            *instr_loc = loc;
+        }
+    }
+
+    /// Adds a new local of the given type to the function being built.
+    pub(crate) fn add_local(&mut self, ty: ValType) -> LocalId {
+        let local_id = self.module.locals.add(ty);
+        self.current_fn
+            .as_mut()
+            .expect("Attempt to add a local when no function is being built.")
+            .locals
+            .push(local_id);
+        local_id
+    }
+
+    /// Builds a new instruction sequence (such as a branch of an `if`) in the function
+    /// being built, returning its id. While `build` runs, instructions emitted through
+    /// `get_function` are added to the new sequence rather than the current one.
+    pub(crate) fn build_block(
+        &mut self,
+        ty: impl Into<InstrSeqType>,
+        build: impl FnOnce(&mut Self) -> WasmGenerationResult,
+    ) -> Result<InstrSeqId, WasmGenerationError> {
+        let func = self
+            .current_fn
+            .as_mut()
+            .expect("Attempt to build a block when no function is being built.");
+        let block_id = func.function_builder.dangling_instr_seq(ty).id();
+        let parent_id = std::mem::replace(&mut func.id, block_id);
+
+        let result = build(self);
+
+        self.current_fn.as_mut().unwrap().id = parent_id;
+        result.map(|_| block_id)
+    }
+
+    /// Emits a `ref.func` to the given function. Functions referenced this way must be
+    /// declared by an element segment, so they're added to the module's declared
+    /// segment.
+    pub(crate) fn ref_func(&mut self, function_id: FunctionId) {
+        let element_id = *self.declared_functions.get_or_insert_with(|| {
+            self.module
+                .elements
+                .add(ElementKind::Declared, ValType::Funcref, Vec::new())
+        });
+        let members = &mut self.module.elements.get_mut(element_id).members;
+        if !members.contains(&Some(function_id)) {
+            members.push(Some(function_id));
+        }
+
+        self.get_function().ref_func(function_id);
     }
 
     /// Gets whether or not the WasmGenerator is currently in the middle of building
@@ -124,4 +543,32 @@ impl WasmGenerator {
     pub fn is_building_function(&self) -> bool {
         self.current_fn.is_some()
     }
+
+    /// Gets the `FunctionId` of the `wasm-stdlib` function with the given name (e.g.
+    /// `add-int128`), which can then be called directly. The stdlib is linked into the
+    /// module the first time one of its functions is requested.
+    pub(crate) fn stdlib_function(
+        &mut self,
+        name: &str,
+    ) -> Result<FunctionId, WasmGenerationError> {
+        if self.stdlib.is_none() {
+            let linked =
+                stdlib::link_into(&mut self.module).map_err(WasmGenerationError::StdlibLink)?;
+            self.stdlib = Some(linked);
+        }
+
+        self.stdlib
+            .as_ref()
+            .and_then(|linked| linked.function(name))
+            .ok_or_else(|| WasmGenerationError::UnknownFunction(name.to_string()))
+    }
+}
+
+/// Gets whether the given top-level expression is a definition (e.g. `define-public`).
+fn is_definition(expr: &SymbolicExpression) -> bool {
+    expr.match_list()
+        .and_then(|list| list.first())
+        .and_then(|name| name.match_atom())
+        .and_then(|name| DefineFunctions::lookup_by_name(name))
+        .is_some()
 }
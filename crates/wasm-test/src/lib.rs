@@ -9,8 +9,18 @@
 pub mod compiler;
 pub mod runtime;
 pub mod serialization;
+pub mod stdlib;
 
-use runtime::alloc::WasmAllocator;
+use std::{collections::HashMap, sync::OnceLock};
+
+use clarity::vm::{
+    types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, TypeSignature},
+    ClarityName, ClarityVersion, Value,
+};
+use runtime::{
+    AllocatorStats, BlockInfoProvider, ClarityDatastore, ContractRegistry, MemoryDatastore,
+    NameInterner, NoBlockInfo, ScratchPool, WasmAllocator, WasmMemoryAllocator,
+};
 // Public exports
 pub use runtime::get_all_functions;
 
@@ -18,20 +28,263 @@ pub use runtime::get_all_functions;
 #[cfg(test)]
 mod tests;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug)]
 pub struct ClarityWasmContext {
-    pub alloc: WasmAllocator,
+    pub alloc: Box<dyn WasmMemoryAllocator>,
+    clarity_version: ClarityVersion,
+    mainnet: bool,
+    tx_sender: PrincipalData,
+    contract_caller: PrincipalData,
+    block_height: u32,
+    burn_block_height: u32,
+    contract_id: Option<QualifiedContractIdentifier>,
+    contracts: ContractRegistry,
+    names: HashMap<Option<QualifiedContractIdentifier>, NameInterner>,
+    datastore: Box<dyn ClarityDatastore>,
+    data_vars: HashMap<(QualifiedContractIdentifier, i32), TypeSignature>,
+    read_only: bool,
+    block_info: Box<dyn BlockInfoProvider>,
+    events: Vec<Value>,
+    scratch: ScratchPool,
 }
 
 impl ClarityWasmContext {
     pub fn new() -> Self {
         ClarityWasmContext {
-            alloc: WasmAllocator::new(),
+            alloc: Box::new(WasmAllocator::new()),
+            clarity_version: ClarityVersion::latest(),
+            mainnet: false,
+            tx_sender: PrincipalData::Standard(StandardPrincipalData::transient()),
+            contract_caller: PrincipalData::Standard(StandardPrincipalData::transient()),
+            block_height: 0,
+            burn_block_height: 0,
+            contract_id: None,
+            contracts: ContractRegistry::new(),
+            names: HashMap::new(),
+            datastore: Box::new(MemoryDatastore::new()),
+            data_vars: HashMap::new(),
+            read_only: false,
+            block_info: Box::new(NoBlockInfo),
+            events: Vec::new(),
+            scratch: ScratchPool::new(),
         }
     }
+
+    /// Sets the Clarity version used to determine which native functions are
+    /// available to contracts executed with this context.
+    pub fn with_clarity_version(mut self, clarity_version: ClarityVersion) -> Self {
+        self.clarity_version = clarity_version;
+        self
+    }
+
+    /// Sets whether contracts executed with this context run on mainnet, which
+    /// determines the address versions used by the principal functions.
+    pub fn with_mainnet(mut self, mainnet: bool) -> Self {
+        self.mainnet = mainnet;
+        self
+    }
+
+    /// Sets the datastore backing the data maps of contracts executed with this
+    /// context. Defaults to an empty `MemoryDatastore`.
+    pub fn with_datastore(mut self, datastore: impl ClarityDatastore + 'static) -> Self {
+        self.datastore = Box::new(datastore);
+        self
+    }
+
+    /// Sets the allocator used to reserve Wasm memory for values written by the
+    /// host, e.g. a `BuddyAllocator` so that memory can be reclaimed. Defaults to
+    /// the bump `WasmAllocator`.
+    pub fn with_allocator(mut self, alloc: impl WasmMemoryAllocator + 'static) -> Self {
+        self.alloc = Box::new(alloc);
+        self
+    }
+
+    /// Sets the provider of the block data returned by `get-block-info?` and
+    /// `get-burn-block-info?`. Defaults to `NoBlockInfo`.
+    pub fn with_block_info(mut self, block_info: impl BlockInfoProvider + 'static) -> Self {
+        self.block_info = Box::new(block_info);
+        self
+    }
+
+    /// Gets statistics about the Wasm memory handed out by the context's allocator,
+    /// e.g. for cost accounting.
+    pub fn alloc_stats(&self) -> AllocatorStats {
+        self.alloc.stats()
+    }
+
+    /// Gets the Clarity version of this context.
+    pub fn clarity_version(&self) -> ClarityVersion {
+        self.clarity_version
+    }
+
+    /// Gets whether or not contracts executed with this context run on mainnet.
+    pub fn is_mainnet(&self) -> bool {
+        self.mainnet
+    }
+
+    /// Gets the principal which originated the current transaction (`tx-sender`).
+    pub fn tx_sender(&self) -> &PrincipalData {
+        &self.tx_sender
+    }
+
+    /// Sets the principal which originated the current transaction (`tx-sender`).
+    pub fn set_tx_sender(&mut self, tx_sender: PrincipalData) {
+        self.tx_sender = tx_sender;
+    }
+
+    /// Gets the principal which called the current contract (`contract-caller`).
+    pub fn contract_caller(&self) -> &PrincipalData {
+        &self.contract_caller
+    }
+
+    /// Sets the principal which called the current contract (`contract-caller`).
+    pub fn set_contract_caller(&mut self, contract_caller: PrincipalData) {
+        self.contract_caller = contract_caller;
+    }
+
+    /// Gets the current Stacks block height (`block-height`).
+    pub fn block_height(&self) -> u32 {
+        self.block_height
+    }
+
+    /// Sets the current Stacks block height (`block-height`).
+    pub fn set_block_height(&mut self, block_height: u32) {
+        self.block_height = block_height;
+    }
+
+    /// Gets the current burnchain block height (`burn-block-height`).
+    pub fn burn_block_height(&self) -> u32 {
+        self.burn_block_height
+    }
+
+    /// Sets the current burnchain block height (`burn-block-height`).
+    pub fn set_burn_block_height(&mut self, burn_block_height: u32) {
+        self.burn_block_height = burn_block_height;
+    }
+
+    /// Gets the identifier of the contract currently being executed, if any.
+    pub fn contract_id(&self) -> Option<&QualifiedContractIdentifier> {
+        self.contract_id.as_ref()
+    }
+
+    /// Sets the identifier of the contract currently being executed.
+    pub fn set_contract_id(&mut self, contract_id: Option<QualifiedContractIdentifier>) {
+        self.contract_id = contract_id;
+    }
+
+    /// Gets the registry of contracts which can be called using `contract-call?`.
+    pub fn contracts(&self) -> &ContractRegistry {
+        &self.contracts
+    }
+
+    /// Gets a mutable reference to the registry of contracts which can be called
+    /// using `contract-call?`.
+    pub fn contracts_mut(&mut self) -> &mut ContractRegistry {
+        &mut self.contracts
+    }
+
+    /// Gets the interned names (e.g. tuple field names) which can be referenced by
+    /// id from Wasm. Each contract has its own ids, so these are the names of the
+    /// contract currently being executed (or of no contract, if none is).
+    pub fn names(&self) -> &NameInterner {
+        static NO_NAMES: OnceLock<NameInterner> = OnceLock::new();
+        self.names
+            .get(&self.contract_id)
+            .unwrap_or_else(|| NO_NAMES.get_or_init(NameInterner::new))
+    }
+
+    /// Gets a mutable reference to the interned names of the contract currently
+    /// being executed (see `names`).
+    pub fn names_mut(&mut self) -> &mut NameInterner {
+        self.names.entry(self.contract_id.clone()).or_default()
+    }
+
+    /// Gets a mutable reference to the interned names of the given contract, e.g. to
+    /// intern the names referenced by the contract before it is executed.
+    pub fn contract_names_mut(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+    ) -> &mut NameInterner {
+        self.names.entry(Some(contract_id.clone())).or_default()
+    }
+
+    /// Gets the datastore backing the data maps of executed contracts.
+    pub fn datastore(&self) -> &dyn ClarityDatastore {
+        self.datastore.as_ref()
+    }
+
+    /// Gets a mutable reference to the datastore backing the data maps of
+    /// executed contracts.
+    pub fn datastore_mut(&mut self) -> &mut dyn ClarityDatastore {
+        self.datastore.as_mut()
+    }
+
+    /// Runs `f` with an empty scratch buffer taken from this context's pool, which
+    /// is returned to the pool afterwards. Host functions use this to serialize
+    /// values (e.g. map keys) without allocating on every call. The context itself
+    /// is passed to `f` too, as it is otherwise borrowed while the buffer is in use.
+    pub fn with_scratch<R>(&mut self, f: impl FnOnce(&mut Self, &mut Vec<u8>) -> R) -> R {
+        let mut buffer = self.scratch.take();
+        let result = f(self, &mut buffer);
+        self.scratch.give_back(buffer);
+        result
+    }
+
+    /// Gets the provider of block data for executed contracts.
+    pub fn block_info(&self) -> &dyn BlockInfoProvider {
+        self.block_info.as_ref()
+    }
+
+    /// Declares a data variable of the given contract, interning its name and
+    /// returning the id which is used to reference it from Wasm. Values written
+    /// using `var-set` must be admitted by the declared type.
+    pub fn declare_data_var(
+        &mut self,
+        contract_id: QualifiedContractIdentifier,
+        name: ClarityName,
+        type_signature: TypeSignature,
+    ) -> i32 {
+        let id = self.contract_names_mut(&contract_id).intern(name);
+        self.data_vars.insert((contract_id, id), type_signature);
+        id
+    }
+
+    /// Gets the declared type of the data variable with the given name id in the
+    /// given contract.
+    pub fn data_var_type(
+        &self,
+        contract_id: &QualifiedContractIdentifier,
+        name_id: i32,
+    ) -> Option<&TypeSignature> {
+        self.data_vars.get(&(contract_id.clone(), name_id))
+    }
+
+    /// Gets whether or not the current call is read-only, in which case writes to
+    /// the datastore fail.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Sets whether or not the current call is read-only (e.g. a `define-read-only`
+    /// function or `at-block` expression is being evaluated).
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Appends a value to the event log (i.e. the output of `print`).
+    pub fn push_event(&mut self, value: Value) {
+        self.events.push(value);
+    }
+
+    /// Retrieves the events emitted since the last call to `take_events`, in the
+    /// order in which they were emitted, leaving the event log empty.
+    pub fn take_events(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.events)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ptr {
     pub offset: i32,
     pub len: i32,
@@ -1,9 +1,31 @@
 pub(crate) mod alloc;
+pub(crate) mod block_info;
+pub(crate) mod builder;
+pub(crate) mod contracts;
+pub(crate) mod datastore;
+pub(crate) mod deadline;
+pub(crate) mod guest_panic;
+pub(crate) mod memory;
+pub(crate) mod names;
 pub(crate) mod native_functions;
+pub(crate) mod scratch;
 
-pub use native_functions::get_all_functions;
+pub use alloc::{
+    AllocatorStats, BuddyAllocator, FrameAllocator, GuardedAllocator, Realloc, Relocation,
+    RelocationTable, WasmAllocator, WasmMemoryAllocator,
+};
+pub use block_info::{BlockInfoProvider, NoBlockInfo};
+pub use builder::RuntimeBuilder;
+pub use contracts::ContractRegistry;
+pub use datastore::{ClarityDatastore, MemoryDatastore};
+pub use deadline::{call_with_deadline, EpochTicker, ExecutionDeadline};
+pub use guest_panic::{read_guest_panic, GuestPanic, GuestPanicReason, PANIC_INFO_GLOBAL};
+pub use memory::{alloc_in_memory, read_value_from_memory, write_value_to_memory};
+pub use names::NameInterner;
+pub use native_functions::{get_all_functions, link_host_functions, HOST_MODULE_NAME};
 use num::FromPrimitive;
 use num_derive::{FromPrimitive, ToPrimitive};
+pub use scratch::ScratchPool;
 
 use crate::Ptr;
 
@@ -18,6 +40,72 @@ pub enum RuntimeError {
     ArgumentTypeMismatch = 5,
     ArithmeticOverflow = 6,
     FailedToWriteResultToMemory = 7,
+    ExecutionTimedOut = 8,
+    ContractNotFound = 9,
+    FunctionNotFound = 10,
+    ArithmeticUnderflow = 11,
+    DivisionByZero = 12,
+    MaxLengthExceeded = 13,
+    UnwrapFailed = 14,
+    NameNotFound = 15,
+    FailedToSerializeValue = 16,
+    FailedToDeserializeStoredValue = 17,
+    WriteInReadOnlyContext = 18,
+    DataVarNotDeclared = 19,
+    BlockNotFound = 20,
+    InvalidArithmeticArgument = 21,
+    IndexOutOfRange = 22,
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            RuntimeError::InvalidRuntimeError => "invalid runtime error code",
+            RuntimeError::None => "no error",
+            RuntimeError::FunctionArgumentRequired => {
+                "a required function argument was not provided"
+            }
+            RuntimeError::FailedToDeserializeValueFromMemory => {
+                "failed to deserialize value from memory"
+            }
+            RuntimeError::FailedToDiscernSerializedType => "failed to discern serialized type",
+            RuntimeError::FunctionOnlySupportsIntegralValues => {
+                "function only supports integral values"
+            }
+            RuntimeError::ArgumentTypeMismatch => "argument type mismatch",
+            RuntimeError::ArithmeticOverflow => "arithmetic overflow",
+            RuntimeError::FailedToWriteResultToMemory => "failed to write result to memory",
+            RuntimeError::ExecutionTimedOut => "execution deadline exceeded",
+            RuntimeError::ContractNotFound => "contract not found",
+            RuntimeError::FunctionNotFound => "function not found",
+            RuntimeError::ArithmeticUnderflow => "arithmetic underflow",
+            RuntimeError::DivisionByZero => "division by zero",
+            RuntimeError::MaxLengthExceeded => "maximum sequence length exceeded",
+            RuntimeError::UnwrapFailed => "attempted to unwrap a none or err value",
+            RuntimeError::NameNotFound => "name not found",
+            RuntimeError::FailedToSerializeValue => "failed to serialize value",
+            RuntimeError::FailedToDeserializeStoredValue => {
+                "failed to deserialize value from the datastore"
+            }
+            RuntimeError::WriteInReadOnlyContext => "attempted to write in a read-only context",
+            RuntimeError::DataVarNotDeclared => "data variable not declared",
+            RuntimeError::BlockNotFound => "block not found",
+            RuntimeError::InvalidArithmeticArgument => "invalid argument to an arithmetic function",
+            RuntimeError::IndexOutOfRange => "sequence index out of range",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl RuntimeError {
+    /// Recovers the Clarity runtime error (e.g. an arithmetic overflow) which caused
+    /// a call into Wasm to fail. Returns `None` if the call failed for any other
+    /// reason, such as a Wasm trap, so that embedders can tell the two apart.
+    pub fn from_error(err: &wasmtime::Error) -> Option<RuntimeError> {
+        err.downcast_ref::<RuntimeError>().copied()
+    }
 }
 
 pub type FuncResult = (i32, i32, i32);
@@ -1,28 +1,433 @@
+use std::fmt::Debug;
+
 use crate::Ptr;
 
-#[derive(Debug, Copy, Clone)]
+pub(crate) mod buddy;
+pub(crate) mod frames;
+pub(crate) mod guarded;
+
+pub use buddy::BuddyAllocator;
+pub use frames::FrameAllocator;
+pub use guarded::GuardedAllocator;
+
+/// Statistics about the memory handed out by an allocator, e.g. for cost
+/// accounting. Byte counts are cumulative, and those of the `BuddyAllocator`
+/// include the rounding of allocations up to block sizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// The total number of bytes allocated, including by growing allocations.
+    pub bytes_allocated: usize,
+    /// The total number of bytes freed, including by shrinking allocations.
+    pub bytes_freed: usize,
+    /// The number of allocations which haven't been freed.
+    pub live_allocations: usize,
+    /// The highest number of bytes allocated at any one time.
+    pub peak_usage: usize,
+    /// The size of the memory region currently managed by the allocator, i.e. the
+    /// bump allocator's next offset or the buddy allocator's arena size.
+    pub memory_size: usize,
+}
+
+impl AllocatorStats {
+    /// Gets the number of bytes currently allocated.
+    pub fn live_bytes(&self) -> usize {
+        self.bytes_allocated.saturating_sub(self.bytes_freed)
+    }
+
+    /// Estimates the fragmentation of the managed memory, as the fraction of it
+    /// which isn't allocated, from `0.0` (all of it is in use) to `1.0`.
+    pub fn fragmentation(&self) -> f64 {
+        if self.memory_size == 0 {
+            return 0.0;
+        }
+        (1.0 - self.live_bytes() as f64 / self.memory_size as f64).max(0.0)
+    }
+
+    fn record_alloc(&mut self, size: usize) {
+        self.bytes_allocated += size;
+        self.live_allocations += 1;
+        self.peak_usage = self.peak_usage.max(self.live_bytes());
+    }
+
+    fn record_free(&mut self, size: usize) {
+        self.bytes_freed += size;
+        self.live_allocations = self.live_allocations.saturating_sub(1);
+    }
+
+    fn record_resize(&mut self, old_size: usize, new_size: usize) {
+        if new_size > old_size {
+            self.bytes_allocated += new_size - old_size;
+            self.peak_usage = self.peak_usage.max(self.live_bytes());
+        } else {
+            self.bytes_freed += old_size - new_size;
+        }
+    }
+
+    /// Records all live allocations as freed at once.
+    fn record_reset(&mut self) {
+        self.bytes_freed = self.bytes_allocated;
+        self.live_allocations = 0;
+    }
+}
+
+/// A region moved by `compact()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Relocation {
+    /// The offset the region was moved from.
+    pub old_offset: i32,
+    /// The offset the region was moved to.
+    pub new_offset: i32,
+    /// The length of the region.
+    pub len: i32,
+}
+
+/// The regions moved by `compact()`, which is used to patch any outstanding `Ptr`s
+/// into them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelocationTable {
+    /// The moved regions, sorted by their old offset.
+    relocations: Vec<Relocation>,
+}
+
+impl RelocationTable {
+    fn new(mut relocations: Vec<Relocation>) -> Self {
+        relocations.sort_by_key(|relocation| relocation.old_offset);
+        RelocationTable { relocations }
+    }
+
+    /// Gets the new offset of the given offset, which is unchanged unless it lies
+    /// within a moved region.
+    pub fn relocate_offset(&self, offset: i32) -> i32 {
+        let index = self
+            .relocations
+            .partition_point(|relocation| relocation.old_offset <= offset);
+        match index.checked_sub(1).map(|index| self.relocations[index]) {
+            Some(relocation) if offset < relocation.old_offset + relocation.len => {
+                relocation.new_offset + (offset - relocation.old_offset)
+            }
+            _ => offset,
+        }
+    }
+
+    /// Gets the new location of the given `Ptr`.
+    pub fn relocate(&self, ptr: Ptr) -> Ptr {
+        Ptr::new(self.relocate_offset(ptr.offset), ptr.len)
+    }
+
+    /// Gets the moved regions, sorted by their old offset.
+    pub fn relocations(&self) -> &[Relocation] {
+        &self.relocations
+    }
+
+    /// Gets whether no regions were moved.
+    pub fn is_empty(&self) -> bool {
+        self.relocations.is_empty()
+    }
+}
+
+/// The result of resizing an allocation using `realloc()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Realloc {
+    /// The resized allocation.
+    pub ptr: Ptr,
+    /// Whether the allocation had to be moved, in which case the caller must copy
+    /// its contents over to `ptr`. The old region has been freed, so this must be
+    /// done before allocating anything else.
+    pub needs_copy: bool,
+}
+
+impl Realloc {
+    fn in_place(ptr: Ptr) -> Self {
+        Realloc {
+            ptr,
+            needs_copy: false,
+        }
+    }
+
+    fn moved(ptr: Ptr) -> Self {
+        Realloc {
+            ptr,
+            needs_copy: true,
+        }
+    }
+}
+
+/// An allocator of Wasm memory, used by a `ClarityWasmContext` to reserve memory
+/// for values written by the host. Implemented by the bump `WasmAllocator` and the
+/// `BuddyAllocator`, and can be selected using `ClarityWasmContext::with_allocator()`.
+pub trait WasmMemoryAllocator: Debug + Send + Sync {
+    /// Retrieve a pointer to a region which can store the given number of bytes.
+    fn alloc_for_size(&mut self, size: usize) -> Ptr;
+
+    /// Retrieve a pointer to a region which can store the given data slice.
+    fn alloc_for_buffer(&mut self, data: &[u8]) -> Ptr {
+        self.alloc_for_size(data.len())
+    }
+
+    /// Frees the allocation pointed to by `ptr`, returning whether it was freed.
+    fn dealloc(&mut self, ptr: Ptr) -> bool;
+
+    /// Resizes the allocation pointed to by `ptr`, returning `None` if it doesn't
+    /// point to an allocation.
+    fn realloc(&mut self, ptr: Ptr, new_size: usize) -> Option<Realloc>;
+
+    /// Frees all allocations at once, e.g. between contract calls.
+    fn reset(&mut self);
+
+    /// Gets statistics about the memory handed out by the allocator.
+    fn stats(&self) -> AllocatorStats;
+
+    /// Gets the maximum number of pages the Wasm memory may be grown to, if any.
+    fn max_memory_pages(&self) -> Option<u64>;
+
+    /// Gets the number of canary bytes reserved on either side of each allocation,
+    /// which is zero unless the allocator is a `GuardedAllocator`.
+    fn guard_size(&self) -> usize {
+        0
+    }
+
+    /// Writes the canaries of the allocations made (or resized) since the last
+    /// call to the provided memory, if the allocator reserves any.
+    fn write_canaries(&mut self, _memory: &mut [u8]) {}
+
+    /// Checks that the canaries around the live allocations in the provided memory
+    /// are intact, returning the first allocation whose canaries were overwritten.
+    fn check_canaries(&self, _memory: &[u8]) -> Result<(), Ptr> {
+        Ok(())
+    }
+
+    /// Starts a new frame, e.g. when a function is called. Only a `FrameAllocator`
+    /// keeps track of frames; other allocators ignore them.
+    fn push_frame(&mut self) {}
+
+    /// Drops the most recent frame, freeing the allocations made during it if the
+    /// allocator is a `FrameAllocator`.
+    fn drop_frame(&mut self) {}
+
+    /// Moves the live allocations together in the provided memory, so that the
+    /// memory freed between them can be re-used in one piece, and returns where
+    /// they were moved to. Any outstanding `Ptr`s into the memory must be patched
+    /// using `RelocationTable::relocate()`.
+    fn compact(&mut self, memory: &mut [u8]) -> RelocationTable;
+}
+
+/// Defaults to the bump `WasmAllocator`.
+impl Default for Box<dyn WasmMemoryAllocator> {
+    fn default() -> Self {
+        Box::new(WasmAllocator::new())
+    }
+}
+
+/// The number of size classes of freed regions, which covers every region length
+/// representable by a `Ptr`.
+const SIZE_CLASSES: usize = 32;
+
+#[derive(Debug, Clone)]
 pub struct WasmAllocator {
     next_offset: i32,
+    /// Freed regions, bucketed by size class: class `n` holds regions of
+    /// `2^n..2^(n+1)` bytes.
+    free_lists: Vec<Vec<Ptr>>,
+    stats: AllocatorStats,
+    max_memory_pages: Option<u64>,
 }
 
-/// A simple bump allocator for handling Wasm memory.
+/// A simple bump allocator for handling Wasm memory. Regions freed using
+/// `dealloc()` are kept in size-bucketed free lists and re-used by later
+/// allocations which fit in them.
 impl WasmAllocator {
     /// Creates a new `WasmAllocator` with its next offset set to `0`.
     pub fn new() -> Self {
-        WasmAllocator { next_offset: 0 }
+        WasmAllocator {
+            next_offset: 0,
+            free_lists: vec![Vec::new(); SIZE_CLASSES],
+            stats: AllocatorStats::default(),
+            max_memory_pages: None,
+        }
+    }
+
+    /// Limits the number of pages the Wasm memory may be grown to in order to fit
+    /// the allocations, see `alloc_in_memory()`. By default the memory can be grown
+    /// up to the maximum of its own type.
+    pub fn with_max_memory_pages(mut self, max_memory_pages: u64) -> Self {
+        self.max_memory_pages = Some(max_memory_pages);
+        self
+    }
+
+    /// Gets whether `ptr` is a non-empty region of the allocated memory.
+    fn is_allocated(&self, ptr: Ptr) -> bool {
+        match ptr.offset.checked_add(ptr.len) {
+            Some(end) => ptr.offset >= 0 && ptr.len > 0 && end <= self.next_offset,
+            None => false,
+        }
     }
 
-    /// Retrieve a pointer to the next available offset for the given size.
-    pub fn alloc_for_size(&mut self, size: usize) -> Ptr {
+    /// Returns a region to the free lists, or moves the next offset back if the
+    /// region is at the end of the allocated memory.
+    fn free_region(&mut self, ptr: Ptr) {
+        if ptr.offset + ptr.len == self.next_offset {
+            self.next_offset = ptr.offset;
+        } else {
+            self.free_lists[size_class(ptr.len)].push(ptr);
+        }
+    }
+
+    /// Takes a freed region which can store the given size from the free lists.
+    fn take_free_region(&mut self, size: usize) -> Option<Ptr> {
+        if size == 0 {
+            return None;
+        }
+
+        // The regions in the size's own class may be too small for it ...
+        let class = size_class(size as i32);
+        if let Some(index) = self.free_lists[class]
+            .iter()
+            .position(|region| region.len as usize >= size)
+        {
+            return Some(self.free_lists[class].swap_remove(index));
+        }
+
+        // ... whereas those of any larger class fit.
+        self.free_lists[class + 1..].iter_mut().find_map(Vec::pop)
+    }
+}
+
+impl WasmMemoryAllocator for WasmAllocator {
+    /// Gets the maximum number of pages the Wasm memory may be grown to, if any.
+    fn max_memory_pages(&self) -> Option<u64> {
+        self.max_memory_pages
+    }
+
+    /// Retrieve a pointer to a freed region which can store the given size, or to
+    /// the next available offset if there is none.
+    fn alloc_for_size(&mut self, size: usize) -> Ptr {
+        self.stats.record_alloc(size);
+
         let len = size as i32;
+        if let Some(region) = self.take_free_region(size) {
+            // Return what's left of the region to the free lists.
+            if region.len > len {
+                self.free_lists[size_class(region.len - len)]
+                    .push(Ptr::new(region.offset + len, region.len - len));
+            }
+            return Ptr::new(region.offset, len);
+        }
+
         let ptr = Ptr::new(self.next_offset, len);
         self.next_offset += len;
         return ptr;
     }
 
-    /// Retrieve a pointer to the next available offset which can store the given
-    /// data slice.
-    pub fn alloc_for_buffer(&mut self, data: &[u8]) -> Ptr {
-        self.alloc_for_size(data.len())
+    /// Frees the region pointed to by `ptr` so that it can be re-used by later
+    /// allocations. The most recent allocation is freed by moving the next offset
+    /// back. Returns `false` if `ptr` is empty or lies outside of the allocated
+    /// memory. Freeing a region which isn't allocated (e.g. twice) is not detected,
+    /// and will result in it being handed out more than once.
+    fn dealloc(&mut self, ptr: Ptr) -> bool {
+        if !self.is_allocated(ptr) {
+            return false;
+        }
+
+        self.free_region(ptr);
+        self.stats.record_free(ptr.len as usize);
+        true
+    }
+
+    /// Resizes the allocation pointed to by `ptr` to the given size. Shrinking
+    /// frees the end of the region, and the most recent allocation is grown in
+    /// place; any other allocation is moved to a new region. Returns `None` if `ptr`
+    /// is empty or lies outside of the allocated memory.
+    fn realloc(&mut self, ptr: Ptr, new_size: usize) -> Option<Realloc> {
+        if !self.is_allocated(ptr) {
+            return None;
+        }
+
+        let new_len = new_size as i32;
+        let resized = Ptr::new(ptr.offset, new_len);
+        if new_len <= ptr.len {
+            if new_len < ptr.len {
+                self.free_region(Ptr::new(ptr.offset + new_len, ptr.len - new_len));
+            }
+            self.stats.record_resize(ptr.len as usize, new_size);
+            Some(Realloc::in_place(resized))
+        } else if ptr.offset + ptr.len == self.next_offset {
+            self.next_offset = ptr.offset + new_len;
+            self.stats.record_resize(ptr.len as usize, new_size);
+            Some(Realloc::in_place(resized))
+        } else {
+            let moved = self.alloc_for_size(new_size);
+            self.dealloc(ptr);
+            Some(Realloc::moved(moved))
+        }
+    }
+
+    /// Frees all allocations at once, e.g. between contract calls, resetting the
+    /// next offset to `0`.
+    fn reset(&mut self) {
+        self.next_offset = 0;
+        self.free_lists.iter_mut().for_each(Vec::clear);
+        self.stats.record_reset();
     }
+
+    /// Gets statistics about the memory handed out by this allocator.
+    fn stats(&self) -> AllocatorStats {
+        AllocatorStats {
+            memory_size: self.next_offset as usize,
+            ..self.stats
+        }
+    }
+
+    /// Moves the allocated regions between the freed ones down, in order, and moves
+    /// the next offset back to the end of the last one. Adjacent allocations are
+    /// moved (and relocated) as a single region.
+    fn compact(&mut self, memory: &mut [u8]) -> RelocationTable {
+        let mut free_regions = self
+            .free_lists
+            .iter_mut()
+            .flat_map(|list| list.drain(..))
+            .collect::<Vec<_>>();
+        free_regions.sort_by_key(|region| region.offset);
+
+        let mut relocations = Vec::new();
+        let (mut live_start, mut next_offset) = (0, 0);
+        for free in free_regions
+            .into_iter()
+            .chain([Ptr::new(self.next_offset, 0)])
+        {
+            let len = free.offset - live_start;
+            if len > 0 {
+                if live_start != next_offset {
+                    move_bytes(memory, live_start, len, next_offset);
+                    relocations.push(Relocation {
+                        old_offset: live_start,
+                        new_offset: next_offset,
+                        len,
+                    });
+                }
+                next_offset += len;
+            }
+            live_start = live_start.max(free.offset + free.len);
+        }
+
+        self.next_offset = next_offset;
+        RelocationTable::new(relocations)
+    }
+}
+
+/// Moves the region at the given offset/length of the provided memory to a new
+/// offset, ignoring any part of it which lies outside of the memory.
+fn move_bytes(memory: &mut [u8], offset: i32, len: i32, new_offset: i32) {
+    let start = (offset as usize).min(memory.len());
+    let end = ((offset + len) as usize).min(memory.len());
+    let new_end = new_offset as usize + (end - start);
+    if new_end <= memory.len() {
+        memory.copy_within(start..end, new_offset as usize);
+    }
+}
+
+/// Gets the size class of a (non-empty) region of the given length, i.e. the
+/// largest `n` for which `2^n <= len`.
+#[inline]
+fn size_class(len: i32) -> usize {
+    (u32::BITS - 1 - (len as u32).leading_zeros()) as usize
 }
@@ -0,0 +1,275 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+};
+
+use super::{AllocatorStats, Realloc, Relocation, RelocationTable, WasmMemoryAllocator};
+use crate::Ptr;
+
+/// The size of the smallest block handed out by the allocator. Smaller requests are
+/// rounded up to it.
+const MIN_BLOCK_SIZE: usize = 16;
+
+/// The initial size of the arena (one Wasm page), which is doubled whenever it
+/// can't satisfy a request.
+const INITIAL_ARENA_SIZE: usize = 64 * 1024;
+
+/// A buddy allocator for handling Wasm memory. Blocks are sized in powers of two,
+/// and larger blocks are split in halves ("buddies") to satisfy smaller requests.
+/// When a block is freed it is merged with its buddy if that is free as well, so
+/// that freed memory can be re-used by requests of any size. Unlike the bump
+/// `WasmAllocator`, this allows memory to be reclaimed using `dealloc()`, at the
+/// cost of rounding each allocation up to a power of two.
+#[derive(Debug, Clone, Default)]
+pub struct BuddyAllocator {
+    /// The size of the arena, which is zero until the first allocation and a power
+    /// of two from then on.
+    arena_size: usize,
+    /// The offsets of the free blocks of each order, where blocks of order `n` are
+    /// `MIN_BLOCK_SIZE << n` bytes long.
+    free_blocks: Vec<BTreeSet<usize>>,
+    /// The order of the block backing each live allocation, by offset.
+    allocations: HashMap<usize, usize>,
+    stats: AllocatorStats,
+    max_memory_pages: Option<u64>,
+}
+
+impl BuddyAllocator {
+    /// Creates a new, empty `BuddyAllocator` whose arena starts at offset `0`.
+    pub fn new() -> Self {
+        BuddyAllocator {
+            arena_size: 0,
+            free_blocks: Vec::new(),
+            allocations: HashMap::new(),
+            stats: AllocatorStats::default(),
+            max_memory_pages: None,
+        }
+    }
+
+    /// Limits the number of pages the Wasm memory may be grown to in order to fit
+    /// the arena, see `alloc_in_memory()`. By default the memory can be grown up to
+    /// the maximum of its own type.
+    pub fn with_max_memory_pages(mut self, max_memory_pages: u64) -> Self {
+        self.max_memory_pages = Some(max_memory_pages);
+        self
+    }
+
+    /// Gets the size of the memory region managed by this allocator, starting at
+    /// offset `0`.
+    pub fn arena_size(&self) -> usize {
+        self.arena_size
+    }
+
+    /// Takes the lowest free block of the given order, splitting a larger block if
+    /// there is none.
+    fn take_free_block(&mut self, order: usize) -> Option<usize> {
+        let (mut block_order, offset) =
+            (order..self.free_blocks.len()).find_map(|block_order| {
+                let offset = self.free_blocks[block_order].pop_first()?;
+                Some((block_order, offset))
+            })?;
+
+        // Split the block in halves until it has the requested size, keeping the
+        // lower half and freeing the upper one each time.
+        while block_order > order {
+            block_order -= 1;
+            self.free_blocks[block_order].insert(offset + block_size(block_order));
+        }
+
+        Some(offset)
+    }
+
+    /// Returns a block to the free lists, merging it with its buddy for as long as
+    /// the buddy is free as well.
+    fn free_block(&mut self, mut offset: usize, mut order: usize) {
+        while block_size(order) < self.arena_size {
+            // Blocks are aligned to their size, so the buddy of a block is found by
+            // flipping the bit of its offset corresponding to its size.
+            let buddy = offset ^ block_size(order);
+            if !self.free_blocks[order].remove(&buddy) {
+                break;
+            }
+            offset = offset.min(buddy);
+            order += 1;
+        }
+
+        self.free_blocks[order].insert(offset);
+    }
+
+    /// Creates the arena, or doubles its size if it already exists, freeing the new
+    /// memory (which merges with the existing arena if that is entirely free).
+    fn grow(&mut self) {
+        let (offset, order) = if self.arena_size == 0 {
+            self.arena_size = INITIAL_ARENA_SIZE;
+            (0, order_for_size(INITIAL_ARENA_SIZE))
+        } else {
+            let offset = self.arena_size;
+            self.arena_size *= 2;
+            (offset, order_for_size(offset))
+        };
+
+        let arena_order = order_for_size(self.arena_size);
+        if self.free_blocks.len() <= arena_order {
+            self.free_blocks.resize_with(arena_order + 1, BTreeSet::new);
+        }
+        self.free_block(offset, order);
+    }
+}
+
+impl WasmMemoryAllocator for BuddyAllocator {
+    /// Gets the maximum number of pages the Wasm memory may be grown to, if any.
+    fn max_memory_pages(&self) -> Option<u64> {
+        self.max_memory_pages
+    }
+
+    /// Allocates a block which can store the given number of bytes, growing the
+    /// arena if there is no free block large enough, and returns a pointer to it.
+    fn alloc_for_size(&mut self, size: usize) -> Ptr {
+        let order = order_for_size(size);
+        let offset = loop {
+            match self.take_free_block(order) {
+                Some(offset) => break offset,
+                None => self.grow(),
+            }
+        };
+        self.allocations.insert(offset, order);
+        self.stats.record_alloc(block_size(order));
+
+        Ptr::new(offset as i32, size as i32)
+    }
+
+    /// Frees the allocation pointed to by `ptr`, making its block available to
+    /// subsequent allocations. Returns `false` if `ptr` doesn't point to a live
+    /// allocation, e.g. because it has already been freed.
+    fn dealloc(&mut self, ptr: Ptr) -> bool {
+        let allocation = usize::try_from(ptr.offset)
+            .ok()
+            .and_then(|offset| Some((offset, self.allocations.remove(&offset)?)));
+
+        match allocation {
+            Some((offset, order)) => {
+                self.free_block(offset, order);
+                self.stats.record_free(block_size(order));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resizes the allocation pointed to by `ptr` to the given size. The block is
+    /// split when shrinking, and merged with its buddies when growing if they are
+    /// free; otherwise the allocation is moved to a new block. Returns `None` if
+    /// `ptr` doesn't point to a live allocation.
+    fn realloc(&mut self, ptr: Ptr, new_size: usize) -> Option<Realloc> {
+        let offset = usize::try_from(ptr.offset).ok()?;
+        let order = *self.allocations.get(&offset)?;
+        let new_order = order_for_size(new_size);
+        let resized = Ptr::new(ptr.offset, new_size as i32);
+
+        if new_order <= order {
+            // Free the upper halves of the block until it has the new size.
+            for split_order in (new_order..order).rev() {
+                self.free_block(offset + block_size(split_order), split_order);
+            }
+            self.allocations.insert(offset, new_order);
+            self.stats
+                .record_resize(block_size(order), block_size(new_order));
+            return Some(Realloc::in_place(resized));
+        }
+
+        // The block can only grow in place if it's the lower buddy at each order up
+        // to the new one, and the upper buddies are all free.
+        let can_grow = (order..new_order).all(|merge_order| {
+            offset % block_size(merge_order + 1) == 0
+                && block_size(merge_order + 1) <= self.arena_size
+                && self.free_blocks[merge_order].contains(&(offset + block_size(merge_order)))
+        });
+        if can_grow {
+            for merge_order in order..new_order {
+                self.free_blocks[merge_order].remove(&(offset + block_size(merge_order)));
+            }
+            self.allocations.insert(offset, new_order);
+            self.stats
+                .record_resize(block_size(order), block_size(new_order));
+            return Some(Realloc::in_place(resized));
+        }
+
+        let moved = self.alloc_for_size(new_size);
+        self.dealloc(ptr);
+        Some(Realloc::moved(moved))
+    }
+
+    /// Frees all allocations at once, e.g. between contract calls, leaving the
+    /// allocator as if it was newly created.
+    fn reset(&mut self) {
+        self.arena_size = 0;
+        self.free_blocks.clear();
+        self.allocations.clear();
+        self.stats.record_reset();
+    }
+
+    /// Gets statistics about the memory handed out by this allocator, where each
+    /// allocation counts with the size of its block.
+    fn stats(&self) -> AllocatorStats {
+        AllocatorStats {
+            memory_size: self.arena_size,
+            ..self.stats
+        }
+    }
+
+    /// Re-allocates the live blocks from an entirely free arena, largest first so
+    /// that the smaller ones fill up the rest of it without any gaps, and moves the
+    /// contents of the blocks which ended up at a different offset.
+    fn compact(&mut self, memory: &mut [u8]) -> RelocationTable {
+        let mut allocations = self.allocations.drain().collect::<Vec<_>>();
+        allocations.sort_by_key(|&(offset, order)| (Reverse(order), offset));
+
+        // Copy the contents of the blocks out first, as they may be moved onto each
+        // other.
+        let contents = allocations
+            .iter()
+            .map(|&(offset, order)| {
+                let end = (offset + block_size(order)).min(memory.len());
+                memory.get(offset..end).unwrap_or_default().to_vec()
+            })
+            .collect::<Vec<_>>();
+
+        self.free_blocks.iter_mut().for_each(BTreeSet::clear);
+        if self.arena_size > 0 {
+            self.free_blocks[order_for_size(self.arena_size)].insert(0);
+        }
+
+        let mut relocations = Vec::new();
+        for ((offset, order), bytes) in allocations.into_iter().zip(contents) {
+            let new_offset = self
+                .take_free_block(order)
+                .expect("the arena fits all of the live blocks");
+            self.allocations.insert(new_offset, order);
+
+            if new_offset != offset {
+                if let Some(moved) = memory.get_mut(new_offset..new_offset + bytes.len()) {
+                    moved.copy_from_slice(&bytes);
+                }
+                relocations.push(Relocation {
+                    old_offset: offset as i32,
+                    new_offset: new_offset as i32,
+                    len: block_size(order) as i32,
+                });
+            }
+        }
+
+        RelocationTable::new(relocations)
+    }
+}
+
+/// Gets the size of the blocks of the given order.
+#[inline]
+fn block_size(order: usize) -> usize {
+    MIN_BLOCK_SIZE << order
+}
+
+/// Gets the order of the smallest block which can store the given number of bytes.
+#[inline]
+fn order_for_size(size: usize) -> usize {
+    (size.max(MIN_BLOCK_SIZE).next_power_of_two() / MIN_BLOCK_SIZE).trailing_zeros() as usize
+}
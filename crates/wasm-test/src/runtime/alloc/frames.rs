@@ -0,0 +1,118 @@
+use super::{AllocatorStats, Realloc, RelocationTable, WasmMemoryAllocator};
+use crate::Ptr;
+
+/// An arena allocator which wraps another allocator, tagging allocations with the
+/// frame they were made in. Frames are started using `push_frame()`, and all
+/// allocations made during a frame which are still live are freed at once when
+/// it's dropped using `drop_frame()`, mirroring the lifetime of a function's
+/// locals. Allocations made outside of any frame live until they are freed.
+#[derive(Debug, Clone)]
+pub struct FrameAllocator<A> {
+    inner: A,
+    /// The live allocations of each frame, indexed by frame.
+    frames: Vec<Vec<Ptr>>,
+}
+
+impl<A: WasmMemoryAllocator + 'static> FrameAllocator<A> {
+    /// Creates a new `FrameAllocator` which reserves memory using `inner`.
+    pub fn new(inner: A) -> Self {
+        FrameAllocator {
+            inner,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Gets the number of frames which haven't been dropped yet.
+    pub fn frame_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Finds the frame index and position within it of the given allocation.
+    fn find(&self, ptr: Ptr) -> Option<(usize, usize)> {
+        self.frames
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, frame)| {
+                let position = frame
+                    .iter()
+                    .position(|allocation| allocation.offset == ptr.offset)?;
+                Some((index, position))
+            })
+    }
+}
+
+impl<A: WasmMemoryAllocator + 'static> WasmMemoryAllocator for FrameAllocator<A> {
+    fn alloc_for_size(&mut self, size: usize) -> Ptr {
+        let ptr = self.inner.alloc_for_size(size);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.push(ptr);
+        }
+        ptr
+    }
+
+    fn dealloc(&mut self, ptr: Ptr) -> bool {
+        match self.find(ptr) {
+            Some((index, position)) => {
+                let allocation = self.frames[index].remove(position);
+                self.inner.dealloc(allocation)
+            }
+            None => self.inner.dealloc(ptr),
+        }
+    }
+
+    fn realloc(&mut self, ptr: Ptr, new_size: usize) -> Option<Realloc> {
+        let location = self.find(ptr);
+        let realloc = self.inner.realloc(ptr, new_size)?;
+        if let Some((index, position)) = location {
+            self.frames[index][position] = realloc.ptr;
+        }
+        Some(realloc)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.frames.iter_mut().for_each(Vec::clear);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        self.inner.stats()
+    }
+
+    fn max_memory_pages(&self) -> Option<u64> {
+        self.inner.max_memory_pages()
+    }
+
+    fn guard_size(&self) -> usize {
+        self.inner.guard_size()
+    }
+
+    fn write_canaries(&mut self, memory: &mut [u8]) {
+        self.inner.write_canaries(memory)
+    }
+
+    fn check_canaries(&self, memory: &[u8]) -> Result<(), Ptr> {
+        self.inner.check_canaries(memory)
+    }
+
+    fn push_frame(&mut self) {
+        self.frames.push(Vec::new());
+    }
+
+    fn drop_frame(&mut self) {
+        // Free the allocations in reverse order, which lets a bump allocator move
+        // its next offset back over all of them.
+        for ptr in self.frames.pop().into_iter().flatten().rev() {
+            self.inner.dealloc(ptr);
+        }
+    }
+
+    fn compact(&mut self, memory: &mut [u8]) -> RelocationTable {
+        let relocations = self.inner.compact(memory);
+        for ptr in self.frames.iter_mut().flatten() {
+            *ptr = relocations.relocate(*ptr);
+        }
+
+        relocations
+    }
+}
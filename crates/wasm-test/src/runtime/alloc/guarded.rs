@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{AllocatorStats, Realloc, RelocationTable, WasmMemoryAllocator};
+use crate::Ptr;
+
+/// The number of canary bytes reserved on either side of each allocation.
+const GUARD_SIZE: usize = 8;
+
+/// The value of each canary byte.
+const CANARY: u8 = 0xCA;
+
+/// A debugging allocator which wraps another allocator, reserving canary bytes
+/// on either side of each allocation. Checking the canaries using
+/// `check_canaries()` catches host functions which write past the bounds of a
+/// `Ptr` into adjacent values, e.g. because of a miscalculated serialized size.
+///
+/// The canaries are written by `alloc_in_memory()` (and thereby by
+/// `write_value_to_memory()`) once the memory spans the allocation. Statistics
+/// are those of the wrapped allocator, and so include the canary bytes.
+#[derive(Debug, Clone)]
+pub struct GuardedAllocator<A> {
+    inner: A,
+    /// The length of each live allocation, by offset.
+    allocations: BTreeMap<i32, i32>,
+    /// The offsets of the allocations whose canaries haven't been written yet.
+    pending: BTreeSet<i32>,
+}
+
+impl<A: WasmMemoryAllocator + 'static> GuardedAllocator<A> {
+    /// Creates a new `GuardedAllocator` which reserves memory using `inner`.
+    pub fn new(inner: A) -> Self {
+        GuardedAllocator {
+            inner,
+            allocations: BTreeMap::new(),
+            pending: BTreeSet::new(),
+        }
+    }
+
+    /// Gets the region reserved for the allocation at the given offset/length,
+    /// including its canaries.
+    #[inline]
+    fn guarded_region(offset: i32, len: i32) -> Ptr {
+        Ptr::new(offset - GUARD_SIZE as i32, len + 2 * GUARD_SIZE as i32)
+    }
+}
+
+impl<A: WasmMemoryAllocator + 'static> WasmMemoryAllocator for GuardedAllocator<A> {
+    fn alloc_for_size(&mut self, size: usize) -> Ptr {
+        let region = self.inner.alloc_for_size(size + 2 * GUARD_SIZE);
+        let ptr = Ptr::new(region.offset + GUARD_SIZE as i32, size as i32);
+
+        self.allocations.insert(ptr.offset, ptr.len);
+        self.pending.insert(ptr.offset);
+        ptr
+    }
+
+    fn dealloc(&mut self, ptr: Ptr) -> bool {
+        match self.allocations.remove(&ptr.offset) {
+            Some(len) => {
+                self.pending.remove(&ptr.offset);
+                self.inner.dealloc(Self::guarded_region(ptr.offset, len))
+            }
+            None => false,
+        }
+    }
+
+    fn realloc(&mut self, ptr: Ptr, new_size: usize) -> Option<Realloc> {
+        let len = *self.allocations.get(&ptr.offset)?;
+        let realloc = self.inner.realloc(
+            Self::guarded_region(ptr.offset, len),
+            new_size + 2 * GUARD_SIZE,
+        )?;
+
+        // The trailing canary has moved (along with the leading one if the
+        // allocation has), so they are written again.
+        let resized = Ptr::new(realloc.ptr.offset + GUARD_SIZE as i32, new_size as i32);
+        self.allocations.remove(&ptr.offset);
+        self.pending.remove(&ptr.offset);
+        self.allocations.insert(resized.offset, resized.len);
+        self.pending.insert(resized.offset);
+
+        Some(Realloc {
+            ptr: resized,
+            needs_copy: realloc.needs_copy,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.allocations.clear();
+        self.pending.clear();
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        self.inner.stats()
+    }
+
+    fn max_memory_pages(&self) -> Option<u64> {
+        self.inner.max_memory_pages()
+    }
+
+    fn guard_size(&self) -> usize {
+        GUARD_SIZE
+    }
+
+    fn write_canaries(&mut self, memory: &mut [u8]) {
+        for offset in std::mem::take(&mut self.pending) {
+            let len = self.allocations[&offset];
+            let start = (offset as usize) - GUARD_SIZE;
+            let end = (offset + len) as usize;
+
+            for range in [start..offset as usize, end..end + GUARD_SIZE] {
+                if let Some(canary) = memory.get_mut(range) {
+                    canary.fill(CANARY);
+                }
+            }
+        }
+    }
+
+    fn check_canaries(&self, memory: &[u8]) -> Result<(), Ptr> {
+        for (&offset, &len) in &self.allocations {
+            if self.pending.contains(&offset) {
+                continue;
+            }
+
+            let start = (offset as usize) - GUARD_SIZE;
+            let end = (offset + len) as usize;
+            let intact = [start..offset as usize, end..end + GUARD_SIZE]
+                .into_iter()
+                .all(|range| {
+                    memory
+                        .get(range)
+                        .is_some_and(|canary| canary.iter().all(|byte| *byte == CANARY))
+                });
+            if !intact {
+                return Err(Ptr::new(offset, len));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compact(&mut self, memory: &mut [u8]) -> RelocationTable {
+        // The canaries are moved along with the allocations, as they are part of the
+        // regions reserved from the wrapped allocator.
+        let relocations = self.inner.compact(memory);
+        self.allocations = self
+            .allocations
+            .iter()
+            .map(|(&offset, &len)| (relocations.relocate_offset(offset), len))
+            .collect();
+        self.pending = self
+            .pending
+            .iter()
+            .map(|&offset| relocations.relocate_offset(offset))
+            .collect();
+
+        relocations
+    }
+}
@@ -0,0 +1,46 @@
+use std::fmt::Debug;
+
+use clarity::vm::{
+    types::{BlockInfoProperty, BurnBlockInfoProperty},
+    Value,
+};
+
+/// Provides the block data made available to contracts through `get-block-info?`,
+/// `get-burn-block-info?` and `at-block`. Embedders wire this up to their
+/// chainstate, while tests can stub out only the data they need.
+pub trait BlockInfoProvider: Debug + Send + Sync {
+    /// Gets the given property of the Stacks block at the given height, or `None`
+    /// if the block or property is unknown.
+    fn get_block_info(&self, block_height: u32, property: BlockInfoProperty) -> Option<Value>;
+
+    /// Gets the given property of the burnchain block at the given height, or `None`
+    /// if the block or property is unknown.
+    fn get_burn_block_info(
+        &self,
+        burn_block_height: u32,
+        property: BurnBlockInfoProperty,
+    ) -> Option<Value>;
+
+    /// Gets the height of the Stacks block with the given index block hash
+    /// (`id-header-hash`), as used by `at-block`.
+    fn get_block_height(&self, id_header_hash: &[u8]) -> Option<u32>;
+}
+
+/// A `BlockInfoProvider` which doesn't know about any blocks. This is the default
+/// provider of a `ClarityWasmContext`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoBlockInfo;
+
+impl BlockInfoProvider for NoBlockInfo {
+    fn get_block_info(&self, _: u32, _: BlockInfoProperty) -> Option<Value> {
+        None
+    }
+
+    fn get_burn_block_info(&self, _: u32, _: BurnBlockInfoProperty) -> Option<Value> {
+        None
+    }
+
+    fn get_block_height(&self, _: &[u8]) -> Option<u32> {
+        None
+    }
+}
@@ -0,0 +1,106 @@
+use wasmtime::{Config, Engine, Instance, Linker, Module, Store};
+
+use super::native_functions::{get_all_functions, link_host_functions, FuncMap};
+use crate::ClarityWasmContext;
+
+/// Function used to define the host functions which are made available to the
+/// instantiated module.
+type HostFunctionsFn = Box<dyn FnOnce(&mut Store<ClarityWasmContext>) -> Vec<FuncMap>>;
+
+/// Builder which takes care of the engine, store and host function setup required
+/// to instantiate a Wasm module against a `ClarityWasmContext`.
+///
+/// ```ignore
+/// let (instance, mut store) = RuntimeBuilder::new()
+///     .with_context(ClarityWasmContext::new())
+///     .instantiate(&wasm_bytes)?;
+/// ```
+pub struct RuntimeBuilder {
+    config: Config,
+    context: ClarityWasmContext,
+    host_functions: HostFunctionsFn,
+    epoch_interruption: bool,
+}
+
+impl RuntimeBuilder {
+    /// Creates a new `RuntimeBuilder` with WASM reference types enabled, a default
+    /// `ClarityWasmContext` and all host functions from `get_all_functions`.
+    pub fn new() -> Self {
+        let mut config = Config::default();
+        config.wasm_reference_types(true);
+
+        RuntimeBuilder {
+            config,
+            context: ClarityWasmContext::new(),
+            host_functions: Box::new(|store: &mut Store<ClarityWasmContext>| {
+                get_all_functions(store)
+            }),
+            epoch_interruption: false,
+        }
+    }
+
+    /// Replaces the Wasmtime `Config` used to create the `Engine`.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the `ClarityWasmContext` which the `Store` will be created with.
+    pub fn with_context(mut self, context: ClarityWasmContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Replaces the host functions which are made available to the module. The
+    /// provided function is called with the newly created `Store`.
+    pub fn with_host_functions(
+        mut self,
+        host_functions: impl FnOnce(&mut Store<ClarityWasmContext>) -> Vec<FuncMap> + 'static,
+    ) -> Self {
+        self.host_functions = Box::new(host_functions);
+        self
+    }
+
+    /// Enables epoch interruption so that calls can be bounded using an
+    /// `ExecutionDeadline`. Until a deadline is applied, calls are not interrupted.
+    pub fn with_epoch_interruption(mut self) -> Self {
+        self.epoch_interruption = true;
+        self
+    }
+
+    /// Creates the engine and store, defines the host functions and instantiates
+    /// the provided Wasm module.
+    pub fn instantiate(
+        mut self,
+        wasm_bytes: &[u8],
+    ) -> wasmtime::Result<(Instance, Store<ClarityWasmContext>)> {
+        if self.epoch_interruption {
+            self.config.epoch_interruption(true);
+        }
+
+        let engine = Engine::new(&self.config)?;
+        let module = Module::new(&engine, wasm_bytes)?;
+        let mut store = Store::new(&engine, self.context);
+
+        if self.epoch_interruption {
+            // Don't interrupt calls until a deadline has been explicitly applied.
+            store.set_epoch_deadline(u64::MAX);
+        }
+
+        // Register each host function under its module/name so that imports are
+        // resolved by name rather than by position.
+        let mut linker = Linker::new(&engine);
+        let host_functions = (self.host_functions)(&mut store);
+        link_host_functions(&mut linker, &store, &host_functions)?;
+
+        let instance = linker.instantiate(&mut store, &module)?;
+
+        Ok((instance, store))
+    }
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
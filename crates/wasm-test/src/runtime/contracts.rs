@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use clarity::vm::types::QualifiedContractIdentifier;
+use wasmtime::Instance;
+
+/// Maps contract identifiers to their instantiated modules, allowing contracts
+/// to call into each other (`contract-call?`). All registered instances must
+/// belong to the same `Store`. Contract identifiers can also be interned, so that
+/// generated code can refer to the contracts it calls by id. Each calling contract
+/// has its own ids.
+#[derive(Debug, Default, Clone)]
+pub struct ContractRegistry {
+    contracts: HashMap<QualifiedContractIdentifier, Instance>,
+    ids: HashMap<QualifiedContractIdentifier, Vec<QualifiedContractIdentifier>>,
+}
+
+impl ContractRegistry {
+    /// Creates a new, empty `ContractRegistry`.
+    pub fn new() -> Self {
+        ContractRegistry {
+            contracts: HashMap::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Interns the identifier of a contract called by the given contract, returning
+    /// its id among the contracts which that contract calls. Interning the same
+    /// identifier more than once for the same caller returns the same id.
+    pub fn intern(
+        &mut self,
+        caller: &QualifiedContractIdentifier,
+        contract_id: QualifiedContractIdentifier,
+    ) -> i32 {
+        let ids = self.ids.entry(caller.clone()).or_default();
+        match ids.iter().position(|id| *id == contract_id) {
+            Some(index) => index as i32,
+            None => {
+                ids.push(contract_id);
+                (ids.len() - 1) as i32
+            }
+        }
+    }
+
+    /// Gets the identifier of the contract with the given id among the contracts
+    /// called by the given contract.
+    pub fn contract_id(
+        &self,
+        caller: &QualifiedContractIdentifier,
+        id: i32,
+    ) -> Option<&QualifiedContractIdentifier> {
+        let ids = self.ids.get(caller)?;
+        usize::try_from(id).ok().and_then(|id| ids.get(id))
+    }
+
+    /// Registers an instantiated contract under the given identifier, returning
+    /// the previously registered instance, if any.
+    pub fn register(
+        &mut self,
+        contract_id: QualifiedContractIdentifier,
+        instance: Instance,
+    ) -> Option<Instance> {
+        self.contracts.insert(contract_id, instance)
+    }
+
+    /// Removes the contract with the given identifier from the registry.
+    pub fn unregister(&mut self, contract_id: &QualifiedContractIdentifier) -> Option<Instance> {
+        self.contracts.remove(contract_id)
+    }
+
+    /// Retrieves the instance for the contract with the given identifier.
+    pub fn get(&self, contract_id: &QualifiedContractIdentifier) -> Option<Instance> {
+        self.contracts.get(contract_id).copied()
+    }
+
+    /// Gets whether or not a contract with the given identifier is registered.
+    pub fn contains(&self, contract_id: &QualifiedContractIdentifier) -> bool {
+        self.contracts.contains_key(contract_id)
+    }
+
+    /// Gets the number of registered contracts.
+    pub fn len(&self) -> usize {
+        self.contracts.len()
+    }
+
+    /// Gets whether or not the registry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use clarity::vm::types::QualifiedContractIdentifier;
+
+/// Persistent storage backing a contract's data maps and variables. Keys and
+/// values are passed in their serialized form (see `serialization.rs`), so
+/// implementations don't need to know anything about Clarity values.
+pub trait ClarityDatastore: Debug + Send + Sync {
+    /// Gets the serialized value stored under `key` in the given map, if any.
+    fn get_map_entry(
+        &self,
+        contract_id: &QualifiedContractIdentifier,
+        map_name: &str,
+        key: &[u8],
+    ) -> Option<Vec<u8>>;
+
+    /// Stores `value` under `key` in the given map, returning the previously
+    /// stored value, if any.
+    fn set_map_entry(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+        map_name: &str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Option<Vec<u8>>;
+
+    /// Removes the entry stored under `key` in the given map, returning the
+    /// removed value, if any.
+    fn delete_map_entry(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+        map_name: &str,
+        key: &[u8],
+    ) -> Option<Vec<u8>>;
+
+    /// Gets the serialized value of the given data variable, if it has been set.
+    fn get_data_var(
+        &self,
+        contract_id: &QualifiedContractIdentifier,
+        name: &str,
+    ) -> Option<Vec<u8>>;
+
+    /// Sets the serialized value of the given data variable.
+    fn set_data_var(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+        name: &str,
+        value: Vec<u8>,
+    );
+}
+
+/// A `ClarityDatastore` which keeps all data in memory. This is the default
+/// datastore of a `ClarityWasmContext` and is mostly useful for testing.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryDatastore {
+    maps: HashMap<(QualifiedContractIdentifier, String), HashMap<Vec<u8>, Vec<u8>>>,
+    vars: HashMap<(QualifiedContractIdentifier, String), Vec<u8>>,
+}
+
+impl MemoryDatastore {
+    /// Creates a new, empty `MemoryDatastore`.
+    pub fn new() -> Self {
+        MemoryDatastore {
+            maps: HashMap::new(),
+            vars: HashMap::new(),
+        }
+    }
+}
+
+impl ClarityDatastore for MemoryDatastore {
+    fn get_map_entry(
+        &self,
+        contract_id: &QualifiedContractIdentifier,
+        map_name: &str,
+        key: &[u8],
+    ) -> Option<Vec<u8>> {
+        self.maps
+            .get(&(contract_id.clone(), map_name.to_string()))
+            .and_then(|map| map.get(key))
+            .cloned()
+    }
+
+    fn set_map_entry(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+        map_name: &str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        self.maps
+            .entry((contract_id.clone(), map_name.to_string()))
+            .or_default()
+            .insert(key, value)
+    }
+
+    fn delete_map_entry(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+        map_name: &str,
+        key: &[u8],
+    ) -> Option<Vec<u8>> {
+        self.maps
+            .get_mut(&(contract_id.clone(), map_name.to_string()))
+            .and_then(|map| map.remove(key))
+    }
+
+    fn get_data_var(
+        &self,
+        contract_id: &QualifiedContractIdentifier,
+        name: &str,
+    ) -> Option<Vec<u8>> {
+        self.vars
+            .get(&(contract_id.clone(), name.to_string()))
+            .cloned()
+    }
+
+    fn set_data_var(
+        &mut self,
+        contract_id: &QualifiedContractIdentifier,
+        name: &str,
+        value: Vec<u8>,
+    ) {
+        self.vars
+            .insert((contract_id.clone(), name.to_string()), value);
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use wasmtime::{Engine, Func, Store, Trap, Val};
+
+use super::RuntimeError;
+
+/// The default interval at which the `EpochTicker` increments the engine's epoch.
+pub const DEFAULT_EPOCH_TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Describes a wall-clock execution deadline for calls into a Wasm module. Deadlines
+/// are implemented using Wasmtime's epoch interruption, so the `Engine` must be created
+/// with `Config::epoch_interruption(true)` and an `EpochTicker` must be running for
+/// the deadline to ever be reached.
+#[derive(Debug, Copy, Clone)]
+pub struct ExecutionDeadline {
+    tick_interval: Duration,
+    ticks: u64,
+}
+
+impl ExecutionDeadline {
+    /// Creates a new `ExecutionDeadline` which expires after the given number of
+    /// epoch ticks, where each tick is `tick_interval` long.
+    pub fn new(tick_interval: Duration, ticks: u64) -> Self {
+        ExecutionDeadline {
+            tick_interval,
+            ticks: ticks.max(1),
+        }
+    }
+
+    /// Creates a new `ExecutionDeadline` which expires after (approximately) the
+    /// given duration, using the `DEFAULT_EPOCH_TICK_INTERVAL`.
+    pub fn from_duration(timeout: Duration) -> Self {
+        let interval = DEFAULT_EPOCH_TICK_INTERVAL.as_nanos();
+        let ticks = (timeout.as_nanos() + interval - 1) / interval;
+        Self::new(DEFAULT_EPOCH_TICK_INTERVAL, ticks as u64)
+    }
+
+    /// Gets the interval at which the epoch must be incremented for this deadline
+    /// to be accurate.
+    pub fn tick_interval(&self) -> Duration {
+        self.tick_interval
+    }
+
+    /// Gets the number of epoch ticks after which execution is interrupted.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Arms this deadline on the provided `Store`. The deadline is relative to the
+    /// engine's current epoch, so this should be called immediately before each call
+    /// into the module.
+    pub fn apply<T>(&self, store: &mut Store<T>) {
+        store.set_epoch_deadline(self.ticks);
+        store.epoch_deadline_trap();
+    }
+}
+
+/// A background thread which periodically calls `Engine::increment_epoch()`. The
+/// thread is stopped and joined when the `EpochTicker` is dropped.
+#[derive(Debug)]
+pub struct EpochTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    /// Starts a new ticker for the provided `Engine`, incrementing its epoch once
+    /// every `interval`.
+    pub fn start(engine: &Engine, interval: Duration) -> Self {
+        let engine = engine.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let handle = std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                std::thread::sleep(interval);
+                engine.increment_epoch();
+            }
+        });
+
+        EpochTicker {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Starts a new ticker for the provided `Engine` using the tick interval of
+    /// the given `ExecutionDeadline`.
+    pub fn for_deadline(engine: &Engine, deadline: &ExecutionDeadline) -> Self {
+        Self::start(engine, deadline.tick_interval())
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Calls the provided function with the given deadline armed. If the deadline is
+/// reached, the resulting trap is converted to `RuntimeError::ExecutionTimedOut`,
+/// which can be retrieved from the returned error using `downcast_ref::<RuntimeError>()`.
+pub fn call_with_deadline<T>(
+    store: &mut Store<T>,
+    deadline: &ExecutionDeadline,
+    func: &Func,
+    params: &[Val],
+    results: &mut [Val],
+) -> wasmtime::Result<()> {
+    deadline.apply(store);
+    func.call(&mut *store, params, results)
+        .map_err(map_interrupt_trap)
+}
+
+/// Converts an epoch interruption trap into `RuntimeError::ExecutionTimedOut`,
+/// leaving all other errors untouched.
+pub fn map_interrupt_trap(err: wasmtime::Error) -> wasmtime::Error {
+    match err.downcast_ref::<Trap>() {
+        Some(Trap::Interrupt) => RuntimeError::ExecutionTimedOut.into(),
+        _ => err,
+    }
+}
@@ -0,0 +1,62 @@
+use num::FromPrimitive;
+use num_derive::FromPrimitive;
+use wasmtime::{AsContextMut, Instance};
+
+/// The name of the global exported by `wasm-stdlib` which holds the address of its
+/// panic record.
+pub const PANIC_INFO_GLOBAL: &str = "panic-info";
+
+/// The size of the panic record's header, i.e. the reason and message length.
+const PANIC_RECORD_HEADER_SIZE: usize = 8;
+
+/// The maximum length of a recorded message, see `MAX_MESSAGE_LEN` in `wasm-stdlib`.
+const MAX_MESSAGE_LEN: usize = 256;
+
+/// The reason a guest module (built on `wasm-stdlib`) failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum GuestPanicReason {
+    /// The guest panicked, e.g. because of a failed assertion.
+    Panic = 1,
+    /// An allocation failed, e.g. because the memory couldn't be grown.
+    AllocationFailed = 2,
+}
+
+/// The failure recorded by a guest module before it trapped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestPanic {
+    pub reason: GuestPanicReason,
+    /// The (possibly truncated) panic message, including its location if known.
+    pub message: String,
+}
+
+impl std::fmt::Display for GuestPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "guest failed ({:?}): {}", self.reason, self.message)
+    }
+}
+
+/// Reads the failure recorded by the guest module in `instance`, e.g. after a call
+/// into it trapped. Returns `None` if the module doesn't export a panic record (and
+/// its memory as `memory`), or hasn't recorded a failure.
+pub fn read_guest_panic(mut store: impl AsContextMut, instance: &Instance) -> Option<GuestPanic> {
+    let address = instance
+        .get_global(&mut store, PANIC_INFO_GLOBAL)?
+        .get(&mut store)
+        .i32()? as usize;
+    let memory = instance.get_memory(&mut store, "memory")?;
+    let data = memory.data(&store);
+
+    let header = data.get(address..address + PANIC_RECORD_HEADER_SIZE)?;
+    let reason = i32::from_le_bytes(header[0..4].try_into().ok()?);
+    let message_len = i32::from_le_bytes(header[4..8].try_into().ok()?) as usize;
+
+    let start = address + PANIC_RECORD_HEADER_SIZE;
+    let message = data.get(start..start + message_len.min(MAX_MESSAGE_LEN))?;
+
+    Some(GuestPanic {
+        reason: GuestPanicReason::from_i32(reason)?,
+        // Truncation may have split a character, which is replaced rather than
+        // discarding the whole message.
+        message: String::from_utf8_lossy(message).into_owned(),
+    })
+}
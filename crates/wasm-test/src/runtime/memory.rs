@@ -0,0 +1,96 @@
+use clarity::vm::Value;
+use wasmtime::{AsContext, AsContextMut, Memory};
+
+use super::alloc::WasmMemoryAllocator;
+use crate::{
+    serialization::{
+        deserialize_clarity_value, serialize_clarity_value_into, serialized_size,
+        SerializationError,
+    },
+    Ptr,
+};
+
+/// The size of a Wasm memory page.
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+/// Allocates a region of the given size using the provided allocator, growing the
+/// Wasm memory if the region (or its canaries, see `GuardedAllocator`) lies partly
+/// outside of it, and returns a pointer to it. Fails with `BufferTooSmall` if the
+/// memory can't be grown enough, either because of the allocator's
+/// `max_memory_pages()` or the maximum of the memory's own type.
+pub fn alloc_in_memory(
+    mut store: impl AsContextMut,
+    memory: Memory,
+    alloc: &mut dyn WasmMemoryAllocator,
+    size: usize,
+) -> Result<Ptr, SerializationError> {
+    let ptr = alloc.alloc_for_size(size);
+
+    let end = ptr.offset as usize + ptr.len as usize + alloc.guard_size();
+    let data_size = memory.data_size(&store);
+    if end > data_size {
+        let pages = (end - data_size).div_ceil(WASM_PAGE_SIZE) as u64;
+        let within_max = alloc
+            .max_memory_pages()
+            .map_or(true, |max_pages| memory.size(&store) + pages <= max_pages);
+        if !within_max || memory.grow(&mut store, pages).is_err() {
+            // Release the region so that a failed allocation doesn't leak it.
+            alloc.dealloc(ptr);
+            Err(SerializationError::BufferTooSmall)?
+        }
+    }
+    alloc.write_canaries(memory.data_mut(&mut store));
+
+    Ok(ptr)
+}
+
+/// Serializes a Clarity `Value` directly into Wasm memory, allocating exactly as
+/// much memory as it needs using the provided allocator (growing the memory if
+/// needed, see `alloc_in_memory()`), and returns a pointer to it. This saves
+/// embedders from serializing, allocating and writing the value themselves.
+pub fn write_value_to_memory(
+    mut store: impl AsContextMut,
+    memory: Memory,
+    alloc: &mut dyn WasmMemoryAllocator,
+    value: &Value,
+) -> Result<Ptr, SerializationError> {
+    let ptr = alloc_in_memory(&mut store, memory, alloc, serialized_size(value))?;
+
+    let buffer = memory
+        .data_mut(&mut store)
+        .get_mut(ptr.offset as usize..ptr.offset as usize + ptr.len as usize)
+        .ok_or(SerializationError::BufferTooSmall)?;
+    serialize_clarity_value_into(value, buffer)?;
+
+    Ok(ptr)
+}
+
+/// Reads and deserializes the value at the given offset/length in Wasm memory. The
+/// region is bounds-checked against the size of the memory, failing with
+/// `IndexOutOfRange` rather than panicking if it (or any part of it) lies outside.
+pub fn read_value_from_memory(
+    store: impl AsContext,
+    memory: Memory,
+    offset: i32,
+    len: i32,
+) -> Result<Value, SerializationError> {
+    deserialize_clarity_value(memory_slice(memory.data(&store), offset, len)?)
+}
+
+/// Gets the region at the given offset/length of the provided memory data, failing
+/// if it is negative or out of bounds.
+#[inline]
+pub(crate) fn memory_slice(
+    data: &[u8],
+    offset: i32,
+    len: i32,
+) -> Result<&[u8], SerializationError> {
+    let start = usize::try_from(offset).map_err(|_| SerializationError::IndexOutOfRange)?;
+    let len = usize::try_from(len).map_err(|_| SerializationError::IndexOutOfRange)?;
+    let end = start
+        .checked_add(len)
+        .ok_or(SerializationError::IndexOutOfRange)?;
+
+    data.get(start..end)
+        .ok_or(SerializationError::IndexOutOfRange)
+}
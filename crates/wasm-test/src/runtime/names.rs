@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use clarity::vm::ClarityName;
+
+/// Interns Clarity names (e.g. tuple field names) to `i32` ids, so that names can
+/// be passed across the Wasm boundary as plain integers instead of being serialized
+/// on every call.
+#[derive(Debug, Default, Clone)]
+pub struct NameInterner {
+    names: Vec<ClarityName>,
+    ids: HashMap<String, i32>,
+}
+
+impl NameInterner {
+    /// Creates a new, empty `NameInterner`.
+    pub fn new() -> Self {
+        NameInterner {
+            names: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Interns the provided name, returning its id. Interning the same name more
+    /// than once returns the same id.
+    pub fn intern(&mut self, name: ClarityName) -> i32 {
+        if let Some(id) = self.ids.get(name.as_str()) {
+            return *id;
+        }
+
+        let id = self.names.len() as i32;
+        self.ids.insert(name.to_string(), id);
+        self.names.push(name);
+        id
+    }
+
+    /// Gets the id of the provided name, if it has been interned.
+    pub fn id_of(&self, name: &str) -> Option<i32> {
+        self.ids.get(name).copied()
+    }
+
+    /// Gets the name with the given id.
+    pub fn get(&self, id: i32) -> Option<&ClarityName> {
+        usize::try_from(id).ok().and_then(|id| self.names.get(id))
+    }
+
+    /// Iterates over the interned names, in the order of their ids.
+    pub fn iter(&self) -> impl Iterator<Item = &ClarityName> {
+        self.names.iter()
+    }
+
+    /// Gets the number of interned names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Gets whether or not no names have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
@@ -1,27 +1,45 @@
-// This module defines all of the Clarity native RUNTIME functions. The matching type definitions
-// must be imported into modules IN THE SAME ORDER as their import order in the module. For example,
-// if Walrus is used to generate the module, the type definitions must be imported in the same
-// order as when imported into the Wasmtime module.
+// This module defines all of the Clarity native RUNTIME functions. Each function is registered
+// in a `wasmtime::Linker` under the `HOST_MODULE_NAME` module using its `FuncMap` name, so modules
+// must import the functions using the same module/name pair (the import order does not matter).
+
+use std::cmp::Ordering;
+use std::sync::{Mutex, PoisonError};
 
 use crate::runtime::FuncResultTrait;
 use crate::serialization::{
-    deserialize_clarity_seq_to_ptrs, deserialize_clarity_value,
-    get_type_indicator_from_serialized_value, serialize_clarity_value, TypeIndicator,
+    deserialize_clarity_value, deserialize_packed_int_list,
+    get_type_indicator_from_serialized_value, is_packed_int_list, serialize_clarity_value_into,
+    serialized_size, u128_from_bytes, u128_to_bytes, ByteOrder, ClaritySeqIterator, TypeIndicator,
 };
-use crate::ClarityWasmContext;
+use crate::{ClarityWasmContext, Ptr};
+use clarity::util::hash::{Hash160, Keccak256Hash, Sha256Sum, Sha512Sum, Sha512Trunc256Sum};
+use clarity::util::secp256k1::{secp256k1_recover, secp256k1_verify, Secp256k1PublicKey};
 use clarity::vm::{
-    types::{CharType, SequenceData},
-    Value,
+    errors::{CheckErrors, Error},
+    types::{
+        ASCIIData, BlockInfoProperty, BuffData, BurnBlockInfoProperty, CharType, PrincipalData,
+        QualifiedContractIdentifier, SequenceData, StandardPrincipalData, TupleData, TypeSignature,
+        UTF8Data, MAX_VALUE_SIZE,
+    },
+    ClarityVersion, ContractName, Value,
+};
+use wasmtime::{AsContext, AsContextMut, Caller, ExternRef, Func, Linker, Memory, Val};
+
+use super::{
+    memory::{alloc_in_memory, memory_slice},
+    FuncResult, RuntimeError,
 };
-use wasmtime::{AsContext, AsContextMut, Caller, ExternRef, Func, Val};
 
-use super::{FuncResult, RuntimeError};
+/// The module name under which all host functions are registered.
+pub const HOST_MODULE_NAME: &str = "clarity";
 
-/// Holds a native function name and function implementation.
+/// Holds a native function name and function implementation, along with the
+/// first Clarity version in which the function is available.
 #[derive(Debug)]
 pub struct FuncMap {
     pub name: String,
     pub func: Func,
+    pub min_version: ClarityVersion,
 }
 
 impl FuncMap {
@@ -29,43 +47,133 @@ impl FuncMap {
         FuncMap {
             name: name.to_string(),
             func,
+            min_version: ClarityVersion::Clarity1,
+        }
+    }
+
+    /// Sets the first Clarity version in which this function is available.
+    pub fn since(mut self, min_version: ClarityVersion) -> Self {
+        self.min_version = min_version;
+        self
+    }
+
+    /// Gets whether or not this function is available at the given Clarity version.
+    pub fn is_available_at(&self, version: ClarityVersion) -> bool {
+        self.min_version <= version
+    }
+}
+
+/// Extracts a reference to the Clarity `Value` held by an `ExternRef` argument.
+#[inline]
+fn extref_value(value: &Option<ExternRef>) -> Result<&Value, RuntimeError> {
+    value
+        .as_ref()
+        .ok_or(RuntimeError::FunctionArgumentRequired)?
+        .data()
+        .downcast_ref::<Value>()
+        .ok_or(RuntimeError::ArgumentTypeMismatch)
+}
+
+/// Applies one of the provided checked operations to two integral Clarity values
+/// of the same type.
+#[inline]
+fn checked_integer_op(
+    a: &Value,
+    b: &Value,
+    int_op: fn(i128, i128) -> Option<i128>,
+    uint_op: fn(u128, u128) -> Option<u128>,
+) -> Result<Value, RuntimeError> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => int_op(*a, *b)
+            .map(Value::Int)
+            .ok_or(RuntimeError::ArithmeticOverflow),
+        (Value::UInt(a), Value::UInt(b)) => uint_op(*a, *b)
+            .map(Value::UInt)
+            .ok_or(RuntimeError::ArithmeticOverflow),
+        (Value::Int(_), _) | (Value::UInt(_), _) => Err(RuntimeError::ArgumentTypeMismatch),
+        _ => Err(RuntimeError::FunctionOnlySupportsIntegralValues),
+    }
+}
+
+/// Adds two integral Clarity values of the same type.
+#[inline]
+fn checked_add_values(a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+    checked_integer_op(a, b, i128::checked_add, u128::checked_add)
+}
+
+/// Subtracts `b` from `a`, where both are integral Clarity values of the same type.
+/// Results which would fall below the type's minimum value are reported as an
+/// `ArithmeticUnderflow` rather than an overflow.
+#[inline]
+fn checked_sub_values(a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+    checked_integer_op(a, b, i128::checked_sub, u128::checked_sub).map_err(|e| match (e, b) {
+        (RuntimeError::ArithmeticOverflow, Value::Int(b)) if *b < 0 => e,
+        (RuntimeError::ArithmeticOverflow, _) => RuntimeError::ArithmeticUnderflow,
+        _ => e,
+    })
+}
+
+/// Multiplies two integral Clarity values of the same type.
+#[inline]
+fn checked_mul_values(a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+    checked_integer_op(a, b, i128::checked_mul, u128::checked_mul)
+}
+
+/// Divides `a` by `b`, where both are integral Clarity values of the same type. As
+/// in Clarity, the quotient is truncated towards zero and dividing by zero results
+/// in a `DivisionByZero` error rather than a trap.
+#[inline]
+fn checked_div_values(a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+    checked_integer_op(a, b, i128::checked_div, u128::checked_div).map_err(|e| division_error(e, b))
+}
+
+/// Computes the remainder of dividing `a` by `b`, where both are integral Clarity
+/// values of the same type. The result takes the sign of `a`, as in Clarity.
+#[inline]
+fn checked_mod_values(a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+    checked_integer_op(a, b, i128::checked_rem, u128::checked_rem).map_err(|e| division_error(e, b))
+}
+
+/// The checked division operations return `None` both when dividing by zero and when
+/// the result overflows (`i128::MIN / -1`), so we need to discern between the two.
+#[inline]
+fn division_error(e: RuntimeError, divisor: &Value) -> RuntimeError {
+    match (e, divisor) {
+        (RuntimeError::ArithmeticOverflow, Value::Int(0) | Value::UInt(0)) => {
+            RuntimeError::DivisionByZero
         }
+        _ => e,
     }
 }
 
+/// Defines a function taking two `ExternRef` arguments which applies the provided
+/// operation to the referenced Clarity values.
+#[inline]
+fn define_integer_op_extref(
+    mut store: impl AsContextMut,
+    op: fn(&Value, &Value) -> Result<Value, RuntimeError>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |a: Option<ExternRef>, b: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let result = op(extref_value(&a)?, extref_value(&b)?)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
 /// Defines the `add_extref` function. This function makes full use of `ExternRef`s
 /// instead of value types or memory, meaning that the values coming across are
 /// pure references to real Clarity `Value` enum variants.
 #[inline]
-pub fn define_add_extref(mut store: impl AsContextMut) -> Func {
-    Func::wrap(&mut store, |a: Option<ExternRef>, b: Option<ExternRef>| {
-        let a = a.unwrap();
-        let b = b.unwrap();
-
-        let result = match a.data().downcast_ref::<Value>() {
-            Some(Value::Int(int_a)) => {
-                if let Some(Value::Int(int_b)) = b.data().downcast_ref::<Value>() {
-                    let result = int_a.checked_add(*int_b).expect("Failed to add");
-                    Some(ExternRef::new(Value::Int(result)))
-                } else {
-                    panic!(
-                        "[add] Value type mismatch (int): b = {:?}",
-                        b.data().downcast_ref::<Value>()
-                    );
-                }
-            }
-            Some(Value::UInt(uint_a)) => {
-                if let Some(Value::UInt(uint_b)) = b.data().downcast_ref::<Value>() {
-                    Some(ExternRef::new(Value::UInt(uint_a + uint_b)))
-                } else {
-                    panic!("Value type mismatch");
-                }
-            }
-            _ => panic!("Invalid type..."),
-        };
+pub fn define_add_extref(store: impl AsContextMut) -> Func {
+    define_integer_op_extref(store, checked_add_values)
+}
 
-        Ok(result)
-    })
+/// Defines the `sub_extref` function, the `ExternRef` counterpart of `add_extref`.
+#[inline]
+pub fn define_sub_extref(store: impl AsContextMut) -> Func {
+    define_integer_op_extref(store, checked_sub_values)
 }
 
 /// Defines the `add_native_int128` function. This function makes use of Wasm "native"
@@ -75,142 +183,278 @@ pub fn define_add_extref(mut store: impl AsContextMut) -> Func {
 pub fn define_add_native(mut store: impl AsContextMut) -> Func {
     Func::wrap(
         &mut store,
-        |a_low: i64, a_high: i64, b_low: i64, b_high: i64| {
-            let a = ((a_high as u64) as u128) << 64 | ((a_low as u64) as u128);
-            let b = ((b_high as u64) as u128) << 64 | ((b_low as u64) as u128);
+        |a_low: i64, a_high: i64, b_low: i64, b_high: i64| -> wasmtime::Result<(i64, i64)> {
+            let a = (((a_high as u64) as u128) << 64 | ((a_low as u64) as u128)) as i128;
+            let b = (((b_high as u64) as u128) << 64 | ((b_low as u64) as u128)) as i128;
 
-            let result: i128 = a.checked_add(b).unwrap().try_into().unwrap();
+            let result = a.checked_add(b).ok_or(if b < 0 {
+                RuntimeError::ArithmeticUnderflow
+            } else {
+                RuntimeError::ArithmeticOverflow
+            })?;
 
-            (
+            Ok((
                 (result & 0xFFFFFFFFFFFFFFFF) as i64,
                 ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
-            )
+            ))
         },
     )
 }
 
+/// Defines the `sub_native` function, which subtracts two signed 128-bit integers
+/// passed as low/high i64 pairs and returns the result as a low/high i64 pair.
 #[inline]
-pub fn define_add_memory(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+pub fn define_sub_native(mut store: impl AsContextMut) -> Func {
     Func::wrap(
         &mut store,
-        |mut caller: Caller<'_, ClarityWasmContext>,
-         a_ptr: i32,
-         a_len: i32,
-         b_ptr: i32,
-         b_len: i32|
-         -> FuncResult {
+        |a_low: i64, a_high: i64, b_low: i64, b_high: i64| -> wasmtime::Result<(i64, i64)> {
+            let a = (((a_high as u64) as u128) << 64 | ((a_low as u64) as u128)) as i128;
+            let b = (((b_high as u64) as u128) << 64 | ((b_low as u64) as u128)) as i128;
+
+            let result = a.checked_sub(b).ok_or(if b < 0 {
+                RuntimeError::ArithmeticOverflow
+            } else {
+                RuntimeError::ArithmeticUnderflow
+            })?;
+
+            Ok((
+                (result & 0xFFFFFFFFFFFFFFFF) as i64,
+                ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
+            ))
+        },
+    )
+}
+
+/// Reads a serialized integral value (`int` or `uint`) from the provided memory
+/// slice.
+#[inline]
+fn read_integer_operand(data: &[u8], ptr: i32, len: i32) -> Result<Value, RuntimeError> {
+    let start = ptr as usize;
+    let end = start
+        .checked_add(len as usize)
+        .ok_or(RuntimeError::FailedToDeserializeValueFromMemory)?;
+
+    // Fetch the serialized bytes (header + value) from memory.
+    let bytes = data
+        .get(start..end)
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or(RuntimeError::FailedToDeserializeValueFromMemory)?;
+
+    // Get the type of the value and assert that it is an integral type.
+    let ty = get_type_indicator_from_serialized_value(bytes)
+        .map_err(|_| RuntimeError::FailedToDiscernSerializedType)?;
+    if !ty.is_integer() {
+        return Err(RuntimeError::FunctionOnlySupportsIntegralValues);
+    }
+
+    // Fetch the value bytes (excluding the header).
+    let value: [u8; 16] = bytes
+        .get(3..)
+        .and_then(|value| value.try_into().ok())
+        .ok_or(RuntimeError::FailedToDeserializeValueFromMemory)?;
+
+    if ty == TypeIndicator::Int {
+        Ok(Value::Int(i128::from_le_bytes(value)))
+    } else {
+        Ok(Value::UInt(u128::from_le_bytes(value)))
+    }
+}
+
+/// Defines a function taking two serialized integral values in memory which applies
+/// the provided operation to them and writes the serialized result back to memory.
+#[inline]
+fn define_integer_op_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+    op: fn(&Value, &Value) -> Result<Value, RuntimeError>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |mut caller: Caller<'_, ClarityWasmContext>,
+              a_ptr: i32,
+              a_len: i32,
+              b_ptr: i32,
+              b_len: i32|
+              -> FuncResult {
             // Retrieve an instance of the `vm_mem` exported memory.
-            let memory = caller.get_export("vm_mem").unwrap().into_memory().unwrap();
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
             // Get a handle to a slice representing the in-memory data.
             let data = memory.data(&caller);
 
-            // Fetch the bytes for `a` from memory.
-            let a_bytes: [u8; 16] = data[(a_ptr + 3) as usize..(a_ptr + 3 + a_len - 3) as usize]
-                .try_into()
-                .map_err(|_| FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory))
-                .unwrap();
+            // Fetch `a` and `b` from memory and apply the operation.
+            let result = read_integer_operand(data, a_ptr, a_len)
+                .and_then(|a| read_integer_operand(data, b_ptr, b_len).and_then(|b| op(&a, &b)));
 
-            // Get the type of `a`.
-            let a_ty = get_type_indicator_from_serialized_value(&a_bytes)
-                .map_err(|_| FuncResult::err(RuntimeError::FailedToDiscernSerializedType))
-                .unwrap();
-
-            // Assert that `a` is an integral type.
-            if !a_ty.is_integer() {
-                return FuncResult::err(RuntimeError::FunctionOnlySupportsIntegralValues);
+            match result {
+                Ok(result) => write_result_to_memory(&mut caller, &result),
+                Err(e) => FuncResult::err(e),
             }
+        },
+    )
+}
+
+#[inline]
+pub fn define_add_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_integer_op_memory(store, checked_add_values)
+}
 
-            // Fetch the bytes for `b` from memory.
-            let b_bytes: [u8; 16] = data[b_ptr as usize..(b_ptr + 3 + b_len - 3) as usize]
-                .try_into()
-                .map_err(|_| FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory))
-                .unwrap();
+/// Defines the `sub_memory` function, the memory counterpart of `sub_extref`.
+#[inline]
+pub fn define_sub_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_integer_op_memory(store, checked_sub_values)
+}
 
-            // Get the type of `b`.
-            let b_ty = get_type_indicator_from_serialized_value(&b_bytes)
-                .map_err(|_| FuncResult::err(RuntimeError::FailedToDiscernSerializedType))
-                .unwrap();
+/// Defines the `mul` (multiply) function.
+#[inline]
+pub fn define_mul_extref(store: impl AsContextMut) -> Func {
+    define_integer_op_extref(store, checked_mul_values)
+}
 
-            // Assert that `b` is an integral type.
-            if !b_ty.is_integer() {
-                return FuncResult::err(RuntimeError::FunctionOnlySupportsIntegralValues);
-            }
+/// Defines the `div` (divide) function.
+#[inline]
+pub fn define_div_extref(store: impl AsContextMut) -> Func {
+    define_integer_op_extref(store, checked_div_values)
+}
 
-            // Assert that `a` and `b` are of the same type.
-            if a_ty != b_ty {
-                return FuncResult::err(RuntimeError::ArgumentTypeMismatch);
-            }
+/// Defines the `div` (divide) function for the memory convention. Errors, such as
+/// division by zero, are returned through the `FuncResult`.
+#[inline]
+pub fn define_div_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_integer_op_memory(store, checked_div_values)
+}
 
-            // Result buffer
-            let mut result: [u8; 16] = [0; 16];
-
-            if a_ty == TypeIndicator::Int {
-                // Handle case for signed integers
-                let a = i128::from_le_bytes(a_bytes);
-                let b = i128::from_le_bytes(b_bytes);
-                if let Some(add_result) = a.checked_add(b) {
-                    result = add_result.to_le_bytes();
-                } else {
-                    return FuncResult::err(RuntimeError::ArithmeticOverflow);
-                }
-            } else if a_ty == TypeIndicator::UInt {
-                // Handle case for unsigned integers
-                let a = u128::from_le_bytes(a_bytes);
-                let b = u128::from_le_bytes(b_bytes);
-                if let Some(add_result) = a.checked_add(b) {
-                    result = add_result.to_le_bytes();
-                } else {
-                    return FuncResult::err(RuntimeError::ArithmeticOverflow);
-                }
-            }
+/// Defines the `mod` (modulo) function.
+#[inline]
+pub fn define_mod_extref(store: impl AsContextMut) -> Func {
+    define_integer_op_extref(store, checked_mod_values)
+}
 
-            // Retrieve a memory ptr for the result.
-            let alloc = caller.data_mut().alloc.alloc_for_buffer(&result);
+/// Defines the `mod` (modulo) function for the memory convention. Errors, such as
+/// division by zero, are returned through the `FuncResult`.
+#[inline]
+pub fn define_mod_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_integer_op_memory(store, checked_mod_values)
+}
 
-            // Write the result to memory
-            memory
-                .write(&mut caller, alloc.offset as usize, &result)
-                .map_err(|_| FuncResult::err(RuntimeError::FailedToWriteResultToMemory))
-                .unwrap();
+/// Compares two integral Clarity values of the same type.
+#[inline]
+fn compare_integer_values(a: &Value, b: &Value) -> Result<Ordering, RuntimeError> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+        (Value::UInt(a), Value::UInt(b)) => Ok(a.cmp(b)),
+        (Value::Int(_), _) | (Value::UInt(_), _) => Err(RuntimeError::ArgumentTypeMismatch),
+        _ => Err(RuntimeError::FunctionOnlySupportsIntegralValues),
+    }
+}
 
-            // Return
-            FuncResult::ok(alloc)
+/// Defines a comparison function taking two `ExternRef` arguments and returning a
+/// Clarity `bool` indicating whether or not the `Ordering` of `a` to `b` satisfies
+/// the provided predicate.
+#[inline]
+fn define_comparison_extref(mut store: impl AsContextMut, predicate: fn(Ordering) -> bool) -> Func {
+    Func::wrap(
+        &mut store,
+        move |a: Option<ExternRef>, b: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let ordering = compare_integer_values(extref_value(&a)?, extref_value(&b)?)?;
+            Ok(Some(ExternRef::new(Value::Bool(predicate(ordering)))))
         },
     )
 }
 
-/// Defines the `mul` (multiply) function.
+/// Defines the `>` (greater than) function.
 #[inline]
-pub fn define_mul_extref(mut store: impl AsContextMut) -> Func {
-    Func::wrap(&mut store, |a: Option<ExternRef>, b: Option<ExternRef>| {
-        let a = a.unwrap();
-        let b = b.unwrap();
-
-        let result = match a.data().downcast_ref::<Value>() {
-            Some(Value::Int(int_a)) => {
-                if let Some(Value::Int(int_b)) = b.data().downcast_ref::<Value>() {
-                    let result = int_a.checked_mul(*int_b).expect("Failed to multiply");
-                    Some(ExternRef::new(Value::Int(result)))
-                } else {
-                    panic!(
-                        "[mul] Value type mismatch (int): b = {:?}",
-                        b.data().downcast_ref::<Value>()
-                    );
-                }
-            }
-            Some(Value::UInt(uint_a)) => {
-                if let Some(Value::UInt(uint_b)) = b.data().downcast_ref::<Value>() {
-                    Some(ExternRef::new(Value::UInt(
-                        uint_a.checked_mul(*uint_b).expect("Fail"),
-                    )))
-                } else {
-                    panic!("Value type mismatch (uint)");
-                }
-            }
-            _ => panic!("Invalid type..."),
-        };
+pub fn define_gt_extref(store: impl AsContextMut) -> Func {
+    define_comparison_extref(store, Ordering::is_gt)
+}
+
+/// Defines the `>=` (greater than or equal) function.
+#[inline]
+pub fn define_ge_extref(store: impl AsContextMut) -> Func {
+    define_comparison_extref(store, Ordering::is_ge)
+}
+
+/// Defines the `<` (less than) function.
+#[inline]
+pub fn define_lt_extref(store: impl AsContextMut) -> Func {
+    define_comparison_extref(store, Ordering::is_lt)
+}
+
+/// Defines the `<=` (less than or equal) function.
+#[inline]
+pub fn define_le_extref(store: impl AsContextMut) -> Func {
+    define_comparison_extref(store, Ordering::is_le)
+}
+
+/// Defines the `is-eq` function for two values. Unlike the ordering comparisons,
+/// `is-eq` accepts values of any type (including strings and buffers), comparing
+/// them structurally.
+#[inline]
+pub fn define_is_eq_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |a: Option<ExternRef>, b: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let result = extref_value(&a)? == extref_value(&b)?;
+            Ok(Some(ExternRef::new(Value::Bool(result))))
+        },
+    )
+}
+
+/// Defines a comparison function taking two signed 128-bit integers, as low/high
+/// `i64` pairs, and returning `1` if the `Ordering` of `a` to `b` satisfies the
+/// provided predicate, or `0` otherwise.
+#[inline]
+fn define_comparison_native(mut store: impl AsContextMut, predicate: fn(Ordering) -> bool) -> Func {
+    Func::wrap(
+        &mut store,
+        move |a_low: i64, a_high: i64, b_low: i64, b_high: i64| -> i32 {
+            let a = (((a_high as u64) as u128) << 64 | ((a_low as u64) as u128)) as i128;
+            let b = (((b_high as u64) as u128) << 64 | ((b_low as u64) as u128)) as i128;
+
+            predicate(a.cmp(&b)) as i32
+        },
+    )
+}
+
+/// Defines the `>` (greater than) function for `int`s passed as low/high `i64` pairs.
+#[inline]
+pub fn define_gt_native(store: impl AsContextMut) -> Func {
+    define_comparison_native(store, Ordering::is_gt)
+}
+
+/// Defines the `>=` (greater than or equal) function for `int`s passed as low/high
+/// `i64` pairs.
+#[inline]
+pub fn define_ge_native(store: impl AsContextMut) -> Func {
+    define_comparison_native(store, Ordering::is_ge)
+}
+
+/// Defines the `<` (less than) function for `int`s passed as low/high `i64` pairs.
+#[inline]
+pub fn define_lt_native(store: impl AsContextMut) -> Func {
+    define_comparison_native(store, Ordering::is_lt)
+}
 
-        Ok(result)
+/// Defines the `<=` (less than or equal) function for `int`s passed as low/high
+/// `i64` pairs.
+#[inline]
+pub fn define_le_native(store: impl AsContextMut) -> Func {
+    define_comparison_native(store, Ordering::is_le)
+}
+
+/// Defines the `is-eq` function for two `int`s passed as low/high `i64` pairs.
+#[inline]
+pub fn define_is_eq_native(store: impl AsContextMut) -> Func {
+    define_comparison_native(store, Ordering::is_eq)
+}
+
+/// Defines the `not` function.
+#[inline]
+pub fn define_not_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| match value {
+        Value::Bool(b) => Ok(Value::Bool(!b)),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
     })
 }
 
@@ -224,30 +468,60 @@ pub fn define_fold_memory(mut store: impl AsContextMut<Data = ClarityWasmContext
          seq_len: i32,
          init_ptr: i32,
          init_len: i32|
-         -> FuncResult {
+         -> wasmtime::Result<FuncResult> {
             // The function to fold over must be supplied.
-            if func.is_none() {
-                return FuncResult::err(RuntimeError::FunctionArgumentRequired);
-            }
+            let func = match func {
+                Some(func) => func,
+                None => return Ok(FuncResult::err(RuntimeError::FunctionArgumentRequired)),
+            };
 
             // Retrieve an instance of the `vm_mem` exported memory.
-            let memory = caller.get_export("vm_mem").unwrap().into_memory().unwrap();
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => {
+                    return Ok(FuncResult::err(
+                        RuntimeError::FailedToDeserializeValueFromMemory,
+                    ))
+                }
+            };
             // Get a handle to a slice representing the in-memory data.
             let data = memory.data(&caller);
             // Extract the raw serialized sequence.
-            let seq_data = &data[seq_ptr as usize..seq_len as usize];
-            // Deserialize the sequence to a list of pointers to its values (we don't actually care about
-            // the values in this function, so we don't need to deserialize them).
-            let sequence_ptrs = deserialize_clarity_seq_to_ptrs(seq_data)
-                .map_err(|_| FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory))
-                .unwrap();
-
-            // Grab our function to fold over.
-            let func = func.unwrap();
+            let seq_data = match memory_slice(data, seq_ptr, seq_len) {
+                Ok(seq_data) => seq_data,
+                Err(_) => {
+                    return Ok(FuncResult::err(
+                        RuntimeError::FailedToDeserializeValueFromMemory,
+                    ))
+                }
+            };
+            // Walk the sequence to find the (pointers-to) its values. We don't actually care about
+            // the values in this function, so we don't need to deserialize them. Memory can't stay
+            // borrowed while we call back into Wasm, so only the pointers are collected. Packed
+            // integer lists have no per-element headers, so their elements are written out first.
+            let sequence_ptrs = if is_packed_int_list(seq_data) {
+                write_packed_elements(&mut caller, memory, seq_ptr, seq_len)
+            } else {
+                ClaritySeqIterator::new(seq_data)
+                    .and_then(|elements| {
+                        elements
+                            .map(|element| {
+                                element.map(|element| {
+                                    Ptr::new(seq_ptr + element.ptr.offset, element.ptr.len)
+                                })
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .map_err(|_| RuntimeError::FailedToDeserializeValueFromMemory)
+            };
+            let sequence_ptrs = match sequence_ptrs {
+                Ok(ptrs) => ptrs,
+                Err(e) => return Ok(FuncResult::err(e)),
+            };
 
             // We use the `init` value for the first round, and the result of the
             // function call for further rounds.
-            let mut is_first = true;
+            let mut acc = (init_ptr, init_len);
 
             // We'll re-use the same result array to avoid re-allocations.
             let mut result = [Val::I32(0), Val::I32(0), Val::I32(0)];
@@ -255,40 +529,86 @@ pub fn define_fold_memory(mut store: impl AsContextMut<Data = ClarityWasmContext
             // Iterate through each of the (pointers-to) the values of the sequence and call the
             // provided function to fold over.
             for ptr in sequence_ptrs {
-                if is_first {
-                    func.call(
-                        &mut caller,
-                        &[
-                            Val::I32(ptr.offset),
-                            Val::I32(ptr.len),
-                            Val::I32(init_ptr),
-                            Val::I32(init_len),
-                        ],
-                        &mut result,
-                    )
-                    .unwrap();
-                    is_first = false;
-                } else {
-                    func.call(
-                        &mut caller,
-                        &[
-                            Val::I32(ptr.offset),
-                            Val::I32(ptr.len),
-                            result[1].clone(),
-                            result[2].clone(),
-                        ],
-                        &mut result,
-                    )
-                    .unwrap();
+                func.call(
+                    &mut caller,
+                    &[
+                        Val::I32(ptr.offset),
+                        Val::I32(ptr.len),
+                        Val::I32(acc.0),
+                        Val::I32(acc.1),
+                    ],
+                    &mut result,
+                )?;
+
+                // Propagate errors returned by the function to fold over.
+                if result[0].unwrap_i32() != 0 {
+                    return Ok((result[0].unwrap_i32(), 0, 0));
                 }
+
+                acc = (result[1].unwrap_i32(), result[2].unwrap_i32());
             }
 
-            (0, result[1].unwrap_i32(), result[2].unwrap_i32())
+            Ok((0, acc.0, acc.1))
         },
     )
 }
 
-/// Defines the `fold` function.
+/// Writes the elements of the packed integer list (see `serialize_packed_int_list`) at
+/// the given offset/length of the `vm_mem` memory to a single new region of it, using
+/// the regular encoding expected by the function folded over, and returns pointers to
+/// each of them. The list is decoded in one pass, without per-element headers.
+fn write_packed_elements(
+    caller: &mut Caller<'_, ClarityWasmContext>,
+    memory: Memory,
+    seq_ptr: i32,
+    seq_len: i32,
+) -> Result<Vec<Ptr>, RuntimeError> {
+    let elements = memory_slice(memory.data(&*caller), seq_ptr, seq_len)
+        .and_then(deserialize_packed_int_list)
+        .map_err(|_| RuntimeError::FailedToDeserializeValueFromMemory)?;
+    let elements = match elements {
+        Value::Sequence(SequenceData::List(list)) => list.data,
+        _ => return Err(RuntimeError::FailedToDeserializeValueFromMemory),
+    };
+    // All of the elements are `int`s or all of them are `uint`s, so they're the same size.
+    let element_size = match elements.first() {
+        Some(element) => serialized_size(element),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut alloc = std::mem::take(&mut caller.data_mut().alloc);
+    let region = alloc_in_memory(
+        &mut *caller,
+        memory,
+        &mut alloc,
+        element_size * elements.len(),
+    );
+    caller.data_mut().alloc = alloc;
+    let region = region.map_err(|_| RuntimeError::FailedToWriteResultToMemory)?;
+
+    let buffer = memory
+        .data_mut(&mut *caller)
+        .get_mut(region.offset as usize..region.offset as usize + region.len as usize)
+        .ok_or(RuntimeError::FailedToWriteResultToMemory)?;
+    buffer
+        .chunks_exact_mut(element_size)
+        .zip(&elements)
+        .enumerate()
+        .map(|(i, (chunk, element))| {
+            serialize_clarity_value_into(element, chunk).map(|_| {
+                Ptr::new(
+                    region.offset + (i * element_size) as i32,
+                    element_size as i32,
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| RuntimeError::FailedToWriteResultToMemory)
+}
+
+/// Defines the `fold` function. The provided function is called once per element of
+/// the sequence (lists, buffers and strings alike) with the element and the current
+/// accumulator, starting with `init`, and the final accumulator is returned.
 #[inline]
 pub fn define_fold_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
     Func::wrap(
@@ -296,109 +616,2603 @@ pub fn define_fold_extref(mut store: impl AsContextMut<Data = ClarityWasmContext
         |mut caller: Caller<'_, ClarityWasmContext>,
          func: Option<Func>,
          seq: Option<ExternRef>,
-         init: Option<ExternRef>| {
-            let func = func.unwrap();
-            let seq = seq.unwrap();
-            let init = init.unwrap();
-
-            // Verify that the provided function to fold over has a compatible type signature
-            // TODO: Verify against allowed types, if possible?
-            let fn_type = func.ty(caller.as_context());
-            debug_assert_eq!(fn_type.params().len(), 2);
-            debug_assert_eq!(fn_type.results().len(), 1);
-
-            // Define our output parameters to be used for each iteration of fold.
-            let results = &mut [
-                Val::ExternRef(Some(ExternRef::new(Value::none()))), // Option<ExternRef>
-            ];
-
-            // Iterate through each item in the provided sequence.
-            let result = match seq.data().downcast_ref::<Value>().unwrap() {
-                Value::Sequence(SequenceData::List(list)) => {
-                    let result = list.data.iter().fold(init, |acc, val| {
-                        let val_ref = Some(ExternRef::new(val.clone()));
-                        // Call the provided function to fold over.
-                        func.call(
-                            &mut caller,
-                            &[Val::ExternRef(val_ref), Val::ExternRef(Some(acc))],
-                            results,
-                        )
-                        .expect("Failed to call fold inner function");
-
-                        // TODO: Verify that the returned value is of the same type as `init`.
-                        results[0].unwrap_externref().unwrap()
-                    });
-                    Some(result)
-                }
-                Value::Sequence(SequenceData::Buffer(buff)) => {
-                    let result = buff.data.iter().fold(init, |acc, val| {
-                        let val_ref = Some(ExternRef::new(*val));
-                        func.call(
-                            &mut caller,
-                            &[Val::ExternRef(val_ref), Val::ExternRef(Some(acc))],
-                            results,
-                        )
-                        .expect("Failed to call fold inner function");
-
-                        // TODO: Verify that the returned value is of the same type as `init`.
-                        results[0].unwrap_externref().unwrap()
-                    });
-                    Some(result)
-                }
-                Value::Sequence(SequenceData::String(char_type)) => {
-                    match char_type {
-                        CharType::ASCII(str) => {
-                            let result = str.data.iter().fold(init, |acc, val| {
-                                let val_ref = Some(ExternRef::new(*val));
-                                func.call(
-                                    &mut caller,
-                                    &[Val::ExternRef(val_ref), Val::ExternRef(Some(acc))],
-                                    results,
-                                )
-                                .expect("Failed to call fold inner function");
-
-                                // TODO: Verify that the returned value is of the same type as `init`.
-                                results[0].unwrap_externref().unwrap()
-                            });
-                            Some(result)
-                        }
-                        CharType::UTF8(str) => {
-                            // TODO: This should probably be converted to i32 and compared from there (utf8 is 4 bytes)
-                            let result = str.data.iter().fold(init, |acc, val| {
-                                let val_ref = Some(ExternRef::new(val.clone()));
-                                func.call(
-                                    &mut caller,
-                                    &[Val::ExternRef(val_ref), Val::ExternRef(Some(acc))],
-                                    results,
-                                )
-                                .expect("Failed to call fold inner function");
-
-                                // TODO: Verify that the returned value is of the same type as `init`.
-                                results[0].unwrap_externref().unwrap()
-                            });
-                            Some(result)
-                        }
-                    }
+         init: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let func = func.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            let init = init.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            let elements = sequence_elements(extref_value(&seq)?)?;
+            check_callback_signature(&caller, &func, 2)?;
+
+            // We'll re-use the same result array for each element.
+            let results = &mut [Val::ExternRef(None)];
+
+            // The accumulator is passed back in as-is, so it never needs to be
+            // unwrapped and re-wrapped between iterations.
+            let mut acc = init;
+
+            for element in elements {
+                let args = [
+                    Val::ExternRef(Some(ExternRef::new(element))),
+                    Val::ExternRef(Some(acc)),
+                ];
+                func.call(&mut caller, &args, results)?;
+                acc = results[0]
+                    .unwrap_externref()
+                    .ok_or(RuntimeError::FunctionArgumentRequired)?;
+            }
+
+            Ok(Some(acc))
+        },
+    )
+}
+
+/// Splits a Clarity sequence into its elements. List elements are returned as-is,
+/// while buffers and strings yield single-element values of the same sequence type
+/// (i.e. `(buff 1)`, `(string-ascii 1)` and `(string-utf8 1)`), as in Clarity.
+#[inline]
+fn sequence_elements(seq: &Value) -> Result<Vec<Value>, RuntimeError> {
+    match seq {
+        Value::Sequence(SequenceData::List(list)) => Ok(list.data.clone()),
+        Value::Sequence(SequenceData::Buffer(buff)) => Ok(buff
+            .data
+            .iter()
+            .map(|byte| Value::Sequence(SequenceData::Buffer(BuffData { data: vec![*byte] })))
+            .collect()),
+        Value::Sequence(SequenceData::String(CharType::ASCII(str))) => Ok(str
+            .data
+            .iter()
+            .map(|byte| {
+                Value::Sequence(SequenceData::String(CharType::ASCII(ASCIIData {
+                    data: vec![*byte],
+                })))
+            })
+            .collect()),
+        Value::Sequence(SequenceData::String(CharType::UTF8(str))) => Ok(str
+            .data
+            .iter()
+            .map(|char| {
+                Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data {
+                    data: vec![char.clone()],
+                })))
+            })
+            .collect()),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    }
+}
+
+/// Verifies that the provided function takes `params` arguments and returns a
+/// single value, as required for functions passed to `map`, `filter` and `fold`.
+#[inline]
+fn check_callback_signature(
+    caller: &Caller<'_, ClarityWasmContext>,
+    func: &Func,
+    params: usize,
+) -> Result<(), RuntimeError> {
+    let fn_type = func.ty(caller);
+    if fn_type.params().len() != params || fn_type.results().len() != 1 {
+        return Err(RuntimeError::ArgumentTypeMismatch);
+    }
+    Ok(())
+}
+
+/// Calls the provided function with the given `ExternRef` arguments, returning the
+/// Clarity value it returns. The `results` array is re-used between calls.
+#[inline]
+fn call_callback_extref(
+    caller: &mut Caller<'_, ClarityWasmContext>,
+    func: &Func,
+    args: &[Val],
+    results: &mut [Val; 1],
+) -> wasmtime::Result<Value> {
+    func.call(&mut *caller, args, results)?;
+    let result = results[0].unwrap_externref();
+    Ok(extref_value(&result)?.clone())
+}
+
+/// Defines the `map` function. The provided function is called once per element of
+/// the sequence and the results are collected into a Clarity list.
+#[inline]
+pub fn define_map_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         func: Option<Func>,
+         seq: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let func = func.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            let elements = sequence_elements(extref_value(&seq)?)?;
+            check_callback_signature(&caller, &func, 1)?;
+
+            // We'll re-use the same result array for each element.
+            let results = &mut [Val::ExternRef(None)];
+            let mut mapped = Vec::with_capacity(elements.len());
+
+            for element in elements {
+                let args = [Val::ExternRef(Some(ExternRef::new(element)))];
+                mapped.push(call_callback_extref(&mut caller, &func, &args, results)?);
+            }
+
+            // Building the list validates that all of the results are of the same type.
+            let list = list_from(mapped)?;
+
+            Ok(Some(ExternRef::new(list)))
+        },
+    )
+}
+
+/// Defines the `filter` function. The provided predicate is called once per element
+/// of the sequence, and a sequence of the same type containing only the elements for
+/// which the predicate returned `true` is returned.
+#[inline]
+pub fn define_filter_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         func: Option<Func>,
+         seq: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let func = func.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            let seq = extref_value(&seq)?;
+            let elements = sequence_elements(seq)?;
+            check_callback_signature(&caller, &func, 1)?;
+
+            // We'll re-use the same result array for each element.
+            let results = &mut [Val::ExternRef(None)];
+            let mut keep = Vec::with_capacity(elements.len());
+
+            for element in elements {
+                let args = [Val::ExternRef(Some(ExternRef::new(element)))];
+                match call_callback_extref(&mut caller, &func, &args, results)? {
+                    Value::Bool(b) => keep.push(b),
+                    _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
                 }
-                _ => panic!("Not a valid sequence type"),
+            }
+
+            // Re-assemble a sequence of the same type from the retained elements.
+            let filtered = retain_sequence(seq, &keep)?;
+
+            Ok(Some(ExternRef::new(filtered)))
+        },
+    )
+}
+
+/// Builds a sequence of the same type as `seq` containing only the elements for
+/// which the corresponding entry in `mask` is `true`.
+#[inline]
+fn retain_sequence(seq: &Value, mask: &[bool]) -> Result<Value, RuntimeError> {
+    let retained = match seq {
+        Value::Sequence(SequenceData::List(list)) => list_from(retain_by_mask(&list.data, mask))?,
+        Value::Sequence(SequenceData::Buffer(buff)) => {
+            Value::Sequence(SequenceData::Buffer(BuffData {
+                data: retain_by_mask(&buff.data, mask),
+            }))
+        }
+        Value::Sequence(SequenceData::String(CharType::ASCII(str))) => {
+            Value::Sequence(SequenceData::String(CharType::ASCII(ASCIIData {
+                data: retain_by_mask(&str.data, mask),
+            })))
+        }
+        Value::Sequence(SequenceData::String(CharType::UTF8(str))) => {
+            Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data {
+                data: retain_by_mask(&str.data, mask),
+            })))
+        }
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    Ok(retained)
+}
+
+/// Returns the items for which the corresponding entry in `mask` is `true`.
+#[inline]
+fn retain_by_mask<T: Clone>(items: &[T], mask: &[bool]) -> Vec<T> {
+    items
+        .iter()
+        .zip(mask)
+        .filter(|(_, keep)| **keep)
+        .map(|(item, _)| item.clone())
+        .collect()
+}
+
+/// Extracts an unsigned integer from an `ExternRef` argument, as used for sequence
+/// indices.
+#[inline]
+fn extref_index(value: &Option<ExternRef>) -> Result<usize, RuntimeError> {
+    match extref_value(value)? {
+        // Indices which don't fit in a `usize` are out of range for any sequence.
+        Value::UInt(index) => Ok(usize::try_from(*index).unwrap_or(usize::MAX)),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    }
+}
+
+/// Wraps the provided value in a Clarity `optional`.
+#[inline]
+fn optional_value(value: Option<Value>) -> Result<Value, RuntimeError> {
+    match value {
+        Some(value) => Value::some(value).map_err(|_| RuntimeError::ArgumentTypeMismatch),
+        None => Ok(Value::none()),
+    }
+}
+
+/// Defines the `len` function, returning the number of elements in a sequence.
+#[inline]
+pub fn define_len_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |seq: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let len = match extref_value(&seq)? {
+                Value::Sequence(seq) => seq.len(),
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
             };
 
-            Ok(result)
+            Ok(Some(ExternRef::new(Value::UInt(len as u128))))
         },
     )
 }
 
+/// Defines the `element-at?` function, returning `(some element)` or `none` if the
+/// index is out of range.
 #[inline]
-pub fn get_all_functions(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Vec<FuncMap> {
-    vec![
-        // `add` functions
-        FuncMap::new("add_extref", define_add_extref(&mut store)),
-        FuncMap::new("add_native", define_add_native(&mut store)),
-        FuncMap::new("add_memory", define_add_memory(&mut store)),
-        // `mul` (multiplication) functions
-        FuncMap::new("mul_extref", define_mul_extref(&mut store)),
-        // `fold` functions
-        FuncMap::new("fold_extref", define_fold_extref(&mut store)),
-        FuncMap::new("fold_memory", define_fold_memory(&mut store)),
-    ]
+pub fn define_element_at_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |seq: Option<ExternRef>, index: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let index = extref_index(&index)?;
+            let element = sequence_element(extref_value(&seq)?, index)?;
+
+            Ok(Some(ExternRef::new(optional_value(element)?)))
+        },
+    )
+}
+
+/// Defines the `seq_len` function, returning the length of a sequence as an `i32`.
+/// Together with `seq_get`, this lets generated code iterate over a sequence with a
+/// native index.
+#[inline]
+pub fn define_seq_len_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |seq: Option<ExternRef>| -> wasmtime::Result<i32> {
+            match extref_value(&seq)? {
+                Value::Sequence(seq) => Ok(seq.len() as i32),
+                _ => Err(RuntimeError::ArgumentTypeMismatch.into()),
+            }
+        },
+    )
+}
+
+/// Defines the `seq_get` function, returning the element of a sequence at the given
+/// `i32` index. Unlike `element-at?`, the element isn't wrapped in an optional, and
+/// an index out of range is an error.
+#[inline]
+pub fn define_seq_get_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |seq: Option<ExternRef>, index: i32| -> wasmtime::Result<Option<ExternRef>> {
+            let index = usize::try_from(index).map_err(|_| RuntimeError::IndexOutOfRange)?;
+            let element = sequence_element(extref_value(&seq)?, index)?
+                .ok_or(RuntimeError::IndexOutOfRange)?;
+
+            Ok(Some(ExternRef::new(element)))
+        },
+    )
+}
+
+/// Gets the element of a Clarity sequence at the given index, as yielded by
+/// `sequence_elements`, without splitting the whole sequence.
+#[inline]
+fn sequence_element(seq: &Value, index: usize) -> Result<Option<Value>, RuntimeError> {
+    let element = match seq {
+        Value::Sequence(SequenceData::List(list)) => list.data.get(index).cloned(),
+        Value::Sequence(SequenceData::Buffer(buff)) => buff
+            .data
+            .get(index)
+            .map(|byte| Value::Sequence(SequenceData::Buffer(BuffData { data: vec![*byte] }))),
+        Value::Sequence(SequenceData::String(CharType::ASCII(str))) => {
+            str.data.get(index).map(|byte| {
+                Value::Sequence(SequenceData::String(CharType::ASCII(ASCIIData {
+                    data: vec![*byte],
+                })))
+            })
+        }
+        Value::Sequence(SequenceData::String(CharType::UTF8(str))) => {
+            str.data.get(index).map(|char| {
+                Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data {
+                    data: vec![char.clone()],
+                })))
+            })
+        }
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    Ok(element)
+}
+
+/// Defines the `slice?` function, returning `(some sub-sequence)` for the elements
+/// in `[left, right)`, or `none` if the range is invalid or out of range.
+#[inline]
+pub fn define_slice_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |seq: Option<ExternRef>,
+         left: Option<ExternRef>,
+         right: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let left = extref_index(&left)?;
+            let right = extref_index(&right)?;
+            let seq = extref_value(&seq)?;
+            let len = match seq {
+                Value::Sequence(seq) => seq.len(),
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+            };
+
+            let slice = if left <= right && right <= len {
+                let mask = (0..len).map(|i| i >= left && i < right).collect::<Vec<_>>();
+                Some(retain_sequence(seq, &mask)?)
+            } else {
+                None
+            };
+
+            Ok(Some(ExternRef::new(optional_value(slice)?)))
+        },
+    )
+}
+
+/// Defines the `index-of?` function, returning `(some index)` of the first element
+/// equal to the provided item, or `none` if there is no such element.
+#[inline]
+pub fn define_index_of_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |seq: Option<ExternRef>, item: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let item = extref_value(&item)?;
+            let index = sequence_elements(extref_value(&seq)?)?
+                .iter()
+                .position(|element| element == item)
+                .map(|index| Value::UInt(index as u128));
+
+            Ok(Some(ExternRef::new(optional_value(index)?)))
+        },
+    )
+}
+
+/// Verifies that a buffer or string whose contents take the given number of bytes
+/// doesn't exceed Clarity's maximum value size.
+#[inline]
+fn check_sequence_size(size: usize) -> Result<(), RuntimeError> {
+    if size > MAX_VALUE_SIZE as usize {
+        return Err(RuntimeError::MaxLengthExceeded);
+    }
+    Ok(())
+}
+
+/// Builds a list from the given elements. Clarity checks the size of the list's type
+/// (i.e. its length times the size of its element type), so a list which is too
+/// large fails with `MaxLengthExceeded`, while elements of different types fail with
+/// `ArgumentTypeMismatch`.
+#[inline]
+fn list_from(elements: Vec<Value>) -> Result<Value, RuntimeError> {
+    Value::list_from(elements).map_err(|err| match err {
+        Error::Unchecked(CheckErrors::ValueTooLarge | CheckErrors::ConstructedListTooLarge) => {
+            RuntimeError::MaxLengthExceeded
+        }
+        _ => RuntimeError::ArgumentTypeMismatch,
+    })
+}
+
+/// Concatenates two sequences of the same type.
+#[inline]
+fn concat_sequences(a: &Value, b: &Value) -> Result<Value, RuntimeError> {
+    let result = match (a, b) {
+        (Value::Sequence(SequenceData::List(a)), Value::Sequence(SequenceData::List(b))) => {
+            let data = a.data.iter().chain(b.data.iter()).cloned().collect();
+            list_from(data)?
+        }
+        (Value::Sequence(SequenceData::Buffer(a)), Value::Sequence(SequenceData::Buffer(b))) => {
+            check_sequence_size(a.data.len() + b.data.len())?;
+            Value::Sequence(SequenceData::Buffer(BuffData {
+                data: [a.data.as_slice(), b.data.as_slice()].concat(),
+            }))
+        }
+        (
+            Value::Sequence(SequenceData::String(CharType::ASCII(a))),
+            Value::Sequence(SequenceData::String(CharType::ASCII(b))),
+        ) => {
+            check_sequence_size(a.data.len() + b.data.len())?;
+            Value::Sequence(SequenceData::String(CharType::ASCII(ASCIIData {
+                data: [a.data.as_slice(), b.data.as_slice()].concat(),
+            })))
+        }
+        (
+            Value::Sequence(SequenceData::String(CharType::UTF8(a))),
+            Value::Sequence(SequenceData::String(CharType::UTF8(b))),
+        ) => {
+            // As in the type of a `string-utf8`, each character counts as 4 bytes.
+            check_sequence_size(4 * (a.data.len() + b.data.len()))?;
+            Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data {
+                data: [a.data.as_slice(), b.data.as_slice()].concat(),
+            })))
+        }
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    Ok(result)
+}
+
+/// Defines the `append` function, returning a new list with the provided element
+/// appended to the end.
+#[inline]
+pub fn define_append_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |list: Option<ExternRef>,
+         element: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let list = match extref_value(&list)? {
+                Value::Sequence(SequenceData::List(list)) => list,
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+            };
+            let element = extref_value(&element)?;
+
+            let mut data = list.data.clone();
+            data.push(element.clone());
+
+            // Building the list validates that the element is of the list's type.
+            let result = list_from(data)?;
+
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// The elements of a list being built by `list_push`, e.g. the results of a `map`
+/// lowered as a loop. The elements are pushed in place, so that building a list of
+/// `n` elements doesn't copy it `n` times as `append` would.
+#[derive(Debug, Default)]
+pub struct ListBuilder {
+    elements: Mutex<Vec<Value>>,
+}
+
+/// Defines the `list_push` function, pushing an element onto a `ListBuilder` (or a
+/// new one, if null) and returning the builder.
+#[inline]
+pub fn define_list_push_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |builder: Option<ExternRef>,
+         element: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let element = extref_value(&element)?.clone();
+            let builder = builder.unwrap_or_else(|| ExternRef::new(ListBuilder::default()));
+            builder
+                .data()
+                .downcast_ref::<ListBuilder>()
+                .ok_or(RuntimeError::ArgumentTypeMismatch)?
+                .elements
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(element);
+
+            Ok(Some(builder))
+        },
+    )
+}
+
+/// Defines the `list_build` function, returning a list of the elements pushed onto
+/// a `ListBuilder` (or an empty list, if null).
+#[inline]
+pub fn define_list_build_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |builder: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let elements = match &builder {
+                Some(builder) => std::mem::take(
+                    &mut *builder
+                        .data()
+                        .downcast_ref::<ListBuilder>()
+                        .ok_or(RuntimeError::ArgumentTypeMismatch)?
+                        .elements
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner),
+                ),
+                None => Vec::new(),
+            };
+
+            // Building the list validates that the elements are of the same type.
+            let result = list_from(elements)?;
+
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `concat` function, returning a new sequence containing the elements
+/// of `a` followed by the elements of `b`.
+#[inline]
+pub fn define_concat_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |a: Option<ExternRef>, b: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let result = concat_sequences(extref_value(&a)?, extref_value(&b)?)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `int-to-ascii` function, converting an integer to its decimal
+/// `string-ascii` representation.
+#[inline]
+pub fn define_int_to_ascii_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |value: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let str = integer_to_string(extref_value(&value)?)?;
+            let result = Value::string_ascii_from_bytes(str.into_bytes())
+                .map_err(|_| RuntimeError::FailedToWriteResultToMemory)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `int-to-utf8` function, converting an integer to its decimal
+/// `string-utf8` representation.
+#[inline]
+pub fn define_int_to_utf8_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |value: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let str = integer_to_string(extref_value(&value)?)?;
+            let result = Value::string_utf8_from_bytes(str.into_bytes())
+                .map_err(|_| RuntimeError::FailedToWriteResultToMemory)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `string-to-int?` function, returning `(some int)` or `none` if the
+/// string is not a valid signed integer.
+#[inline]
+pub fn define_string_to_int_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |value: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let str = string_value(extref_value(&value)?)?;
+            let result = str.parse::<i128>().ok().map(Value::Int);
+            Ok(Some(ExternRef::new(optional_value(result)?)))
+        },
+    )
+}
+
+/// Defines the `string-to-uint?` function, returning `(some uint)` or `none` if the
+/// string is not a valid unsigned integer.
+#[inline]
+pub fn define_string_to_uint_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |value: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let str = string_value(extref_value(&value)?)?;
+            let result = str.parse::<u128>().ok().map(Value::UInt);
+            Ok(Some(ExternRef::new(optional_value(result)?)))
+        },
+    )
+}
+
+/// Gets the decimal string representation of an integral Clarity value.
+#[inline]
+fn integer_to_string(value: &Value) -> Result<String, RuntimeError> {
+    match value {
+        Value::Int(i) => Ok(i.to_string()),
+        Value::UInt(u) => Ok(u.to_string()),
+        _ => Err(RuntimeError::FunctionOnlySupportsIntegralValues),
+    }
+}
+
+/// Gets the contents of a `string-ascii` or `string-utf8` Clarity value.
+#[inline]
+fn string_value(value: &Value) -> Result<String, RuntimeError> {
+    let bytes = match value {
+        Value::Sequence(SequenceData::String(CharType::ASCII(str))) => str.data.clone(),
+        Value::Sequence(SequenceData::String(CharType::UTF8(str))) => str.data.concat(),
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    String::from_utf8(bytes).map_err(|_| RuntimeError::ArgumentTypeMismatch)
+}
+
+/// A hash function as used by Clarity's hashing builtins.
+type HashFn = fn(&[u8]) -> Vec<u8>;
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256Sum::from_data(data).as_bytes().to_vec()
+}
+
+fn sha512(data: &[u8]) -> Vec<u8> {
+    Sha512Sum::from_data(data).as_bytes().to_vec()
+}
+
+fn sha512_256(data: &[u8]) -> Vec<u8> {
+    Sha512Trunc256Sum::from_data(data).as_bytes().to_vec()
+}
+
+fn hash160(data: &[u8]) -> Vec<u8> {
+    Hash160::from_data(data).as_bytes().to_vec()
+}
+
+fn keccak256(data: &[u8]) -> Vec<u8> {
+    Keccak256Hash::from_data(data).as_bytes().to_vec()
+}
+
+/// Hashes the provided Clarity value. As in Clarity, buffers are hashed as-is while
+/// integers are hashed using their 16-byte little-endian representation.
+#[inline]
+fn hash_value(value: &Value, hash: HashFn) -> Result<Value, RuntimeError> {
+    let digest = match value {
+        Value::Sequence(SequenceData::Buffer(buff)) => hash(&buff.data),
+        Value::Int(i) => hash(&i.to_le_bytes()),
+        Value::UInt(u) => hash(&u.to_le_bytes()),
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    Ok(Value::Sequence(SequenceData::Buffer(BuffData {
+        data: digest,
+    })))
+}
+
+/// Defines a hashing function taking and returning `ExternRef`s.
+#[inline]
+fn define_hash_extref(mut store: impl AsContextMut, hash: HashFn) -> Func {
+    Func::wrap(
+        &mut store,
+        move |value: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let result = hash_value(extref_value(&value)?, hash)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines a hashing function which reads its input from, and writes the resulting
+/// buffer to, the `vm_mem` exported memory.
+#[inline]
+fn define_hash_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+    hash: HashFn,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |mut caller: Caller<'_, ClarityWasmContext>, ptr: i32, len: i32| -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+
+            let result = read_value_from_data(memory.data(&caller), ptr, len)
+                .and_then(|value| hash_value(&value, hash));
+
+            match result {
+                Ok(result) => write_result_to_memory(&mut caller, &result),
+                Err(e) => FuncResult::err(e),
+            }
+        },
+    )
+}
+
+/// Defines the `sha256` function.
+#[inline]
+pub fn define_sha256_extref(store: impl AsContextMut) -> Func {
+    define_hash_extref(store, sha256)
+}
+
+/// Defines the `sha256` function for the memory convention.
+#[inline]
+pub fn define_sha256_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_hash_memory(store, sha256)
+}
+
+/// Defines the `sha512` function.
+#[inline]
+pub fn define_sha512_extref(store: impl AsContextMut) -> Func {
+    define_hash_extref(store, sha512)
+}
+
+/// Defines the `sha512` function for the memory convention.
+#[inline]
+pub fn define_sha512_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_hash_memory(store, sha512)
+}
+
+/// Defines the `sha512/256` function.
+#[inline]
+pub fn define_sha512_256_extref(store: impl AsContextMut) -> Func {
+    define_hash_extref(store, sha512_256)
+}
+
+/// Defines the `sha512/256` function for the memory convention.
+#[inline]
+pub fn define_sha512_256_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_hash_memory(store, sha512_256)
+}
+
+/// Defines the `hash160` function.
+#[inline]
+pub fn define_hash160_extref(store: impl AsContextMut) -> Func {
+    define_hash_extref(store, hash160)
+}
+
+/// Defines the `hash160` function for the memory convention.
+#[inline]
+pub fn define_hash160_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_hash_memory(store, hash160)
+}
+
+/// Defines the `keccak256` function.
+#[inline]
+pub fn define_keccak256_extref(store: impl AsContextMut) -> Func {
+    define_hash_extref(store, keccak256)
+}
+
+/// Defines the `keccak256` function for the memory convention.
+#[inline]
+pub fn define_keccak256_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_hash_memory(store, keccak256)
+}
+
+/// Extracts the bytes of a buffer from an `ExternRef` argument.
+#[inline]
+fn extref_buffer(value: &Option<ExternRef>) -> Result<&[u8], RuntimeError> {
+    value_buffer(extref_value(value)?)
+}
+
+/// Gets the bytes of a Clarity buffer value.
+#[inline]
+fn value_buffer(value: &Value) -> Result<&[u8], RuntimeError> {
+    match value {
+        Value::Sequence(SequenceData::Buffer(buff)) => Ok(&buff.data),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    }
+}
+
+/// Recovers the public key used to sign the given 32-byte message hash. As in
+/// Clarity, `(err u2)` is returned for malformed signatures and `(err u1)` if
+/// recovery fails.
+fn secp256k1_recover_value(message: &[u8], signature: &[u8]) -> Result<Value, RuntimeError> {
+    if message.len() != 32 || signature.len() > 65 {
+        return Err(RuntimeError::ArgumentTypeMismatch);
+    }
+
+    if signature.len() < 65 || signature[64] > 3 {
+        Value::error(Value::UInt(2))
+    } else {
+        match secp256k1_recover(message, signature) {
+            Ok(pubkey) => Value::okay(Value::Sequence(SequenceData::Buffer(BuffData {
+                data: pubkey.to_vec(),
+            }))),
+            Err(_) => Value::error(Value::UInt(1)),
+        }
+    }
+    .map_err(|_| RuntimeError::ArgumentTypeMismatch)
+}
+
+/// Checks that the signature of the given 32-byte message hash was produced by the
+/// given public key.
+fn secp256k1_verify_value(
+    message: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+) -> Result<Value, RuntimeError> {
+    if message.len() != 32 || signature.len() > 65 || pubkey.len() != 33 {
+        return Err(RuntimeError::ArgumentTypeMismatch);
+    }
+
+    // Signatures may optionally include the recovery id, which must be valid.
+    let valid = match signature.len() {
+        64 => secp256k1_verify(message, signature, pubkey).is_ok(),
+        65 if signature[64] <= 3 => secp256k1_verify(message, signature, pubkey).is_ok(),
+        _ => false,
+    };
+
+    Ok(Value::Bool(valid))
+}
+
+/// Defines the `secp256k1-recover?` function, which recovers the public key used to
+/// sign the given 32-byte message hash. As in Clarity, `(err u2)` is returned for
+/// malformed signatures and `(err u1)` if recovery fails.
+#[inline]
+pub fn define_secp256k1_recover_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |message: Option<ExternRef>,
+         signature: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let result =
+                secp256k1_recover_value(extref_buffer(&message)?, extref_buffer(&signature)?)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `secp256k1-recover?` function for the memory convention.
+#[inline]
+pub fn define_secp256k1_recover_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         message_ptr: i32,
+         message_len: i32,
+         signature_ptr: i32,
+         signature_len: i32|
+         -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+
+            let data = memory.data(&caller);
+            let result = read_value_from_data(data, message_ptr, message_len).and_then(|message| {
+                let signature = read_value_from_data(data, signature_ptr, signature_len)?;
+                secp256k1_recover_value(value_buffer(&message)?, value_buffer(&signature)?)
+            });
+
+            match result {
+                Ok(result) => write_result_to_memory(&mut caller, &result),
+                Err(e) => FuncResult::err(e),
+            }
+        },
+    )
+}
+
+/// Defines the `secp256k1-verify` function, which checks that the signature of the
+/// given 32-byte message hash was produced by the given public key.
+#[inline]
+pub fn define_secp256k1_verify_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |message: Option<ExternRef>,
+         signature: Option<ExternRef>,
+         pubkey: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let result = secp256k1_verify_value(
+                extref_buffer(&message)?,
+                extref_buffer(&signature)?,
+                extref_buffer(&pubkey)?,
+            )?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `secp256k1-verify` function for the memory convention.
+#[inline]
+pub fn define_secp256k1_verify_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         message_ptr: i32,
+         message_len: i32,
+         signature_ptr: i32,
+         signature_len: i32,
+         pubkey_ptr: i32,
+         pubkey_len: i32|
+         -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+
+            let data = memory.data(&caller);
+            let result = read_value_from_data(data, message_ptr, message_len).and_then(|message| {
+                let signature = read_value_from_data(data, signature_ptr, signature_len)?;
+                let pubkey = read_value_from_data(data, pubkey_ptr, pubkey_len)?;
+                secp256k1_verify_value(
+                    value_buffer(&message)?,
+                    value_buffer(&signature)?,
+                    value_buffer(&pubkey)?,
+                )
+            });
+
+            match result {
+                Ok(result) => write_result_to_memory(&mut caller, &result),
+                Err(e) => FuncResult::err(e),
+            }
+        },
+    )
+}
+
+/// Address versions for single- and multi-signature principals on each network.
+const ADDRESS_VERSION_MAINNET_SINGLESIG: u8 = 22;
+const ADDRESS_VERSION_MAINNET_MULTISIG: u8 = 20;
+const ADDRESS_VERSION_TESTNET_SINGLESIG: u8 = 26;
+const ADDRESS_VERSION_TESTNET_MULTISIG: u8 = 21;
+
+/// Gets whether or not the provided address version belongs to the given network.
+#[inline]
+fn version_matches_network(version: u8, mainnet: bool) -> bool {
+    if mainnet {
+        version == ADDRESS_VERSION_MAINNET_SINGLESIG || version == ADDRESS_VERSION_MAINNET_MULTISIG
+    } else {
+        version == ADDRESS_VERSION_TESTNET_SINGLESIG || version == ADDRESS_VERSION_TESTNET_MULTISIG
+    }
+}
+
+/// Creates a Clarity buffer value from the provided bytes.
+#[inline]
+fn buffer_value(data: Vec<u8>) -> Value {
+    Value::Sequence(SequenceData::Buffer(BuffData { data }))
+}
+
+/// Implements `principal-of?`, deriving the single-signature principal for the given
+/// compressed public key on the context's network. Returns `(err u1)` if the public
+/// key is invalid.
+#[inline]
+fn principal_of(context: &ClarityWasmContext, pubkey: &Value) -> Result<Value, RuntimeError> {
+    let pubkey = match pubkey {
+        Value::Sequence(SequenceData::Buffer(buff)) if buff.data.len() == 33 => &buff.data,
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    let result = if Secp256k1PublicKey::from_slice(pubkey).is_ok() {
+        // Clarity 1 always derives testnet principals.
+        let version =
+            if context.is_mainnet() && context.clarity_version() > ClarityVersion::Clarity1 {
+                ADDRESS_VERSION_MAINNET_SINGLESIG
+            } else {
+                ADDRESS_VERSION_TESTNET_SINGLESIG
+            };
+        let principal = StandardPrincipalData(version, Hash160::from_data(pubkey).0);
+        Value::okay(Value::Principal(PrincipalData::Standard(principal)))
+    } else {
+        Value::error(Value::UInt(1))
+    };
+
+    result.map_err(|_| RuntimeError::ArgumentTypeMismatch)
+}
+
+/// Implements `principal-destruct?`, splitting a principal into its version, hash
+/// bytes and (optional) contract name. The tuple is wrapped in `ok` if the version
+/// matches the context's network, and in `err` otherwise.
+#[inline]
+fn principal_destruct(
+    context: &ClarityWasmContext,
+    principal: &Value,
+) -> Result<Value, RuntimeError> {
+    let (issuer, name) = match principal {
+        Value::Principal(PrincipalData::Standard(issuer)) => (issuer, None),
+        Value::Principal(PrincipalData::Contract(contract)) => {
+            (&contract.issuer, Some(contract.name.to_string()))
+        }
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    let name = match name {
+        Some(name) => Some(
+            Value::string_ascii_from_bytes(name.into_bytes())
+                .map_err(|_| RuntimeError::ArgumentTypeMismatch)?,
+        ),
+        None => None,
+    };
+    let name = optional_value(name)?;
+
+    let tuple = TupleData::from_data(vec![
+        ("version".into(), buffer_value(vec![issuer.0])),
+        ("hash-bytes".into(), buffer_value(issuer.1.to_vec())),
+        ("name".into(), name),
+    ])
+    .map_err(|_| RuntimeError::ArgumentTypeMismatch)?;
+
+    let result = if version_matches_network(issuer.0, context.is_mainnet()) {
+        Value::okay(Value::Tuple(tuple))
+    } else {
+        Value::error(Value::Tuple(tuple))
+    };
+
+    result.map_err(|_| RuntimeError::ArgumentTypeMismatch)
+}
+
+/// Creates the `(err { error_code, value })` response returned by a failed
+/// `principal-construct?`.
+#[inline]
+fn principal_construct_error(
+    error_code: u128,
+    value: Option<Value>,
+) -> Result<Value, RuntimeError> {
+    let tuple = TupleData::from_data(vec![
+        ("error_code".into(), Value::UInt(error_code)),
+        ("value".into(), optional_value(value)?),
+    ])
+    .map_err(|_| RuntimeError::ArgumentTypeMismatch)?;
+
+    Value::error(Value::Tuple(tuple)).map_err(|_| RuntimeError::ArgumentTypeMismatch)
+}
+
+/// Implements `principal-construct?`. As in Clarity, malformed buffers result in
+/// error code `u1`, an invalid contract name in error code `u2`, and a version which
+/// does not match the context's network in error code `u0` (with the principal).
+#[inline]
+fn principal_construct(
+    context: &ClarityWasmContext,
+    version: &Value,
+    hash_bytes: &Value,
+    name: Option<&Value>,
+) -> Result<Value, RuntimeError> {
+    let (version, hash_bytes) = match (version, hash_bytes) {
+        (
+            Value::Sequence(SequenceData::Buffer(version)),
+            Value::Sequence(SequenceData::Buffer(hash_bytes)),
+        ) => (&version.data, &hash_bytes.data),
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    if version.len() != 1 || version[0] >= 32 || hash_bytes.len() != 20 {
+        return principal_construct_error(1, None);
+    }
+
+    let mut data = [0u8; 20];
+    data.copy_from_slice(hash_bytes);
+    let issuer = StandardPrincipalData(version[0], data);
+
+    let principal = match name {
+        None => PrincipalData::Standard(issuer),
+        Some(Value::Sequence(SequenceData::String(CharType::ASCII(name)))) => {
+            let name = match String::from_utf8(name.data.clone())
+                .ok()
+                .and_then(|name| ContractName::try_from(name).ok())
+            {
+                Some(name) => name,
+                None => return principal_construct_error(2, None),
+            };
+            PrincipalData::Contract(QualifiedContractIdentifier::new(issuer, name))
+        }
+        Some(_) => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+
+    if !version_matches_network(version[0], context.is_mainnet()) {
+        return principal_construct_error(0, Some(Value::Principal(principal)));
+    }
+
+    Value::okay(Value::Principal(principal)).map_err(|_| RuntimeError::ArgumentTypeMismatch)
+}
+
+/// Defines the `principal-of?` function.
+#[inline]
+pub fn define_principal_of_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         pubkey: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let result = principal_of(caller.data(), extref_value(&pubkey)?)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `principal-destruct?` function.
+#[inline]
+pub fn define_principal_destruct_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         principal: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let result = principal_destruct(caller.data(), extref_value(&principal)?)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines the `principal-construct?` function. The contract name is optional, so a
+/// null `ExternRef` may be passed to construct a standard principal.
+#[inline]
+pub fn define_principal_construct_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         version: Option<ExternRef>,
+         hash_bytes: Option<ExternRef>,
+         name: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let name = match &name {
+                Some(_) => Some(extref_value(&name)?),
+                None => None,
+            };
+            let result = principal_construct(
+                caller.data(),
+                extref_value(&version)?,
+                extref_value(&hash_bytes)?,
+                name,
+            )?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Defines a function taking a single serialized value in memory which applies the
+/// provided operation to it and writes the serialized result back to memory.
+#[inline]
+fn define_context_op_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+    op: fn(&ClarityWasmContext, &Value) -> Result<Value, RuntimeError>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |mut caller: Caller<'_, ClarityWasmContext>, ptr: i32, len: i32| -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+
+            let result = read_value_from_data(memory.data(&caller), ptr, len)
+                .and_then(|value| op(caller.data(), &value));
+
+            match result {
+                Ok(result) => write_result_to_memory(&mut caller, &result),
+                Err(e) => FuncResult::err(e),
+            }
+        },
+    )
+}
+
+/// Defines the `principal-of?` function for the memory convention.
+#[inline]
+pub fn define_principal_of_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_context_op_memory(store, principal_of)
+}
+
+/// Defines the `principal-destruct?` function for the memory convention.
+#[inline]
+pub fn define_principal_destruct_memory(
+    store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    define_context_op_memory(store, principal_destruct)
+}
+
+/// Defines the `principal-construct?` function for the memory convention. A zero
+/// `name_len` indicates that no contract name was provided.
+#[inline]
+pub fn define_principal_construct_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         version_ptr: i32,
+         version_len: i32,
+         hash_bytes_ptr: i32,
+         hash_bytes_len: i32,
+         name_ptr: i32,
+         name_len: i32|
+         -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+            let data = memory.data(&caller);
+
+            let name = match name_len {
+                0 => Ok(None),
+                _ => read_value_from_data(data, name_ptr, name_len).map(Some),
+            };
+            let result = read_value_from_data(data, version_ptr, version_len).and_then(|version| {
+                let hash_bytes = read_value_from_data(data, hash_bytes_ptr, hash_bytes_len)?;
+                principal_construct(caller.data(), &version, &hash_bytes, name?.as_ref())
+            });
+
+            match result {
+                Ok(result) => write_result_to_memory(&mut caller, &result),
+                Err(e) => FuncResult::err(e),
+            }
+        },
+    )
+}
+
+/// Defines a function taking a single `ExternRef` argument which applies the
+/// provided operation to the referenced Clarity value.
+#[inline]
+fn define_value_op_extref(
+    mut store: impl AsContextMut,
+    op: fn(&Value) -> Result<Value, RuntimeError>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |value: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let result = op(extref_value(&value)?)?;
+            Ok(Some(ExternRef::new(result)))
+        },
+    )
+}
+
+/// Gets whether the provided optional value is a `(some ...)` (`Ok(true)`) or `none`
+/// (`Ok(false)`), failing with `ArgumentTypeMismatch` if it isn't an optional.
+#[inline]
+fn is_some(value: &Value) -> Result<bool, RuntimeError> {
+    match value {
+        Value::Optional(opt) => Ok(opt.data.is_some()),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    }
+}
+
+/// Gets whether the provided response value is an `(ok ...)`.
+#[inline]
+fn is_ok(value: &Value) -> Result<bool, RuntimeError> {
+    match value {
+        Value::Response(res) => Ok(res.committed),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    }
+}
+
+/// Defines the `is-some` function.
+#[inline]
+pub fn define_is_some_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| is_some(value).map(Value::Bool))
+}
+
+/// Defines the `is-none` function.
+#[inline]
+pub fn define_is_none_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| is_some(value).map(|b| Value::Bool(!b)))
+}
+
+/// Defines the `is-ok` function.
+#[inline]
+pub fn define_is_ok_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| is_ok(value).map(Value::Bool))
+}
+
+/// Defines the `is-err` function.
+#[inline]
+pub fn define_is_err_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| is_ok(value).map(|b| Value::Bool(!b)))
+}
+
+/// Defines a helper used by generated code to branch on a `bool`, returning `1` for
+/// `true` and `0` for `false`.
+#[inline]
+pub fn define_to_bool_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |value: Option<ExternRef>| -> wasmtime::Result<i32> {
+            match extref_value(&value)? {
+                Value::Bool(b) => Ok(*b as i32),
+                _ => Err(RuntimeError::ArgumentTypeMismatch.into()),
+            }
+        },
+    )
+}
+
+/// Defines a helper used by generated code to branch on whether an optional or a
+/// response holds a value (i.e. is a `(some ...)` or an `(ok ...)`), returning `1`
+/// if it does and `0` otherwise.
+#[inline]
+pub fn define_is_success_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |value: Option<ExternRef>| -> wasmtime::Result<i32> {
+            let value = extref_value(&value)?;
+            let success = match value {
+                Value::Optional(_) => is_some(value)?,
+                _ => is_ok(value)?,
+            };
+            Ok(success as i32)
+        },
+    )
+}
+
+/// Defines the `unwrap` function, returning the inner value of a `(some ...)` or
+/// `(ok ...)`. Generated code is expected to branch on `is-none`/`is-err` to handle
+/// the thrown value of `unwrap!` before calling this function; unwrapping a `none`
+/// or `(err ...)` results in a `RuntimeError::UnwrapFailed` trap.
+#[inline]
+pub fn define_unwrap_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| match value {
+        Value::Optional(opt) => opt
+            .data
+            .as_deref()
+            .cloned()
+            .ok_or(RuntimeError::UnwrapFailed),
+        Value::Response(res) if res.committed => Ok(*res.data.clone()),
+        Value::Response(_) => Err(RuntimeError::UnwrapFailed),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    })
+}
+
+/// Defines the `unwrap-err` function, returning the inner value of an `(err ...)`.
+/// Unwrapping an `(ok ...)` results in a `RuntimeError::UnwrapFailed` trap.
+#[inline]
+pub fn define_unwrap_err_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| match value {
+        Value::Response(res) if !res.committed => Ok(*res.data.clone()),
+        Value::Response(_) => Err(RuntimeError::UnwrapFailed),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    })
+}
+
+/// Defines the `ok` function, wrapping the provided value in an `(ok ...)`.
+#[inline]
+pub fn define_ok_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        Value::okay(value.clone()).map_err(|_| RuntimeError::ArgumentTypeMismatch)
+    })
+}
+
+/// Defines the `err` function, wrapping the provided value in an `(err ...)`.
+#[inline]
+pub fn define_err_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        Value::error(value.clone()).map_err(|_| RuntimeError::ArgumentTypeMismatch)
+    })
+}
+
+/// Defines the `some` function, wrapping the provided value in a `(some ...)`.
+#[inline]
+pub fn define_some_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| optional_value(Some(value.clone())))
+}
+
+/// Defines the `get` function for tuples. The field name is passed as an id which
+/// was interned using `ClarityWasmContext::names_mut()`. There is no `_native`
+/// variant, as the `RustRefStack` convention only passes `int`s on the Wasm stack
+/// and so can't represent a tuple.
+#[inline]
+pub fn define_tuple_get_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         tuple: Option<ExternRef>,
+         name_id: i32|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let tuple = match extref_value(&tuple)? {
+                Value::Tuple(tuple) => tuple,
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+            };
+            let name = caller
+                .data()
+                .names()
+                .get(name_id)
+                .ok_or(RuntimeError::NameNotFound)?;
+            let value = tuple
+                .get(name.as_str())
+                .map_err(|_| RuntimeError::NameNotFound)?
+                .clone();
+
+            Ok(Some(ExternRef::new(value)))
+        },
+    )
+}
+
+/// Defines the `merge` function, returning a tuple containing the fields of both
+/// tuples. Fields in `b` override fields of the same name in `a`. As with
+/// `tuple_get_extref`, there is no `_native` variant.
+#[inline]
+pub fn define_merge_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |a: Option<ExternRef>, b: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let (a, b) = match (extref_value(&a)?, extref_value(&b)?) {
+                (Value::Tuple(a), Value::Tuple(b)) => (a.clone(), b.clone()),
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+            };
+            let merged =
+                TupleData::shallow_merge(a, b).map_err(|_| RuntimeError::ArgumentTypeMismatch)?;
+
+            Ok(Some(ExternRef::new(Value::Tuple(merged))))
+        },
+    )
+}
+
+/// Converts a `uint` to an `int`, failing if the value is larger than `i128::MAX`.
+#[inline]
+fn to_int(value: u128) -> Result<i128, RuntimeError> {
+    i128::try_from(value).map_err(|_| RuntimeError::ArithmeticOverflow)
+}
+
+/// Converts an `int` to a `uint`, failing if the value is negative.
+#[inline]
+fn to_uint(value: i128) -> Result<u128, RuntimeError> {
+    u128::try_from(value).map_err(|_| RuntimeError::ArithmeticUnderflow)
+}
+
+/// Defines the `to-int` function.
+#[inline]
+pub fn define_to_int_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| match value {
+        Value::UInt(u) => to_int(*u).map(Value::Int),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    })
+}
+
+/// Defines the `to-uint` function.
+#[inline]
+pub fn define_to_uint_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| match value {
+        Value::Int(i) => to_uint(*i).map(Value::UInt),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    })
+}
+
+/// Defines the `to-int` function for native types. The `uint` is passed, and the
+/// resulting `int` returned, as a low/high i64 pair.
+#[inline]
+pub fn define_to_int_native(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |low: i64, high: i64| -> wasmtime::Result<(i64, i64)> {
+            let value = ((high as u64) as u128) << 64 | ((low as u64) as u128);
+            let result = to_int(value)?;
+
+            Ok((
+                (result & 0xFFFFFFFFFFFFFFFF) as i64,
+                ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
+            ))
+        },
+    )
+}
+
+/// Defines the `to-uint` function for native types. The `int` is passed, and the
+/// resulting `uint` returned, as a low/high i64 pair.
+#[inline]
+pub fn define_to_uint_native(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |low: i64, high: i64| -> wasmtime::Result<(i64, i64)> {
+            let value = (((high as u64) as u128) << 64 | ((low as u64) as u128)) as i128;
+            let result = to_uint(value)?;
+
+            Ok((
+                (result & 0xFFFFFFFFFFFFFFFF) as i64,
+                ((result >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
+            ))
+        },
+    )
+}
+
+/// Converts a buffer of up to 16 bytes to an integer, as per `buff-to-int-*` and
+/// `buff-to-uint-*`.
+#[inline]
+fn buffer_to_integer(value: &Value, order: ByteOrder, signed: bool) -> Result<Value, RuntimeError> {
+    let bytes = match value {
+        Value::Sequence(SequenceData::Buffer(buff)) => &buff.data,
+        _ => return Err(RuntimeError::ArgumentTypeMismatch),
+    };
+    let value = u128_from_bytes(bytes, order).map_err(|_| RuntimeError::ArgumentTypeMismatch)?;
+
+    if signed {
+        Ok(Value::Int(value as i128))
+    } else {
+        Ok(Value::UInt(value))
+    }
+}
+
+/// Converts an integer to its 16-byte buffer representation.
+#[inline]
+fn integer_to_buffer(value: &Value, order: ByteOrder) -> Result<Value, RuntimeError> {
+    let value = match value {
+        Value::Int(i) => *i as u128,
+        Value::UInt(u) => *u,
+        _ => return Err(RuntimeError::FunctionOnlySupportsIntegralValues),
+    };
+
+    Ok(buffer_value(u128_to_bytes(value, order).to_vec()))
+}
+
+/// Defines the `buff-to-int-le` function.
+#[inline]
+pub fn define_buff_to_int_le_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        buffer_to_integer(value, ByteOrder::LittleEndian, true)
+    })
+}
+
+/// Defines the `buff-to-int-be` function.
+#[inline]
+pub fn define_buff_to_int_be_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        buffer_to_integer(value, ByteOrder::BigEndian, true)
+    })
+}
+
+/// Defines the `buff-to-uint-le` function.
+#[inline]
+pub fn define_buff_to_uint_le_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        buffer_to_integer(value, ByteOrder::LittleEndian, false)
+    })
+}
+
+/// Defines the `buff-to-uint-be` function.
+#[inline]
+pub fn define_buff_to_uint_be_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        buffer_to_integer(value, ByteOrder::BigEndian, false)
+    })
+}
+
+/// Defines a function converting an `int` or `uint` to its 16-byte little-endian
+/// buffer representation, the inverse of `buff-to-int-le`/`buff-to-uint-le`.
+#[inline]
+pub fn define_int_to_buff_le_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        integer_to_buffer(value, ByteOrder::LittleEndian)
+    })
+}
+
+/// Defines a function converting an `int` or `uint` to its 16-byte big-endian
+/// buffer representation, the inverse of `buff-to-int-be`/`buff-to-uint-be`.
+#[inline]
+pub fn define_int_to_buff_be_extref(store: impl AsContextMut) -> Func {
+    define_value_op_extref(store, |value| {
+        integer_to_buffer(value, ByteOrder::BigEndian)
+    })
+}
+
+/// Resolves the map with the given interned name id in the current contract,
+/// returning the contract id and map name which address its entries in the
+/// datastore.
+#[inline]
+fn map_location(
+    context: &ClarityWasmContext,
+    map_id: i32,
+) -> Result<(QualifiedContractIdentifier, String), RuntimeError> {
+    let contract_id = context
+        .contract_id()
+        .ok_or(RuntimeError::ContractNotFound)?
+        .clone();
+    let map_name = context
+        .names()
+        .get(map_id)
+        .ok_or(RuntimeError::NameNotFound)?
+        .to_string();
+
+    Ok((contract_id, map_name))
+}
+
+/// Fails with `WriteInReadOnlyContext` if the datastore may not currently be
+/// written to.
+#[inline]
+fn check_writable(context: &ClarityWasmContext) -> Result<(), RuntimeError> {
+    if context.is_read_only() {
+        return Err(RuntimeError::WriteInReadOnlyContext);
+    }
+    Ok(())
+}
+
+/// Serializes a value into the provided buffer, replacing its contents. Used with
+/// `ClarityWasmContext::with_scratch()` to serialize keys without allocating.
+#[inline]
+fn serialize_into_buffer(value: &Value, buffer: &mut Vec<u8>) -> Result<(), RuntimeError> {
+    buffer.clear();
+    buffer.resize(serialized_size(value), 0);
+    serialize_clarity_value_into(value, buffer)
+        .map(|_| ())
+        .map_err(|_| RuntimeError::FailedToSerializeValue)
+}
+
+/// Serializes a value so that it can be written to the datastore, allocating
+/// exactly as much as it needs.
+#[inline]
+fn serialize_stored_value(value: &Value) -> Result<Vec<u8>, RuntimeError> {
+    let mut bytes = Vec::new();
+    serialize_into_buffer(value, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Defines the `map-get?` function, returning `(some value)` if the map contains
+/// an entry for the given key, or `none` otherwise. The map name is passed as an
+/// id which was interned using `ClarityWasmContext::names_mut()`.
+#[inline]
+pub fn define_map_get_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         map_id: i32,
+         key: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let key = extref_value(&key)?;
+            let value = caller.data_mut().with_scratch(|context, key_bytes| {
+                let (contract_id, map_name) = map_location(context, map_id)?;
+                serialize_into_buffer(key, key_bytes)?;
+                context
+                    .datastore()
+                    .get_map_entry(&contract_id, &map_name, key_bytes)
+                    .map(|bytes| deserialize_clarity_value(&bytes))
+                    .transpose()
+                    .map_err(|_| RuntimeError::FailedToDeserializeStoredValue)
+            })?;
+
+            Ok(Some(ExternRef::new(optional_value(value)?)))
+        },
+    )
+}
+
+/// Defines the `map-set` function, storing the value under the given key whether
+/// or not an entry already exists. Always returns `true`.
+#[inline]
+pub fn define_map_set_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         map_id: i32,
+         key: Option<ExternRef>,
+         value: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            check_writable(caller.data())?;
+            let (contract_id, map_name) = map_location(caller.data(), map_id)?;
+            let key = serialize_stored_value(extref_value(&key)?)?;
+            let value = serialize_stored_value(extref_value(&value)?)?;
+            caller
+                .data_mut()
+                .datastore_mut()
+                .set_map_entry(&contract_id, &map_name, key, value);
+
+            Ok(Some(ExternRef::new(Value::Bool(true))))
+        },
+    )
+}
+
+/// Defines the `map-insert` function, storing the value under the given key only
+/// if no entry exists yet. Returns whether or not the value was inserted.
+#[inline]
+pub fn define_map_insert_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         map_id: i32,
+         key: Option<ExternRef>,
+         value: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            check_writable(caller.data())?;
+            let key = extref_value(&key)?;
+            let value = extref_value(&value)?;
+            let inserted = caller.data_mut().with_scratch(|context, key_bytes| {
+                let (contract_id, map_name) = map_location(context, map_id)?;
+                serialize_into_buffer(key, key_bytes)?;
+                let datastore = context.datastore_mut();
+                if datastore
+                    .get_map_entry(&contract_id, &map_name, key_bytes)
+                    .is_some()
+                {
+                    return Ok::<_, RuntimeError>(false);
+                }
+
+                // Only an inserted key needs to be owned by the datastore.
+                let value = serialize_stored_value(value)?;
+                datastore.set_map_entry(&contract_id, &map_name, key_bytes.clone(), value);
+                Ok(true)
+            })?;
+
+            Ok(Some(ExternRef::new(Value::Bool(inserted))))
+        },
+    )
+}
+
+/// Defines the `map-delete` function, removing the entry stored under the given
+/// key. Returns whether or not an entry was removed.
+#[inline]
+pub fn define_map_delete_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         map_id: i32,
+         key: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            check_writable(caller.data())?;
+            let key = extref_value(&key)?;
+            let removed = caller.data_mut().with_scratch(|context, key_bytes| {
+                let (contract_id, map_name) = map_location(context, map_id)?;
+                serialize_into_buffer(key, key_bytes)?;
+                Ok::<_, RuntimeError>(
+                    context
+                        .datastore_mut()
+                        .delete_map_entry(&contract_id, &map_name, key_bytes)
+                        .is_some(),
+                )
+            })?;
+
+            Ok(Some(ExternRef::new(Value::Bool(removed))))
+        },
+    )
+}
+
+/// Resolves the data variable with the given interned name id in the current
+/// contract, returning the contract id, the variable name and its declared type.
+#[inline]
+fn data_var(
+    context: &ClarityWasmContext,
+    name_id: i32,
+) -> Result<(QualifiedContractIdentifier, String, TypeSignature), RuntimeError> {
+    let contract_id = context
+        .contract_id()
+        .ok_or(RuntimeError::ContractNotFound)?
+        .clone();
+    let name = context
+        .names()
+        .get(name_id)
+        .ok_or(RuntimeError::NameNotFound)?
+        .to_string();
+    let type_signature = context
+        .data_var_type(&contract_id, name_id)
+        .ok_or(RuntimeError::DataVarNotDeclared)?
+        .clone();
+
+    Ok((contract_id, name, type_signature))
+}
+
+/// Defines the `var-get` function. The variable name is passed as an id which was
+/// returned by `ClarityWasmContext::declare_data_var()`.
+#[inline]
+pub fn define_var_get_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         name_id: i32|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let context = caller.data();
+            let (contract_id, name, _) = data_var(context, name_id)?;
+            let bytes = context
+                .datastore()
+                .get_data_var(&contract_id, &name)
+                .ok_or(RuntimeError::DataVarNotDeclared)?;
+            let value = deserialize_clarity_value(&bytes)
+                .map_err(|_| RuntimeError::FailedToDeserializeStoredValue)?;
+
+            Ok(Some(ExternRef::new(value)))
+        },
+    )
+}
+
+/// Defines the `var-set` function, failing if the value isn't admitted by the
+/// variable's declared type or if the current call is read-only. Always returns
+/// `true`.
+#[inline]
+pub fn define_var_set_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         name_id: i32,
+         value: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            check_writable(caller.data())?;
+            let (contract_id, name, type_signature) = data_var(caller.data(), name_id)?;
+            let value = extref_value(&value)?;
+            if !type_signature.admits(value) {
+                return Err(RuntimeError::ArgumentTypeMismatch.into());
+            }
+
+            let value = serialize_stored_value(value)?;
+            caller
+                .data_mut()
+                .datastore_mut()
+                .set_data_var(&contract_id, &name, value);
+
+            Ok(Some(ExternRef::new(Value::Bool(true))))
+        },
+    )
+}
+
+/// Reads a block height argument, which must be a `uint`.
+#[inline]
+fn block_height_arg(value: &Option<ExternRef>) -> Result<u32, RuntimeError> {
+    match extref_value(value)? {
+        Value::UInt(height) => Ok(u32::try_from(*height).unwrap_or(u32::MAX)),
+        _ => Err(RuntimeError::ArgumentTypeMismatch),
+    }
+}
+
+/// Gets the name of the property with the given interned name id.
+#[inline]
+fn property_name(context: &ClarityWasmContext, property_id: i32) -> Result<&str, RuntimeError> {
+    context
+        .names()
+        .get(property_id)
+        .map(|name| name.as_str())
+        .ok_or(RuntimeError::NameNotFound)
+}
+
+/// Defines the `get-block-info?` function. The property name is passed as an id
+/// which was interned using `ClarityWasmContext::names_mut()`. Returns `none` for
+/// blocks at or above the current block height, or which the context's
+/// `BlockInfoProvider` doesn't know about.
+#[inline]
+pub fn define_get_block_info_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         property_id: i32,
+         height: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let context = caller.data();
+            let property = BlockInfoProperty::lookup_by_name(property_name(context, property_id)?)
+                .ok_or(RuntimeError::NameNotFound)?;
+            let height = block_height_arg(&height)?;
+
+            let info = if height < context.block_height() {
+                context.block_info().get_block_info(height, property)
+            } else {
+                None
+            };
+
+            Ok(Some(ExternRef::new(optional_value(info)?)))
+        },
+    )
+}
+
+/// Defines the `get-burn-block-info?` function. The property name is passed as an
+/// id which was interned using `ClarityWasmContext::names_mut()`. Returns `none`
+/// for blocks which the context's `BlockInfoProvider` doesn't know about.
+#[inline]
+pub fn define_get_burn_block_info_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>,
+         property_id: i32,
+         height: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let context = caller.data();
+            let property =
+                BurnBlockInfoProperty::lookup_by_name(property_name(context, property_id)?)
+                    .ok_or(RuntimeError::NameNotFound)?;
+            let height = block_height_arg(&height)?;
+            let info = context.block_info().get_burn_block_info(height, property);
+
+            Ok(Some(ExternRef::new(optional_value(info)?)))
+        },
+    )
+}
+
+/// Defines the `at-block` function. The provided function is called with no
+/// arguments, in a read-only context with `block-height` set to the height of the
+/// block with the given `id-header-hash`. Note that the datastore is not rewound, so
+/// embedders wanting historical reads must provide a datastore which supports them.
+#[inline]
+pub fn define_at_block_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         id_header_hash: Option<ExternRef>,
+         func: Option<Func>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let func = func.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            check_callback_signature(&caller, &func, 0)?;
+            let height = caller
+                .data()
+                .block_info()
+                .get_block_height(extref_buffer(&id_header_hash)?)
+                .ok_or(RuntimeError::BlockNotFound)?;
+
+            let context = caller.data_mut();
+            let block_height = context.block_height();
+            let read_only = context.is_read_only();
+            context.set_block_height(height);
+            context.set_read_only(true);
+
+            let results = &mut [Val::ExternRef(None)];
+            let result = call_callback_extref(&mut caller, &func, &[], results);
+
+            // Restore the context regardless of whether or not the call succeeded.
+            let context = caller.data_mut();
+            context.set_block_height(block_height);
+            context.set_read_only(read_only);
+
+            Ok(Some(ExternRef::new(result?)))
+        },
+    )
+}
+
+/// Defines the `print` function. The provided value is appended to the context's
+/// event log and then returned unchanged, as per Clarity's `print` semantics.
+#[inline]
+pub fn define_print_extref(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         value: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let value = value.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            let clarity_value = value
+                .data()
+                .downcast_ref::<Value>()
+                .ok_or(RuntimeError::ArgumentTypeMismatch)?
+                .clone();
+
+            caller.data_mut().push_event(clarity_value);
+
+            Ok(Some(value))
+        },
+    )
+}
+
+/// Defines the `print` function for the memory convention. The serialized value
+/// at the given pointer is deserialized and appended to the context's event log,
+/// and the same pointer is returned.
+#[inline]
+pub fn define_print_memory(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>, ptr: i32, len: i32| -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+
+            // Fetch and deserialize the value from memory.
+            let value = match read_value_from_data(memory.data(&caller), ptr, len) {
+                Ok(value) => value,
+                Err(e) => return FuncResult::err(e),
+            };
+
+            caller.data_mut().push_event(value);
+
+            FuncResult::ok(Ptr::new(ptr, len))
+        },
+    )
+}
+
+/// The size of a `Ptr` in Wasm memory, i.e. its offset and length as little-endian
+/// `i32`s.
+const PTR_SIZE: usize = 8;
+
+/// Defines the `drop_ptrs` function for the memory convention, which frees `count`
+/// consecutive `Ptr`s read from `ptr_offset` in `vm_mem` using the context's
+/// allocator. This lets generated code release many intermediate values (e.g. those
+/// of each `fold` iteration) in one call. Pointers which don't point to a live
+/// allocation are skipped, and the length of the returned `FuncResult` is the number
+/// of pointers which were freed.
+#[inline]
+pub fn define_drop_ptrs_memory(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>, ptr_offset: i32, count: i32| -> FuncResult {
+            // Retrieve an instance of the `vm_mem` exported memory.
+            let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+                Some(memory) => memory,
+                None => return FuncResult::err(RuntimeError::FailedToDeserializeValueFromMemory),
+            };
+
+            let len = match usize::try_from(count)
+                .ok()
+                .and_then(|count| count.checked_mul(PTR_SIZE))
+                .and_then(|len| i32::try_from(len).ok())
+            {
+                Some(len) => len,
+                None => return FuncResult::err(RuntimeError::IndexOutOfRange),
+            };
+
+            let mut alloc = std::mem::take(&mut caller.data_mut().alloc);
+            let freed = memory_slice(memory.data(&caller), ptr_offset, len).map(|ptrs| {
+                ptrs.chunks_exact(PTR_SIZE)
+                    .map(|ptr| {
+                        let offset = i32::from_le_bytes([ptr[0], ptr[1], ptr[2], ptr[3]]);
+                        let len = i32::from_le_bytes([ptr[4], ptr[5], ptr[6], ptr[7]]);
+                        Ptr::new(offset, len)
+                    })
+                    .filter(|ptr| alloc.dealloc(*ptr))
+                    .count()
+            });
+            caller.data_mut().alloc = alloc;
+
+            match freed {
+                Ok(freed) => FuncResult::ok(Ptr::new(ptr_offset, freed as i32)),
+                Err(_) => FuncResult::err(RuntimeError::IndexOutOfRange),
+            }
+        },
+    )
+}
+
+/// Reads and deserializes the value at the given offset/length from the provided
+/// memory slice.
+#[inline]
+fn read_value_from_data(data: &[u8], ptr: i32, len: i32) -> Result<Value, RuntimeError> {
+    memory_slice(data, ptr, len)
+        .and_then(deserialize_clarity_value)
+        .map_err(|_| RuntimeError::FailedToDeserializeValueFromMemory)
+}
+
+/// Serializes the provided value and writes it to the `vm_mem` exported memory,
+/// returning a `FuncResult` pointing to the written bytes.
+fn write_result_to_memory(
+    caller: &mut Caller<'_, ClarityWasmContext>,
+    value: &Value,
+) -> FuncResult {
+    // Retrieve an instance of the `vm_mem` exported memory.
+    let memory = match caller.get_export("vm_mem").and_then(|e| e.into_memory()) {
+        Some(memory) => memory,
+        None => return FuncResult::err(RuntimeError::FailedToWriteResultToMemory),
+    };
+
+    // Allocate exactly as much memory as the serialized value needs, growing the
+    // memory if needed, and serialize it straight into memory.
+    let mut alloc = std::mem::take(&mut caller.data_mut().alloc);
+    let ptr = alloc_in_memory(&mut *caller, memory, &mut alloc, serialized_size(value));
+    caller.data_mut().alloc = alloc;
+    let ptr = match ptr {
+        Ok(ptr) => ptr,
+        Err(_) => return FuncResult::err(RuntimeError::FailedToWriteResultToMemory),
+    };
+    let written = memory
+        .data_mut(&mut *caller)
+        .get_mut(ptr.offset as usize..(ptr.offset + ptr.len) as usize)
+        .and_then(|buffer| serialize_clarity_value_into(value, buffer).ok());
+    if written != Some(ptr.len as usize) {
+        return FuncResult::err(RuntimeError::FailedToWriteResultToMemory);
+    }
+
+    FuncResult::ok(ptr)
+}
+
+/// Defines a function which takes no arguments and returns a value read from the
+/// `ClarityWasmContext` as an `ExternRef`.
+#[inline]
+fn define_context_value_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+    getter: fn(&ClarityWasmContext) -> Value,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |caller: Caller<'_, ClarityWasmContext>| -> Option<ExternRef> {
+            Some(ExternRef::new(getter(caller.data())))
+        },
+    )
+}
+
+/// Defines a function which takes no arguments and writes a value read from the
+/// `ClarityWasmContext` to memory.
+#[inline]
+fn define_context_value_memory(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+    getter: fn(&ClarityWasmContext) -> Value,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |mut caller: Caller<'_, ClarityWasmContext>| -> FuncResult {
+            let value = getter(caller.data());
+            write_result_to_memory(&mut caller, &value)
+        },
+    )
+}
+
+/// Defines a function which takes no arguments and returns an unsigned integer read
+/// from the `ClarityWasmContext` as a low/high i64 pair.
+#[inline]
+fn define_context_uint_native(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+    getter: fn(&ClarityWasmContext) -> u128,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        move |caller: Caller<'_, ClarityWasmContext>| -> (i64, i64) {
+            let value = getter(caller.data());
+            (
+                (value & 0xFFFFFFFFFFFFFFFF) as i64,
+                ((value >> 64) & 0xFFFFFFFFFFFFFFFF) as i64,
+            )
+        },
+    )
+}
+
+/// Defines the `get_tx_sender` function (`tx-sender`).
+#[inline]
+pub fn define_get_tx_sender_extref(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_context_value_extref(store, |ctx| Value::Principal(ctx.tx_sender().clone()))
+}
+
+/// Defines the `get_tx_sender` function (`tx-sender`) for the memory convention.
+#[inline]
+pub fn define_get_tx_sender_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_context_value_memory(store, |ctx| Value::Principal(ctx.tx_sender().clone()))
+}
+
+/// Defines the `get_contract_caller` function (`contract-caller`).
+#[inline]
+pub fn define_get_contract_caller_extref(
+    store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    define_context_value_extref(store, |ctx| Value::Principal(ctx.contract_caller().clone()))
+}
+
+/// Defines the `get_contract_caller` function (`contract-caller`) for the memory convention.
+#[inline]
+pub fn define_get_contract_caller_memory(
+    store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    define_context_value_memory(store, |ctx| Value::Principal(ctx.contract_caller().clone()))
+}
+
+/// Defines the `get_block_height` function (`block-height`).
+#[inline]
+pub fn define_get_block_height_extref(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_context_value_extref(store, |ctx| Value::UInt(ctx.block_height() as u128))
+}
+
+/// Defines the `get_block_height` function (`block-height`) for the memory convention.
+#[inline]
+pub fn define_get_block_height_memory(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_context_value_memory(store, |ctx| Value::UInt(ctx.block_height() as u128))
+}
+
+/// Defines the `get_block_height` function (`block-height`) using native Wasm types.
+#[inline]
+pub fn define_get_block_height_native(store: impl AsContextMut<Data = ClarityWasmContext>) -> Func {
+    define_context_uint_native(store, |ctx| ctx.block_height() as u128)
+}
+
+/// Defines the `get_burn_block_height` function (`burn-block-height`).
+#[inline]
+pub fn define_get_burn_block_height_extref(
+    store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    define_context_value_extref(store, |ctx| Value::UInt(ctx.burn_block_height() as u128))
+}
+
+/// Defines the `get_burn_block_height` function (`burn-block-height`) for the memory convention.
+#[inline]
+pub fn define_get_burn_block_height_memory(
+    store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    define_context_value_memory(store, |ctx| Value::UInt(ctx.burn_block_height() as u128))
+}
+
+/// Defines the `get_burn_block_height` function (`burn-block-height`) using native Wasm types.
+#[inline]
+pub fn define_get_burn_block_height_native(
+    store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    define_context_uint_native(store, |ctx| ctx.burn_block_height() as u128)
+}
+
+/// Defines the `contract_call` function (`contract-call?`). The target contract is
+/// resolved from the `ContractRegistry`, the execution context is switched to the
+/// target contract (with the calling contract becoming `contract-caller`), and the
+/// exported function is called with the elements of the `args` list, or with the
+/// arguments collected by `push_arg` (a null reference if there are none). The
+/// context is restored once the call completes, regardless of its outcome.
+#[inline]
+pub fn define_contract_call_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         contract: Option<ExternRef>,
+         function: Option<ExternRef>,
+         args: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let contract = contract.ok_or(RuntimeError::FunctionArgumentRequired)?;
+            let function = function.ok_or(RuntimeError::FunctionArgumentRequired)?;
+
+            // Resolve the target contract identifier.
+            let contract_id = match contract.data().downcast_ref::<Value>() {
+                Some(Value::Principal(PrincipalData::Contract(contract_id))) => contract_id.clone(),
+                Some(Value::CallableContract(callable)) => callable.contract_identifier.clone(),
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+            };
+
+            // Resolve the name of the function to call.
+            let function_name = match function.data().downcast_ref::<Value>() {
+                Some(Value::Sequence(SequenceData::String(CharType::ASCII(name)))) => {
+                    String::from_utf8(name.data.clone())
+                        .map_err(|_| RuntimeError::ArgumentTypeMismatch)?
+                }
+                _ => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+            };
+
+            let args = match args
+                .as_ref()
+                .and_then(|args| args.data().downcast_ref::<Value>())
+            {
+                Some(Value::Sequence(SequenceData::List(list))) => list.data.clone(),
+                Some(_) => return Err(RuntimeError::ArgumentTypeMismatch.into()),
+                None => contract_call_args(&args)?,
+            };
+
+            call_contract(&mut caller, contract_id, &function_name, &args)
+        },
+    )
+}
+
+/// Defines the `contract_call_static` function, lowering a `contract-call?` whose
+/// target contract is known at compile time. The contract and function are passed
+/// as their ids interned for the calling contract (see `ContractRegistry::intern`
+/// and `ClarityWasmContext::names`), and the arguments as collected by `push_arg`
+/// (or a null reference if there are none). Otherwise, it behaves as `contract_call`.
+#[inline]
+pub fn define_contract_call_static_extref(
+    mut store: impl AsContextMut<Data = ClarityWasmContext>,
+) -> Func {
+    Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, ClarityWasmContext>,
+         contract_id: i32,
+         function_id: i32,
+         args: Option<ExternRef>|
+         -> wasmtime::Result<Option<ExternRef>> {
+            let ctx = caller.data();
+            let contract_id = ctx
+                .contract_id()
+                .and_then(|calling_contract| {
+                    ctx.contracts().contract_id(calling_contract, contract_id)
+                })
+                .cloned()
+                .ok_or(RuntimeError::ContractNotFound)?;
+            let function_name = caller
+                .data()
+                .names()
+                .get(function_id)
+                .ok_or(RuntimeError::NameNotFound)?
+                .to_string();
+            let args = contract_call_args(&args)?;
+
+            call_contract(&mut caller, contract_id, &function_name, &args)
+        },
+    )
+}
+
+/// Defines the `push_arg` function, returning the provided arguments of a
+/// `contract-call?` (or none, if null) with a value appended. The arguments are
+/// collected in a `Vec` rather than a Clarity list, as they may be of different types.
+#[inline]
+pub fn define_push_arg_extref(mut store: impl AsContextMut) -> Func {
+    Func::wrap(
+        &mut store,
+        |args: Option<ExternRef>, arg: Option<ExternRef>| -> wasmtime::Result<Option<ExternRef>> {
+            let mut args = contract_call_args(&args)?;
+            args.push(extref_value(&arg)?.clone());
+
+            Ok(Some(ExternRef::new(args)))
+        },
+    )
+}
+
+/// Gets the arguments of a `contract-call?`, as collected by `push_arg`. A null
+/// reference holds no arguments.
+#[inline]
+fn contract_call_args(args: &Option<ExternRef>) -> Result<Vec<Value>, RuntimeError> {
+    match args {
+        Some(args) => args
+            .data()
+            .downcast_ref::<Vec<Value>>()
+            .cloned()
+            .ok_or(RuntimeError::ArgumentTypeMismatch),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Calls the function with the given name, exported by the given contract, with the
+/// provided arguments. The execution context is switched to the target contract
+/// (with the calling contract becoming `contract-caller`) for the duration of the
+/// call, and restored once it completes, regardless of its outcome. The function's
+/// result, e.g. the response of a public function, is returned as-is.
+fn call_contract(
+    caller: &mut Caller<'_, ClarityWasmContext>,
+    contract_id: QualifiedContractIdentifier,
+    function_name: &str,
+    args: &[Value],
+) -> wasmtime::Result<Option<ExternRef>> {
+    // Convert the arguments to `ExternRef` parameters.
+    let params = args
+        .iter()
+        .map(|arg| Val::ExternRef(Some(ExternRef::new(arg.clone()))))
+        .collect::<Vec<Val>>();
+
+    // Look up the target contract instance and function.
+    let instance = caller
+        .data()
+        .contracts()
+        .get(&contract_id)
+        .ok_or(RuntimeError::ContractNotFound)?;
+    let func = instance
+        .get_func(&mut *caller, function_name)
+        .ok_or(RuntimeError::FunctionNotFound)?;
+
+    // Switch the execution context to the target contract. The calling contract
+    // (or the tx-sender, if called from outside of a contract) becomes the
+    // `contract-caller`.
+    let ctx = caller.data_mut();
+    let new_caller = match ctx.contract_id() {
+        Some(id) => PrincipalData::Contract(id.clone()),
+        None => ctx.tx_sender().clone(),
+    };
+    let prev_caller = std::mem::replace(&mut ctx.contract_caller, new_caller);
+    let prev_contract = ctx.contract_id.replace(contract_id);
+
+    let mut results = [Val::ExternRef(None)];
+    let result = func.call(&mut *caller, &params, &mut results);
+
+    // Restore the execution context.
+    let ctx = caller.data_mut();
+    ctx.contract_caller = prev_caller;
+    ctx.contract_id = prev_contract;
+
+    result?;
+    Ok(results[0].unwrap_externref())
+}
+
+/// Gets all of the host functions which are available at the Clarity version
+/// configured on the store's `ClarityWasmContext`.
+#[inline]
+pub fn get_all_functions(mut store: impl AsContextMut<Data = ClarityWasmContext>) -> Vec<FuncMap> {
+    let version = store.as_context().data().clarity_version();
+
+    let functions = vec![
+        // `add` functions
+        FuncMap::new("add_extref", define_add_extref(&mut store)),
+        FuncMap::new("add_native", define_add_native(&mut store)),
+        FuncMap::new("add_memory", define_add_memory(&mut store)),
+        // `sub` (subtraction) functions
+        FuncMap::new("sub_extref", define_sub_extref(&mut store)),
+        FuncMap::new("sub_native", define_sub_native(&mut store)),
+        FuncMap::new("sub_memory", define_sub_memory(&mut store)),
+        // `mul` (multiplication) functions
+        FuncMap::new("mul_extref", define_mul_extref(&mut store)),
+        // `div` (division) and `mod` (modulo) functions
+        FuncMap::new("div_extref", define_div_extref(&mut store)),
+        FuncMap::new("div_memory", define_div_memory(&mut store)),
+        FuncMap::new("mod_extref", define_mod_extref(&mut store)),
+        FuncMap::new("mod_memory", define_mod_memory(&mut store)),
+        // Comparison functions
+        FuncMap::new("gt_extref", define_gt_extref(&mut store)),
+        FuncMap::new("ge_extref", define_ge_extref(&mut store)),
+        FuncMap::new("lt_extref", define_lt_extref(&mut store)),
+        FuncMap::new("le_extref", define_le_extref(&mut store)),
+        FuncMap::new("is_eq_extref", define_is_eq_extref(&mut store)),
+        FuncMap::new("gt_native", define_gt_native(&mut store)),
+        FuncMap::new("ge_native", define_ge_native(&mut store)),
+        FuncMap::new("lt_native", define_lt_native(&mut store)),
+        FuncMap::new("le_native", define_le_native(&mut store)),
+        FuncMap::new("is_eq_native", define_is_eq_native(&mut store)),
+        // Boolean functions
+        FuncMap::new("not_extref", define_not_extref(&mut store)),
+        // `fold` functions
+        FuncMap::new("fold_extref", define_fold_extref(&mut store)),
+        FuncMap::new("fold_memory", define_fold_memory(&mut store)),
+        // `map` functions
+        FuncMap::new("map_extref", define_map_extref(&mut store)),
+        // `filter` functions
+        FuncMap::new("filter_extref", define_filter_extref(&mut store)),
+        // Sequence primitives
+        FuncMap::new("len_extref", define_len_extref(&mut store)),
+        FuncMap::new("element_at_extref", define_element_at_extref(&mut store)),
+        FuncMap::new("seq_len_extref", define_seq_len_extref(&mut store)),
+        FuncMap::new("seq_get_extref", define_seq_get_extref(&mut store)),
+        FuncMap::new("slice_extref", define_slice_extref(&mut store))
+            .since(ClarityVersion::Clarity2),
+        FuncMap::new("index_of_extref", define_index_of_extref(&mut store)),
+        FuncMap::new("append_extref", define_append_extref(&mut store)),
+        FuncMap::new("list_push_extref", define_list_push_extref(&mut store)),
+        FuncMap::new("list_build_extref", define_list_build_extref(&mut store)),
+        FuncMap::new("concat_extref", define_concat_extref(&mut store)),
+        // `optional` and `response` functions
+        FuncMap::new("is_some_extref", define_is_some_extref(&mut store)),
+        FuncMap::new("is_none_extref", define_is_none_extref(&mut store)),
+        FuncMap::new("is_ok_extref", define_is_ok_extref(&mut store)),
+        FuncMap::new("is_err_extref", define_is_err_extref(&mut store)),
+        FuncMap::new("unwrap_extref", define_unwrap_extref(&mut store)),
+        FuncMap::new("unwrap_err_extref", define_unwrap_err_extref(&mut store)),
+        FuncMap::new("ok_extref", define_ok_extref(&mut store)),
+        FuncMap::new("err_extref", define_err_extref(&mut store)),
+        FuncMap::new("some_extref", define_some_extref(&mut store)),
+        // Control flow helpers
+        FuncMap::new("to_bool_extref", define_to_bool_extref(&mut store)),
+        FuncMap::new("is_success_extref", define_is_success_extref(&mut store)),
+        // Integer conversion functions
+        FuncMap::new("to_int_extref", define_to_int_extref(&mut store)),
+        FuncMap::new("to_int_native", define_to_int_native(&mut store)),
+        FuncMap::new("to_uint_extref", define_to_uint_extref(&mut store)),
+        FuncMap::new("to_uint_native", define_to_uint_native(&mut store)),
+        // Data map functions
+        FuncMap::new("map_get_extref", define_map_get_extref(&mut store)),
+        FuncMap::new("map_set_extref", define_map_set_extref(&mut store)),
+        FuncMap::new("map_insert_extref", define_map_insert_extref(&mut store)),
+        FuncMap::new("map_delete_extref", define_map_delete_extref(&mut store)),
+        // Data variable functions
+        FuncMap::new("var_get_extref", define_var_get_extref(&mut store)),
+        FuncMap::new("var_set_extref", define_var_set_extref(&mut store)),
+        // Block info functions
+        FuncMap::new(
+            "get_block_info_extref",
+            define_get_block_info_extref(&mut store),
+        ),
+        FuncMap::new(
+            "get_burn_block_info_extref",
+            define_get_burn_block_info_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new("at_block_extref", define_at_block_extref(&mut store)),
+        // Buffer conversion functions
+        FuncMap::new(
+            "buff_to_int_le_extref",
+            define_buff_to_int_le_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "buff_to_int_be_extref",
+            define_buff_to_int_be_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "buff_to_uint_le_extref",
+            define_buff_to_uint_le_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "buff_to_uint_be_extref",
+            define_buff_to_uint_be_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "int_to_buff_le_extref",
+            define_int_to_buff_le_extref(&mut store),
+        ),
+        FuncMap::new(
+            "int_to_buff_be_extref",
+            define_int_to_buff_be_extref(&mut store),
+        ),
+        // String conversion functions
+        FuncMap::new(
+            "int_to_ascii_extref",
+            define_int_to_ascii_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new("int_to_utf8_extref", define_int_to_utf8_extref(&mut store))
+            .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "string_to_int_extref",
+            define_string_to_int_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "string_to_uint_extref",
+            define_string_to_uint_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        // Hash functions
+        FuncMap::new("sha256_extref", define_sha256_extref(&mut store)),
+        FuncMap::new("sha256_memory", define_sha256_memory(&mut store)),
+        FuncMap::new("sha512_extref", define_sha512_extref(&mut store)),
+        FuncMap::new("sha512_memory", define_sha512_memory(&mut store)),
+        FuncMap::new("sha512_256_extref", define_sha512_256_extref(&mut store)),
+        FuncMap::new("sha512_256_memory", define_sha512_256_memory(&mut store)),
+        FuncMap::new("hash160_extref", define_hash160_extref(&mut store)),
+        FuncMap::new("hash160_memory", define_hash160_memory(&mut store)),
+        FuncMap::new("keccak256_extref", define_keccak256_extref(&mut store)),
+        FuncMap::new("keccak256_memory", define_keccak256_memory(&mut store)),
+        // Tuple functions
+        FuncMap::new("tuple_get_extref", define_tuple_get_extref(&mut store)),
+        FuncMap::new("merge_extref", define_merge_extref(&mut store)),
+        // Principal functions
+        FuncMap::new(
+            "principal_of_extref",
+            define_principal_of_extref(&mut store),
+        ),
+        FuncMap::new(
+            "principal_of_memory",
+            define_principal_of_memory(&mut store),
+        ),
+        FuncMap::new(
+            "principal_destruct_extref",
+            define_principal_destruct_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "principal_destruct_memory",
+            define_principal_destruct_memory(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "principal_construct_extref",
+            define_principal_construct_extref(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        FuncMap::new(
+            "principal_construct_memory",
+            define_principal_construct_memory(&mut store),
+        )
+        .since(ClarityVersion::Clarity2),
+        // `secp256k1` functions
+        FuncMap::new(
+            "secp256k1_recover_extref",
+            define_secp256k1_recover_extref(&mut store),
+        ),
+        FuncMap::new(
+            "secp256k1_recover_memory",
+            define_secp256k1_recover_memory(&mut store),
+        ),
+        FuncMap::new(
+            "secp256k1_verify_extref",
+            define_secp256k1_verify_extref(&mut store),
+        ),
+        FuncMap::new(
+            "secp256k1_verify_memory",
+            define_secp256k1_verify_memory(&mut store),
+        ),
+        // Memory management functions
+        FuncMap::new("drop_ptrs_memory", define_drop_ptrs_memory(&mut store)),
+        // `print` functions
+        FuncMap::new("print_extref", define_print_extref(&mut store)),
+        FuncMap::new("print_memory", define_print_memory(&mut store)),
+        // Transaction context functions
+        FuncMap::new(
+            "get_tx_sender_extref",
+            define_get_tx_sender_extref(&mut store),
+        ),
+        FuncMap::new(
+            "get_tx_sender_memory",
+            define_get_tx_sender_memory(&mut store),
+        ),
+        FuncMap::new(
+            "get_contract_caller_extref",
+            define_get_contract_caller_extref(&mut store),
+        ),
+        FuncMap::new(
+            "get_contract_caller_memory",
+            define_get_contract_caller_memory(&mut store),
+        ),
+        FuncMap::new(
+            "get_block_height_extref",
+            define_get_block_height_extref(&mut store),
+        ),
+        FuncMap::new(
+            "get_block_height_memory",
+            define_get_block_height_memory(&mut store),
+        ),
+        FuncMap::new(
+            "get_block_height_native",
+            define_get_block_height_native(&mut store),
+        ),
+        FuncMap::new(
+            "get_burn_block_height_extref",
+            define_get_burn_block_height_extref(&mut store),
+        ),
+        FuncMap::new(
+            "get_burn_block_height_memory",
+            define_get_burn_block_height_memory(&mut store),
+        ),
+        FuncMap::new(
+            "get_burn_block_height_native",
+            define_get_burn_block_height_native(&mut store),
+        ),
+        // `contract-call?` functions
+        FuncMap::new(
+            "contract_call_extref",
+            define_contract_call_extref(&mut store),
+        ),
+        FuncMap::new(
+            "contract_call_static_extref",
+            define_contract_call_static_extref(&mut store),
+        ),
+        FuncMap::new("push_arg_extref", define_push_arg_extref(&mut store)),
+    ];
+
+    functions
+        .into_iter()
+        .filter(|f| f.is_available_at(version))
+        .collect()
+}
+
+/// Registers the provided host functions in the given `Linker`, each under the
+/// `HOST_MODULE_NAME` module using its `FuncMap` name.
+pub fn link_host_functions(
+    linker: &mut Linker<ClarityWasmContext>,
+    store: impl AsContext<Data = ClarityWasmContext>,
+    functions: &[FuncMap],
+) -> wasmtime::Result<()> {
+    for f in functions {
+        linker.define(&store, HOST_MODULE_NAME, &f.name, f.func)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,64 @@
+/// The initial capacity of newly allocated scratch buffers, which fits most
+/// serialized keys and values.
+const INITIAL_BUFFER_CAPACITY: usize = 256;
+
+/// Buffers which have grown beyond this capacity are dropped instead of being
+/// returned to the pool, so that a single large value doesn't pin its memory for
+/// the lifetime of the context.
+const MAX_POOLED_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// The maximum number of buffers kept in the pool.
+const MAX_POOLED_BUFFERS: usize = 8;
+
+/// A pool of re-usable byte buffers, used by host functions to serialize values
+/// without allocating a new `Vec<u8>` on every call. See
+/// `ClarityWasmContext::with_scratch()`.
+#[derive(Debug, Default)]
+pub struct ScratchPool {
+    buffers: Vec<Vec<u8>>,
+}
+
+impl ScratchPool {
+    /// Creates a new, empty `ScratchPool`.
+    pub fn new() -> Self {
+        ScratchPool {
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Takes an empty buffer from the pool, allocating a new one if the pool is
+    /// empty.
+    pub fn take(&mut self) -> Vec<u8> {
+        self.buffers
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(INITIAL_BUFFER_CAPACITY))
+    }
+
+    /// Clears the provided buffer and returns it to the pool, unless the pool is
+    /// full or the buffer has grown too large to be worth keeping.
+    pub fn give_back(&mut self, mut buffer: Vec<u8>) {
+        if self.buffers.len() < MAX_POOLED_BUFFERS
+            && buffer.capacity() <= MAX_POOLED_BUFFER_CAPACITY
+        {
+            buffer.clear();
+            self.buffers.push(buffer);
+        }
+    }
+
+    /// Gets the number of buffers currently held by the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Gets whether or not the pool currently holds no buffers.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+}
+
+impl Clone for ScratchPool {
+    /// Cloning a pool doesn't copy its buffers, as they hold no data.
+    fn clone(&self) -> Self {
+        ScratchPool::new()
+    }
+}
@@ -1,16 +1,48 @@
 use clarity::vm::{
     types::{
-        BuffData, CallableData, CharType, OptionalData, PrincipalData, QualifiedContractIdentifier,
-        ResponseData, SequenceData, StandardPrincipalData, TraitIdentifier, Value,
+        BuffData, CallableData, CharType, ListData, OptionalData, PrincipalData,
+        QualifiedContractIdentifier, ResponseData, SequenceData, StandardPrincipalData,
+        TraitIdentifier, TupleData, TypeSignature, UTF8Data, Value, MAX_TYPE_DEPTH, MAX_VALUE_SIZE,
     },
-    ContractName,
+    ClarityName, ContractName,
 };
 use num::FromPrimitive;
 use num_derive::{FromPrimitive, ToPrimitive};
 
 use crate::Ptr;
 
-#[derive(Debug, Clone, Copy)]
+pub(crate) mod access;
+pub(crate) mod borrowed;
+pub(crate) mod consensus;
+pub(crate) mod explain;
+pub(crate) mod iter;
+pub(crate) mod packed;
+#[cfg(feature = "serde")]
+pub(crate) mod serde_support;
+pub(crate) mod signature;
+/// Utilities for property-testing the serialization of Clarity values, e.g. from
+/// downstream crates or CI.
+#[cfg(feature = "testing")]
+pub mod testing;
+pub(crate) mod writer;
+
+pub use access::{get_list_element, get_tuple_field};
+pub use borrowed::{deserialize_clarity_value_borrowed, ClarityValueRef};
+pub use consensus::{
+    deserialize_consensus, from_consensus_bytes, serialize_consensus, to_consensus_bytes,
+};
+pub use explain::explain;
+pub use iter::{ClaritySeqIterator, SeqElement};
+pub use packed::{
+    deserialize_packed_int_list, is_packed_int_list, serialize_packed_int_list, unpack_int_list,
+    unpack_uint_list, PACKED_LIST_FLAG,
+};
+#[cfg(feature = "serde")]
+pub use serde_support::SerializedValue;
+pub use signature::describe;
+pub use writer::{serialize_clarity_value_into, serialized_size};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SerializationError {
     IndexOutOfRange,
     FailedToConvertBytesToAscii,
@@ -18,7 +50,7 @@ pub enum SerializationError {
     InvalidTypeIndicator(u8),
     LengthIndicatorDoesNotMatchBufferLength,
     FailedToDeserializeLengthIndicator,
-    InvalidBufferLength { expected: u16, received: u16 },
+    InvalidBufferLength { expected: u32, received: u32 },
     FailedToDeserializeList,
     FailedToDeserializeListLength,
     AttemtToDeserializeZeroLengthBuffer,
@@ -27,9 +59,130 @@ pub enum SerializationError {
     FailedToDeserializePtr,
     InvalidPtrLength,
     TypeNotAllowed { received: TypeIndicator },
+    FailedToDeserializeTuple,
+    FailedToDeserializeTupleFieldName,
+    ValueTooLarge,
+    BufferTooSmall,
+    TypeMismatch,
+    MaxDepthExceeded,
+    MaxElementsExceeded,
+    MaxBytesExceeded,
+    FailedToDeserializeTypeSignature,
+}
+
+impl std::fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            SerializationError::IndexOutOfRange => "unexpected end of buffer",
+            SerializationError::FailedToConvertBytesToAscii => {
+                "failed to convert bytes to an ascii string"
+            }
+            SerializationError::FailedToConvertBytesToUtf8 => {
+                "failed to convert bytes to a utf8 string"
+            }
+            SerializationError::InvalidTypeIndicator(indicator) => {
+                return write!(f, "invalid type indicator {indicator}");
+            }
+            SerializationError::LengthIndicatorDoesNotMatchBufferLength => {
+                "length indicator does not match the buffer length"
+            }
+            SerializationError::FailedToDeserializeLengthIndicator => {
+                "failed to deserialize length indicator"
+            }
+            SerializationError::InvalidBufferLength { expected, received } => {
+                return write!(
+                    f,
+                    "invalid buffer length: expected {expected} bytes, received {received}"
+                );
+            }
+            SerializationError::FailedToDeserializeList => "failed to deserialize list",
+            SerializationError::FailedToDeserializeListLength => {
+                "failed to deserialize list length"
+            }
+            SerializationError::AttemtToDeserializeZeroLengthBuffer => {
+                "attempted to deserialize a zero-length buffer"
+            }
+            SerializationError::FailedToDeserializeContractName => {
+                "failed to deserialize contract name"
+            }
+            SerializationError::FailedToDeserializeTraitName => "failed to deserialize trait name",
+            SerializationError::FailedToDeserializePtr => "failed to deserialize pointer",
+            SerializationError::InvalidPtrLength => "invalid pointer length",
+            SerializationError::TypeNotAllowed { received } => {
+                return write!(f, "type not allowed: {received:?}");
+            }
+            SerializationError::FailedToDeserializeTuple => "failed to deserialize tuple",
+            SerializationError::FailedToDeserializeTupleFieldName => {
+                "failed to deserialize tuple field name"
+            }
+            SerializationError::ValueTooLarge => "value too large",
+            SerializationError::BufferTooSmall => "buffer too small",
+            SerializationError::TypeMismatch => "value does not match the expected type",
+            SerializationError::MaxDepthExceeded => "maximum nesting depth exceeded",
+            SerializationError::MaxElementsExceeded => "maximum element count exceeded",
+            SerializationError::MaxBytesExceeded => "maximum value size exceeded",
+            SerializationError::FailedToDeserializeTypeSignature => {
+                "failed to deserialize type signature"
+            }
+        };
+        write!(f, "{msg}")
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+/// A `SerializationError` along with where it occurred, so that failures in nested
+/// values can be tracked down. Returned by `deserialize_clarity_value_with_context`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializationError {
+    /// The underlying error.
+    pub error: SerializationError,
+    /// The offset of the value which failed to deserialize (i.e. of its header),
+    /// relative to the start of the buffer.
+    pub offset: usize,
+    /// The path to the value which failed to deserialize, e.g. `[3].field.some`.
+    /// Empty if it is the outermost value.
+    pub path: String,
+}
+
+impl DeserializationError {
+    /// Moves this error into the context of the parent value in `parent`, of which
+    /// `child` (the value which failed) is a sub-slice at the given path segment.
+    fn within(mut self, parent: &[u8], child: &[u8], segment: impl std::fmt::Display) -> Self {
+        self.offset += child.as_ptr() as usize - parent.as_ptr() as usize;
+        self.path.insert_str(0, &segment.to_string());
+        self
+    }
+}
+
+impl From<SerializationError> for DeserializationError {
+    fn from(error: SerializationError) -> Self {
+        DeserializationError {
+            error,
+            offset: 0,
+            path: String::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for DeserializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (at byte {}, value{})",
+            self.error, self.offset, self.path
+        )
+    }
+}
+
+impl std::error::Error for DeserializationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TypeIndicator {
     UInt = 1,
     Int = 2,
@@ -53,6 +206,219 @@ impl TypeIndicator {
     }
 }
 
+/// The serialization formats supported by this module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// The format used to pass values across the Wasm boundary, as implemented by
+    /// `serialize_clarity_value` and `deserialize_clarity_value`.
+    #[default]
+    Wasm,
+    /// Clarity's consensus serialization format (SIP-005), which is byte-compatible
+    /// with the on-chain representation of values.
+    Consensus,
+}
+
+/// Serializes a Clarity `Value` using the given `Codec`.
+#[inline]
+pub fn serialize_clarity_value_with(
+    value: &Value,
+    codec: Codec,
+) -> Result<Vec<u8>, SerializationError> {
+    match codec {
+        Codec::Wasm => serialize_clarity_value(value),
+        Codec::Consensus => serialize_consensus(value),
+    }
+}
+
+/// Deserializes a Clarity `Value` from the provided buffer using the given `Codec`.
+#[inline]
+pub fn deserialize_clarity_value_with(
+    buffer: &[u8],
+    codec: Codec,
+) -> Result<Value, SerializationError> {
+    match codec {
+        Codec::Wasm => deserialize_clarity_value(buffer),
+        Codec::Consensus => deserialize_consensus(buffer),
+    }
+}
+
+/// The byte order used when converting between integers and buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// Converts a buffer of up to 16 bytes to a `u128`. Shorter buffers are zero-padded
+/// on their most significant side, as in Clarity's `buff-to-uint-le`/`buff-to-uint-be`.
+#[inline]
+pub fn u128_from_bytes(bytes: &[u8], order: ByteOrder) -> Result<u128, SerializationError> {
+    if bytes.len() > 16 {
+        Err(SerializationError::InvalidBufferLength {
+            expected: 16,
+            received: bytes.len().min(u32::MAX as usize) as u32,
+        })?;
+    }
+
+    let mut buffer = [0u8; 16];
+    let value = match order {
+        ByteOrder::LittleEndian => {
+            buffer[..bytes.len()].copy_from_slice(bytes);
+            u128::from_le_bytes(buffer)
+        }
+        ByteOrder::BigEndian => {
+            buffer[16 - bytes.len()..].copy_from_slice(bytes);
+            u128::from_be_bytes(buffer)
+        }
+    };
+
+    Ok(value)
+}
+
+/// Converts a `u128` to its 16-byte representation in the given byte order.
+#[inline]
+pub fn u128_to_bytes(value: u128, order: ByteOrder) -> [u8; 16] {
+    match order {
+        ByteOrder::LittleEndian => value.to_le_bytes(),
+        ByteOrder::BigEndian => value.to_be_bytes(),
+    }
+}
+
+/// Lengths (and element counts) are written as a `u16`. Values which don't fit are
+/// written as this escape value followed by the actual length as a `u32`, so that
+/// values up to Clarity's 1MB limit can be serialized while the headers of smaller
+/// values remain unchanged.
+pub const LENGTH_ESCAPE: u16 = u16::MAX;
+
+/// Limits enforced by `deserialize_clarity_value`, so that a hostile buffer (e.g.
+/// one read from Wasm memory) cannot exhaust the host's stack or cause huge
+/// allocations. The defaults admit any value which is valid in Clarity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializationLimits {
+    /// The maximum nesting depth, where a value which contains no other values has
+    /// a depth of 1.
+    pub max_depth: usize,
+    /// The maximum number of elements in a single list, or fields in a single tuple.
+    pub max_elements: usize,
+    /// The maximum size of the buffer being deserialized, in bytes.
+    pub max_bytes: usize,
+}
+
+impl Default for DeserializationLimits {
+    fn default() -> Self {
+        DeserializationLimits {
+            max_depth: MAX_TYPE_DEPTH as usize,
+            max_elements: MAX_VALUE_SIZE as usize,
+            // Every value carries a three-byte header, so values can be up to four
+            // times larger than their consensus serialization (e.g. lists of bools).
+            max_bytes: 4 * MAX_VALUE_SIZE as usize,
+        }
+    }
+}
+
+/// Writes a length (or element count) indicator, escaping it to a `u32` if it
+/// doesn't fit in a `u16`.
+#[inline]
+fn write_length(buffer: &mut Vec<u8>, len: usize) -> Result<(), SerializationError> {
+    if len < LENGTH_ESCAPE as usize {
+        buffer.extend_from_slice(&(len as u16).to_le_bytes());
+    } else {
+        let len = u32::try_from(len).map_err(|_| SerializationError::ValueTooLarge)?;
+        buffer.extend_from_slice(&LENGTH_ESCAPE.to_le_bytes());
+        buffer.extend_from_slice(&len.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Reads a length (or element count) indicator written by `write_length` from the
+/// start of the provided buffer, returning the length and the number of bytes the
+/// indicator occupied.
+#[inline]
+fn read_length(buffer: &[u8]) -> Result<(usize, usize), SerializationError> {
+    let short: [u8; 2] = buffer
+        .get(0..2)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(SerializationError::FailedToDeserializeLengthIndicator)?;
+
+    match u16::from_le_bytes(short) {
+        LENGTH_ESCAPE => {
+            let long: [u8; 4] = buffer
+                .get(2..6)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or(SerializationError::FailedToDeserializeLengthIndicator)?;
+            Ok((u32::from_le_bytes(long) as usize, 6))
+        }
+        len => Ok((len as usize, 2)),
+    }
+}
+
+/// Reads the header (type indicator and length) of the serialized value at the
+/// start of the provided buffer, returning the length of the value and the total
+/// length of the header.
+#[inline]
+fn read_value_header(buffer: &[u8]) -> Result<(usize, usize), SerializationError> {
+    let (len, len_size) = read_length(
+        buffer
+            .get(1..)
+            .ok_or(SerializationError::FailedToDeserializeLengthIndicator)?,
+    )?;
+    Ok((len, 1 + len_size))
+}
+
+/// Reads a standard principal (a version byte followed by 20 bytes of hash data)
+/// from the start of the provided buffer, returning it and the remaining bytes.
+#[inline]
+fn read_standard_principal(
+    buffer: &[u8],
+) -> Result<(StandardPrincipalData, &[u8]), SerializationError> {
+    let (version, rest) = buffer
+        .split_first()
+        .ok_or(SerializationError::IndexOutOfRange)?;
+    let data: [u8; 20] = rest
+        .get(..20)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(SerializationError::IndexOutOfRange)?;
+    Ok((StandardPrincipalData(*version, data), &rest[20..]))
+}
+
+/// Reads a contract or trait name (prefixed with a two-byte length indicator) from
+/// the start of the provided buffer, returning it and the remaining bytes.
+#[inline]
+fn read_name(buffer: &[u8]) -> Result<(&str, &[u8]), SerializationError> {
+    let len_bytes: [u8; 2] = buffer
+        .get(..2)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(SerializationError::FailedToDeserializeLengthIndicator)?;
+    let len = u16::from_le_bytes(len_bytes) as usize;
+    let name = buffer
+        .get(2..2 + len)
+        .ok_or(SerializationError::IndexOutOfRange)?;
+    let name = std::str::from_utf8(name).map_err(|_| SerializationError::IndexOutOfRange)?;
+    Ok((name, &buffer[2 + len..]))
+}
+
+/// Checks that each element of a `string-utf8` holds exactly one UTF-8 encoded
+/// Unicode scalar value, which is how Clarity defines the characters of a string
+/// (and how `Value::string_utf8_from_bytes` builds them). A serialized
+/// `string-utf8` is the concatenation of its characters' bytes, and deserializing
+/// it splits the string on scalar value boundaries again, so only such values
+/// roundtrip losslessly.
+#[inline]
+fn check_utf8_chars(data: &UTF8Data) -> Result<(), SerializationError> {
+    let valid = data.data.iter().all(|c| match std::str::from_utf8(c) {
+        Ok(c) => {
+            let mut chars = c.chars();
+            chars.next().is_some() && chars.next().is_none()
+        }
+        Err(_) => false,
+    });
+
+    if !valid {
+        Err(SerializationError::FailedToConvertBytesToUtf8)?
+    }
+    Ok(())
+}
+
 /// Gets the type indicator value for the provided `Value`. This indicator is used to
 /// prefix serialized values so that the type can be known during deserialization, especially
 /// in the cases where multiple possible types are allowed in a `TypeSignature`.
@@ -93,7 +459,11 @@ fn type_indicator_byte_to_type_indicator(
 pub fn get_type_indicator_from_serialized_value(
     data: &[u8],
 ) -> Result<TypeIndicator, SerializationError> {
-    type_indicator_byte_to_type_indicator(data[0])
+    type_indicator_byte_to_type_indicator(
+        *data
+            .first()
+            .ok_or(SerializationError::AttemtToDeserializeZeroLengthBuffer)?,
+    )
 }
 
 /// Deserializes a clarity sequence value (buffer, ascii, utf8, list, etc.) to a list of
@@ -102,7 +472,7 @@ pub fn get_type_indicator_from_serialized_value(
 /// only passing the pointers further to the function to fold over.
 #[inline]
 pub fn deserialize_clarity_seq_to_ptrs(buffer: &[u8]) -> Result<Vec<Ptr>, SerializationError> {
-    let type_indicator = type_indicator_byte_to_type_indicator(buffer[0])?;
+    let type_indicator = get_type_indicator_from_serialized_value(buffer)?;
 
     // This method only supports sequence types.
     if ![
@@ -119,43 +489,38 @@ pub fn deserialize_clarity_seq_to_ptrs(buffer: &[u8]) -> Result<Vec<Ptr>, Serial
     }
 
     // Extract the length of this serialized value (excluding header).
-    let length_indicator_bytes: [u8; 2] = buffer[1..=2]
-        .try_into()
-        .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?;
-    let length_indicator = u16::from_le_bytes(length_indicator_bytes);
+    let (length_indicator, header_len) = read_value_header(buffer)?;
 
     // Create a slice that contains only the value bytes (excluding the header).
-    let value = &buffer[3..];
+    let value = &buffer[header_len..];
 
     // Ensure that our value slice length matches the parsed value length indicator.
-    let value_length = value.len() as u16;
-    if value_length != length_indicator {
+    if value.len() != length_indicator {
         Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
     }
 
-    // Split to retrieve the list length (first two bytes of the buffer)
-    let (list_len_bytes, value_bytes) = value.split_at(2);
-
     // Deserialize the list length
-    let list_len = u16::from_le_bytes(
-        list_len_bytes
-            .try_into()
-            .map_err(|_| SerializationError::FailedToDeserializeListLength)?,
-    );
-
-    let mut ptrs = Vec::<Ptr>::with_capacity(list_len as usize);
+    let (list_len, list_len_size) =
+        read_length(value).map_err(|_| SerializationError::FailedToDeserializeListLength)?;
+    // Skip over the list's type, which isn't needed to locate its items.
+    let list_type_size = signature::skip_list_type(&value[list_len_size..])?;
+    let value_bytes = &value[list_len_size + list_type_size..];
+
+    // Don't trust the length indicator for the allocation size; every item takes up
+    // at least one byte.
+    let mut ptrs = Vec::<Ptr>::with_capacity(list_len.min(value_bytes.len()));
     let mut index = 0;
 
     for _i in 0..list_len {
         // Deserialize the length of the next item
-        let value_len = u16::from_le_bytes(
-            value_bytes[(index + 1)..=(index + 2)]
-                .try_into()
-                .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?,
-        ) as usize;
+        let (value_len, item_header_len) = read_value_header(
+            value_bytes
+                .get(index..)
+                .ok_or(SerializationError::IndexOutOfRange)?,
+        )?;
 
         ptrs.push(Ptr::new(index as i32, value_len as i32));
-        index += value_len + 3;
+        index += value_len + item_header_len;
     }
 
     Ok(ptrs)
@@ -166,6 +531,47 @@ pub fn deserialize_clarity_seq_to_ptrs(buffer: &[u8]) -> Result<Vec<Ptr>, Serial
 /// can be found in the `pass_argument_to_wasm` function.
 #[inline]
 pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationError> {
+    deserialize_clarity_value_with_limits(buffer, &DeserializationLimits::default())
+}
+
+/// Deserializes a Clarity `Value` from the provided buffer, failing if the value
+/// exceeds any of the provided `DeserializationLimits`.
+#[inline]
+pub fn deserialize_clarity_value_with_limits(
+    buffer: &[u8],
+    limits: &DeserializationLimits,
+) -> Result<Value, SerializationError> {
+    if buffer.len() > limits.max_bytes {
+        Err(SerializationError::MaxBytesExceeded)?
+    }
+
+    deserialize_value(buffer, limits, 1).map_err(|e| e.error)
+}
+
+/// Deserializes a Clarity `Value` from the provided buffer like
+/// `deserialize_clarity_value`, but on failure also reports the offset of and path
+/// to the (possibly nested) value which couldn't be deserialized.
+pub fn deserialize_clarity_value_with_context(
+    buffer: &[u8],
+) -> Result<Value, DeserializationError> {
+    let limits = DeserializationLimits::default();
+    if buffer.len() > limits.max_bytes {
+        Err(SerializationError::MaxBytesExceeded)?
+    }
+
+    deserialize_value(buffer, &limits, 1)
+}
+
+/// Deserializes the value in the provided buffer, which is nested at the given depth.
+fn deserialize_value(
+    buffer: &[u8],
+    limits: &DeserializationLimits,
+    depth: usize,
+) -> Result<Value, DeserializationError> {
+    if depth > limits.max_depth {
+        Err(SerializationError::MaxDepthExceeded)?
+    }
+
     // We cannot deserialize empty buffers.
     if buffer.is_empty() {
         Err(SerializationError::AttemtToDeserializeZeroLengthBuffer)?;
@@ -175,16 +581,13 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
     let type_indicator = type_indicator_byte_to_type_indicator(buffer[0])?;
 
     // Extract the length of this serialized value (excluding header).
-    let length_indicator_bytes: [u8; 2] = buffer[1..=2]
-        .try_into()
-        .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?;
-    let length_indicator = u16::from_le_bytes(length_indicator_bytes);
+    let (length_indicator, header_len) = read_value_header(buffer)?;
 
     // Create a slice that contains only the value bytes (excluding the header).
-    let value = &buffer[3..];
+    let value = &buffer[header_len..];
 
     // Ensure that our value slice length matches the parsed value length indicator.
-    let value_length = value.len() as u16;
+    let value_length = value.len();
     if value_length != length_indicator {
         Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
     }
@@ -195,7 +598,7 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
             if value_length != 16 {
                 Err(SerializationError::InvalidBufferLength {
                     expected: 16,
-                    received: value_length,
+                    received: value_length as u32,
                 })?;
             }
 
@@ -209,7 +612,7 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
             if value_length != 16 {
                 Err(SerializationError::InvalidBufferLength {
                     expected: 16,
-                    received: value_length,
+                    received: value_length as u32,
                 })?;
             }
 
@@ -219,21 +622,14 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
 
             Value::Int(i128::from_le_bytes(bytes))
         }
-        TypeIndicator::Bool => {
-            debug_assert!(
-                value_length == 1,
-                "Expected buffer length to be 1 for bool, received {value_length}"
-            );
-
-            let val = value[0];
-
-            debug_assert!(
-                value[0] == 1 || value[0] == 0,
-                "Expected boolean value to be 1 or 0, received {val}"
-            );
-
-            Value::Bool(val == 1)
-        }
+        TypeIndicator::Bool => match value {
+            [0] => Value::Bool(false),
+            [1] => Value::Bool(true),
+            _ => Err(SerializationError::InvalidBufferLength {
+                expected: 1,
+                received: value_length as u32,
+            })?,
+        },
         TypeIndicator::AsciiString => Value::string_ascii_from_bytes(value.to_vec())
             .map_err(|_| SerializationError::FailedToConvertBytesToAscii)?,
         TypeIndicator::Utf8String => Value::string_utf8_from_bytes(value.to_vec())
@@ -242,10 +638,15 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
             data: value.to_vec(),
         })),
         TypeIndicator::Response => {
-            let inner_value = deserialize_clarity_value(&value[1..])?;
+            let (indicator, inner) = value
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
+            let segment = if *indicator == 1 { ".ok" } else { ".err" };
+            let inner_value = deserialize_value(inner, limits, depth + 1)
+                .map_err(|e| e.within(buffer, inner, segment))?;
 
             // Read the first byte (indicator). 1/true = Ok, 0/false = Err.
-            if value[0] == 1 {
+            if *indicator == 1 {
                 // If Ok, we will deserialize using the Ok `TypeSignature` (position 0 in the tuple).
                 Value::Response(ResponseData {
                     committed: true,
@@ -260,12 +661,17 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
             }
         }
         TypeIndicator::Optional => {
+            let (indicator, inner) = value
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
+
             // Read the first byte (indicator). 1/true = Some, 0/false = None.
-            if value[0] == 1 {
+            if *indicator == 1 {
                 // If Some, grab the remainder of the buffer and deserialize using the Option `TypeSignature`.
                 // Note that there are no additional bytes if the value is None, so we only do this if we
                 // have a Some indicator above.
-                let val = deserialize_clarity_value(&value[1..])?;
+                let val = deserialize_value(inner, limits, depth + 1)
+                    .map_err(|e| e.within(buffer, inner, ".some"))?;
                 Value::Optional(OptionalData {
                     data: Some(Box::new(val)),
                 })
@@ -275,135 +681,105 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
             }
         }
         TypeIndicator::StandardPrincipal => {
-            // Extract the standard principal data from the buffer.
-            let standard_principal_data: [u8; 20] = value[1..=21]
-                .try_into()
-                .map_err(|_| SerializationError::IndexOutOfRange)?;
-
-            let standard_principal = StandardPrincipalData(
-                value[0],                // Version
-                standard_principal_data, // Data
-            );
-
+            let (standard_principal, _) = read_standard_principal(value)?;
             Value::Principal(PrincipalData::Standard(standard_principal))
         }
         TypeIndicator::ContractPrincipal => {
-            // Extract the standard principal data from the buffer.
-            let standard_principal_data: [u8; 20] = value[1..=21]
-                .try_into()
-                .map_err(|_| SerializationError::IndexOutOfRange)?;
-
-            let standard_principal = StandardPrincipalData(
-                value[0],                // Version
-                standard_principal_data, // Data
-            );
-
-            // Parse out the contract name length
-            let name_len_bytes: [u8; 2] = value[22..=23]
-                .try_into()
-                .map_err(|_| SerializationError::IndexOutOfRange)?;
-            let name_len = u16::from_le_bytes(name_len_bytes) as usize;
-
-            let mut name: &[u8] = &[];
-            if name_len > 0 {
-                name = &value[24..=(24 + name_len)];
-            }
-
-            // Convert the name to a string
-            let name_str =
-                std::str::from_utf8(name).map_err(|_| SerializationError::IndexOutOfRange)?;
+            // Extract the issuer's standard principal data and the contract name.
+            let (standard_principal, rest) = read_standard_principal(value)?;
+            let (name_str, _) =
+                read_name(rest).map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+            let name = ContractName::try_from(name_str.to_string())
+                .map_err(|_| SerializationError::FailedToDeserializeContractName)?;
 
             // Return the contract principal
             Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier {
                 issuer: standard_principal,
-                name: name_str.into(),
+                name,
             }))
         }
         TypeIndicator::List => {
-            // Split to retrieve the list length (first two bytes of the buffer)
-            let (list_len_bytes, value_bytes) = value.split_at(2);
-
             // Deserialize the list length
-            let list_len = u16::from_le_bytes(
-                list_len_bytes
-                    .try_into()
-                    .map_err(|_| SerializationError::FailedToDeserializeListLength)?,
-            );
+            let (list_len, list_len_size) = read_length(value)
+                .map_err(|_| SerializationError::FailedToDeserializeListLength)?;
+            if list_len > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+            // Deserialize the list's type, which every item must be admitted by.
+            let (type_signature, list_type_size) =
+                signature::read_list_type(&value[list_len_size..])?;
+            if list_len > type_signature.get_max_len() as usize {
+                Err(SerializationError::TypeMismatch)?
+            }
+            let item_type = type_signature.get_list_item_type();
+            let value_bytes = &value[list_len_size + list_type_size..];
 
-            let mut values = Vec::<Value>::with_capacity(list_len as usize);
+            let mut values = Vec::<Value>::with_capacity(list_len.min(value_bytes.len()));
             let mut index = 0;
 
-            for _i in 0..list_len {
+            for i in 0..list_len {
                 // Deserialize the length of the next item
-                let value_len = u16::from_le_bytes(
-                    value_bytes[(index + 1)..=(index + 2)]
-                        .try_into()
-                        .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?,
-                ) as usize;
-
-                let val_buffer = &value_bytes[index..=(index + value_len + 2)];
-                let val = deserialize_clarity_value(val_buffer)?;
+                let (value_len, item_header_len) = read_value_header(
+                    value_bytes
+                        .get(index..)
+                        .ok_or(SerializationError::IndexOutOfRange)?,
+                )?;
+                let item_len = item_header_len + value_len;
+
+                let val_buffer = value_bytes
+                    .get(index..index + item_len)
+                    .ok_or(SerializationError::IndexOutOfRange)?;
+                let val = deserialize_value(val_buffer, limits, depth + 1)
+                    .map_err(|e| e.within(buffer, val_buffer, format_args!("[{i}]")))?;
+                if !item_type.admits(&val) {
+                    Err(
+                        DeserializationError::from(SerializationError::TypeMismatch).within(
+                            buffer,
+                            val_buffer,
+                            format_args!("[{i}]"),
+                        ),
+                    )?
+                }
                 values.push(val);
-                index += value_len + 3;
+                index += item_len;
             }
 
-            Value::list_from(values).map_err(|_| SerializationError::FailedToDeserializeList)?
+            Value::Sequence(SequenceData::List(ListData {
+                data: values,
+                type_signature,
+            }))
         }
         TypeIndicator::CallableContract => {
-            // Extract the standard principal data from the buffer.
-            let standard_principal_data: [u8; 20] = value[1..=21]
-                .try_into()
-                .map_err(|_| SerializationError::IndexOutOfRange)?;
-
-            // Build the standard principal (contract identifier).
-            let standard_principal = StandardPrincipalData(
-                value[0],                // Version
-                standard_principal_data, // Data
-            );
-
-            // Extract the contract name
-            let ctr_name_len_bytes: [u8; 2] = value[22..=23]
-                .try_into()
-                .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?;
-            let ctr_name_len = u16::from_le_bytes(ctr_name_len_bytes);
-            let ctr_name_str = std::str::from_utf8(&value[24..(24 + ctr_name_len as usize)])
-                .map_err(|_| SerializationError::FailedToDeserializeContractName)?;
-
-            // Build the `QualifiedContractIdentifier`.
+            // Build the contract identifier from its principal and name.
+            let (standard_principal, rest) = read_standard_principal(value)?;
+            let (ctr_name_str, rest) =
+                read_name(rest).map_err(|_| SerializationError::FailedToDeserializeContractName)?;
             let contract_id = QualifiedContractIdentifier::new(
                 standard_principal,
-                ContractName::from(ctr_name_str),
+                ContractName::try_from(ctr_name_str.to_string())
+                    .map_err(|_| SerializationError::FailedToDeserializeContractName)?,
             );
 
             let mut trait_id: Option<TraitIdentifier> = None;
 
-            let (_, trait_bytes) = value.split_at(24 + ctr_name_len as usize);
+            let (trait_indicator, trait_bytes) = rest
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
 
             // If the trait identifier indicator is 1 then we also need to build
             // up the `TraitIdentifier`.
-            if trait_bytes[0] == 1 {
-                // Extract the trait principal data.
-                let trait_principal_data: [u8; 20] = value[2..=22]
-                    .try_into()
-                    .map_err(|_| SerializationError::IndexOutOfRange)?;
-
-                // Build the trait's standard principal.
-                let trait_principal = StandardPrincipalData(trait_bytes[1], trait_principal_data);
-
-                // Extract the trait name.
-                let trait_name_len_bytes: [u8; 2] = trait_bytes[23..=24]
-                    .try_into()
-                    .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?;
-                let trait_name_len = u16::from_le_bytes(trait_name_len_bytes);
-                let trait_name_str =
-                    std::str::from_utf8(&trait_bytes[24..(24 + trait_name_len as usize)])
-                        .map_err(|_| SerializationError::FailedToDeserializeTraitName)?;
+            if *trait_indicator == 1 {
+                // Build the trait's standard principal and extract the trait name.
+                let (trait_principal, rest) = read_standard_principal(trait_bytes)?;
+                let (trait_name_str, _) = read_name(rest)
+                    .map_err(|_| SerializationError::FailedToDeserializeTraitName)?;
 
                 // Construct the trait identifier and attach it to the contract identifier.
                 trait_id = Some(TraitIdentifier::new(
                     trait_principal,
                     contract_id.name.clone(),
-                    trait_name_str.into(),
+                    ClarityName::try_from(trait_name_str.to_string())
+                        .map_err(|_| SerializationError::FailedToDeserializeTraitName)?,
                 ));
             }
 
@@ -413,13 +789,71 @@ pub fn deserialize_clarity_value(buffer: &[u8]) -> Result<Value, SerializationEr
             })
         }
         TypeIndicator::Tuple => {
-            todo!("type not yet implemented")
+            // Deserialize the field count
+            let (field_count, field_count_size) =
+                read_length(value).map_err(|_| SerializationError::FailedToDeserializeTuple)?;
+            if field_count > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+            let mut field_bytes = &value[field_count_size..];
+
+            let mut fields =
+                Vec::<(ClarityName, Value)>::with_capacity(field_count.min(field_bytes.len()));
+
+            for _i in 0..field_count {
+                // Each field starts with a one-byte name length indicator and the name.
+                let (name_len, rest) = field_bytes
+                    .split_first()
+                    .ok_or(SerializationError::FailedToDeserializeTuple)?;
+                let name = rest
+                    .get(..*name_len as usize)
+                    .ok_or(SerializationError::FailedToDeserializeTupleFieldName)?;
+                let name = std::str::from_utf8(name)
+                    .map_err(|_| SerializationError::FailedToDeserializeTupleFieldName)?;
+                let name = ClarityName::try_from(name.to_string())
+                    .map_err(|_| SerializationError::FailedToDeserializeTupleFieldName)?;
+                let rest = &rest[*name_len as usize..];
+
+                // ... followed by the serialized value, including its own header.
+                let (value_len, value_header_len) = read_value_header(rest)?;
+                let value_len = value_header_len + value_len;
+                let val_buffer = rest
+                    .get(..value_len)
+                    .ok_or(SerializationError::FailedToDeserializeTuple)?;
+
+                let val = deserialize_value(val_buffer, limits, depth + 1)
+                    .map_err(|e| e.within(buffer, val_buffer, format_args!(".{name}")))?;
+                fields.push((name, val));
+                field_bytes = &rest[value_len..];
+            }
+
+            Value::Tuple(
+                TupleData::from_data(fields)
+                    .map_err(|_| SerializationError::FailedToDeserializeTuple)?,
+            )
         }
     };
 
     Ok(val)
 }
 
+/// Deserializes a Clarity `Value` from the provided buffer and validates it against
+/// the expected `TypeSignature` (element types, maximum lengths, tuple fields,
+/// etc.), failing with `TypeMismatch` if the value isn't admitted by it. Use this
+/// rather than `deserialize_clarity_value` for values produced by untrusted Wasm
+/// modules.
+#[inline]
+pub fn deserialize_clarity_value_typed(
+    buffer: &[u8],
+    type_signature: &TypeSignature,
+) -> Result<Value, SerializationError> {
+    let value = deserialize_clarity_value(buffer)?;
+    if !type_signature.admits(&value) {
+        Err(SerializationError::TypeMismatch)?
+    }
+    Ok(value)
+}
+
 /// Convert a Clarity 'Value' into a byte buffer. This is intended to be used
 /// together with `pass_argument_to_wasm` for generating the buffer to be written
 /// to WASM linear memory. More documentation regarding how values are serialized
@@ -457,14 +891,10 @@ pub fn serialize_clarity_value(value: &Value) -> Result<Vec<u8>, SerializationEr
                 result.extend_from_slice(&s.data);
             }
             CharType::UTF8(s) => {
-                let mut data = s
-                    .data
-                    .iter()
-                    .flat_map(|s| s.iter())
-                    .map(|e| *e)
-                    .collect::<Vec<u8>>();
-
-                result.append(&mut data);
+                check_utf8_chars(s)?;
+                for c in &s.data {
+                    result.extend_from_slice(c);
+                }
             }
         },
         Value::Sequence(SequenceData::Buffer(b)) => {
@@ -472,7 +902,13 @@ pub fn serialize_clarity_value(value: &Value) -> Result<Vec<u8>, SerializationEr
         }
         Value::Sequence(SequenceData::List(l)) => {
             // Append the list length indicator
-            result.extend_from_slice(&(l.data.len() as u16).to_le_bytes());
+            write_length(&mut result, l.data.len())?;
+
+            // Append the list's type
+            signature::write_list_type(&l.type_signature, &mut |bytes| {
+                result.extend_from_slice(bytes);
+                Ok(())
+            })?;
 
             // Append each list item
             for item in &l.data {
@@ -536,15 +972,21 @@ pub fn serialize_clarity_value(value: &Value) -> Result<Vec<u8>, SerializationEr
             }
         }
         Value::Tuple(tuple) => {
-            for val in &tuple.data_map {
-                let mut data = serialize_clarity_value(val.1)?;
-                result.append(&mut data);
+            // Append the field count indicator
+            write_length(&mut result, tuple.data_map.len())?;
+
+            // Append each field as its name (prefixed with a one-byte length, as
+            // Clarity names are at most 128 characters) followed by its value. The
+            // fields are sorted by name, so the output is stable.
+            for (name, val) in &tuple.data_map {
+                result.push(name.len() as u8);
+                result.extend_from_slice(name.as_bytes());
+                result.append(&mut serialize_clarity_value(val)?);
             }
         }
     }
 
-    let len_bytes = (result.len() as u16).to_le_bytes();
-    header.extend_from_slice(&len_bytes);
+    write_length(&mut header, result.len())?;
 
     header.append(&mut result);
 
@@ -0,0 +1,72 @@
+use super::{
+    read_length, read_value_header, type_indicator_byte_to_type_indicator, ClaritySeqIterator,
+    SerializationError, TypeIndicator,
+};
+use crate::Ptr;
+
+/// Locates the element at the given index of the serialized list in `buffer`,
+/// returning its location (header included) relative to the start of `buffer`, or
+/// `None` if the index is out of range. Only the headers of the preceding elements
+/// are read, so the element can be passed on or deserialized on its own without
+/// decoding the rest of the list.
+pub fn get_list_element(buffer: &[u8], index: usize) -> Result<Option<Ptr>, SerializationError> {
+    for (i, element) in ClaritySeqIterator::new(buffer)?.enumerate() {
+        let element = element?;
+        if i == index {
+            return Ok(Some(element.ptr));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Locates the field with the given name of the serialized tuple in `buffer`,
+/// returning the location of its value (header included) relative to the start of
+/// `buffer`, or `None` if the tuple has no such field. Only the headers of the
+/// preceding fields are read.
+pub fn get_tuple_field(buffer: &[u8], name: &str) -> Result<Option<Ptr>, SerializationError> {
+    let type_indicator = type_indicator_byte_to_type_indicator(
+        *buffer
+            .first()
+            .ok_or(SerializationError::AttemtToDeserializeZeroLengthBuffer)?,
+    )?;
+    if type_indicator != TypeIndicator::Tuple {
+        Err(SerializationError::TypeNotAllowed {
+            received: type_indicator,
+        })?;
+    }
+
+    let (length_indicator, header_len) = read_value_header(buffer)?;
+    if buffer.len() - header_len != length_indicator {
+        Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+    }
+
+    let (field_count, field_count_size) = read_length(&buffer[header_len..])
+        .map_err(|_| SerializationError::FailedToDeserializeTuple)?;
+    let mut offset = header_len + field_count_size;
+
+    for _i in 0..field_count {
+        // Each field starts with a one-byte name length indicator and the name ...
+        let name_len = *buffer
+            .get(offset)
+            .ok_or(SerializationError::FailedToDeserializeTuple)? as usize;
+        let field_name = buffer
+            .get(offset + 1..offset + 1 + name_len)
+            .ok_or(SerializationError::FailedToDeserializeTupleFieldName)?;
+        offset += 1 + name_len;
+
+        // ... followed by the serialized value, including its own header.
+        let (value_len, value_header_len) = read_value_header(&buffer[offset..])?;
+        let value_len = value_header_len + value_len;
+        if buffer.len() - offset < value_len {
+            Err(SerializationError::FailedToDeserializeTuple)?
+        }
+
+        if field_name == name.as_bytes() {
+            return Ok(Some(Ptr::new(offset as i32, value_len as i32)));
+        }
+        offset += value_len;
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+
+use clarity::vm::{
+    types::{
+        ASCIIData, BuffData, CharType, ListData, ListTypeData, SequenceData, TupleData, UTF8Data,
+    },
+    ClarityName, Value,
+};
+
+use super::{
+    deserialize_clarity_value, read_length, read_value_header, signature::read_list_type,
+    type_indicator_byte_to_type_indicator, SerializationError, TypeIndicator,
+};
+
+/// A view of a serialized Clarity value which borrows the bytes of strings and
+/// buffers from the buffer it was deserialized from, rather than copying each of
+/// them into a new `Vec`. Values without variable-length byte data (principals and
+/// callable contracts) are deserialized as owned `Value`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClarityValueRef<'a> {
+    UInt(u128),
+    Int(i128),
+    Bool(bool),
+    Optional(Option<Box<ClarityValueRef<'a>>>),
+    Response {
+        committed: bool,
+        data: Box<ClarityValueRef<'a>>,
+    },
+    AsciiString(Cow<'a, [u8]>),
+    Utf8String(Cow<'a, str>),
+    Buffer(Cow<'a, [u8]>),
+    List(Vec<ClarityValueRef<'a>>, ListTypeData),
+    Tuple(Vec<(Cow<'a, str>, ClarityValueRef<'a>)>),
+    Owned(Value),
+}
+
+impl<'a> ClarityValueRef<'a> {
+    /// Converts this view into an owned Clarity `Value`, copying any borrowed bytes.
+    pub fn into_value(self) -> Result<Value, SerializationError> {
+        let value = match self {
+            ClarityValueRef::UInt(n) => Value::UInt(n),
+            ClarityValueRef::Int(n) => Value::Int(n),
+            ClarityValueRef::Bool(b) => Value::Bool(b),
+            ClarityValueRef::Optional(None) => Value::none(),
+            ClarityValueRef::Optional(Some(data)) => {
+                Value::some(data.into_value()?).map_err(|_| SerializationError::ValueTooLarge)?
+            }
+            ClarityValueRef::Response { committed, data } => {
+                let data = data.into_value()?;
+                if committed {
+                    Value::okay(data)
+                } else {
+                    Value::error(data)
+                }
+                .map_err(|_| SerializationError::ValueTooLarge)?
+            }
+            ClarityValueRef::AsciiString(bytes) => {
+                Value::Sequence(SequenceData::String(CharType::ASCII(ASCIIData {
+                    data: bytes.into_owned(),
+                })))
+            }
+            ClarityValueRef::Utf8String(str) => {
+                Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data {
+                    data: str.chars().map(|c| c.to_string().into_bytes()).collect(),
+                })))
+            }
+            ClarityValueRef::Buffer(bytes) => Value::Sequence(SequenceData::Buffer(BuffData {
+                data: bytes.into_owned(),
+            })),
+            ClarityValueRef::List(items, type_signature) => {
+                let data = items
+                    .into_iter()
+                    .map(ClarityValueRef::into_value)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let item_type = type_signature.get_list_item_type();
+                if !data.iter().all(|item| item_type.admits(item)) {
+                    Err(SerializationError::TypeMismatch)?
+                }
+                Value::Sequence(SequenceData::List(ListData {
+                    data,
+                    type_signature,
+                }))
+            }
+            ClarityValueRef::Tuple(fields) => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let name = ClarityName::try_from(name.into_owned())
+                            .map_err(|_| SerializationError::FailedToDeserializeTupleFieldName)?;
+                        Ok((name, value.into_value()?))
+                    })
+                    .collect::<Result<Vec<_>, SerializationError>>()?;
+                Value::Tuple(
+                    TupleData::from_data(fields)
+                        .map_err(|_| SerializationError::FailedToDeserializeTuple)?,
+                )
+            }
+            ClarityValueRef::Owned(value) => value,
+        };
+
+        Ok(value)
+    }
+}
+
+/// Deserializes a Clarity value from the provided buffer without copying the bytes
+/// of strings and buffers, which are borrowed from `buffer` instead. This is useful
+/// when reading large sequences directly out of Wasm memory. Use
+/// `ClarityValueRef::into_value` to obtain an owned `Value`.
+#[inline]
+pub fn deserialize_clarity_value_borrowed(
+    buffer: &[u8],
+) -> Result<ClarityValueRef<'_>, SerializationError> {
+    // We cannot deserialize empty buffers.
+    let type_indicator = type_indicator_byte_to_type_indicator(
+        *buffer
+            .first()
+            .ok_or(SerializationError::AttemtToDeserializeZeroLengthBuffer)?,
+    )?;
+
+    // Extract the value bytes (excluding the header), ensuring that they match the
+    // length indicator.
+    let (length_indicator, header_len) = read_value_header(buffer)?;
+    let value = &buffer[header_len..];
+    if value.len() != length_indicator {
+        Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+    }
+
+    let val = match type_indicator {
+        TypeIndicator::UInt | TypeIndicator::Int => {
+            let bytes: [u8; 16] =
+                value
+                    .try_into()
+                    .map_err(|_| SerializationError::InvalidBufferLength {
+                        expected: 16,
+                        received: value.len() as u32,
+                    })?;
+            if type_indicator == TypeIndicator::UInt {
+                ClarityValueRef::UInt(u128::from_le_bytes(bytes))
+            } else {
+                ClarityValueRef::Int(i128::from_le_bytes(bytes))
+            }
+        }
+        TypeIndicator::Bool => match value {
+            [0] => ClarityValueRef::Bool(false),
+            [1] => ClarityValueRef::Bool(true),
+            _ => Err(SerializationError::InvalidBufferLength {
+                expected: 1,
+                received: value.len() as u32,
+            })?,
+        },
+        TypeIndicator::Optional => match value.split_first() {
+            Some((1, inner)) => ClarityValueRef::Optional(Some(Box::new(
+                deserialize_clarity_value_borrowed(inner)?,
+            ))),
+            Some(_) => ClarityValueRef::Optional(None),
+            None => Err(SerializationError::IndexOutOfRange)?,
+        },
+        TypeIndicator::Response => {
+            let (committed, inner) = value
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
+            ClarityValueRef::Response {
+                committed: *committed == 1,
+                data: Box::new(deserialize_clarity_value_borrowed(inner)?),
+            }
+        }
+        TypeIndicator::AsciiString => {
+            if !value.is_ascii() {
+                Err(SerializationError::FailedToConvertBytesToAscii)?
+            }
+            ClarityValueRef::AsciiString(Cow::Borrowed(value))
+        }
+        TypeIndicator::Utf8String => ClarityValueRef::Utf8String(Cow::Borrowed(
+            std::str::from_utf8(value)
+                .map_err(|_| SerializationError::FailedToConvertBytesToUtf8)?,
+        )),
+        TypeIndicator::Buffer => ClarityValueRef::Buffer(Cow::Borrowed(value)),
+        TypeIndicator::List => {
+            let (list_len, list_len_size) = read_length(value)
+                .map_err(|_| SerializationError::FailedToDeserializeListLength)?;
+            let (type_signature, list_type_size) = read_list_type(&value[list_len_size..])?;
+            if list_len > type_signature.get_max_len() as usize {
+                Err(SerializationError::TypeMismatch)?
+            }
+            let mut rest = &value[list_len_size + list_type_size..];
+            // Don't trust the length indicator for the allocation size; every item
+            // takes up at least one byte.
+            let mut items = Vec::with_capacity(list_len.min(rest.len()));
+
+            for _i in 0..list_len {
+                let (item, remainder) = split_value(rest)?;
+                items.push(deserialize_clarity_value_borrowed(item)?);
+                rest = remainder;
+            }
+
+            ClarityValueRef::List(items, type_signature)
+        }
+        TypeIndicator::Tuple => {
+            let (field_count, field_count_size) =
+                read_length(value).map_err(|_| SerializationError::FailedToDeserializeTuple)?;
+            let mut rest = &value[field_count_size..];
+            let mut fields = Vec::with_capacity(field_count.min(rest.len()));
+
+            for _i in 0..field_count {
+                let (name_len, remainder) = rest
+                    .split_first()
+                    .ok_or(SerializationError::FailedToDeserializeTuple)?;
+                let name = remainder
+                    .get(..*name_len as usize)
+                    .ok_or(SerializationError::FailedToDeserializeTupleFieldName)?;
+                let name = std::str::from_utf8(name)
+                    .map_err(|_| SerializationError::FailedToDeserializeTupleFieldName)?;
+
+                let (item, remainder) = split_value(&remainder[*name_len as usize..])?;
+                fields.push((
+                    Cow::Borrowed(name),
+                    deserialize_clarity_value_borrowed(item)?,
+                ));
+                rest = remainder;
+            }
+
+            ClarityValueRef::Tuple(fields)
+        }
+        TypeIndicator::StandardPrincipal
+        | TypeIndicator::ContractPrincipal
+        | TypeIndicator::CallableContract => {
+            ClarityValueRef::Owned(deserialize_clarity_value(buffer)?)
+        }
+    };
+
+    Ok(val)
+}
+
+/// Splits the serialized value (header included) at the start of the provided
+/// buffer from the bytes which follow it.
+#[inline]
+fn split_value(buffer: &[u8]) -> Result<(&[u8], &[u8]), SerializationError> {
+    let (value_len, header_len) = read_value_header(buffer)?;
+    let len = header_len + value_len;
+    if buffer.len() < len {
+        Err(SerializationError::IndexOutOfRange)?
+    }
+    Ok(buffer.split_at(len))
+}
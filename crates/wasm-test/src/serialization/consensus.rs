@@ -0,0 +1,693 @@
+use clarity::vm::{
+    types::{
+        BuffData, CharType, PrincipalData, QualifiedContractIdentifier, SequenceData,
+        StandardPrincipalData, TupleData, TypeSignature,
+    },
+    ClarityName, ContractName, Value,
+};
+
+use super::{
+    check_utf8_chars, deserialize_clarity_value, read_length, read_name, read_standard_principal,
+    read_value_header,
+    signature::{skip_list_type, write_list_type},
+    type_indicator_byte_to_type_indicator, write_length, DeserializationLimits, SerializationError,
+    TypeIndicator, LENGTH_ESCAPE, PACKED_LIST_FLAG,
+};
+
+// Type prefixes of Clarity's consensus serialization format (SIP-005).
+const PREFIX_INT: u8 = 0x00;
+const PREFIX_UINT: u8 = 0x01;
+const PREFIX_BUFFER: u8 = 0x02;
+const PREFIX_BOOL_TRUE: u8 = 0x03;
+const PREFIX_BOOL_FALSE: u8 = 0x04;
+const PREFIX_STANDARD_PRINCIPAL: u8 = 0x05;
+const PREFIX_CONTRACT_PRINCIPAL: u8 = 0x06;
+const PREFIX_RESPONSE_OK: u8 = 0x07;
+const PREFIX_RESPONSE_ERR: u8 = 0x08;
+const PREFIX_NONE: u8 = 0x09;
+const PREFIX_SOME: u8 = 0x0a;
+const PREFIX_LIST: u8 = 0x0b;
+const PREFIX_TUPLE: u8 = 0x0c;
+const PREFIX_STRING_ASCII: u8 = 0x0d;
+const PREFIX_STRING_UTF8: u8 = 0x0e;
+
+/// Serializes a Clarity `Value` using Clarity's consensus serialization format, as
+/// described in SIP-005. The output is byte-compatible with the on-chain
+/// representation of the value. Callable contracts are serialized as their contract
+/// principal, as in Clarity.
+#[inline]
+pub fn serialize_consensus(value: &Value) -> Result<Vec<u8>, SerializationError> {
+    let mut result = Vec::<u8>::with_capacity(256);
+    write_value(&mut result, value)?;
+    Ok(result)
+}
+
+/// Deserializes a Clarity `Value` from Clarity's consensus serialization format.
+/// The buffer must contain exactly one value.
+#[inline]
+pub fn deserialize_consensus(buffer: &[u8]) -> Result<Value, SerializationError> {
+    let mut reader = Reader { buffer, offset: 0 };
+    let value = read_value(&mut reader)?;
+
+    if reader.offset != buffer.len() {
+        Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+    }
+
+    Ok(value)
+}
+
+/// Transcodes a value serialized by `serialize_clarity_value` (or
+/// `serialize_packed_int_list`) to Clarity's consensus serialization format,
+/// without deserializing it to a `Value`. The output is the same as that of
+/// `serialize_consensus` for the deserialized value.
+pub fn to_consensus_bytes(buffer: &[u8]) -> Result<Vec<u8>, SerializationError> {
+    let limits = DeserializationLimits::default();
+    if buffer.len() > limits.max_bytes {
+        Err(SerializationError::MaxBytesExceeded)?
+    }
+
+    let mut result = Vec::<u8>::with_capacity(buffer.len());
+    transcode_to_consensus(&mut result, buffer, &limits, 1)?;
+    Ok(result)
+}
+
+/// Transcodes a value from Clarity's consensus serialization format to this
+/// crate's format, without deserializing it to a `Value`. The output is the same
+/// as that of `serialize_clarity_value` for the deserialized value. The buffer must
+/// contain exactly one value.
+pub fn from_consensus_bytes(buffer: &[u8]) -> Result<Vec<u8>, SerializationError> {
+    let limits = DeserializationLimits::default();
+    let mut reader = Reader { buffer, offset: 0 };
+    let mut result = Vec::<u8>::with_capacity(buffer.len() * 2);
+    transcode_from_consensus(&mut result, &mut reader, &limits, 1)?;
+
+    if reader.offset != buffer.len() {
+        Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+    }
+
+    Ok(result)
+}
+
+/// Writes a `u32` length prefix, which is big-endian in the consensus format.
+#[inline]
+fn write_u32_len(buffer: &mut Vec<u8>, len: usize) -> Result<(), SerializationError> {
+    let len = u32::try_from(len).map_err(|_| SerializationError::ValueTooLarge)?;
+    buffer.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn write_standard_principal(buffer: &mut Vec<u8>, principal: &StandardPrincipalData) {
+    buffer.push(principal.0);
+    buffer.extend_from_slice(&principal.1);
+}
+
+fn write_contract_principal(buffer: &mut Vec<u8>, contract: &QualifiedContractIdentifier) {
+    buffer.push(PREFIX_CONTRACT_PRINCIPAL);
+    write_standard_principal(buffer, &contract.issuer);
+    // Contract names are at most 128 bytes, so a one-byte length suffices.
+    buffer.push(contract.name.len() as u8);
+    buffer.extend_from_slice(contract.name.as_bytes());
+}
+
+fn write_value(buffer: &mut Vec<u8>, value: &Value) -> Result<(), SerializationError> {
+    match value {
+        Value::Int(n) => {
+            buffer.push(PREFIX_INT);
+            buffer.extend_from_slice(&n.to_be_bytes());
+        }
+        Value::UInt(n) => {
+            buffer.push(PREFIX_UINT);
+            buffer.extend_from_slice(&n.to_be_bytes());
+        }
+        Value::Bool(true) => buffer.push(PREFIX_BOOL_TRUE),
+        Value::Bool(false) => buffer.push(PREFIX_BOOL_FALSE),
+        Value::Optional(o) => match &o.data {
+            Some(data) => {
+                buffer.push(PREFIX_SOME);
+                write_value(buffer, data)?;
+            }
+            None => buffer.push(PREFIX_NONE),
+        },
+        Value::Response(r) => {
+            buffer.push(if r.committed {
+                PREFIX_RESPONSE_OK
+            } else {
+                PREFIX_RESPONSE_ERR
+            });
+            write_value(buffer, &r.data)?;
+        }
+        Value::Sequence(SequenceData::Buffer(b)) => {
+            buffer.push(PREFIX_BUFFER);
+            write_u32_len(buffer, b.data.len())?;
+            buffer.extend_from_slice(&b.data);
+        }
+        Value::Sequence(SequenceData::String(CharType::ASCII(s))) => {
+            buffer.push(PREFIX_STRING_ASCII);
+            write_u32_len(buffer, s.data.len())?;
+            buffer.extend_from_slice(&s.data);
+        }
+        Value::Sequence(SequenceData::String(CharType::UTF8(s))) => {
+            check_utf8_chars(s)?;
+            buffer.push(PREFIX_STRING_UTF8);
+            write_u32_len(buffer, s.data.iter().map(Vec::len).sum())?;
+            for c in &s.data {
+                buffer.extend_from_slice(c);
+            }
+        }
+        Value::Sequence(SequenceData::List(l)) => {
+            buffer.push(PREFIX_LIST);
+            write_u32_len(buffer, l.data.len())?;
+            for item in &l.data {
+                write_value(buffer, item)?;
+            }
+        }
+        Value::Principal(PrincipalData::Standard(std)) => {
+            buffer.push(PREFIX_STANDARD_PRINCIPAL);
+            write_standard_principal(buffer, std);
+        }
+        Value::Principal(PrincipalData::Contract(ctr)) => write_contract_principal(buffer, ctr),
+        Value::CallableContract(ctr) => write_contract_principal(buffer, &ctr.contract_identifier),
+        Value::Tuple(tuple) => {
+            buffer.push(PREFIX_TUPLE);
+            write_u32_len(buffer, tuple.data_map.len())?;
+            // The fields are sorted by name, as required by the consensus format.
+            for (name, val) in &tuple.data_map {
+                buffer.push(name.len() as u8);
+                buffer.extend_from_slice(name.as_bytes());
+                write_value(buffer, val)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads from a consensus-serialized buffer, failing rather than panicking when the
+/// buffer is truncated.
+struct Reader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], SerializationError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .ok_or(SerializationError::IndexOutOfRange)?;
+        let bytes = self
+            .buffer
+            .get(self.offset..end)
+            .ok_or(SerializationError::IndexOutOfRange)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SerializationError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32_len(&mut self) -> Result<usize, SerializationError> {
+        let bytes: [u8; 4] = self
+            .read_bytes(4)?
+            .try_into()
+            .map_err(|_| SerializationError::FailedToDeserializeLengthIndicator)?;
+        Ok(u32::from_be_bytes(bytes) as usize)
+    }
+
+    fn read_16(&mut self) -> Result<[u8; 16], SerializationError> {
+        self.read_bytes(16)?
+            .try_into()
+            .map_err(|_| SerializationError::IndexOutOfRange)
+    }
+
+    fn read_standard_principal(&mut self) -> Result<StandardPrincipalData, SerializationError> {
+        let version = self.read_u8()?;
+        let data: [u8; 20] = self
+            .read_bytes(20)?
+            .try_into()
+            .map_err(|_| SerializationError::IndexOutOfRange)?;
+        Ok(StandardPrincipalData(version, data))
+    }
+
+    fn read_name(&mut self) -> Result<&'a str, SerializationError> {
+        let len = self.read_u8()? as usize;
+        std::str::from_utf8(self.read_bytes(len)?)
+            .map_err(|_| SerializationError::FailedToDeserializeTupleFieldName)
+    }
+}
+
+fn read_value(reader: &mut Reader<'_>) -> Result<Value, SerializationError> {
+    let value = match reader.read_u8()? {
+        PREFIX_INT => Value::Int(i128::from_be_bytes(reader.read_16()?)),
+        PREFIX_UINT => Value::UInt(u128::from_be_bytes(reader.read_16()?)),
+        PREFIX_BOOL_TRUE => Value::Bool(true),
+        PREFIX_BOOL_FALSE => Value::Bool(false),
+        PREFIX_NONE => Value::none(),
+        PREFIX_SOME => {
+            Value::some(read_value(reader)?).map_err(|_| SerializationError::ValueTooLarge)?
+        }
+        PREFIX_RESPONSE_OK => {
+            Value::okay(read_value(reader)?).map_err(|_| SerializationError::ValueTooLarge)?
+        }
+        PREFIX_RESPONSE_ERR => {
+            Value::error(read_value(reader)?).map_err(|_| SerializationError::ValueTooLarge)?
+        }
+        PREFIX_BUFFER => {
+            let len = reader.read_u32_len()?;
+            Value::Sequence(SequenceData::Buffer(BuffData {
+                data: reader.read_bytes(len)?.to_vec(),
+            }))
+        }
+        PREFIX_STRING_ASCII => {
+            let len = reader.read_u32_len()?;
+            Value::string_ascii_from_bytes(reader.read_bytes(len)?.to_vec())
+                .map_err(|_| SerializationError::FailedToConvertBytesToAscii)?
+        }
+        PREFIX_STRING_UTF8 => {
+            let len = reader.read_u32_len()?;
+            Value::string_utf8_from_bytes(reader.read_bytes(len)?.to_vec())
+                .map_err(|_| SerializationError::FailedToConvertBytesToUtf8)?
+        }
+        PREFIX_LIST => {
+            let len = reader.read_u32_len()?;
+            // Every item takes up at least one byte, which bounds the allocation.
+            let mut items = Vec::with_capacity(len.min(reader.buffer.len()));
+            for _i in 0..len {
+                items.push(read_value(reader)?);
+            }
+            Value::list_from(items).map_err(|_| SerializationError::FailedToDeserializeList)?
+        }
+        PREFIX_TUPLE => {
+            let len = reader.read_u32_len()?;
+            let mut fields = Vec::with_capacity(len.min(reader.buffer.len()));
+            for _i in 0..len {
+                let name = ClarityName::try_from(reader.read_name()?.to_string())
+                    .map_err(|_| SerializationError::FailedToDeserializeTupleFieldName)?;
+                fields.push((name, read_value(reader)?));
+            }
+            Value::Tuple(
+                TupleData::from_data(fields)
+                    .map_err(|_| SerializationError::FailedToDeserializeTuple)?,
+            )
+        }
+        PREFIX_STANDARD_PRINCIPAL => {
+            Value::Principal(PrincipalData::Standard(reader.read_standard_principal()?))
+        }
+        PREFIX_CONTRACT_PRINCIPAL => {
+            let issuer = reader.read_standard_principal()?;
+            let name = reader
+                .read_name()
+                .and_then(|name| {
+                    ContractName::try_from(name.to_string())
+                        .map_err(|_| SerializationError::FailedToDeserializeContractName)
+                })
+                .map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+            Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier {
+                issuer,
+                name,
+            }))
+        }
+        prefix => Err(SerializationError::InvalidTypeIndicator(prefix))?,
+    };
+
+    Ok(value)
+}
+
+/// Transcodes the single value in `buffer` (in this crate's format) to the
+/// consensus format. This accepts the same buffers as `deserialize_clarity_value`.
+fn transcode_to_consensus(
+    out: &mut Vec<u8>,
+    buffer: &[u8],
+    limits: &DeserializationLimits,
+    depth: usize,
+) -> Result<(), SerializationError> {
+    if depth > limits.max_depth {
+        Err(SerializationError::MaxDepthExceeded)?
+    }
+
+    let indicator = *buffer
+        .first()
+        .ok_or(SerializationError::AttemtToDeserializeZeroLengthBuffer)?;
+    let (len, header_len) = read_value_header(buffer)?;
+    let body = &buffer[header_len..];
+    if body.len() != len {
+        Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+    }
+
+    if indicator & PACKED_LIST_FLAG != 0 {
+        return transcode_packed_list_to_consensus(out, indicator, body, limits);
+    }
+
+    match type_indicator_byte_to_type_indicator(indicator)? {
+        TypeIndicator::UInt | TypeIndicator::Int => {
+            let mut bytes: [u8; 16] =
+                body.try_into()
+                    .map_err(|_| SerializationError::InvalidBufferLength {
+                        expected: 16,
+                        received: body.len() as u32,
+                    })?;
+            // Integers are little-endian in this crate's format, but big-endian in
+            // the consensus format.
+            bytes.reverse();
+            out.push(if indicator == TypeIndicator::Int as u8 {
+                PREFIX_INT
+            } else {
+                PREFIX_UINT
+            });
+            out.extend_from_slice(&bytes);
+        }
+        TypeIndicator::Bool => match body {
+            [0] => out.push(PREFIX_BOOL_FALSE),
+            [1] => out.push(PREFIX_BOOL_TRUE),
+            _ => Err(SerializationError::InvalidBufferLength {
+                expected: 1,
+                received: body.len() as u32,
+            })?,
+        },
+        TypeIndicator::Optional => match body.split_first() {
+            Some((1, inner)) => {
+                out.push(PREFIX_SOME);
+                transcode_to_consensus(out, inner, limits, depth + 1)?;
+            }
+            Some(_) => out.push(PREFIX_NONE),
+            None => Err(SerializationError::IndexOutOfRange)?,
+        },
+        TypeIndicator::Response => {
+            let (indicator, inner) = body
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
+            out.push(if *indicator == 1 {
+                PREFIX_RESPONSE_OK
+            } else {
+                PREFIX_RESPONSE_ERR
+            });
+            transcode_to_consensus(out, inner, limits, depth + 1)?;
+        }
+        TypeIndicator::AsciiString => {
+            if !body.is_ascii() {
+                Err(SerializationError::FailedToConvertBytesToAscii)?
+            }
+            out.push(PREFIX_STRING_ASCII);
+            write_u32_len(out, body.len())?;
+            out.extend_from_slice(body);
+        }
+        TypeIndicator::Utf8String => {
+            std::str::from_utf8(body)
+                .map_err(|_| SerializationError::FailedToConvertBytesToUtf8)?;
+            out.push(PREFIX_STRING_UTF8);
+            write_u32_len(out, body.len())?;
+            out.extend_from_slice(body);
+        }
+        TypeIndicator::Buffer => {
+            out.push(PREFIX_BUFFER);
+            write_u32_len(out, body.len())?;
+            out.extend_from_slice(body);
+        }
+        TypeIndicator::List => {
+            let (count, count_size) =
+                read_length(body).map_err(|_| SerializationError::FailedToDeserializeListLength)?;
+            if count > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+
+            out.push(PREFIX_LIST);
+            write_u32_len(out, count)?;
+            // The consensus format doesn't carry the list's type.
+            let list_type_size = skip_list_type(&body[count_size..])?;
+            let mut items = &body[count_size + list_type_size..];
+            for _i in 0..count {
+                let (item, rest) = split_value(items)?;
+                transcode_to_consensus(out, item, limits, depth + 1)?;
+                items = rest;
+            }
+        }
+        TypeIndicator::Tuple => {
+            let (count, count_size) =
+                read_length(body).map_err(|_| SerializationError::FailedToDeserializeTuple)?;
+            if count > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+
+            let mut fields = Vec::<(&[u8], &[u8])>::with_capacity(count.min(body.len()));
+            let mut field_bytes = &body[count_size..];
+            for _i in 0..count {
+                let (name_len, rest) = field_bytes
+                    .split_first()
+                    .ok_or(SerializationError::FailedToDeserializeTuple)?;
+                let name = rest
+                    .get(..*name_len as usize)
+                    .ok_or(SerializationError::FailedToDeserializeTupleFieldName)?;
+                let (value, rest) = split_value(&rest[name.len()..])?;
+                fields.push((name, value));
+                field_bytes = rest;
+            }
+
+            // The consensus format requires the fields to be sorted by name.
+            fields.sort_unstable_by_key(|(name, _)| *name);
+            if fields.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                Err(SerializationError::FailedToDeserializeTuple)?
+            }
+
+            out.push(PREFIX_TUPLE);
+            write_u32_len(out, fields.len())?;
+            for (name, value) in fields {
+                out.push(name.len() as u8);
+                out.extend_from_slice(name);
+                transcode_to_consensus(out, value, limits, depth + 1)?;
+            }
+        }
+        TypeIndicator::StandardPrincipal => {
+            let (principal, _) = read_standard_principal(body)?;
+            out.push(PREFIX_STANDARD_PRINCIPAL);
+            write_standard_principal(out, &principal);
+        }
+        TypeIndicator::ContractPrincipal | TypeIndicator::CallableContract => {
+            // Callable contracts are written as their contract principal, as in
+            // `serialize_consensus`.
+            let (issuer, rest) = read_standard_principal(body)?;
+            let (name, _) =
+                read_name(rest).map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+            let name_len = u8::try_from(name.len())
+                .map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+
+            out.push(PREFIX_CONTRACT_PRINCIPAL);
+            write_standard_principal(out, &issuer);
+            out.push(name_len);
+            out.extend_from_slice(name.as_bytes());
+        }
+    }
+
+    Ok(())
+}
+
+/// Transcodes the body of a packed integer list (see `packed.rs`) to a consensus
+/// list of integers.
+fn transcode_packed_list_to_consensus(
+    out: &mut Vec<u8>,
+    indicator: u8,
+    body: &[u8],
+    limits: &DeserializationLimits,
+) -> Result<(), SerializationError> {
+    let prefix = match type_indicator_byte_to_type_indicator(indicator ^ PACKED_LIST_FLAG) {
+        Ok(TypeIndicator::Int) => PREFIX_INT,
+        Ok(TypeIndicator::UInt) => PREFIX_UINT,
+        _ => Err(SerializationError::InvalidTypeIndicator(indicator))?,
+    };
+    if body.len() % 16 != 0 {
+        Err(SerializationError::FailedToDeserializeList)?
+    }
+    if body.len() / 16 > limits.max_elements {
+        Err(SerializationError::MaxElementsExceeded)?
+    }
+
+    out.push(PREFIX_LIST);
+    write_u32_len(out, body.len() / 16)?;
+    for element in body.chunks_exact(16) {
+        out.push(prefix);
+        out.extend(element.iter().rev());
+    }
+
+    Ok(())
+}
+
+/// Splits the serialized value (including its header) at the start of the
+/// provided buffer from the bytes which follow it.
+#[inline]
+fn split_value(buffer: &[u8]) -> Result<(&[u8], &[u8]), SerializationError> {
+    let (len, header_len) = read_value_header(buffer)?;
+    let value_len = header_len
+        .checked_add(len)
+        .filter(|value_len| *value_len <= buffer.len())
+        .ok_or(SerializationError::IndexOutOfRange)?;
+    Ok(buffer.split_at(value_len))
+}
+
+/// Transcodes the next consensus-serialized value from the reader to this crate's
+/// format.
+fn transcode_from_consensus(
+    out: &mut Vec<u8>,
+    reader: &mut Reader<'_>,
+    limits: &DeserializationLimits,
+    depth: usize,
+) -> Result<(), SerializationError> {
+    if depth > limits.max_depth {
+        Err(SerializationError::MaxDepthExceeded)?
+    }
+
+    match reader.read_u8()? {
+        prefix @ (PREFIX_INT | PREFIX_UINT) => {
+            let mut bytes = reader.read_16()?;
+            bytes.reverse();
+            let type_indicator = if prefix == PREFIX_INT {
+                TypeIndicator::Int
+            } else {
+                TypeIndicator::UInt
+            };
+            write_with_header(out, type_indicator, |out| {
+                out.extend_from_slice(&bytes);
+                Ok(())
+            })?;
+        }
+        prefix @ (PREFIX_BOOL_TRUE | PREFIX_BOOL_FALSE) => {
+            write_with_header(out, TypeIndicator::Bool, |out| {
+                out.push((prefix == PREFIX_BOOL_TRUE) as u8);
+                Ok(())
+            })?;
+        }
+        PREFIX_NONE => write_with_header(out, TypeIndicator::Optional, |out| {
+            out.push(0);
+            Ok(())
+        })?,
+        PREFIX_SOME => write_with_header(out, TypeIndicator::Optional, |out| {
+            out.push(1);
+            transcode_from_consensus(out, reader, limits, depth + 1)
+        })?,
+        prefix @ (PREFIX_RESPONSE_OK | PREFIX_RESPONSE_ERR) => {
+            write_with_header(out, TypeIndicator::Response, |out| {
+                out.push((prefix == PREFIX_RESPONSE_OK) as u8);
+                transcode_from_consensus(out, reader, limits, depth + 1)
+            })?
+        }
+        PREFIX_BUFFER => {
+            let len = reader.read_u32_len()?;
+            let bytes = reader.read_bytes(len)?;
+            write_with_header(out, TypeIndicator::Buffer, |out| {
+                out.extend_from_slice(bytes);
+                Ok(())
+            })?;
+        }
+        PREFIX_STRING_ASCII => {
+            let len = reader.read_u32_len()?;
+            let bytes = reader.read_bytes(len)?;
+            if !bytes.is_ascii() {
+                Err(SerializationError::FailedToConvertBytesToAscii)?
+            }
+            write_with_header(out, TypeIndicator::AsciiString, |out| {
+                out.extend_from_slice(bytes);
+                Ok(())
+            })?;
+        }
+        PREFIX_STRING_UTF8 => {
+            let len = reader.read_u32_len()?;
+            let bytes = reader.read_bytes(len)?;
+            std::str::from_utf8(bytes)
+                .map_err(|_| SerializationError::FailedToConvertBytesToUtf8)?;
+            write_with_header(out, TypeIndicator::Utf8String, |out| {
+                out.extend_from_slice(bytes);
+                Ok(())
+            })?;
+        }
+        PREFIX_LIST => {
+            let count = reader.read_u32_len()?;
+            if count > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+            // The consensus format doesn't carry the list's type, so, as in Clarity,
+            // it's inferred from the items.
+            let mut items = Vec::new();
+            let mut values = Vec::with_capacity(count.min(reader.buffer.len()));
+            for _i in 0..count {
+                let start = items.len();
+                transcode_from_consensus(&mut items, reader, limits, depth + 1)?;
+                values.push(deserialize_clarity_value(&items[start..])?);
+            }
+            let list_type = TypeSignature::construct_parent_list_type(&values)
+                .map_err(|_| SerializationError::FailedToDeserializeList)?;
+
+            write_with_header(out, TypeIndicator::List, |out| {
+                write_length(out, count)?;
+                write_list_type(&list_type, &mut |bytes| {
+                    out.extend_from_slice(bytes);
+                    Ok(())
+                })?;
+                out.extend_from_slice(&items);
+                Ok(())
+            })?;
+        }
+        PREFIX_TUPLE => {
+            let count = reader.read_u32_len()?;
+            if count > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+            write_with_header(out, TypeIndicator::Tuple, |out| {
+                write_length(out, count)?;
+                for _i in 0..count {
+                    let name = reader.read_name()?;
+                    out.push(name.len() as u8);
+                    out.extend_from_slice(name.as_bytes());
+                    transcode_from_consensus(out, reader, limits, depth + 1)?;
+                }
+                Ok(())
+            })?;
+        }
+        PREFIX_STANDARD_PRINCIPAL => {
+            let principal = reader.read_standard_principal()?;
+            write_with_header(out, TypeIndicator::StandardPrincipal, |out| {
+                write_standard_principal(out, &principal);
+                Ok(())
+            })?;
+        }
+        PREFIX_CONTRACT_PRINCIPAL => {
+            let issuer = reader.read_standard_principal()?;
+            let name = reader
+                .read_name()
+                .map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+            write_with_header(out, TypeIndicator::ContractPrincipal, |out| {
+                write_standard_principal(out, &issuer);
+                out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                out.extend_from_slice(name.as_bytes());
+                Ok(())
+            })?;
+        }
+        prefix => Err(SerializationError::InvalidTypeIndicator(prefix))?,
+    }
+
+    Ok(())
+}
+
+/// Writes a value header followed by the body written by `write_body`, filling in
+/// the length once the body has been written.
+fn write_with_header(
+    out: &mut Vec<u8>,
+    type_indicator: TypeIndicator,
+    write_body: impl FnOnce(&mut Vec<u8>) -> Result<(), SerializationError>,
+) -> Result<(), SerializationError> {
+    out.push(type_indicator as u8);
+    let len_offset = out.len();
+    out.extend_from_slice(&[0, 0]);
+    write_body(out)?;
+
+    let len = out.len() - len_offset - 2;
+    if len < LENGTH_ESCAPE as usize {
+        out[len_offset..len_offset + 2].copy_from_slice(&(len as u16).to_le_bytes());
+    } else {
+        // Rare enough that shifting the body to make room for the escaped length
+        // is cheaper than measuring every body up front.
+        let len = u32::try_from(len).map_err(|_| SerializationError::ValueTooLarge)?;
+        out[len_offset..len_offset + 2].copy_from_slice(&LENGTH_ESCAPE.to_le_bytes());
+        out.splice(len_offset + 2..len_offset + 2, len.to_le_bytes());
+    }
+
+    Ok(())
+}
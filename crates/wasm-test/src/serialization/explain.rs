@@ -0,0 +1,401 @@
+use std::fmt::{Display, Write};
+
+use clarity::vm::types::{SequenceSubtype, TypeSignature};
+
+use super::{
+    read_length, read_name, read_standard_principal, signature::read_list_type,
+    type_indicator_byte_to_type_indicator, DeserializationLimits, SerializationError,
+    TypeIndicator, PACKED_LIST_FLAG,
+};
+
+/// The maximum number of bytes shown on a single line of the dump. Longer fields
+/// (e.g. strings and buffers) are truncated.
+const BYTES_PER_LINE: usize = 16;
+
+/// The width of the hex column, i.e. `BYTES_PER_LINE` bytes plus a truncation
+/// marker and separator.
+const HEX_COLUMN_WIDTH: usize = BYTES_PER_LINE * 3 + 3;
+
+/// Produces an annotated hexdump of a serialized value, with one line per type
+/// indicator, length field and piece of data, indented by nesting depth, e.g.:
+///
+/// ```text
+/// 0000  05                                                 response
+/// 0001  14 00                                                length: 20
+/// 0003  01                                                   ok
+/// 0004  01                                                   uint
+/// 0005  10 00                                                  length: 16
+/// 0007  2a 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00        value: 42
+/// ```
+///
+/// Malformed buffers are dumped up to the first problem, which is reported along
+/// with the offending bytes, making this useful for debugging mismatches between
+/// what the host wrote and what the guest read.
+pub fn explain(buffer: &[u8]) -> String {
+    let mut explainer = Explainer {
+        buffer,
+        out: String::new(),
+    };
+
+    match explainer.value(0, buffer.len(), 0, "") {
+        Ok(end) if end < buffer.len() => explainer.error(
+            end,
+            SerializationError::LengthIndicatorDoesNotMatchBufferLength,
+        ),
+        Ok(_) => {}
+        Err((offset, error)) => explainer.error(offset, error),
+    }
+
+    explainer.out
+}
+
+/// The offset at which explaining a buffer failed, along with the reason.
+type Failure = (usize, SerializationError);
+
+struct Explainer<'a> {
+    buffer: &'a [u8],
+    out: String,
+}
+
+impl Explainer<'_> {
+    /// Explains the value starting at `start`, which must end at or before `end`,
+    /// returning the offset at which it actually ends.
+    fn value(
+        &mut self,
+        start: usize,
+        end: usize,
+        depth: usize,
+        label: &str,
+    ) -> Result<usize, Failure> {
+        if depth >= DeserializationLimits::default().max_depth {
+            return Err((start, SerializationError::MaxDepthExceeded));
+        }
+
+        let indicator = *self.buffer[start..end].first().ok_or((
+            start,
+            SerializationError::AttemtToDeserializeZeroLengthBuffer,
+        ))?;
+        let packed = indicator & PACKED_LIST_FLAG != 0;
+        let type_indicator =
+            type_indicator_byte_to_type_indicator(indicator & !PACKED_LIST_FLAG)
+                .map_err(|_| (start, SerializationError::InvalidTypeIndicator(indicator)))?;
+
+        if packed {
+            self.line(
+                start,
+                1,
+                depth,
+                format_args!("{label}packed list of {}", type_name(type_indicator)),
+            );
+        } else {
+            self.line(
+                start,
+                1,
+                depth,
+                format_args!("{label}{}", type_name(type_indicator)),
+            );
+        }
+
+        let (len, len_size) =
+            read_length(&self.buffer[start + 1..end]).map_err(|e| (start + 1, e))?;
+        self.line(
+            start + 1,
+            len_size,
+            depth + 1,
+            format_args!("length: {len}"),
+        );
+
+        let body_start = start + 1 + len_size;
+        let body_end = body_start
+            .checked_add(len)
+            .filter(|body_end| *body_end <= end)
+            .ok_or((
+                start + 1,
+                SerializationError::LengthIndicatorDoesNotMatchBufferLength,
+            ))?;
+
+        let consumed = if packed {
+            self.packed_body(type_indicator, body_start, body_end, depth + 1)?
+        } else {
+            self.body(type_indicator, body_start, body_end, depth + 1)?
+        };
+        if consumed != body_end {
+            return Err((
+                consumed,
+                SerializationError::LengthIndicatorDoesNotMatchBufferLength,
+            ));
+        }
+
+        Ok(body_end)
+    }
+
+    /// Explains the body of a value of the given type, returning the offset at
+    /// which the explained data ends.
+    fn body(
+        &mut self,
+        type_indicator: TypeIndicator,
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> Result<usize, Failure> {
+        let buffer = self.buffer;
+        let bytes = &buffer[start..end];
+
+        match type_indicator {
+            TypeIndicator::UInt | TypeIndicator::Int => {
+                let int_bytes: [u8; 16] = bytes.try_into().map_err(|_| {
+                    (
+                        start,
+                        SerializationError::InvalidBufferLength {
+                            expected: 16,
+                            received: bytes.len() as u32,
+                        },
+                    )
+                })?;
+                if type_indicator == TypeIndicator::UInt {
+                    let value = u128::from_le_bytes(int_bytes);
+                    self.line(start, 16, depth, format_args!("value: {value}"));
+                } else {
+                    let value = i128::from_le_bytes(int_bytes);
+                    self.line(start, 16, depth, format_args!("value: {value}"));
+                }
+                Ok(end)
+            }
+            TypeIndicator::Bool => {
+                let value = match bytes {
+                    [0] => false,
+                    [1] => true,
+                    _ => Err((
+                        start,
+                        SerializationError::InvalidBufferLength {
+                            expected: 1,
+                            received: bytes.len() as u32,
+                        },
+                    ))?,
+                };
+                self.line(start, 1, depth, format_args!("value: {value}"));
+                Ok(end)
+            }
+            TypeIndicator::AsciiString => {
+                if !bytes.is_ascii() {
+                    Err((start, SerializationError::FailedToConvertBytesToAscii))?
+                }
+                let value = String::from_utf8_lossy(bytes);
+                self.line(start, bytes.len(), depth, format_args!("value: {value:?}"));
+                Ok(end)
+            }
+            TypeIndicator::Utf8String => {
+                let value = std::str::from_utf8(bytes)
+                    .map_err(|_| (start, SerializationError::FailedToConvertBytesToUtf8))?;
+                self.line(start, bytes.len(), depth, format_args!("value: {value:?}"));
+                Ok(end)
+            }
+            TypeIndicator::Buffer => {
+                self.line(
+                    start,
+                    bytes.len(),
+                    depth,
+                    format_args!("value: {} bytes", bytes.len()),
+                );
+                Ok(end)
+            }
+            TypeIndicator::Optional | TypeIndicator::Response => {
+                let indicator = *bytes
+                    .first()
+                    .ok_or((start, SerializationError::IndexOutOfRange))?;
+                let variant = match (type_indicator, indicator) {
+                    (TypeIndicator::Optional, 1) => "some",
+                    (TypeIndicator::Optional, _) => "none",
+                    (_, 1) => "ok",
+                    _ => "err",
+                };
+                self.line(start, 1, depth, variant);
+
+                if variant == "none" {
+                    Ok(start + 1)
+                } else {
+                    self.value(start + 1, end, depth, "")
+                }
+            }
+            TypeIndicator::StandardPrincipal => self.principal(start, end, depth, "principal"),
+            TypeIndicator::ContractPrincipal => {
+                let name_start = self.principal(start, end, depth, "issuer")?;
+                self.name(name_start, end, depth, "name")
+            }
+            TypeIndicator::CallableContract => {
+                let name_start = self.principal(start, end, depth, "issuer")?;
+                let flag_start = self.name(name_start, end, depth, "name")?;
+                let has_trait = *self.buffer[flag_start..end]
+                    .first()
+                    .ok_or((flag_start, SerializationError::IndexOutOfRange))?;
+                if has_trait != 1 {
+                    self.line(flag_start, 1, depth, "trait: none");
+                    return Ok(flag_start + 1);
+                }
+
+                self.line(flag_start, 1, depth, "trait: some");
+                let trait_name_start =
+                    self.principal(flag_start + 1, end, depth, "trait issuer")?;
+                self.name(trait_name_start, end, depth, "trait name")
+            }
+            TypeIndicator::List => {
+                let (count, count_size) = read_length(bytes).map_err(|e| (start, e))?;
+                self.line(start, count_size, depth, format_args!("count: {count}"));
+
+                let type_start = start + count_size;
+                let (list_type, type_size) =
+                    read_list_type(&bytes[count_size..]).map_err(|e| (type_start, e))?;
+                let list_type = TypeSignature::SequenceType(SequenceSubtype::ListType(list_type));
+                self.line(
+                    type_start,
+                    type_size,
+                    depth,
+                    format_args!("type: {list_type}"),
+                );
+
+                let mut offset = type_start + type_size;
+                for i in 0..count {
+                    offset = self.value(offset, end, depth, &format!("[{i}] "))?;
+                }
+                Ok(offset)
+            }
+            TypeIndicator::Tuple => {
+                let (count, count_size) = read_length(bytes).map_err(|e| (start, e))?;
+                self.line(start, count_size, depth, format_args!("count: {count}"));
+
+                let mut offset = start + count_size;
+                for _ in 0..count {
+                    let name_len = *self.buffer[offset..end]
+                        .first()
+                        .ok_or((offset, SerializationError::FailedToDeserializeTuple))?
+                        as usize;
+                    let name = self.buffer[offset + 1..end].get(..name_len).ok_or((
+                        offset,
+                        SerializationError::FailedToDeserializeTupleFieldName,
+                    ))?;
+                    let name = String::from_utf8_lossy(name).into_owned();
+                    self.line(offset, 1 + name_len, depth, format_args!("field: {name}"));
+
+                    offset = self.value(offset + 1 + name_len, end, depth + 1, "")?;
+                }
+                Ok(offset)
+            }
+        }
+    }
+
+    /// Explains the body of a packed integer list (see `packed.rs`).
+    fn packed_body(
+        &mut self,
+        type_indicator: TypeIndicator,
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> Result<usize, Failure> {
+        if !type_indicator.is_integer() {
+            return Err((
+                start,
+                SerializationError::TypeNotAllowed {
+                    received: type_indicator,
+                },
+            ));
+        }
+
+        let buffer = self.buffer;
+        let mut offset = start;
+        for (i, chunk) in buffer[start..end].chunks(16).enumerate() {
+            let int_bytes: [u8; 16] = chunk.try_into().map_err(|_| {
+                (
+                    offset,
+                    SerializationError::InvalidBufferLength {
+                        expected: 16,
+                        received: chunk.len() as u32,
+                    },
+                )
+            })?;
+            if type_indicator == TypeIndicator::UInt {
+                let value = u128::from_le_bytes(int_bytes);
+                self.line(offset, 16, depth, format_args!("[{i}] {value}"));
+            } else {
+                let value = i128::from_le_bytes(int_bytes);
+                self.line(offset, 16, depth, format_args!("[{i}] {value}"));
+            }
+            offset += 16;
+        }
+        Ok(offset)
+    }
+
+    /// Explains a standard principal, returning the offset at which it ends.
+    fn principal(
+        &mut self,
+        start: usize,
+        end: usize,
+        depth: usize,
+        label: &str,
+    ) -> Result<usize, Failure> {
+        let (principal, _) =
+            read_standard_principal(&self.buffer[start..end]).map_err(|e| (start, e))?;
+        self.line(start, 21, depth, format_args!("{label}: {principal}"));
+        Ok(start + 21)
+    }
+
+    /// Explains a contract or trait name, returning the offset at which it ends.
+    fn name(
+        &mut self,
+        start: usize,
+        end: usize,
+        depth: usize,
+        label: &str,
+    ) -> Result<usize, Failure> {
+        let (name, _) = read_name(&self.buffer[start..end]).map_err(|e| (start, e))?;
+        let len = 2 + name.len();
+        self.line(start, len, depth, format_args!("{label}: {name}"));
+        Ok(start + len)
+    }
+
+    /// Reports an error along with the remaining bytes of the buffer.
+    fn error(&mut self, offset: usize, error: SerializationError) {
+        let remaining = self.buffer.len().saturating_sub(offset);
+        self.line(offset, remaining, 0, format_args!("error: {error}"));
+    }
+
+    /// Writes a single line of the dump, consisting of the offset, the (possibly
+    /// truncated) bytes at it, and the indented annotation.
+    fn line(&mut self, offset: usize, len: usize, depth: usize, note: impl Display) {
+        let end = offset.saturating_add(len).min(self.buffer.len());
+        let bytes = self.buffer.get(offset..end).unwrap_or_default();
+
+        let mut hex = String::with_capacity(HEX_COLUMN_WIDTH);
+        for byte in bytes.iter().take(BYTES_PER_LINE) {
+            let _ = write!(hex, "{byte:02x} ");
+        }
+        if bytes.len() > BYTES_PER_LINE {
+            hex.push_str("..");
+        }
+
+        let _ = writeln!(
+            self.out,
+            "{offset:04x}  {hex:<HEX_COLUMN_WIDTH$}{:indent$}{note}",
+            "",
+            indent = depth * 2
+        );
+    }
+}
+
+/// Gets the Clarity name of the given type.
+fn type_name(type_indicator: TypeIndicator) -> &'static str {
+    match type_indicator {
+        TypeIndicator::UInt => "uint",
+        TypeIndicator::Int => "int",
+        TypeIndicator::Bool => "bool",
+        TypeIndicator::Optional => "optional",
+        TypeIndicator::Response => "response",
+        TypeIndicator::AsciiString => "string-ascii",
+        TypeIndicator::Utf8String => "string-utf8",
+        TypeIndicator::Buffer => "buff",
+        TypeIndicator::List => "list",
+        TypeIndicator::StandardPrincipal => "principal",
+        TypeIndicator::ContractPrincipal => "contract principal",
+        TypeIndicator::CallableContract => "callable contract",
+        TypeIndicator::Tuple => "tuple",
+    }
+}
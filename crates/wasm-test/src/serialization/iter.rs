@@ -0,0 +1,118 @@
+use clarity::vm::Value;
+
+use super::{
+    deserialize_clarity_value, deserialize_clarity_value_borrowed, read_length, read_value_header,
+    signature::skip_list_type, type_indicator_byte_to_type_indicator, ClarityValueRef,
+    SerializationError, TypeIndicator,
+};
+use crate::Ptr;
+
+/// A single element of a serialized list, as yielded by `ClaritySeqIterator`.
+#[derive(Debug, Clone, Copy)]
+pub struct SeqElement<'a> {
+    /// The location of the element (header included) relative to the start of the
+    /// buffer the iterator was created from.
+    pub ptr: Ptr,
+    /// The type of the element.
+    pub type_indicator: TypeIndicator,
+    /// The serialized bytes of the element, header included.
+    pub bytes: &'a [u8],
+}
+
+impl<'a> SeqElement<'a> {
+    /// Deserializes this element to an owned Clarity `Value`.
+    pub fn value(&self) -> Result<Value, SerializationError> {
+        deserialize_clarity_value(self.bytes)
+    }
+
+    /// Deserializes this element to a view which borrows its bytes.
+    pub fn value_ref(&self) -> Result<ClarityValueRef<'a>, SerializationError> {
+        deserialize_clarity_value_borrowed(self.bytes)
+    }
+}
+
+/// Lazily iterates over the elements of a serialized list without deserializing
+/// them up-front, so that functions such as `fold` can walk large sequences without
+/// materializing a `Vec<Value>`. Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct ClaritySeqIterator<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a> ClaritySeqIterator<'a> {
+    /// Creates an iterator over the elements of the serialized list in `buffer`,
+    /// validating the list's header.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, SerializationError> {
+        let type_indicator = type_indicator_byte_to_type_indicator(
+            *buffer
+                .first()
+                .ok_or(SerializationError::AttemtToDeserializeZeroLengthBuffer)?,
+        )?;
+        if type_indicator != TypeIndicator::List {
+            Err(SerializationError::TypeNotAllowed {
+                received: type_indicator,
+            })?;
+        }
+
+        let (length_indicator, header_len) = read_value_header(buffer)?;
+        if buffer.len() - header_len != length_indicator {
+            Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+        }
+
+        let (list_len, list_len_size) = read_length(&buffer[header_len..])
+            .map_err(|_| SerializationError::FailedToDeserializeListLength)?;
+        let list_type_size = skip_list_type(&buffer[header_len + list_len_size..])?;
+
+        Ok(ClaritySeqIterator {
+            buffer,
+            offset: header_len + list_len_size + list_type_size,
+            remaining: list_len,
+        })
+    }
+
+    /// Reads the element at the current offset.
+    fn read_element(&self) -> Result<SeqElement<'a>, SerializationError> {
+        let bytes = &self.buffer[self.offset..];
+        let type_indicator = type_indicator_byte_to_type_indicator(
+            *bytes.first().ok_or(SerializationError::IndexOutOfRange)?,
+        )?;
+        let (value_len, header_len) = read_value_header(bytes)?;
+        let bytes = bytes
+            .get(..header_len + value_len)
+            .ok_or(SerializationError::IndexOutOfRange)?;
+
+        Ok(SeqElement {
+            ptr: Ptr::new(self.offset as i32, bytes.len() as i32),
+            type_indicator,
+            bytes,
+        })
+    }
+}
+
+impl<'a> Iterator for ClaritySeqIterator<'a> {
+    type Item = Result<SeqElement<'a>, SerializationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        match self.read_element() {
+            Ok(element) => {
+                self.offset += element.bytes.len();
+                self.remaining -= 1;
+                Some(Ok(element))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
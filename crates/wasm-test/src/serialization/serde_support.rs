@@ -0,0 +1,106 @@
+use std::fmt;
+
+use clarity::{
+    util::hash::{hex_bytes, to_hex},
+    vm::Value,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    deserialize_clarity_value, get_type_indicator_from_serialized_value, serialize_clarity_value,
+    SerializationError, TypeIndicator,
+};
+
+/// The exact bytes of a value serialized by `serialize_clarity_value`, i.e. the bytes
+/// which cross the Wasm boundary. With `serde`, these are written as a hex string to
+/// human-readable formats (e.g. JSON) and as raw bytes to binary formats (e.g.
+/// bincode), so that fixtures can be read and written without re-encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializedValue(Vec<u8>);
+
+impl SerializedValue {
+    /// Wraps the provided serialized bytes. The bytes are not validated.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        SerializedValue(bytes)
+    }
+
+    /// Serializes the provided Clarity `Value`.
+    pub fn from_value(value: &Value) -> Result<Self, SerializationError> {
+        serialize_clarity_value(value).map(SerializedValue)
+    }
+
+    /// Deserializes the wrapped bytes to a Clarity `Value`.
+    pub fn to_value(&self) -> Result<Value, SerializationError> {
+        deserialize_clarity_value(&self.0)
+    }
+
+    /// Gets the type of the serialized value.
+    pub fn type_indicator(&self) -> Result<TypeIndicator, SerializationError> {
+        if self.0.is_empty() {
+            Err(SerializationError::AttemtToDeserializeZeroLengthBuffer)?
+        }
+        get_type_indicator_from_serialized_value(&self.0)
+    }
+
+    /// Gets the serialized bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Unwraps the serialized bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Serialize for SerializedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&to_hex(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(SerializedValueVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(SerializedValueVisitor)
+        }
+    }
+}
+
+struct SerializedValueVisitor;
+
+impl<'de> de::Visitor<'de> for SerializedValueVisitor {
+    type Value = SerializedValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a hex string or byte array containing a serialized Clarity value")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        hex_bytes(v)
+            .map(SerializedValue)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(SerializedValue(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(SerializedValue(v))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(SerializedValue(bytes))
+    }
+}
@@ -0,0 +1,627 @@
+use std::collections::HashSet;
+
+use clarity::vm::{
+    types::{
+        signatures::CallableSubtype, BufferLength, ListTypeData, QualifiedContractIdentifier,
+        SequenceSubtype, StringSubtype, StringUTF8Length, TraitIdentifier, TupleTypeSignature,
+        TypeSignature, MAX_TYPE_DEPTH,
+    },
+    ClarityName, ContractName,
+};
+
+use super::{
+    read_length, read_name, read_standard_principal, read_value_header,
+    type_indicator_byte_to_type_indicator, writer::STANDARD_PRINCIPAL_SIZE, DeserializationLimits,
+    SerializationError, TypeIndicator, LENGTH_ESCAPE,
+};
+
+// Type signatures are encoded as a tag followed by the type's parameters. Where a
+// type corresponds to a kind of value, its tag is that value's `TypeIndicator`:
+//
+// - `int`, `uint`, `bool`, `principal` and `NoType` (the element type of an empty
+//   list) have no parameters.
+// - `(optional t)` is followed by `t`, and `(response ok err)` by `ok` and `err`.
+// - `(buff n)`, `(string-ascii n)` and `(string-utf8 n)` are followed by `n` as a
+//   `u32 LE`.
+// - `(list n t)` is followed by `n` as a `u32 LE` and `t`.
+// - Tuples are followed by their field count, and then by each field's name (as a
+//   one-byte length and the name) and type, sorted by name.
+// - Callable contracts and trait references (Clarity 2) are followed by the
+//   contract or trait they refer to, encoded like in serialized values.
+
+/// The tag of `NoType`.
+const TAG_NO_TYPE: u8 = 0;
+/// The tag of trait reference types.
+const TAG_TRAIT_REFERENCE: u8 = 14;
+/// The tag of list union types, i.e. lists of callables of differing types.
+const TAG_LIST_UNION: u8 = 15;
+/// The tag of callables of a specific contract.
+const TAG_CALLABLE_PRINCIPAL: u8 = TypeIndicator::ContractPrincipal as u8;
+/// The tag of callables implementing a trait.
+const TAG_CALLABLE_TRAIT: u8 = TypeIndicator::CallableContract as u8;
+
+/// Describes the type of the serialized value in the provided buffer by walking its
+/// headers, without deserializing its data: the bytes of strings and buffers are
+/// only validated, and the type of a list is read from its header rather than
+/// inferred from its items. The result is the type Clarity would infer for the
+/// deserialized value, which makes it cheap to validate the shape of a value in
+/// Wasm memory before committing to a full decode.
+pub fn describe(buffer: &[u8]) -> Result<TypeSignature, SerializationError> {
+    describe_value(buffer, &DeserializationLimits::default(), 1)
+}
+
+/// Describes the value in the provided buffer, which is nested at the given depth.
+fn describe_value(
+    buffer: &[u8],
+    limits: &DeserializationLimits,
+    depth: usize,
+) -> Result<TypeSignature, SerializationError> {
+    if depth > limits.max_depth {
+        Err(SerializationError::MaxDepthExceeded)?
+    }
+
+    let type_indicator = type_indicator_byte_to_type_indicator(
+        *buffer
+            .first()
+            .ok_or(SerializationError::AttemtToDeserializeZeroLengthBuffer)?,
+    )?;
+    let (length_indicator, header_len) = read_value_header(buffer)?;
+    let value = &buffer[header_len..];
+    if value.len() != length_indicator {
+        Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+    }
+
+    let type_signature = match type_indicator {
+        TypeIndicator::UInt | TypeIndicator::Int => {
+            if value.len() != 16 {
+                Err(SerializationError::InvalidBufferLength {
+                    expected: 16,
+                    received: value.len() as u32,
+                })?
+            }
+            if type_indicator == TypeIndicator::UInt {
+                TypeSignature::UIntType
+            } else {
+                TypeSignature::IntType
+            }
+        }
+        TypeIndicator::Bool => match value {
+            [0] | [1] => TypeSignature::BoolType,
+            _ => Err(SerializationError::InvalidBufferLength {
+                expected: 1,
+                received: value.len() as u32,
+            })?,
+        },
+        TypeIndicator::Optional => match value.split_first() {
+            Some((1, inner)) => {
+                TypeSignature::OptionalType(Box::new(describe_value(inner, limits, depth + 1)?))
+            }
+            Some(_) => TypeSignature::OptionalType(Box::new(TypeSignature::NoType)),
+            None => Err(SerializationError::IndexOutOfRange)?,
+        },
+        TypeIndicator::Response => {
+            let (indicator, inner) = value
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
+            let inner = describe_value(inner, limits, depth + 1)?;
+            if *indicator == 1 {
+                TypeSignature::ResponseType(Box::new((inner, TypeSignature::NoType)))
+            } else {
+                TypeSignature::ResponseType(Box::new((TypeSignature::NoType, inner)))
+            }
+        }
+        TypeIndicator::AsciiString => {
+            if !value.is_ascii() {
+                Err(SerializationError::FailedToConvertBytesToAscii)?
+            }
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(
+                buffer_length(value.len())?,
+            )))
+        }
+        TypeIndicator::Utf8String => {
+            let chars = std::str::from_utf8(value)
+                .map_err(|_| SerializationError::FailedToConvertBytesToUtf8)?
+                .chars()
+                .count();
+            let len = u32::try_from(chars)
+                .ok()
+                .and_then(|len| StringUTF8Length::try_from(len).ok())
+                .ok_or(SerializationError::ValueTooLarge)?;
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(len)))
+        }
+        TypeIndicator::Buffer => {
+            TypeSignature::SequenceType(SequenceSubtype::BufferType(buffer_length(value.len())?))
+        }
+        TypeIndicator::List => {
+            let (count, count_size) = read_length(value)
+                .map_err(|_| SerializationError::FailedToDeserializeListLength)?;
+            let (list_type, list_type_size) = read_list_type(&value[count_size..])?;
+            if count > list_type.get_max_len() as usize {
+                Err(SerializationError::TypeMismatch)?
+            }
+
+            // Only the items' headers are read, to check that they fill the list.
+            let mut items = &value[count_size + list_type_size..];
+            for _i in 0..count {
+                let (item_len, item_header_len) = read_value_header(items)?;
+                items = items
+                    .get(item_header_len + item_len..)
+                    .ok_or(SerializationError::IndexOutOfRange)?;
+            }
+            if !items.is_empty() {
+                Err(SerializationError::LengthIndicatorDoesNotMatchBufferLength)?
+            }
+
+            TypeSignature::SequenceType(SequenceSubtype::ListType(list_type))
+        }
+        TypeIndicator::StandardPrincipal => {
+            read_standard_principal(value)?;
+            TypeSignature::PrincipalType
+        }
+        TypeIndicator::ContractPrincipal => {
+            read_contract_identifier(value)?;
+            TypeSignature::PrincipalType
+        }
+        TypeIndicator::CallableContract => {
+            let (contract_id, size) = read_contract_identifier(value)?;
+            let (trait_indicator, trait_bytes) = value[size..]
+                .split_first()
+                .ok_or(SerializationError::IndexOutOfRange)?;
+
+            // Serialized trait identifiers share the contract's name.
+            let subtype = if *trait_indicator == 1 {
+                let (trait_principal, rest) = read_standard_principal(trait_bytes)?;
+                let (trait_name, _) = read_name(rest)
+                    .map_err(|_| SerializationError::FailedToDeserializeTraitName)?;
+                let trait_name = ClarityName::try_from(trait_name.to_string())
+                    .map_err(|_| SerializationError::FailedToDeserializeTraitName)?;
+                CallableSubtype::Trait(TraitIdentifier::new(
+                    trait_principal,
+                    contract_id.name,
+                    trait_name,
+                ))
+            } else {
+                CallableSubtype::Principal(contract_id)
+            };
+            TypeSignature::CallableType(subtype)
+        }
+        TypeIndicator::Tuple => {
+            let (count, count_size) =
+                read_length(value).map_err(|_| SerializationError::FailedToDeserializeTuple)?;
+            if count > limits.max_elements {
+                Err(SerializationError::MaxElementsExceeded)?
+            }
+
+            let mut field_bytes = &value[count_size..];
+            let mut fields = Vec::with_capacity(count.min(field_bytes.len()));
+            for _i in 0..count {
+                let (name_len, rest) = field_bytes
+                    .split_first()
+                    .ok_or(SerializationError::FailedToDeserializeTuple)?;
+                let name = rest
+                    .get(..*name_len as usize)
+                    .and_then(|name| std::str::from_utf8(name).ok())
+                    .and_then(|name| ClarityName::try_from(name.to_string()).ok())
+                    .ok_or(SerializationError::FailedToDeserializeTupleFieldName)?;
+                let rest = &rest[*name_len as usize..];
+
+                let (field_len, field_header_len) = read_value_header(rest)?;
+                let field_buffer = rest
+                    .get(..field_header_len + field_len)
+                    .ok_or(SerializationError::FailedToDeserializeTuple)?;
+                fields.push((name, describe_value(field_buffer, limits, depth + 1)?));
+                field_bytes = &rest[field_buffer.len()..];
+            }
+
+            TypeSignature::TupleType(
+                TupleTypeSignature::try_from(fields)
+                    .map_err(|_| SerializationError::FailedToDeserializeTuple)?,
+            )
+        }
+    };
+
+    Ok(type_signature)
+}
+
+/// Gets the `BufferLength` of a string or buffer of the given length.
+#[inline]
+fn buffer_length(len: usize) -> Result<BufferLength, SerializationError> {
+    u32::try_from(len)
+        .ok()
+        .and_then(|len| BufferLength::try_from(len).ok())
+        .ok_or(SerializationError::ValueTooLarge)
+}
+
+/// Something which encoded bytes can be written to, e.g. a `Vec` or a slice writer.
+pub(crate) type WriteBytes<'a> = dyn FnMut(&[u8]) -> Result<(), SerializationError> + 'a;
+
+/// The size of the encoded type of a list, as written by `write_list_type`.
+#[inline]
+pub(crate) fn list_type_size(list_type: &ListTypeData) -> usize {
+    let len = 4 + type_signature_size(list_type.get_list_item_type());
+    length_size(len) + len
+}
+
+/// Writes the type of a list (its maximum length and element type), prefixed with
+/// the length of the encoding so that readers which don't need the type can skip it
+/// using `skip_list_type`.
+pub(crate) fn write_list_type(
+    list_type: &ListTypeData,
+    write: &mut WriteBytes<'_>,
+) -> Result<(), SerializationError> {
+    write_length(
+        4 + type_signature_size(list_type.get_list_item_type()),
+        write,
+    )?;
+    write(&list_type.get_max_len().to_le_bytes())?;
+    write_type_signature(list_type.get_list_item_type(), write)
+}
+
+/// Reads the type of a list written by `write_list_type` from the start of the
+/// provided buffer, returning it and the number of bytes it occupied.
+pub(crate) fn read_list_type(buffer: &[u8]) -> Result<(ListTypeData, usize), SerializationError> {
+    let (len, len_size) = read_length(buffer)?;
+    let encoded = buffer
+        .get(len_size..len_size + len)
+        .ok_or(SerializationError::FailedToDeserializeTypeSignature)?;
+
+    let max_len = read_u32(encoded)?;
+    let (item_type, item_type_size) = read_type_signature(&encoded[4..], 1)?;
+    if 4 + item_type_size != len {
+        Err(SerializationError::FailedToDeserializeTypeSignature)?
+    }
+
+    let list_type = ListTypeData::new_list(item_type, max_len)
+        .map_err(|_| SerializationError::FailedToDeserializeTypeSignature)?;
+    Ok((list_type, len_size + len))
+}
+
+/// Gets the number of bytes occupied by the type of a list written by
+/// `write_list_type` at the start of the provided buffer, without reading it.
+#[inline]
+pub(crate) fn skip_list_type(buffer: &[u8]) -> Result<usize, SerializationError> {
+    let (len, len_size) = read_length(buffer)?;
+    if buffer.len() - len_size < len {
+        Err(SerializationError::FailedToDeserializeTypeSignature)?
+    }
+    Ok(len_size + len)
+}
+
+#[inline]
+fn length_size(len: usize) -> usize {
+    if len < LENGTH_ESCAPE as usize {
+        2
+    } else {
+        6
+    }
+}
+
+fn write_length(len: usize, write: &mut WriteBytes<'_>) -> Result<(), SerializationError> {
+    if len < LENGTH_ESCAPE as usize {
+        write(&(len as u16).to_le_bytes())
+    } else {
+        let len = u32::try_from(len).map_err(|_| SerializationError::ValueTooLarge)?;
+        write(&LENGTH_ESCAPE.to_le_bytes())?;
+        write(&len.to_le_bytes())
+    }
+}
+
+fn type_signature_size(type_signature: &TypeSignature) -> usize {
+    match type_signature {
+        TypeSignature::NoType
+        | TypeSignature::IntType
+        | TypeSignature::UIntType
+        | TypeSignature::BoolType
+        | TypeSignature::PrincipalType => 1,
+        TypeSignature::OptionalType(inner) => 1 + type_signature_size(inner),
+        TypeSignature::ResponseType(inner) => {
+            1 + type_signature_size(&inner.0) + type_signature_size(&inner.1)
+        }
+        TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => {
+            1 + 4 + type_signature_size(list_type.get_list_item_type())
+        }
+        TypeSignature::SequenceType(_) => 1 + 4,
+        TypeSignature::TupleType(tuple_type) => {
+            let fields = tuple_type.get_type_map();
+            1 + length_size(fields.len())
+                + fields
+                    .iter()
+                    .map(|(name, field_type)| 1 + name.len() + type_signature_size(field_type))
+                    .sum::<usize>()
+        }
+        TypeSignature::CallableType(subtype) => callable_subtype_size(subtype),
+        TypeSignature::ListUnionType(subtypes) => {
+            1 + length_size(subtypes.len())
+                + subtypes.iter().map(callable_subtype_size).sum::<usize>()
+        }
+        TypeSignature::TraitReferenceType(trait_id) => 1 + trait_identifier_size(trait_id),
+    }
+}
+
+fn write_type_signature(
+    type_signature: &TypeSignature,
+    write: &mut WriteBytes<'_>,
+) -> Result<(), SerializationError> {
+    match type_signature {
+        TypeSignature::NoType => write(&[TAG_NO_TYPE])?,
+        TypeSignature::IntType => write(&[TypeIndicator::Int as u8])?,
+        TypeSignature::UIntType => write(&[TypeIndicator::UInt as u8])?,
+        TypeSignature::BoolType => write(&[TypeIndicator::Bool as u8])?,
+        TypeSignature::PrincipalType => write(&[TypeIndicator::StandardPrincipal as u8])?,
+        TypeSignature::OptionalType(inner) => {
+            write(&[TypeIndicator::Optional as u8])?;
+            write_type_signature(inner, write)?;
+        }
+        TypeSignature::ResponseType(inner) => {
+            write(&[TypeIndicator::Response as u8])?;
+            write_type_signature(&inner.0, write)?;
+            write_type_signature(&inner.1, write)?;
+        }
+        TypeSignature::SequenceType(SequenceSubtype::BufferType(len)) => {
+            write(&[TypeIndicator::Buffer as u8])?;
+            write(&u32::from(len).to_le_bytes())?;
+        }
+        TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(len))) => {
+            write(&[TypeIndicator::AsciiString as u8])?;
+            write(&u32::from(len).to_le_bytes())?;
+        }
+        TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(len))) => {
+            write(&[TypeIndicator::Utf8String as u8])?;
+            write(&u32::from(len).to_le_bytes())?;
+        }
+        TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => {
+            write(&[TypeIndicator::List as u8])?;
+            write(&list_type.get_max_len().to_le_bytes())?;
+            write_type_signature(list_type.get_list_item_type(), write)?;
+        }
+        TypeSignature::TupleType(tuple_type) => {
+            let fields = tuple_type.get_type_map();
+            write(&[TypeIndicator::Tuple as u8])?;
+            write_length(fields.len(), write)?;
+            for (name, field_type) in fields {
+                write(&[name.len() as u8])?;
+                write(name.as_bytes())?;
+                write_type_signature(field_type, write)?;
+            }
+        }
+        TypeSignature::CallableType(subtype) => write_callable_subtype(subtype, write)?,
+        TypeSignature::ListUnionType(subtypes) => {
+            write(&[TAG_LIST_UNION])?;
+            write_length(subtypes.len(), write)?;
+            for subtype in subtypes {
+                write_callable_subtype(subtype, write)?;
+            }
+        }
+        TypeSignature::TraitReferenceType(trait_id) => {
+            write(&[TAG_TRAIT_REFERENCE])?;
+            write_trait_identifier(trait_id, write)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a type signature from the start of the provided buffer, returning it and
+/// the number of bytes it occupied.
+fn read_type_signature(
+    buffer: &[u8],
+    depth: u8,
+) -> Result<(TypeSignature, usize), SerializationError> {
+    if depth > MAX_TYPE_DEPTH {
+        Err(SerializationError::MaxDepthExceeded)?
+    }
+
+    let (tag, rest) = buffer
+        .split_first()
+        .ok_or(SerializationError::FailedToDeserializeTypeSignature)?;
+
+    let (type_signature, size) = match *tag {
+        TAG_NO_TYPE => (TypeSignature::NoType, 0),
+        TAG_TRAIT_REFERENCE => {
+            let (trait_id, size) = read_trait_identifier(rest)?;
+            (TypeSignature::TraitReferenceType(trait_id), size)
+        }
+        TAG_LIST_UNION => {
+            let (count, mut size) = read_length(rest)?;
+            let mut subtypes = HashSet::with_capacity(count.min(rest.len()));
+            for _i in 0..count {
+                let (subtype, subtype_size) = read_callable_subtype(
+                    rest.get(size..)
+                        .ok_or(SerializationError::FailedToDeserializeTypeSignature)?,
+                )?;
+                subtypes.insert(subtype);
+                size += subtype_size;
+            }
+            (TypeSignature::ListUnionType(subtypes), size)
+        }
+        tag => match type_indicator_byte_to_type_indicator(tag)? {
+            TypeIndicator::Int => (TypeSignature::IntType, 0),
+            TypeIndicator::UInt => (TypeSignature::UIntType, 0),
+            TypeIndicator::Bool => (TypeSignature::BoolType, 0),
+            TypeIndicator::StandardPrincipal => (TypeSignature::PrincipalType, 0),
+            TypeIndicator::Optional => {
+                let (inner, size) = read_type_signature(rest, depth + 1)?;
+                (TypeSignature::OptionalType(Box::new(inner)), size)
+            }
+            TypeIndicator::Response => {
+                let (ok, ok_size) = read_type_signature(rest, depth + 1)?;
+                let (err, err_size) = read_type_signature(&rest[ok_size..], depth + 1)?;
+                (
+                    TypeSignature::ResponseType(Box::new((ok, err))),
+                    ok_size + err_size,
+                )
+            }
+            TypeIndicator::Buffer => {
+                let len = BufferLength::try_from(read_u32(rest)?)
+                    .map_err(|_| SerializationError::FailedToDeserializeTypeSignature)?;
+                (
+                    TypeSignature::SequenceType(SequenceSubtype::BufferType(len)),
+                    4,
+                )
+            }
+            TypeIndicator::AsciiString => {
+                let len = BufferLength::try_from(read_u32(rest)?)
+                    .map_err(|_| SerializationError::FailedToDeserializeTypeSignature)?;
+                (
+                    TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(
+                        len,
+                    ))),
+                    4,
+                )
+            }
+            TypeIndicator::Utf8String => {
+                let len = StringUTF8Length::try_from(read_u32(rest)?)
+                    .map_err(|_| SerializationError::FailedToDeserializeTypeSignature)?;
+                (
+                    TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(
+                        len,
+                    ))),
+                    4,
+                )
+            }
+            TypeIndicator::List => {
+                let max_len = read_u32(rest)?;
+                let (item_type, size) = read_type_signature(&rest[4..], depth + 1)?;
+                let list_type = ListTypeData::new_list(item_type, max_len)
+                    .map_err(|_| SerializationError::FailedToDeserializeTypeSignature)?;
+                (
+                    TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)),
+                    4 + size,
+                )
+            }
+            TypeIndicator::Tuple => {
+                let (count, mut size) = read_length(rest)?;
+                let mut fields = Vec::with_capacity(count.min(rest.len()));
+                for _i in 0..count {
+                    let (name_len, field) = rest
+                        .get(size..)
+                        .and_then(|rest| rest.split_first())
+                        .ok_or(SerializationError::FailedToDeserializeTypeSignature)?;
+                    let name = field
+                        .get(..*name_len as usize)
+                        .and_then(|name| std::str::from_utf8(name).ok())
+                        .and_then(|name| ClarityName::try_from(name.to_string()).ok())
+                        .ok_or(SerializationError::FailedToDeserializeTupleFieldName)?;
+                    let (field_type, field_type_size) =
+                        read_type_signature(&field[*name_len as usize..], depth + 1)?;
+                    fields.push((name, field_type));
+                    size += 1 + *name_len as usize + field_type_size;
+                }
+                let tuple_type = TupleTypeSignature::try_from(fields)
+                    .map_err(|_| SerializationError::FailedToDeserializeTypeSignature)?;
+                (TypeSignature::TupleType(tuple_type), size)
+            }
+            TypeIndicator::ContractPrincipal | TypeIndicator::CallableContract => {
+                let (subtype, size) = read_callable_subtype(buffer)?;
+                // The tag was read as part of the subtype.
+                (TypeSignature::CallableType(subtype), size - 1)
+            }
+        },
+    };
+
+    Ok((type_signature, 1 + size))
+}
+
+fn callable_subtype_size(subtype: &CallableSubtype) -> usize {
+    match subtype {
+        CallableSubtype::Principal(contract_id) => 1 + contract_identifier_size(contract_id),
+        CallableSubtype::Trait(trait_id) => 1 + trait_identifier_size(trait_id),
+    }
+}
+
+fn write_callable_subtype(
+    subtype: &CallableSubtype,
+    write: &mut WriteBytes<'_>,
+) -> Result<(), SerializationError> {
+    match subtype {
+        CallableSubtype::Principal(contract_id) => {
+            write(&[TAG_CALLABLE_PRINCIPAL])?;
+            write_contract_identifier(contract_id, write)
+        }
+        CallableSubtype::Trait(trait_id) => {
+            write(&[TAG_CALLABLE_TRAIT])?;
+            write_trait_identifier(trait_id, write)
+        }
+    }
+}
+
+fn read_callable_subtype(buffer: &[u8]) -> Result<(CallableSubtype, usize), SerializationError> {
+    let (tag, rest) = buffer
+        .split_first()
+        .ok_or(SerializationError::FailedToDeserializeTypeSignature)?;
+
+    match *tag {
+        TAG_CALLABLE_PRINCIPAL => {
+            let (contract_id, size) = read_contract_identifier(rest)?;
+            Ok((CallableSubtype::Principal(contract_id), 1 + size))
+        }
+        TAG_CALLABLE_TRAIT => {
+            let (trait_id, size) = read_trait_identifier(rest)?;
+            Ok((CallableSubtype::Trait(trait_id), 1 + size))
+        }
+        _ => Err(SerializationError::FailedToDeserializeTypeSignature),
+    }
+}
+
+fn contract_identifier_size(contract_id: &QualifiedContractIdentifier) -> usize {
+    STANDARD_PRINCIPAL_SIZE + 2 + contract_id.name.len()
+}
+
+fn write_contract_identifier(
+    contract_id: &QualifiedContractIdentifier,
+    write: &mut WriteBytes<'_>,
+) -> Result<(), SerializationError> {
+    write(&[contract_id.issuer.0])?;
+    write(&contract_id.issuer.1)?;
+    write(&(contract_id.name.len() as u16).to_le_bytes())?;
+    write(contract_id.name.as_bytes())
+}
+
+fn read_contract_identifier(
+    buffer: &[u8],
+) -> Result<(QualifiedContractIdentifier, usize), SerializationError> {
+    let (issuer, rest) = read_standard_principal(buffer)?;
+    let (name, _) =
+        read_name(rest).map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+    let size = STANDARD_PRINCIPAL_SIZE + 2 + name.len();
+    let name = ContractName::try_from(name.to_string())
+        .map_err(|_| SerializationError::FailedToDeserializeContractName)?;
+
+    Ok((QualifiedContractIdentifier::new(issuer, name), size))
+}
+
+fn trait_identifier_size(trait_id: &TraitIdentifier) -> usize {
+    contract_identifier_size(&trait_id.contract_identifier) + 2 + trait_id.name.len()
+}
+
+fn write_trait_identifier(
+    trait_id: &TraitIdentifier,
+    write: &mut WriteBytes<'_>,
+) -> Result<(), SerializationError> {
+    write_contract_identifier(&trait_id.contract_identifier, write)?;
+    write(&(trait_id.name.len() as u16).to_le_bytes())?;
+    write(trait_id.name.as_bytes())
+}
+
+fn read_trait_identifier(buffer: &[u8]) -> Result<(TraitIdentifier, usize), SerializationError> {
+    let (contract_id, contract_id_size) = read_contract_identifier(buffer)?;
+    let (name, _) = read_name(&buffer[contract_id_size..])
+        .map_err(|_| SerializationError::FailedToDeserializeTraitName)?;
+    let size = contract_id_size + 2 + name.len();
+    let name = ClarityName::try_from(name.to_string())
+        .map_err(|_| SerializationError::FailedToDeserializeTraitName)?;
+
+    Ok((
+        TraitIdentifier::new(contract_id.issuer, contract_id.name, name),
+        size,
+    ))
+}
+
+#[inline]
+fn read_u32(buffer: &[u8]) -> Result<u32, SerializationError> {
+    buffer
+        .get(..4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or(SerializationError::FailedToDeserializeTypeSignature)
+}
@@ -0,0 +1,140 @@
+use clarity::vm::{
+    types::{
+        CallableData, PrincipalData, QualifiedContractIdentifier, StandardPrincipalData,
+        TraitIdentifier, TupleData,
+    },
+    ClarityName, ContractName, Value,
+};
+use proptest::prelude::*;
+
+use super::{
+    deserialize_clarity_value, deserialize_clarity_value_borrowed, serialize_clarity_value,
+    serialize_clarity_value_into, serialized_size,
+};
+
+/// A strategy which generates arbitrary Clarity values of every type, including
+/// nested optionals, responses, lists and tuples.
+pub fn arb_value() -> impl Strategy<Value = Value> {
+    arb_leaf_value().prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            inner
+                .clone()
+                .prop_map(|value| Value::some(value).expect("Failed to create optional")),
+            (any::<bool>(), inner.clone()).prop_map(|(committed, value)| {
+                if committed {
+                    Value::okay(value)
+                } else {
+                    Value::error(value)
+                }
+                .expect("Failed to create response")
+            }),
+            prop::collection::vec(inner.clone(), 0..8).prop_map(list_of_compatible_items),
+            prop::collection::btree_map(arb_clarity_name(), inner, 1..5).prop_map(|fields| {
+                Value::Tuple(
+                    TupleData::from_data(fields.into_iter().collect())
+                        .expect("Failed to create tuple"),
+                )
+            }),
+        ]
+    })
+}
+
+/// A strategy which generates arbitrary Clarity values which don't contain other
+/// values.
+pub fn arb_leaf_value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<i128>().prop_map(Value::Int),
+        any::<u128>().prop_map(Value::UInt),
+        any::<bool>().prop_map(Value::Bool),
+        Just(Value::none()),
+        "[ -~]{0,32}".prop_map(|s| {
+            Value::string_ascii_from_bytes(s.into_bytes()).expect("Failed to create ascii string")
+        }),
+        any::<String>().prop_map(|s| {
+            Value::string_utf8_from_bytes(s.into_bytes()).expect("Failed to create utf8 string")
+        }),
+        prop::collection::vec(any::<u8>(), 0..64)
+            .prop_map(|bytes| Value::buff_from(bytes).expect("Failed to create buffer")),
+        arb_standard_principal().prop_map(|p| Value::Principal(PrincipalData::Standard(p))),
+        arb_contract_identifier().prop_map(|id| Value::Principal(PrincipalData::Contract(id))),
+        arb_callable_contract(),
+    ]
+}
+
+fn arb_standard_principal() -> impl Strategy<Value = StandardPrincipalData> {
+    (0u8..32, any::<[u8; 20]>()).prop_map(|(version, data)| StandardPrincipalData(version, data))
+}
+
+fn arb_clarity_name() -> impl Strategy<Value = ClarityName> {
+    "[a-z][a-z0-9-]{0,15}"
+        .prop_map(|name| ClarityName::try_from(name).expect("Failed to create clarity name"))
+}
+
+fn arb_contract_identifier() -> impl Strategy<Value = QualifiedContractIdentifier> {
+    (arb_standard_principal(), "[a-z][a-z0-9-]{0,15}").prop_map(|(issuer, name)| {
+        QualifiedContractIdentifier::new(
+            issuer,
+            ContractName::try_from(name).expect("Failed to create contract name"),
+        )
+    })
+}
+
+fn arb_callable_contract() -> impl Strategy<Value = Value> {
+    (
+        arb_contract_identifier(),
+        proptest::option::of((arb_standard_principal(), arb_clarity_name())),
+    )
+        .prop_map(|(contract_identifier, trait_identifier)| {
+            // Only the trait's issuer and name are serialized; its contract name is
+            // taken from the callable contract when deserializing.
+            let trait_identifier = trait_identifier.map(|(issuer, name)| {
+                TraitIdentifier::new(issuer, contract_identifier.name.clone(), name)
+            });
+            Value::CallableContract(CallableData {
+                contract_identifier,
+                trait_identifier,
+            })
+        })
+}
+
+/// Builds a list from the provided items, dropping any item whose type is
+/// incompatible with the items before it, as Clarity lists are homogeneous.
+fn list_of_compatible_items(items: Vec<Value>) -> Value {
+    let mut compatible = Vec::with_capacity(items.len());
+    for item in items {
+        compatible.push(item);
+        if Value::list_from(compatible.clone()).is_err() {
+            compatible.pop();
+        }
+    }
+    Value::list_from(compatible).expect("Failed to create list")
+}
+
+/// Asserts that the provided value survives a round-trip through serialization,
+/// and that all of the ways of serializing and deserializing it agree with each
+/// other.
+pub fn assert_roundtrip(value: &Value) {
+    let serialized = serialize_clarity_value(value).expect("Failed to serialize value");
+    assert_eq!(
+        serialized.len(),
+        serialized_size(value),
+        "serialized_size doesn't match the serialized length of {value:?}"
+    );
+
+    let mut buffer = vec![0u8; serialized.len()];
+    let written = serialize_clarity_value_into(value, &mut buffer)
+        .expect("Failed to serialize value into buffer");
+    assert_eq!(
+        serialized,
+        buffer[..written],
+        "serialize_clarity_value_into doesn't match serialize_clarity_value for {value:?}"
+    );
+
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+    assert_eq!(value, &deserialized, "Value changed during round-trip");
+
+    let borrowed = deserialize_clarity_value_borrowed(&serialized)
+        .and_then(|value| value.into_value())
+        .expect("Failed to deserialize borrowed value");
+    assert_eq!(value, &borrowed, "Borrowed value changed during round-trip");
+}
@@ -0,0 +1,231 @@
+use clarity::vm::{
+    types::{CharType, PrincipalData, SequenceData, StandardPrincipalData},
+    Value,
+};
+
+use super::{
+    check_utf8_chars, get_type_indicator_for_clarity_value,
+    signature::{list_type_size, write_list_type},
+    SerializationError, LENGTH_ESCAPE,
+};
+
+/// Serializes a Clarity `Value` directly into the provided buffer (e.g. a slice of
+/// Wasm memory or a re-usable scratch buffer), returning the number of bytes
+/// written. The output is identical to that of `serialize_clarity_value`, but no
+/// intermediate `Vec`s are allocated. Fails with `BufferTooSmall` if the value
+/// doesn't fit, in which case the contents of the buffer are unspecified.
+#[inline]
+pub fn serialize_clarity_value_into(
+    value: &Value,
+    buffer: &mut [u8],
+) -> Result<usize, SerializationError> {
+    let mut writer = SliceWriter { buffer, offset: 0 };
+    writer.write_value(value)?;
+    Ok(writer.offset)
+}
+
+/// Computes the exact number of bytes `serialize_clarity_value` would produce for
+/// the provided value, without serializing it. This allows an exactly-sized region
+/// to be allocated up-front and the value serialized straight into it using
+/// `serialize_clarity_value_into`.
+pub fn serialized_size(value: &Value) -> usize {
+    let body_len = serialized_body_size(value);
+    header_size(body_len) + body_len
+}
+
+/// The size of a value header (type indicator and length) for a body of the given
+/// length.
+#[inline]
+fn header_size(body_len: usize) -> usize {
+    1 + count_size(body_len)
+}
+
+/// The size of a length (or element count) indicator.
+#[inline]
+fn count_size(count: usize) -> usize {
+    if count < LENGTH_ESCAPE as usize {
+        2
+    } else {
+        6
+    }
+}
+
+/// The size of a serialized standard principal (version and hash bytes).
+pub(super) const STANDARD_PRINCIPAL_SIZE: usize = 21;
+
+fn serialized_body_size(value: &Value) -> usize {
+    match value {
+        Value::UInt(_) | Value::Int(_) => 16,
+        Value::Bool(_) => 1,
+        Value::Optional(o) => 1 + o.data.as_deref().map_or(0, serialized_size),
+        Value::Response(r) => 1 + serialized_size(&r.data),
+        Value::Sequence(SequenceData::String(CharType::ASCII(s))) => s.data.len(),
+        Value::Sequence(SequenceData::String(CharType::UTF8(s))) => {
+            s.data.iter().map(Vec::len).sum()
+        }
+        Value::Sequence(SequenceData::Buffer(b)) => b.data.len(),
+        Value::Sequence(SequenceData::List(l)) => {
+            count_size(l.data.len())
+                + list_type_size(&l.type_signature)
+                + l.data.iter().map(serialized_size).sum::<usize>()
+        }
+        Value::Principal(PrincipalData::Standard(_)) => STANDARD_PRINCIPAL_SIZE,
+        Value::Principal(PrincipalData::Contract(ctr)) => {
+            STANDARD_PRINCIPAL_SIZE + 2 + ctr.name.len()
+        }
+        Value::CallableContract(ctr) => {
+            let trait_size = ctr.trait_identifier.as_ref().map_or(0, |trait_id| {
+                STANDARD_PRINCIPAL_SIZE + 2 + trait_id.name.len()
+            });
+            STANDARD_PRINCIPAL_SIZE + 2 + ctr.contract_identifier.name.len() + 1 + trait_size
+        }
+        Value::Tuple(tuple) => {
+            count_size(tuple.data_map.len())
+                + tuple
+                    .data_map
+                    .iter()
+                    .map(|(name, val)| 1 + name.len() + serialized_size(val))
+                    .sum::<usize>()
+        }
+    }
+}
+
+/// Writes to a fixed-size slice, failing rather than panicking when it is full.
+struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    offset: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) -> Result<(), SerializationError> {
+        let end = self.offset + bytes.len();
+        self.buffer
+            .get_mut(self.offset..end)
+            .ok_or(SerializationError::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.offset = end;
+        Ok(())
+    }
+
+    #[inline]
+    fn write_u8(&mut self, byte: u8) -> Result<(), SerializationError> {
+        self.write(&[byte])
+    }
+
+    #[inline]
+    fn write_count(&mut self, count: usize) -> Result<(), SerializationError> {
+        if count < LENGTH_ESCAPE as usize {
+            self.write(&(count as u16).to_le_bytes())
+        } else {
+            let count = u32::try_from(count).map_err(|_| SerializationError::ValueTooLarge)?;
+            self.write(&LENGTH_ESCAPE.to_le_bytes())?;
+            self.write(&count.to_le_bytes())
+        }
+    }
+
+    #[inline]
+    fn write_standard_principal(
+        &mut self,
+        principal: &StandardPrincipalData,
+    ) -> Result<(), SerializationError> {
+        self.write_u8(principal.0)?;
+        self.write(&principal.1)
+    }
+
+    #[inline]
+    fn write_name(&mut self, name: &str) -> Result<(), SerializationError> {
+        self.write(&(name.len() as u16).to_le_bytes())?;
+        self.write(name.as_bytes())
+    }
+
+    /// Writes a value, header included. As the length of the value isn't known until
+    /// it has been written, the body is written after a short (`u16`) length header,
+    /// and moved along if it turns out to need the escaped (`u32`) header instead.
+    fn write_value(&mut self, value: &Value) -> Result<(), SerializationError> {
+        let start = self.offset;
+        self.write_u8(get_type_indicator_for_clarity_value(value))?;
+        self.write(&[0, 0])?;
+        let body_start = self.offset;
+
+        self.write_body(value)?;
+
+        let body_len = self.offset - body_start;
+        if body_len < LENGTH_ESCAPE as usize {
+            self.buffer[start + 1..body_start].copy_from_slice(&(body_len as u16).to_le_bytes());
+        } else {
+            let len = u32::try_from(body_len).map_err(|_| SerializationError::ValueTooLarge)?;
+            if self.buffer.len() < self.offset + 4 {
+                Err(SerializationError::BufferTooSmall)?
+            }
+            self.buffer
+                .copy_within(body_start..self.offset, body_start + 4);
+            self.buffer[start + 1..start + 3].copy_from_slice(&LENGTH_ESCAPE.to_le_bytes());
+            self.buffer[start + 3..start + 7].copy_from_slice(&len.to_le_bytes());
+            self.offset += 4;
+        }
+
+        Ok(())
+    }
+
+    fn write_body(&mut self, value: &Value) -> Result<(), SerializationError> {
+        match value {
+            Value::UInt(n) => self.write(&n.to_le_bytes())?,
+            Value::Int(n) => self.write(&n.to_le_bytes())?,
+            Value::Bool(b) => self.write_u8(*b as u8)?,
+            Value::Optional(o) => {
+                self.write_u8(o.data.is_some() as u8)?;
+                if let Some(data) = &o.data {
+                    self.write_value(data)?;
+                }
+            }
+            Value::Response(r) => {
+                self.write_u8(r.committed as u8)?;
+                self.write_value(&r.data)?;
+            }
+            Value::Sequence(SequenceData::String(CharType::ASCII(s))) => self.write(&s.data)?,
+            Value::Sequence(SequenceData::String(CharType::UTF8(s))) => {
+                check_utf8_chars(s)?;
+                for c in &s.data {
+                    self.write(c)?;
+                }
+            }
+            Value::Sequence(SequenceData::Buffer(b)) => self.write(&b.data)?,
+            Value::Sequence(SequenceData::List(l)) => {
+                self.write_count(l.data.len())?;
+                write_list_type(&l.type_signature, &mut |bytes| self.write(bytes))?;
+                for item in &l.data {
+                    self.write_value(item)?;
+                }
+            }
+            Value::Principal(PrincipalData::Standard(std)) => self.write_standard_principal(std)?,
+            Value::Principal(PrincipalData::Contract(ctr)) => {
+                self.write_standard_principal(&ctr.issuer)?;
+                self.write_name(&ctr.name)?;
+            }
+            Value::CallableContract(ctr) => {
+                self.write_standard_principal(&ctr.contract_identifier.issuer)?;
+                self.write_name(&ctr.contract_identifier.name)?;
+
+                match &ctr.trait_identifier {
+                    Some(trait_id) => {
+                        self.write_u8(1)?;
+                        self.write_standard_principal(&trait_id.contract_identifier.issuer)?;
+                        self.write_name(&trait_id.name)?;
+                    }
+                    None => self.write_u8(0)?,
+                }
+            }
+            Value::Tuple(tuple) => {
+                self.write_count(tuple.data_map.len())?;
+                for (name, val) in &tuple.data_map {
+                    self.write_u8(name.len() as u8)?;
+                    self.write(name.as_bytes())?;
+                    self.write_value(val)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
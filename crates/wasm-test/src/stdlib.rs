@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use wasmtime::{AsContextMut, Instance, Linker};
+
+pub(crate) mod link;
+
+pub use link::{link_into, LinkedStdlib, StdlibLinkError};
+
+/// The name of the module under which the functions of `wasm-stdlib` are linked, and
+/// thereby imported by generated modules.
+pub const STDLIB_MODULE_NAME: &str = "stdlib";
+
+/// The `wasm-stdlib` module, as embedded by the build script when the `stdlib` feature
+/// is enabled. This is empty otherwise (see `link_into`).
+pub static STDLIB_WASM: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/wasm_stdlib.wasm"));
+
+/// A function exported by `wasm-stdlib`, which is exported both under its
+/// Clarity-style (kebab-case) name and under a snake_case alias.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdlibExport {
+    pub name: &'static str,
+    /// The snake_case alias of the function, which is the same as its name if that
+    /// doesn't contain dashes.
+    pub alias: &'static str,
+}
+
+macro_rules! stdlib_exports {
+    ($($name:literal => $alias:ident,)*) => {
+        &[$(StdlibExport {
+            name: $name,
+            alias: stringify!($alias),
+        }),*]
+    };
+}
+
+/// The functions exported by `wasm-stdlib` (see the `export!` macro in its `lib.rs`).
+const STDLIB_EXPORTS: &[StdlibExport] = stdlib_exports![
+    "alloc" => alloc,
+    "free" => free,
+    "mem-copy" => mem_copy,
+    "mem-compare" => mem_compare,
+    "mem-set" => mem_set,
+    "sha256" => sha256,
+    "add-int128" => add_int128,
+    "add-uint128" => add_uint128,
+    "mul-uint128" => mul_uint128,
+    "sub-int128" => sub_int128,
+    "sub-uint128" => sub_uint128,
+    "div-int128" => div_int128,
+    "div-uint128" => div_uint128,
+    "mod-int128" => mod_int128,
+    "mod-uint128" => mod_uint128,
+    "pow-int128" => pow_int128,
+    "pow-uint128" => pow_uint128,
+    "sqrti-int128" => sqrti_int128,
+    "sqrti-uint128" => sqrti_uint128,
+    "log2-int128" => log2_int128,
+    "log2-uint128" => log2_uint128,
+    "lt-int128" => lt_int128,
+    "lt-uint128" => lt_uint128,
+    "le-int128" => le_int128,
+    "le-uint128" => le_uint128,
+    "gt-int128" => gt_int128,
+    "gt-uint128" => gt_uint128,
+    "ge-int128" => ge_int128,
+    "ge-uint128" => ge_uint128,
+    "eq-int128" => eq_int128,
+    "eq-uint128" => eq_uint128,
+    "bit-and-int128" => bit_and_int128,
+    "bit-and-uint128" => bit_and_uint128,
+    "bit-or-int128" => bit_or_int128,
+    "bit-or-uint128" => bit_or_uint128,
+    "bit-xor-int128" => bit_xor_int128,
+    "bit-xor-uint128" => bit_xor_uint128,
+    "bit-not-int128" => bit_not_int128,
+    "bit-not-uint128" => bit_not_uint128,
+    "bit-shift-left-int128" => bit_shift_left_int128,
+    "bit-shift-left-uint128" => bit_shift_left_uint128,
+    "bit-shift-right-int128" => bit_shift_right_int128,
+    "bit-shift-right-uint128" => bit_shift_right_uint128,
+    "add-uint256" => add_uint256,
+    "sub-uint256" => sub_uint256,
+    "mul-wide-uint128" => mul_wide_uint128,
+    "mul-wide-int128" => mul_wide_int128,
+    "mul-wide-uint256" => mul_wide_uint256,
+    "mul-wide-int256" => mul_wide_int256,
+    "read-int128" => read_int128,
+    "read-uint128" => read_uint128,
+    "add-int128-memory" => add_int128_memory,
+    "add-uint128-memory" => add_uint128_memory,
+    "mul-uint128-memory" => mul_uint128_memory,
+];
+
+/// Lookup table of the functions exported by `wasm-stdlib`, by either of their
+/// names, which is used to link them into a `wasmtime::Linker`.
+#[derive(Debug, Clone)]
+pub struct StdlibExports {
+    by_name: HashMap<&'static str, StdlibExport>,
+}
+
+impl StdlibExports {
+    /// Creates a new `StdlibExports` holding all of the functions exported by
+    /// `wasm-stdlib`.
+    pub fn new() -> Self {
+        let by_name = STDLIB_EXPORTS
+            .iter()
+            .flat_map(|export| [(export.name, *export), (export.alias, *export)])
+            .collect();
+
+        StdlibExports { by_name }
+    }
+
+    /// Gets the function with the given name, which may be either its Clarity-style
+    /// name or its snake_case alias.
+    pub fn get(&self, name: &str) -> Option<StdlibExport> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Gets an iterator over all of the functions exported by `wasm-stdlib`.
+    pub fn iter(&self) -> impl Iterator<Item = &StdlibExport> {
+        STDLIB_EXPORTS.iter()
+    }
+
+    /// Registers the functions of the `wasm-stdlib` module in `instance` in the given
+    /// `Linker`, each under the `STDLIB_MODULE_NAME` module using both of its names.
+    /// Fails if the module doesn't export one of the functions.
+    pub fn link<T>(
+        &self,
+        linker: &mut Linker<T>,
+        mut store: impl AsContextMut<Data = T>,
+        instance: &Instance,
+    ) -> wasmtime::Result<()> {
+        for export in self.iter() {
+            let func = instance.get_func(&mut store, export.name).ok_or_else(|| {
+                wasmtime::Error::msg(format!("wasm-stdlib doesn't export `{}`", export.name))
+            })?;
+
+            linker.define(&store, STDLIB_MODULE_NAME, export.name, func)?;
+            if export.alias != export.name {
+                linker.define(&store, STDLIB_MODULE_NAME, export.alias, func)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for StdlibExports {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use walrus::{
+    ir::{
+        dfs_in_order, dfs_pre_order_mut, Block, Br, BrIf, BrTable, IfElse, Instr, InstrSeq,
+        InstrSeqId, Loop, Visitor, VisitorMut,
+    },
+    ActiveData, ActiveDataLocation, DataId, DataKind, ElementId, ElementKind, ExportItem,
+    FunctionBuilder, FunctionId, FunctionKind, GlobalId, GlobalKind, InitExpr, LocalId, MemoryId,
+    Module, ModuleLocals, TableId, TypeId,
+};
+
+use super::{StdlibExports, STDLIB_WASM};
+
+/// An error which occurred while linking `wasm-stdlib` into a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StdlibLinkError {
+    /// The `wasm-stdlib` module isn't embedded, as this crate was built without the
+    /// `stdlib` feature, so there's nothing to link.
+    NotBuilt,
+    /// The `wasm-stdlib` module couldn't be parsed, or contains something which it
+    /// shouldn't (e.g. an import).
+    InvalidModule(String),
+    /// The module can't hold `wasm-stdlib`, e.g. because it already has a memory.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for StdlibLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StdlibLinkError::NotBuilt => {
+                write!(
+                    f,
+                    "the wasm-stdlib module isn't embedded (see the `stdlib` feature)"
+                )
+            }
+            StdlibLinkError::InvalidModule(msg) => write!(f, "invalid wasm-stdlib module: {msg}"),
+            StdlibLinkError::Unsupported(msg) => {
+                write!(f, "wasm-stdlib can't be linked into the module: {msg}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StdlibLinkError {}
+
+/// The functions of `wasm-stdlib` after it has been linked into a module.
+#[derive(Debug, Clone)]
+pub struct LinkedStdlib {
+    functions: HashMap<String, FunctionId>,
+    exports: StdlibExports,
+}
+
+impl LinkedStdlib {
+    /// Gets the id (in the module it was linked into) of the function with the given
+    /// name, which may be either its Clarity-style name or its snake_case alias.
+    pub fn function(&self, name: &str) -> Option<FunctionId> {
+        let name = self.exports.get(name).map_or(name, |export| export.name);
+        self.functions.get(name).copied()
+    }
+}
+
+/// Links the functions of the embedded `wasm-stdlib` module (see `STDLIB_WASM`) into
+/// `module`, so that they can be called directly rather than through imports. Its
+/// memory and globals are added too, and exported unless the module already has an
+/// export with the same name (e.g. `memory`).
+pub fn link_into(module: &mut Module) -> Result<LinkedStdlib, StdlibLinkError> {
+    if STDLIB_WASM.is_empty() {
+        return Err(StdlibLinkError::NotBuilt);
+    }
+    link_bytes_into(module, STDLIB_WASM)
+}
+
+/// Links the functions of the given `wasm-stdlib` module into `module`, see
+/// `link_into`.
+pub(crate) fn link_bytes_into(
+    module: &mut Module,
+    bytes: &[u8],
+) -> Result<LinkedStdlib, StdlibLinkError> {
+    let mut stdlib =
+        Module::from_buffer(bytes).map_err(|e| StdlibLinkError::InvalidModule(e.to_string()))?;
+
+    if let Some(import) = stdlib.imports.iter().next() {
+        return Err(StdlibLinkError::InvalidModule(format!(
+            "unexpected import `{}::{}`",
+            import.module, import.name
+        )));
+    }
+    // Data segments are placed at fixed addresses, which may overlap those of the
+    // module's own memory.
+    if module.memories.iter().next().is_some() {
+        return Err(StdlibLinkError::Unsupported(
+            "the module already has a memory".to_string(),
+        ));
+    }
+
+    let ids = add_items(module, &stdlib);
+
+    for (stdlib_id, func) in stdlib.funcs.iter_local_mut() {
+        let mut remap = RemapIds {
+            ids: &ids,
+            stdlib_locals: &stdlib.locals,
+            module_locals: &mut module.locals,
+            locals: HashMap::new(),
+            mapped_locals: HashSet::new(),
+        };
+        let entry = func.entry_block();
+        dfs_pre_order_mut(&mut remap, func, entry);
+        let args = func.args.iter().map(|arg| remap.local(*arg)).collect();
+
+        // The instructions now only reference items of the module, except for the
+        // instruction sequences, which are copied into a new function.
+        let ty = stdlib.types.get(func.ty());
+        let mut builder = FunctionBuilder::new(&mut module.types, ty.params(), ty.results());
+
+        let mut collect = CollectSeqs::default();
+        dfs_in_order(&mut collect, func, entry);
+        let seqs: HashMap<InstrSeqId, InstrSeqId> = collect
+            .seqs
+            .iter()
+            .map(|seq| {
+                let id = if seq.id() == entry {
+                    builder.func_body_id()
+                } else {
+                    builder.dangling_instr_seq(seq.ty).id()
+                };
+                (seq.id(), id)
+            })
+            .collect();
+
+        for seq in collect.seqs {
+            let mut body = builder.instr_seq(seqs[&seq.id()]);
+            for (instr, _) in seq.instrs.iter() {
+                let mut instr = instr.clone();
+                remap_seqs(&mut instr, &seqs);
+                body.instr(instr);
+            }
+        }
+
+        module.funcs.get_mut(ids.functions[&stdlib_id]).kind =
+            FunctionKind::Local(builder.local_func(args));
+    }
+
+    let mut functions = HashMap::new();
+    for export in stdlib.exports.iter() {
+        let item = match export.item {
+            ExportItem::Function(func) => {
+                functions.insert(export.name.clone(), ids.functions[&func]);
+                continue;
+            }
+            ExportItem::Memory(memory) => ExportItem::Memory(ids.memories[&memory]),
+            ExportItem::Global(global) => ExportItem::Global(ids.globals[&global]),
+            ExportItem::Table(table) => ExportItem::Table(ids.tables[&table]),
+        };
+        if module.exports.iter().all(|e| e.name != export.name) {
+            module.exports.add(&export.name, item);
+        }
+    }
+
+    Ok(LinkedStdlib {
+        functions,
+        exports: StdlibExports::new(),
+    })
+}
+
+/// Maps the ids of the items of `wasm-stdlib` to those of the module it's linked into.
+#[derive(Default)]
+struct IdMap {
+    types: HashMap<TypeId, TypeId>,
+    functions: HashMap<FunctionId, FunctionId>,
+    globals: HashMap<GlobalId, GlobalId>,
+    memories: HashMap<MemoryId, MemoryId>,
+    tables: HashMap<TableId, TableId>,
+    data: HashMap<DataId, DataId>,
+    elements: HashMap<ElementId, ElementId>,
+}
+
+impl IdMap {
+    fn init_expr(&self, init: &InitExpr) -> InitExpr {
+        match init {
+            InitExpr::Value(value) => InitExpr::Value(*value),
+            InitExpr::Global(global) => InitExpr::Global(self.globals[global]),
+            InitExpr::RefNull(ty) => InitExpr::RefNull(*ty),
+            InitExpr::RefFunc(func) => InitExpr::RefFunc(self.functions[func]),
+        }
+    }
+}
+
+/// Adds the items of `stdlib` to `module`, other than the bodies of its functions,
+/// which are added as empty placeholders so that they can be referenced.
+fn add_items(module: &mut Module, stdlib: &Module) -> IdMap {
+    let mut ids = IdMap::default();
+
+    for ty in stdlib.types.iter() {
+        let id = module.types.add(ty.params(), ty.results());
+        ids.types.insert(ty.id(), id);
+    }
+    for memory in stdlib.memories.iter() {
+        let id = module
+            .memories
+            .add_local(memory.shared, memory.initial, memory.maximum);
+        ids.memories.insert(memory.id(), id);
+    }
+    for table in stdlib.tables.iter() {
+        let id = module
+            .tables
+            .add_local(table.initial, table.maximum, table.element_ty);
+        ids.tables.insert(table.id(), id);
+    }
+    for func in stdlib.funcs.iter() {
+        let ty = stdlib.types.get(func.ty());
+        let id = FunctionBuilder::new(&mut module.types, ty.params(), ty.results())
+            .finish(Vec::new(), &mut module.funcs);
+        module.funcs.get_mut(id).name = func.name.clone();
+        ids.functions.insert(func.id(), id);
+    }
+    for global in stdlib.globals.iter() {
+        let init = match &global.kind {
+            GlobalKind::Local(init) => ids.init_expr(init),
+            GlobalKind::Import(_) => unreachable!("imports are rejected before linking"),
+        };
+        let id = module.globals.add_local(global.ty, global.mutable, init);
+        ids.globals.insert(global.id(), id);
+    }
+    for data in stdlib.data.iter() {
+        let (kind, memory) = match &data.kind {
+            DataKind::Passive => (DataKind::Passive, None),
+            DataKind::Active(active) => {
+                let memory = ids.memories[&active.memory];
+                let location = match active.location {
+                    ActiveDataLocation::Absolute(offset) => ActiveDataLocation::Absolute(offset),
+                    ActiveDataLocation::Relative(global) => {
+                        ActiveDataLocation::Relative(ids.globals[&global])
+                    }
+                };
+                (
+                    DataKind::Active(ActiveData { memory, location }),
+                    Some(memory),
+                )
+            }
+        };
+        let id = module.data.add(kind, data.value.clone());
+        if let Some(memory) = memory {
+            module.memories.get_mut(memory).data_segments.insert(id);
+        }
+        ids.data.insert(data.id(), id);
+    }
+    for element in stdlib.elements.iter() {
+        let (kind, table) = match &element.kind {
+            ElementKind::Passive => (ElementKind::Passive, None),
+            ElementKind::Declared => (ElementKind::Declared, None),
+            ElementKind::Active { table, offset } => {
+                let table = ids.tables[table];
+                let offset = ids.init_expr(offset);
+                (ElementKind::Active { table, offset }, Some(table))
+            }
+        };
+        let members = element
+            .members
+            .iter()
+            .map(|member| member.map(|func| ids.functions[&func]))
+            .collect();
+        let id = module.elements.add(kind, element.ty, members);
+        if let Some(table) = table {
+            module.tables.get_mut(table).elem_segments.insert(id);
+        }
+        ids.elements.insert(element.id(), id);
+    }
+
+    ids
+}
+
+/// Maps the ids referenced by the instructions of a `wasm-stdlib` function to those
+/// of the module it's linked into, adding its locals to the module as they're found.
+struct RemapIds<'a> {
+    ids: &'a IdMap,
+    stdlib_locals: &'a ModuleLocals,
+    module_locals: &'a mut ModuleLocals,
+    locals: HashMap<LocalId, LocalId>,
+    mapped_locals: HashSet<LocalId>,
+}
+
+impl RemapIds<'_> {
+    fn local(&mut self, local: LocalId) -> LocalId {
+        if self.mapped_locals.contains(&local) {
+            return local;
+        }
+        if let Some(id) = self.locals.get(&local) {
+            return *id;
+        }
+        let id = self.module_locals.add(self.stdlib_locals.get(local).ty());
+        self.locals.insert(local, id);
+        self.mapped_locals.insert(id);
+        id
+    }
+}
+
+/// Maps `id` to the module `wasm-stdlib` is linked into. `dfs_pre_order_mut` visits
+/// the operands of each instruction twice, so ids which have already been mapped
+/// (which never compare equal to those of another module) are left as is.
+fn remap<T: Copy + Eq + Hash>(ids: &HashMap<T, T>, id: &mut T) {
+    if let Some(mapped) = ids.get(id) {
+        *id = *mapped;
+    }
+}
+
+impl VisitorMut for RemapIds<'_> {
+    fn visit_local_id_mut(&mut self, local: &mut LocalId) {
+        *local = self.local(*local);
+    }
+
+    fn visit_memory_id_mut(&mut self, memory: &mut MemoryId) {
+        remap(&self.ids.memories, memory);
+    }
+
+    fn visit_table_id_mut(&mut self, table: &mut TableId) {
+        remap(&self.ids.tables, table);
+    }
+
+    fn visit_global_id_mut(&mut self, global: &mut GlobalId) {
+        remap(&self.ids.globals, global);
+    }
+
+    fn visit_function_id_mut(&mut self, function: &mut FunctionId) {
+        remap(&self.ids.functions, function);
+    }
+
+    fn visit_data_id_mut(&mut self, data: &mut DataId) {
+        remap(&self.ids.data, data);
+    }
+
+    fn visit_type_id_mut(&mut self, ty: &mut TypeId) {
+        remap(&self.ids.types, ty);
+    }
+
+    fn visit_element_id_mut(&mut self, elem: &mut ElementId) {
+        remap(&self.ids.elements, elem);
+    }
+}
+
+/// Collects the instruction sequences of a function.
+#[derive(Default)]
+struct CollectSeqs<'a> {
+    seqs: Vec<&'a InstrSeq>,
+}
+
+impl<'a> Visitor<'a> for CollectSeqs<'a> {
+    fn start_instr_seq(&mut self, seq: &'a InstrSeq) {
+        self.seqs.push(seq);
+    }
+}
+
+/// Maps the instruction sequences referenced by `instr` to those of the copied
+/// function. Unlike other ids, these can't be mapped by `RemapIds`, as the traversal
+/// follows them into the sequences of the original function.
+fn remap_seqs(instr: &mut Instr, seqs: &HashMap<InstrSeqId, InstrSeqId>) {
+    match instr {
+        Instr::Block(Block { seq }) | Instr::Loop(Loop { seq }) => *seq = seqs[seq],
+        Instr::Br(Br { block }) | Instr::BrIf(BrIf { block }) => *block = seqs[block],
+        Instr::IfElse(IfElse {
+            consequent,
+            alternative,
+        }) => {
+            *consequent = seqs[consequent];
+            *alternative = seqs[alternative];
+        }
+        Instr::BrTable(BrTable { blocks, default }) => {
+            for block in blocks.iter_mut() {
+                *block = seqs[block];
+            }
+            *default = seqs[default];
+        }
+        _ => {}
+    }
+}
@@ -1,4 +1,9 @@
+mod alloc;
 mod compiler;
 mod datastore;
 mod native_functions;
+mod runtime;
 mod serialization;
+mod stdlib;
+#[cfg(feature = "stdlib")]
+mod stdlib_guest;
@@ -0,0 +1,530 @@
+use crate::{
+    runtime::{
+        AllocatorStats, BuddyAllocator, FrameAllocator, GuardedAllocator, Relocation,
+        WasmAllocator, WasmMemoryAllocator,
+    },
+    ClarityWasmContext, Ptr,
+};
+use test_case::test_case;
+
+/// Helper function. Asserts that none of the provided allocations overlap.
+fn assert_disjoint(ptrs: &[Ptr]) {
+    let mut ranges = ptrs
+        .iter()
+        .map(|ptr| (ptr.offset, ptr.offset + ptr.len.max(1)))
+        .collect::<Vec<_>>();
+    ranges.sort();
+    for pair in ranges.windows(2) {
+        assert!(
+            pair[0].1 <= pair[1].0,
+            "{:?} overlaps {:?}",
+            pair[0],
+            pair[1]
+        );
+    }
+}
+
+#[test]
+fn test_bump_dealloc_reuses_freed_region() {
+    let mut alloc = WasmAllocator::new();
+    let a = alloc.alloc_for_size(10);
+    alloc.alloc_for_size(20);
+    alloc.alloc_for_size(5);
+
+    assert!(alloc.dealloc(a));
+    let reused = alloc.alloc_for_size(8);
+    assert_eq!((0, 8), (reused.offset, reused.len));
+    // The rest of the freed region is kept for smaller allocations.
+    assert_eq!(8, alloc.alloc_for_size(2).offset);
+    assert_eq!(35, alloc.alloc_for_size(2).offset);
+}
+
+#[test]
+fn test_bump_dealloc_uses_larger_size_classes() {
+    let mut alloc = WasmAllocator::new();
+    let a = alloc.alloc_for_size(100);
+    alloc.alloc_for_size(1);
+
+    assert!(alloc.dealloc(a));
+    assert_eq!(0, alloc.alloc_for_size(40).offset);
+    assert_eq!(40, alloc.alloc_for_size(60).offset);
+    assert_eq!(101, alloc.alloc_for_size(1).offset);
+}
+
+#[test]
+fn test_bump_dealloc_most_recent_allocation() {
+    let mut alloc = WasmAllocator::new();
+    alloc.alloc_for_size(10);
+
+    // E.g. a loop which allocates and frees a temporary value on each iteration
+    // doesn't use up any more memory.
+    for _ in 0..10_000 {
+        let ptr = alloc.alloc_for_size(32);
+        assert_eq!(10, ptr.offset);
+        assert!(alloc.dealloc(ptr));
+    }
+}
+
+#[test_case(Ptr::new(0, 0) ; "empty")]
+#[test_case(Ptr::new(-4, 8) ; "negative offset")]
+#[test_case(Ptr::new(8, 16) ; "past the allocated memory")]
+#[test_case(Ptr::new(i32::MAX, 16) ; "overflowing")]
+fn test_bump_dealloc_invalid_region(ptr: Ptr) {
+    let mut alloc = WasmAllocator::new();
+    alloc.alloc_for_size(16);
+
+    assert!(!alloc.dealloc(ptr));
+}
+
+#[test]
+fn test_bump_reset() {
+    let mut alloc = WasmAllocator::new();
+    let a = alloc.alloc_for_size(10);
+    alloc.alloc_for_size(10);
+    alloc.dealloc(a);
+
+    alloc.reset();
+    let ptr = alloc.alloc_for_size(4);
+    assert_eq!(0, ptr.offset);
+    assert_eq!(4, alloc.alloc_for_size(4).offset);
+}
+
+#[test]
+fn test_bump_realloc_grows_most_recent_in_place() {
+    let mut alloc = WasmAllocator::new();
+    let ptr = alloc.alloc_for_size(10);
+
+    let realloc = alloc.realloc(ptr, 30).expect("Failed to realloc");
+    assert!(!realloc.needs_copy);
+    assert_eq!((0, 30), (realloc.ptr.offset, realloc.ptr.len));
+    assert_eq!(30, alloc.alloc_for_size(1).offset);
+}
+
+#[test]
+fn test_bump_realloc_moves_other_allocations() {
+    let mut alloc = WasmAllocator::new();
+    let ptr = alloc.alloc_for_size(10);
+    alloc.alloc_for_size(10);
+
+    let realloc = alloc.realloc(ptr, 30).expect("Failed to realloc");
+    assert!(realloc.needs_copy);
+    assert_eq!((20, 30), (realloc.ptr.offset, realloc.ptr.len));
+    // The old region was freed.
+    assert_eq!(0, alloc.alloc_for_size(10).offset);
+}
+
+#[test]
+fn test_bump_realloc_shrinks_in_place() {
+    let mut alloc = WasmAllocator::new();
+    let ptr = alloc.alloc_for_size(32);
+    alloc.alloc_for_size(1);
+
+    let realloc = alloc.realloc(ptr, 16).expect("Failed to realloc");
+    assert!(!realloc.needs_copy);
+    assert_eq!((0, 16), (realloc.ptr.offset, realloc.ptr.len));
+    assert_eq!(16, alloc.alloc_for_size(16).offset);
+    assert!(alloc.realloc(Ptr::new(40, 4), 8).is_none());
+}
+
+#[test]
+fn test_buddy_realloc_grows_in_place() {
+    let mut alloc = BuddyAllocator::new();
+    let ptr = alloc.alloc_for_size(16);
+
+    let realloc = alloc.realloc(ptr, 64).expect("Failed to realloc");
+    assert!(!realloc.needs_copy);
+    assert_eq!((0, 64), (realloc.ptr.offset, realloc.ptr.len));
+    assert_eq!(64, alloc.alloc_for_size(16).offset);
+}
+
+#[test]
+fn test_buddy_realloc_moves_when_buddy_is_taken() {
+    let mut alloc = BuddyAllocator::new();
+    let a = alloc.alloc_for_size(16);
+    alloc.alloc_for_size(16);
+
+    let realloc = alloc.realloc(a, 32).expect("Failed to realloc");
+    assert!(realloc.needs_copy);
+    assert_eq!(32, realloc.ptr.offset);
+    // The old block was freed.
+    assert!(alloc.realloc(a, 8).is_none());
+    assert_eq!(a.offset, alloc.alloc_for_size(16).offset);
+}
+
+#[test]
+fn test_buddy_realloc_moves_upper_buddies() {
+    let mut alloc = BuddyAllocator::new();
+    let a = alloc.alloc_for_size(16);
+    let b = alloc.alloc_for_size(16);
+
+    // Upper buddies can't grow in place, even if the lower buddy is free.
+    assert!(alloc.dealloc(a));
+    let realloc = alloc.realloc(b, 32).expect("Failed to realloc");
+    assert!(realloc.needs_copy);
+    assert_eq!(32, realloc.ptr.offset);
+}
+
+#[test]
+fn test_buddy_realloc_shrinks_in_place() {
+    let mut alloc = BuddyAllocator::new();
+    let ptr = alloc.alloc_for_size(64);
+
+    let realloc = alloc.realloc(ptr, 16).expect("Failed to realloc");
+    assert!(!realloc.needs_copy);
+    assert_eq!((0, 16), (realloc.ptr.offset, realloc.ptr.len));
+    assert_eq!(16, alloc.alloc_for_size(16).offset);
+    assert_eq!(32, alloc.alloc_for_size(32).offset);
+}
+
+#[test_case(&[1, 16, 17, 100] => vec![0, 16, 32, 128] ; "rounds up to blocks")]
+#[test_case(&[0, 0] => vec![0, 16] ; "empty allocations")]
+#[test_case(&[64, 16, 32] => vec![0, 64, 96] ; "fills split halves")]
+fn test_buddy_alloc_offsets(sizes: &[usize]) -> Vec<i32> {
+    let mut alloc = BuddyAllocator::new();
+
+    sizes
+        .iter()
+        .map(|size| {
+            let ptr = alloc.alloc_for_size(*size);
+            assert_eq!(*size as i32, ptr.len);
+            ptr.offset
+        })
+        .collect()
+}
+
+#[test]
+fn test_buddy_dealloc_reuses_block() {
+    let mut alloc = BuddyAllocator::new();
+    let a = alloc.alloc_for_size(20);
+    let b = alloc.alloc_for_size(20);
+
+    assert!(alloc.dealloc(a));
+    assert_eq!(a.offset, alloc.alloc_for_size(32).offset);
+    assert_eq!(b.offset + 32, alloc.alloc_for_size(20).offset);
+}
+
+#[test]
+fn test_buddy_dealloc_merges_buddies() {
+    let mut alloc = BuddyAllocator::new();
+    let ptrs = (0..8).map(|_| alloc.alloc_for_size(16)).collect::<Vec<_>>();
+
+    // Free the blocks out of order; each ends up merged with its buddy.
+    for i in [3, 0, 6, 1, 7, 2, 5, 4] {
+        assert!(alloc.dealloc(ptrs[i]));
+    }
+
+    // The whole arena is free again, so it can be handed out in one piece.
+    let arena_size = alloc.arena_size();
+    assert_eq!(0, alloc.alloc_for_size(arena_size).offset);
+    assert_eq!(arena_size, alloc.arena_size());
+}
+
+#[test]
+fn test_buddy_dealloc_unknown_pointers() {
+    let mut alloc = BuddyAllocator::new();
+    let ptr = alloc.alloc_for_size(8);
+
+    assert!(!alloc.dealloc(Ptr::new(ptr.offset + 16, 8)));
+    assert!(!alloc.dealloc(Ptr::new(-16, 8)));
+    assert!(alloc.dealloc(ptr));
+    assert!(!alloc.dealloc(ptr), "double free");
+}
+
+#[test]
+fn test_buddy_grows_arena() {
+    let mut alloc = BuddyAllocator::new();
+    assert_eq!(0, alloc.arena_size());
+
+    let first = alloc.alloc_for_size(65_536);
+    let second = alloc.alloc_for_size(1);
+    assert_eq!((0, 65_536), (first.offset, second.offset));
+    assert_eq!(131_072, alloc.arena_size());
+
+    // Requests larger than the arena grow it (repeatedly) as well.
+    let large = alloc.alloc_for_size(200_000);
+    assert_eq!(262_144, large.offset);
+    assert_eq!(524_288, alloc.arena_size());
+    assert_disjoint(&[first, second, large]);
+}
+
+#[test]
+fn test_buddy_alloc_free_pattern() {
+    let mut alloc = BuddyAllocator::new();
+    // A simple deterministic pseudo-random sequence of sizes.
+    let mut seed = 42u32;
+    let mut next_size = move || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (seed >> 16) as usize % 3_000
+    };
+
+    let mut live = (0..200)
+        .map(|_| alloc.alloc_for_size(next_size()))
+        .collect::<Vec<_>>();
+    assert_disjoint(&live);
+
+    // Free every other allocation and fill the holes with new ones.
+    let mut i = 0;
+    live.retain(|ptr| {
+        i += 1;
+        i % 2 == 0 || !alloc.dealloc(*ptr)
+    });
+    let arena_size = alloc.arena_size();
+    live.extend((0..100).map(|_| alloc.alloc_for_size(next_size())));
+    assert_disjoint(&live);
+    assert_eq!(arena_size, alloc.arena_size(), "freed blocks are re-used");
+
+    // Once everything is freed, the blocks merge back into a single one.
+    for ptr in live {
+        assert!(alloc.dealloc(ptr));
+    }
+    let arena_size = alloc.arena_size();
+    assert_eq!(0, alloc.alloc_for_size(arena_size).offset);
+    assert_eq!(arena_size, alloc.arena_size());
+}
+
+#[test]
+fn test_bump_stats() {
+    let mut alloc = WasmAllocator::new();
+    let a = alloc.alloc_for_size(10);
+    let b = alloc.alloc_for_size(30);
+    alloc.alloc_for_size(20);
+    alloc.dealloc(a);
+    alloc.realloc(b, 20);
+
+    assert_eq!(
+        AllocatorStats {
+            bytes_allocated: 60,
+            bytes_freed: 20,
+            live_allocations: 2,
+            peak_usage: 60,
+            memory_size: 60,
+        },
+        alloc.stats()
+    );
+    assert_eq!(40, alloc.stats().live_bytes());
+    assert!((alloc.stats().fragmentation() - 1.0 / 3.0).abs() < 1e-9);
+
+    // Resetting frees everything, but keeps the cumulative counts and the peak.
+    alloc.reset();
+    let stats = alloc.stats();
+    assert_eq!(0, stats.live_bytes());
+    assert_eq!(0, stats.live_allocations);
+    assert_eq!(60, stats.peak_usage);
+    assert_eq!(0.0, stats.fragmentation());
+}
+
+#[test]
+fn test_buddy_stats() {
+    let mut alloc = BuddyAllocator::new();
+    let a = alloc.alloc_for_size(20);
+    let b = alloc.alloc_for_size(16);
+    // The block can't grow in place, so it's moved.
+    alloc.realloc(b, 60);
+    alloc.dealloc(a);
+
+    // Allocations count with the size of their blocks.
+    assert_eq!(
+        AllocatorStats {
+            bytes_allocated: 32 + 16 + 64,
+            bytes_freed: 32 + 16,
+            live_allocations: 1,
+            peak_usage: 32 + 16 + 64,
+            memory_size: 65_536,
+        },
+        alloc.stats()
+    );
+
+    let ptr = alloc.alloc_for_size(16);
+    alloc.realloc(ptr, 32);
+    assert_eq!(64 + 32, alloc.stats().live_bytes());
+}
+
+#[test_case(0, false ; "before the first allocation")]
+#[test_case(0, true ; "past the first allocation")]
+#[test_case(1, false ; "before the second allocation")]
+#[test_case(1, true ; "past the second allocation")]
+fn test_guarded_check_canaries(index: usize, past_end: bool) {
+    let mut alloc = GuardedAllocator::new(WasmAllocator::new());
+    let mut memory = vec![0u8; 256];
+    let ptrs = [alloc.alloc_for_size(10), alloc.alloc_for_size(20)];
+    assert_eq!((8, 34), (ptrs[0].offset, ptrs[1].offset));
+
+    alloc.write_canaries(&mut memory);
+    assert!(alloc.check_canaries(&memory).is_ok());
+    // Writing within the allocations leaves the canaries intact.
+    for ptr in ptrs {
+        memory[ptr.offset as usize..(ptr.offset + ptr.len) as usize].fill(0xFF);
+    }
+    assert!(alloc.check_canaries(&memory).is_ok());
+
+    let ptr = ptrs[index];
+    let overwritten = if past_end {
+        ptr.offset + ptr.len
+    } else {
+        ptr.offset - 1
+    };
+    memory[overwritten as usize] = 0;
+
+    let err = alloc.check_canaries(&memory).unwrap_err();
+    assert_eq!((ptr.offset, ptr.len), (err.offset, err.len));
+}
+
+#[test]
+fn test_guarded_dealloc_and_realloc() {
+    let mut alloc = GuardedAllocator::new(BuddyAllocator::new());
+    let mut memory = vec![0u8; 256];
+    let a = alloc.alloc_for_size(8);
+    let b = alloc.alloc_for_size(8);
+    alloc.write_canaries(&mut memory);
+
+    // Freed allocations are no longer checked.
+    assert!(alloc.dealloc(a));
+    assert!(!alloc.dealloc(a), "double free");
+    memory[..32].fill(0);
+    assert!(alloc.check_canaries(&memory).is_ok());
+
+    // The canaries of resized allocations are written again.
+    let realloc = alloc.realloc(b, 40).expect("Failed to realloc");
+    alloc.write_canaries(&mut memory);
+    let end = (realloc.ptr.offset + realloc.ptr.len) as usize;
+    assert_eq!([0xCA; 8], memory[end..end + 8]);
+    assert!(alloc.check_canaries(&memory).is_ok());
+}
+
+#[test]
+fn test_frame_drop_frees_allocations() {
+    let mut alloc = FrameAllocator::new(WasmAllocator::new());
+    let outside = alloc.alloc_for_size(10);
+
+    alloc.push_frame();
+    alloc.alloc_for_size(20);
+    alloc.alloc_for_size(5);
+    alloc.alloc_for_size(30);
+    alloc.drop_frame();
+
+    // The frame's allocations are freed, and the bump allocator's next offset is
+    // moved back over them; allocations made outside of it are kept.
+    assert_eq!(0, alloc.frame_depth());
+    assert_eq!(1, alloc.stats().live_allocations);
+    assert_eq!(10, alloc.alloc_for_size(4).offset);
+    assert!(alloc.dealloc(outside));
+}
+
+#[test]
+fn test_frame_drop_nested_frames() {
+    let mut alloc = FrameAllocator::new(BuddyAllocator::new());
+    alloc.push_frame();
+    let outer = alloc.alloc_for_size(16);
+
+    alloc.push_frame();
+    let inner = alloc.alloc_for_size(16);
+    let freed = alloc.alloc_for_size(16);
+    assert!(alloc.dealloc(freed));
+    // Resized allocations stay tagged with their frame.
+    let resized = alloc.realloc(inner, 64).expect("Failed to realloc").ptr;
+    assert_eq!(2, alloc.frame_depth());
+    alloc.drop_frame();
+
+    assert_eq!(1, alloc.stats().live_allocations);
+    assert!(!alloc.dealloc(resized), "freed with its frame");
+    assert!(alloc.realloc(outer, 32).is_some());
+
+    alloc.drop_frame();
+    assert_eq!(0, alloc.stats().live_allocations);
+    // Dropping a frame which doesn't exist does nothing.
+    alloc.drop_frame();
+}
+
+/// Helper function. Fills the memory of each allocation with its index.
+fn fill_allocations(memory: &mut [u8], ptrs: &[Ptr]) {
+    for (i, ptr) in ptrs.iter().enumerate() {
+        memory[ptr.offset as usize..(ptr.offset + ptr.len) as usize].fill(i as u8 + 1);
+    }
+}
+
+#[test]
+fn test_bump_compact() {
+    let mut alloc = WasmAllocator::new();
+    let mut memory = vec![0u8; 64];
+    let ptrs = [10, 10, 5, 5].map(|size| alloc.alloc_for_size(size));
+    fill_allocations(&mut memory, &ptrs);
+    alloc.dealloc(ptrs[0]);
+    alloc.dealloc(ptrs[2]);
+
+    let relocations = alloc.compact(&mut memory);
+
+    assert_eq!(
+        &[
+            Relocation {
+                old_offset: 10,
+                new_offset: 0,
+                len: 10
+            },
+            Relocation {
+                old_offset: 25,
+                new_offset: 10,
+                len: 5
+            },
+        ],
+        relocations.relocations()
+    );
+    let (b, d) = (relocations.relocate(ptrs[1]), relocations.relocate(ptrs[3]));
+    assert_eq!((0, 10), (b.offset, d.offset));
+    assert_eq!([2; 10], memory[..10]);
+    assert_eq!([4; 5], memory[10..15]);
+    assert_eq!(50, relocations.relocate_offset(50));
+    assert_eq!(15, alloc.alloc_for_size(4).offset);
+}
+
+#[test]
+fn test_buddy_compact() {
+    let mut alloc = BuddyAllocator::new();
+    let mut memory = vec![0u8; 128];
+    let ptrs = [16, 32, 16].map(|size| alloc.alloc_for_size(size));
+    assert_eq!([0, 32, 16], ptrs.map(|ptr| ptr.offset));
+    fill_allocations(&mut memory, &ptrs);
+    alloc.dealloc(ptrs[0]);
+
+    let relocations = alloc.compact(&mut memory);
+
+    // The largest block is placed first.
+    let (b, c) = (relocations.relocate(ptrs[1]), relocations.relocate(ptrs[2]));
+    assert_eq!((0, 32), (b.offset, c.offset));
+    assert_eq!([2; 32], memory[..32]);
+    assert_eq!([3; 16], memory[32..48]);
+    assert_eq!(48, alloc.alloc_for_size(16).offset);
+    assert!(alloc.dealloc(b));
+    assert!(alloc.dealloc(c));
+}
+
+#[test]
+fn test_guarded_compact() {
+    let mut alloc = GuardedAllocator::new(WasmAllocator::new());
+    let mut memory = vec![0u8; 128];
+    let a = alloc.alloc_for_size(10);
+    let b = alloc.alloc_for_size(10);
+    alloc.write_canaries(&mut memory);
+    alloc.dealloc(a);
+
+    let relocations = alloc.compact(&mut memory);
+
+    // The canaries are moved along with the allocation.
+    let b = relocations.relocate(b);
+    assert_eq!(8, b.offset);
+    assert!(alloc.check_canaries(&memory).is_ok());
+    assert!(alloc.dealloc(b));
+}
+
+#[test]
+fn test_context_allocator_selection() {
+    // The bump allocator is used by default.
+    let mut context = ClarityWasmContext::new();
+    context.alloc.alloc_for_size(10);
+    assert_eq!(10, context.alloc.alloc_for_size(10).offset);
+
+    // The buddy allocator rounds allocations up to blocks.
+    let mut context = ClarityWasmContext::new().with_allocator(BuddyAllocator::new());
+    let ptr = context.alloc.alloc_for_size(10);
+    assert_eq!(16, context.alloc.alloc_for_size(10).offset);
+    assert!(context.alloc.dealloc(ptr));
+}
@@ -1,13 +1,38 @@
 use clarity::{
     types::StacksEpochId,
     vm::{
+        analysis::{AnalysisDatabase, ContractAnalysis},
         costs::LimitedCostTracker,
-        types::{QualifiedContractIdentifier, StandardPrincipalData},
-        ClarityVersion, ContractName,
+        types::{
+            PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, TraitIdentifier,
+            TypeSignature,
+        },
+        ClarityName, ClarityVersion, ContractName, Value,
     },
 };
+use test_case::test_case;
+use wasmtime::{
+    Config, Engine, ExternRef, Func, Global, GlobalType, Instance, Linker, Module, Mutability,
+    Store, Table, TableType, Val, ValType,
+};
 
-use crate::compiler::{analyze_contract, compile};
+#[cfg(feature = "serde")]
+use crate::compiler::DiskCacheBackend;
+#[cfg(not(feature = "stdlib"))]
+use crate::stdlib::StdlibLinkError;
+use crate::{
+    compiler::{
+        analyze_contract, compile, wasm_generator::WasmGenerator, CacheKey, CacheStats,
+        CodegenConvention, CodegenOptions, CompilationCache, CompileResult, DataMapMetadata,
+        DataVarMetadata, FunctionAccess, GenerationError, MemoryCacheBackend, PrepareContextError,
+        SequenceLowering, SourceMap, WasmGenerationError, TOP_LEVEL_FUNCTION,
+    },
+    runtime::{
+        native_functions::{get_all_functions, link_host_functions},
+        read_value_from_memory, write_value_to_memory,
+    },
+    ClarityWasmContext,
+};
 
 use super::datastore::Datastore;
 
@@ -38,3 +63,1246 @@ fn test_compile() {
 
     let _compile_result = compile(&analyze_result.contract_analysis).unwrap();
 }
+
+#[test]
+fn test_import_host_function_is_deduplicated() {
+    let mut generator = WasmGenerator::new();
+
+    let first = generator.import_host_function("gt_extref").unwrap();
+    let second = generator.import_host_function("gt_extref").unwrap();
+    assert_eq!(first, second);
+
+    let other = generator.import_host_function("is_eq_extref").unwrap();
+    assert_ne!(first, other);
+
+    assert!(generator.import_host_function("no_such_function").is_err());
+}
+
+#[cfg(feature = "stdlib")]
+#[test]
+fn test_stdlib_function_is_linked_once() {
+    let mut generator = WasmGenerator::new();
+
+    let first = generator
+        .stdlib_function("add-int128")
+        .expect("Failed to link wasm-stdlib");
+    assert_eq!(first, generator.stdlib_function("add_int128").unwrap());
+    assert_ne!(first, generator.stdlib_function("sub-int128").unwrap());
+    assert_eq!(
+        1,
+        generator
+            .module
+            .exports
+            .iter()
+            .filter(|export| export.name == "memory")
+            .count()
+    );
+}
+
+#[cfg(not(feature = "stdlib"))]
+#[test]
+fn test_stdlib_function_requires_stdlib_feature() {
+    let mut generator = WasmGenerator::new();
+
+    assert!(matches!(
+        generator.stdlib_function("add-int128"),
+        Err(WasmGenerationError::StdlibLink(StdlibLinkError::NotBuilt))
+    ));
+}
+
+#[test_case(Value::Int(3), Value::Int(2) => Value::Bool(true))]
+#[test_case(Value::Int(2), Value::Int(3) => Value::Bool(false))]
+fn test_function_parameters_are_resolved(a: Value, b: Value) -> Value {
+    call_private_function("(define-private (pick (a int) (b int)) (> a b))", &[a, b])
+}
+
+#[test_case(Value::Int(3), Value::Int(2) => Value::Bool(true))]
+#[test_case(Value::Int(2), Value::Int(3) => Value::Bool(false))]
+fn test_let_bindings_are_resolved(a: Value, b: Value) -> Value {
+    call_private_function(
+        "(define-private (pick (a int) (b int))
+            (let ((x b) (y x))
+                (> y a)
+                (> a y)))",
+        &[a, b],
+    )
+}
+
+#[test_case(Value::Int(3), Value::Int(2) => Value::Int(3))]
+#[test_case(Value::Int(2), Value::Int(3) => Value::Int(3))]
+fn test_if(a: Value, b: Value) -> Value {
+    call_private_function(
+        "(define-private (max-of (a int) (b int)) (if (> a b) a b))",
+        &[a, b],
+    )
+}
+
+// The body's value differs from the thrown value when the assertion fails, so the
+// cases tell apart whether the function returned early.
+#[test_case(Value::Int(3), Value::Int(2), Value::Int(1) => Value::Bool(true); "holds")]
+#[test_case(Value::Int(1), Value::Int(2), Value::Int(1) => Value::Bool(true); "throws")]
+#[test_case(Value::Int(1), Value::Int(0), Value::Int(1) => Value::Bool(false); "holds with false body")]
+fn test_asserts(a: Value, b: Value, c: Value) -> Value {
+    call_private_function(
+        "(define-private (check (a int) (b int) (c int))
+            (let ((x (asserts! (> a b) (> b c))))
+                (> a c)))",
+        &[a, b, c],
+    )
+}
+
+#[test_case(Value::okay(Value::Int(5)).unwrap() => Value::okay(Value::Int(1)).unwrap(); "ok above")]
+#[test_case(Value::okay(Value::Int(2)).unwrap() => Value::okay(Value::Int(2)).unwrap(); "ok below")]
+#[test_case(Value::error(Value::Int(7)).unwrap() => Value::error(Value::Int(7)).unwrap(); "err")]
+fn test_try_response(input: Value) -> Value {
+    call_private_function(
+        "(define-private (check (r (response int int)) (z int) (s (response int int)) (t (response int int)))
+            (if (> (try! r) z) s t))",
+        &[
+            input,
+            Value::Int(3),
+            Value::okay(Value::Int(1)).unwrap(),
+            Value::okay(Value::Int(2)).unwrap(),
+        ],
+    )
+}
+
+#[test_case(Value::some(Value::Int(5)).unwrap() => Value::some(Value::Int(1)).unwrap(); "some")]
+#[test_case(Value::none() => Value::none(); "none")]
+fn test_try_optional(input: Value) -> Value {
+    call_private_function(
+        "(define-private (check (o (optional int)) (z int) (s (optional int)) (t (optional int)))
+            (if (> (try! o) z) s t))",
+        &[
+            input,
+            Value::Int(3),
+            Value::some(Value::Int(1)).unwrap(),
+            Value::some(Value::Int(2)).unwrap(),
+        ],
+    )
+}
+
+#[test_case("+", 10, 3, 2 => Value::Int(15))]
+#[test_case("-", 10, 3, 2 => Value::Int(5))]
+#[test_case("*", 10, 3, 2 => Value::Int(60))]
+#[test_case("/", 100, 5, 2 => Value::Int(10))]
+fn test_arithmetic_folds_from_the_left(op: &str, a: i128, b: i128, c: i128) -> Value {
+    call_private_function(
+        &format!("(define-private (calc (a int) (b int) (c int)) ({op} a b c))"),
+        &[Value::Int(a), Value::Int(b), Value::Int(c)],
+    )
+}
+
+#[test_case("int", Value::Int(5) => Value::Int(-5))]
+#[test_case("int", Value::Int(-3) => Value::Int(3))]
+#[test_case("uint", Value::UInt(0) => Value::UInt(0))]
+fn test_unary_minus_negates(ty: &str, a: Value) -> Value {
+    call_private_function(&format!("(define-private (negate (a {ty})) (- a))"), &[a])
+}
+
+#[test_case(Value::UInt(17), Value::UInt(5) => Value::UInt(2))]
+#[test_case(Value::UInt(15), Value::UInt(5) => Value::UInt(0))]
+fn test_mod(a: Value, b: Value) -> Value {
+    call_private_function(
+        "(define-private (calc (a uint) (b uint)) (mod a b))",
+        &[a, b],
+    )
+}
+
+#[test_case(">", 3, 2 => Value::Bool(true))]
+#[test_case(">", 2, 2 => Value::Bool(false))]
+#[test_case(">=", 2, 2 => Value::Bool(true))]
+#[test_case("<", 2, 3 => Value::Bool(true))]
+#[test_case("<=", 3, 2 => Value::Bool(false))]
+#[test_case("is-eq", 2, 2 => Value::Bool(true))]
+#[test_case("is-eq", 2, 3 => Value::Bool(false))]
+fn test_comparison(op: &str, a: i128, b: i128) -> Value {
+    call_private_function(
+        &format!("(define-private (cmp (a int) (b int)) ({op} a b))"),
+        &[Value::Int(a), Value::Int(b)],
+    )
+}
+
+#[test_case(1, 1, 1 => Value::Bool(true))]
+#[test_case(1, 2, 1 => Value::Bool(false))]
+#[test_case(1, 1, 2 => Value::Bool(false))]
+fn test_is_eq_many(a: i128, b: i128, c: i128) -> Value {
+    call_private_function(
+        "(define-private (cmp (a int) (b int) (c int)) (is-eq a b c))",
+        &[Value::Int(a), Value::Int(b), Value::Int(c)],
+    )
+}
+
+#[test]
+fn test_is_eq_single_operand() {
+    assert_eq!(
+        Value::Bool(true),
+        call_private_function("(define-private (cmp (a int)) (is-eq a))", &[Value::Int(1)])
+    );
+}
+
+#[test_case(Value::Int(3), Value::Int(2) => Value::Bool(false))]
+#[test_case(Value::Int(2), Value::Int(3) => Value::Bool(true))]
+fn test_not(a: Value, b: Value) -> Value {
+    call_private_function(
+        "(define-private (cmp (a int) (b int)) (not (> a b)))",
+        &[a, b],
+    )
+}
+
+// The last operand divides by `c`, so with `c` set to zero it would trap if it were
+// evaluated.
+#[test_case("and", 2, 3, 1 => Value::Bool(false); "and short-circuits")]
+#[test_case("and", 3, 2, 1 => Value::Bool(true); "and evaluates all")]
+#[test_case("and", 3, 2, 0 => panics; "and evaluates the last operand")]
+#[test_case("or", 3, 2, 0 => Value::Bool(true); "or short-circuits")]
+#[test_case("or", 2, 3, 1 => Value::Bool(false); "or evaluates all")]
+fn test_short_circuit(op: &str, a: i128, b: i128, c: i128) -> Value {
+    call_private_function(
+        &format!("(define-private (cmp (a int) (b int) (c int)) ({op} (> a b) (> (/ a c) b)))"),
+        &[Value::Int(a), Value::Int(b), Value::Int(c)],
+    )
+}
+
+#[test_case(Value::Int(3), Value::Int(2) => Value::Bool(false))]
+#[test_case(Value::Int(2), Value::Int(3) => Value::Bool(true))]
+fn test_begin(a: Value, b: Value) -> Value {
+    call_private_function(
+        "(define-private (cmp (a int) (b int)) (begin (> a b) (>= a b) (< a b)))",
+        &[a, b],
+    )
+}
+
+#[test_case(Value::some(Value::Int(3)).unwrap() => Value::Bool(true))]
+#[test_case(Value::some(Value::Int(1)).unwrap() => Value::Bool(false))]
+#[test_case(Value::none() => Value::Bool(false); "none")]
+fn test_match_optional(input: Value) -> Value {
+    call_private_function(
+        "(define-private (check (o (optional int)) (b int))
+            (match o value (> value b) (< b b)))",
+        &[input, Value::Int(2)],
+    )
+}
+
+#[test_case(Value::okay(Value::Int(3)).unwrap() => Value::Bool(true))]
+#[test_case(Value::okay(Value::Int(1)).unwrap() => Value::Bool(false))]
+#[test_case(Value::error(Value::Int(1)).unwrap() => Value::Bool(true))]
+#[test_case(Value::error(Value::Int(3)).unwrap() => Value::Bool(false))]
+fn test_match_response(input: Value) -> Value {
+    call_private_function(
+        "(define-private (check (r (response int int)) (b int))
+            (match r value (> value b) error (< error b)))",
+        &[input, Value::Int(2)],
+    )
+}
+
+const MAP_CONTRACT: &str = "
+    (define-map balances int int)
+    (define-private (get-balance (k int)) (map-get? balances k))
+    (define-private (set-balance (k int) (v int)) (map-set balances k v))
+    (define-private (insert-balance (k int) (v int)) (map-insert balances k v))
+    (define-private (delete-balance (k int)) (map-delete balances k))
+";
+
+#[test]
+fn test_map_operations() {
+    let (instance, mut store) = instantiate_contract(MAP_CONTRACT);
+    let mut call = |name: &str, args: &[Value]| {
+        let func = instance.get_func(&mut store, name).unwrap();
+        call_extref(&mut store, func, args)
+    };
+    let key = Value::Int(1);
+
+    assert_eq!(Value::none(), call("get-balance", &[key.clone()]));
+    assert_eq!(
+        Value::Bool(true),
+        call("insert-balance", &[key.clone(), Value::Int(10)])
+    );
+    assert_eq!(
+        Value::Bool(false),
+        call("insert-balance", &[key.clone(), Value::Int(20)])
+    );
+    assert_eq!(
+        Value::some(Value::Int(10)).unwrap(),
+        call("get-balance", &[key.clone()])
+    );
+    assert_eq!(
+        Value::Bool(true),
+        call("set-balance", &[key.clone(), Value::Int(20)])
+    );
+    assert_eq!(
+        Value::some(Value::Int(20)).unwrap(),
+        call("get-balance", &[key.clone()])
+    );
+    assert_eq!(Value::Bool(true), call("delete-balance", &[key.clone()]));
+    assert_eq!(Value::none(), call("get-balance", &[key]));
+}
+
+#[test]
+fn test_compile_result_describes_maps() {
+    let analyze_result = analyze_contract(
+        MAP_CONTRACT,
+        &contract_id(),
+        LimitedCostTracker::new_free(),
+        ClarityVersion::Clarity2,
+        StacksEpochId::Epoch24,
+        &mut Datastore::new(),
+    )
+    .unwrap();
+    let compile_result = compile(&analyze_result.contract_analysis).unwrap();
+
+    assert_eq!(
+        &[DataMapMetadata {
+            name: "balances".into(),
+            key_type: TypeSignature::IntType,
+            value_type: TypeSignature::IntType,
+        }],
+        compile_result.maps()
+    );
+    assert_eq!(&[ClarityName::from("balances")], compile_result.names());
+}
+
+#[test_case(Value::Int(3) => Value::Int(13))]
+#[test_case(Value::Int(-3) => Value::Int(7))]
+fn test_literal_values(a: Value) -> Value {
+    call_private_function("(define-private (add-ten (a int)) (+ a 10))", &[a])
+}
+
+#[test_case(Value::some(Value::Int(3)).unwrap() => Value::Bool(true))]
+#[test_case(Value::none() => Value::Bool(false))]
+fn test_keywords(o: Value) -> Value {
+    call_private_function(
+        "(define-private (is-set (o (optional int))) (if (is-eq o none) false true))",
+        &[o],
+    )
+}
+
+const DATA_VAR_CONTRACT: &str = "
+    (define-data-var counter uint u5)
+    (define-private (get-counter) (var-get counter))
+    (define-private (increment (by uint)) (var-set counter (+ (var-get counter) by)))
+";
+
+#[test]
+fn test_data_var_operations() {
+    let (instance, mut store) = instantiate_contract(DATA_VAR_CONTRACT);
+    let mut call = |name: &str, args: &[Value]| {
+        let func = instance.get_func(&mut store, name).unwrap();
+        call_extref(&mut store, func, args)
+    };
+
+    assert_eq!(Value::UInt(5), call("get-counter", &[]));
+    assert_eq!(Value::Bool(true), call("increment", &[Value::UInt(2)]));
+    assert_eq!(Value::UInt(7), call("get-counter", &[]));
+}
+
+#[test]
+fn test_compile_result_describes_data_vars() {
+    let analyze_result = analyze_contract(
+        DATA_VAR_CONTRACT,
+        &contract_id(),
+        LimitedCostTracker::new_free(),
+        ClarityVersion::Clarity2,
+        StacksEpochId::Epoch24,
+        &mut Datastore::new(),
+    )
+    .unwrap();
+    let compile_result = compile(&analyze_result.contract_analysis).unwrap();
+
+    assert_eq!(
+        &[DataVarMetadata {
+            name: "counter".into(),
+            value_type: TypeSignature::UIntType,
+        }],
+        compile_result.data_vars()
+    );
+    assert_eq!(&[Value::UInt(5)], compile_result.constants());
+}
+
+const TRAIT_CONTRACT: &str = "
+    (define-trait value-trait ((get-value () (response int int))))
+    (define-private (pick (t <value-trait>) (a int)) a)
+";
+
+#[test]
+fn test_trait_typed_parameters() {
+    let callable = Value::Principal(PrincipalData::Contract(contract_id()));
+
+    assert_eq!(
+        Value::Int(3),
+        call_private_function(TRAIT_CONTRACT, &[callable, Value::Int(3)])
+    );
+}
+
+#[test]
+fn test_compile_result_describes_traits() {
+    let analyze_result = analyze_contract(
+        TRAIT_CONTRACT,
+        &contract_id(),
+        LimitedCostTracker::new_free(),
+        ClarityVersion::Clarity2,
+        StacksEpochId::Epoch24,
+        &mut Datastore::new(),
+    )
+    .unwrap();
+    let compile_result = compile(&analyze_result.contract_analysis).unwrap();
+
+    let traits = compile_result.traits();
+    assert_eq!(1, traits.len());
+    assert_eq!("value-trait", traits[0].name.as_str());
+    assert!(traits[0]
+        .functions
+        .contains_key(&ClarityName::from("get-value")));
+
+    assert_eq!(
+        &[TraitIdentifier::new(
+            StandardPrincipalData::transient(),
+            ContractName::from("test"),
+            ClarityName::from("value-trait"),
+        )],
+        compile_result.interfaces()
+    );
+    assert!(compile_result.implemented_traits().is_empty());
+}
+
+#[test]
+fn test_function_types_follow_analysis() {
+    let generator = generate(
+        "(define-read-only (no-params) true)
+         (define-read-only (two-params (a int) (b (list 5 uint))) b)",
+    )
+    .unwrap();
+
+    let function_type = |name: &str| {
+        let function_id = generator.module.funcs.by_name(name).unwrap();
+        let ty = generator
+            .module
+            .types
+            .get(generator.module.funcs.get(function_id).ty());
+        (ty.params().to_vec(), ty.results().to_vec())
+    };
+
+    assert_eq!(
+        (vec![], vec![walrus::ValType::Externref]),
+        function_type("no-params")
+    );
+    assert_eq!(
+        (
+            vec![walrus::ValType::Externref, walrus::ValType::Externref],
+            vec![walrus::ValType::Externref]
+        ),
+        function_type("two-params")
+    );
+}
+
+#[test_case(1, 2 => (4, 0))]
+#[test_case(-5, 2 => (-2, -1))]
+fn test_rust_ref_stack_convention(a: i64, b: i64) -> (i64, i64) {
+    let (instance, mut store) = instantiate_with_convention(
+        "(define-private (add-one (a int) (b int)) (+ a b 1))",
+        CodegenConvention::RustRefStack,
+    );
+    let func = instance.get_func(&mut store, "add-one").unwrap();
+
+    // Each `int` is passed as its low and high 64 bits.
+    let params = [
+        Val::I64(a),
+        Val::I64(a >> 63),
+        Val::I64(b),
+        Val::I64(b >> 63),
+    ];
+    let mut results = [Val::I64(0), Val::I64(0)];
+    func.call(&mut store, &params, &mut results).unwrap();
+
+    (results[0].unwrap_i64(), results[1].unwrap_i64())
+}
+
+#[test_case("(> a 1)", 2 => 1)]
+#[test_case("(> a 1)", -2 => 0)]
+#[test_case("(<= a 1)", 1 => 1)]
+#[test_case("(< a -1)", -2 => 1)]
+#[test_case("(is-eq a 1)", 1 => 1)]
+#[test_case("(is-eq a 1)", i64::MIN => 0)]
+fn test_rust_ref_stack_comparison(body: &str, a: i64) -> i32 {
+    let (instance, mut store) = instantiate_with_convention(
+        &format!("(define-private (f (a int)) {})", body),
+        CodegenConvention::RustRefStack,
+    );
+    let func = instance.get_func(&mut store, "f").unwrap();
+
+    // The resulting `bool` is returned as an `i32`.
+    let mut results = [Val::I32(-1)];
+    func.call(&mut store, &[Val::I64(a), Val::I64(a >> 63)], &mut results)
+        .unwrap();
+
+    results[0].unwrap_i32()
+}
+
+#[test]
+fn test_memory_convention() {
+    let (instance, mut store) = instantiate_with_convention(
+        "(define-private (difference (a int) (b int)) (- a b))",
+        CodegenConvention::Memory,
+    );
+    let func = instance.get_func(&mut store, "difference").unwrap();
+    let memory = instance.get_memory(&mut store, "vm_mem").unwrap();
+
+    let mut params = Vec::new();
+    let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+    for value in [Value::Int(5), Value::Int(11)] {
+        let ptr = write_value_to_memory(&mut store, memory, &mut *alloc, &value).unwrap();
+        params.extend([Val::I32(ptr.offset), Val::I32(ptr.len)]);
+    }
+    store.data_mut().alloc = alloc;
+
+    let mut results = [Val::I32(0), Val::I32(0)];
+    func.call(&mut store, &params, &mut results).unwrap();
+
+    assert_eq!(
+        Value::Int(-6),
+        read_value_from_memory(
+            &store,
+            memory,
+            results[0].unwrap_i32(),
+            results[1].unwrap_i32()
+        )
+        .unwrap()
+    );
+}
+
+#[test_case("(define-private (f (a uint)) (+ a u1))", CodegenConvention::RustRefStack; "uint values")]
+#[test_case("(define-private (f (a int)) (not (> a 1)))", CodegenConvention::RustRefStack; "extref host function")]
+#[test_case("(define-private (f (a int)) (* a a))", CodegenConvention::Memory; "missing host function")]
+#[test_case("(define-private (f (a int)) (+ a 1))", CodegenConvention::Memory; "constants")]
+fn test_unsupported_by_convention(contract_src: &str, convention: CodegenConvention) {
+    assert!(matches!(
+        generate_with_convention(contract_src, convention).map_err(|err| err.kind),
+        Err(WasmGenerationError::UnsupportedByConvention(_, c)) if c == convention
+    ));
+}
+
+#[test]
+fn test_generation_error_is_located() {
+    let contract_src = "(define-private (square (a int))\n  (* a a))";
+    let err = generate_with_convention(contract_src, CodegenConvention::Memory)
+        .unwrap_err()
+        .with_source(contract_src);
+
+    // The error is located at the innermost expression which couldn't be generated.
+    assert_eq!(Some("*"), err.construct.as_deref());
+    let span = err.span.as_ref().unwrap();
+    assert_eq!(
+        (2, 3, 2, 9),
+        (
+            span.start_line,
+            span.start_column,
+            span.end_line,
+            span.end_column
+        )
+    );
+    assert_eq!(
+        "error: `mul` is not supported by the Memory convention (in `*`)\n \
+         --> 2:3\n  \
+         |\n\
+         2 |   (* a a))\n  \
+         |   ^^^^^^^\n \
+         = suggestion: Use the Externref convention, which supports all values",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_unsupported_native_function_is_not_implemented() {
+    let err = generate("(define-private (f (a int)) (to-consensus-buff? a))").unwrap_err();
+    assert!(matches!(err.kind, WasmGenerationError::NotImplemented));
+    assert_eq!(Some("to-consensus-buff?"), err.construct.as_deref());
+}
+
+#[test]
+fn test_emit_wat() {
+    let mut generator = generate("(define-private (add-one (a int)) (+ a 1))").unwrap();
+    let wat = generator.emit_wat().unwrap();
+
+    assert!(wat.contains("(import \"clarity\" \"add_extref\""));
+    assert!(wat.contains("(func $add-one"));
+}
+
+#[test]
+fn test_debug_info() {
+    let mut generator = generate(
+        "(define-private (add-one (a int))
+           (let ((b (+ a 1))) b))",
+    )
+    .unwrap();
+
+    // Locals are named after the variables holding their values.
+    let wat = generator.emit_wat().unwrap();
+    assert!(wat.contains("(param $a externref)"));
+    assert!(wat.contains("(local $b externref)"));
+    assert!(wat.contains("call $add_extref"));
+
+    let compile_result = generator.into_compile_result();
+    let source_map = compile_result.source_map();
+    assert!(!source_map.is_empty());
+    assert_eq!(
+        Some(source_map),
+        SourceMap::decode(&source_map.encode()).as_ref()
+    );
+
+    // The instructions generated from `(+ a 1)` are mapped to its span.
+    let add_span = (0..compile_result.module_bytes().len())
+        .filter_map(|offset| source_map.span_at(offset))
+        .find(|span| span.start_line == 2 && span.start_column == 21)
+        .unwrap();
+    assert_eq!((2, 27), (add_span.end_line, add_span.end_column));
+}
+
+#[test]
+fn test_optimize() {
+    let mut generator = generate(
+        "(define-private (unused (a int)) (+ a 1))
+         (define-private (used (a int) (b int))
+           (let ((x (> a b))) (let ((y (< a b))) y)))",
+    )
+    .unwrap();
+    let used = generator.module.funcs.by_name("used").unwrap();
+    generator.module.exports.add("used", used);
+
+    let report = generator.optimize();
+    assert!(report.size_after < report.size_before);
+
+    // `unused`, and the `add_extref` host function which only it calls, are removed,
+    // and `x` and `y` share a local.
+    let wat = generator.emit_wat().unwrap();
+    assert!(!wat.contains("$unused"));
+    assert!(!wat.contains("add_extref"));
+    assert_eq!(1, wat.matches("(local ").count());
+
+    let compile_result = generator.into_compile_result();
+    let (instance, mut store) = instantiate_generated(
+        compile_result.module_bytes(),
+        ClarityWasmContext::new(),
+        compile_result.constants(),
+    );
+    let func = instance.get_func(&mut store, "used").unwrap();
+    assert_eq!(
+        Value::Bool(true),
+        call_extref(&mut store, func, &[Value::Int(1), Value::Int(2)])
+    );
+}
+
+#[test]
+fn test_contract_compilation() {
+    let contract_src = "
+        (define-data-var counter int 1)
+        (var-set counter (+ (var-get counter) 1))
+        (define-private (double (a int)) (* a 2))
+        (define-read-only (get-counter) (double (var-get counter)))
+        (define-public (set-counter (value int))
+          (begin (var-set counter value) (ok value)))";
+
+    let compile_result = generate(contract_src).unwrap().into_compile_result();
+    let interface = compile_result.contract_interface();
+    assert_eq!(
+        vec!["double", "get-counter", "set-counter"],
+        interface
+            .functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    let set_counter = interface.function("set-counter").unwrap();
+    assert_eq!(FunctionAccess::Public, set_counter.access);
+    assert_eq!(
+        vec![(ClarityName::from("value"), TypeSignature::IntType)],
+        set_counter
+            .args
+            .iter()
+            .map(|arg| (arg.name.clone(), arg.signature.clone()))
+            .collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        &set_counter.returns,
+        TypeSignature::ResponseType(types) if types.0 == TypeSignature::IntType
+    ));
+    assert_eq!(
+        FunctionAccess::ReadOnly,
+        interface.function("get-counter").unwrap().access
+    );
+    assert_eq!(
+        FunctionAccess::Private,
+        interface.function("double").unwrap().access
+    );
+
+    // The public and read-only functions are exported, and the top-level expressions
+    // are evaluated when the contract is initialized.
+    let mut context = ClarityWasmContext::new();
+    context.set_contract_id(Some(contract_id()));
+    compile_result
+        .prepare_context(&mut context, &contract_id())
+        .unwrap();
+    let (instance, mut store) = instantiate_generated(
+        compile_result.module_bytes(),
+        context,
+        compile_result.constants(),
+    );
+    assert!(instance.get_func(&mut store, "double").is_none());
+    let top_level = instance.get_func(&mut store, TOP_LEVEL_FUNCTION).unwrap();
+    top_level.call(&mut store, &[], &mut []).unwrap();
+
+    let get_counter = instance.get_func(&mut store, "get-counter").unwrap();
+    assert_eq!(Value::Int(4), call_extref(&mut store, get_counter, &[]));
+
+    let set_counter = instance.get_func(&mut store, "set-counter").unwrap();
+    assert_eq!(
+        Value::okay(Value::Int(5)).unwrap(),
+        call_extref(&mut store, set_counter, &[Value::Int(5)])
+    );
+    assert_eq!(Value::Int(10), call_extref(&mut store, get_counter, &[]));
+}
+
+#[test]
+fn test_prepare_context_fails_on_mismatched_ids() {
+    let compile_result = generate("(define-data-var counter int 1)")
+        .unwrap()
+        .into_compile_result();
+
+    // The module references `counter` by id 0, which is already taken among the
+    // contract's names.
+    let mut context = ClarityWasmContext::new();
+    context
+        .contract_names_mut(&contract_id())
+        .intern(ClarityName::from("other"));
+    assert_eq!(
+        Err(PrepareContextError::NameIdMismatch {
+            name: ClarityName::from("counter"),
+            expected: 0,
+            actual: 1,
+        }),
+        compile_result.prepare_context(&mut context, &contract_id())
+    );
+}
+
+#[test_case(SequenceLowering::HostCall; "host call")]
+#[test_case(SequenceLowering::Loop; "loop")]
+fn test_fold_and_map(sequence_lowering: SequenceLowering) {
+    let contract_src = "
+        (define-private (add-square (a int) (b int)) (+ (* a a) b))
+        (define-private (square (a int)) (* a a))
+        (define-read-only (sum-of-squares (l (list 8 int))) (fold add-square l 1))
+        (define-read-only (squares (l (list 8 int))) (map square l))";
+    let (instance, mut store) = instantiate_with_generator(
+        contract_src,
+        WasmGenerator::new().with_sequence_lowering(sequence_lowering),
+    );
+    let list = |values: &[i128]| {
+        Value::list_from(values.iter().copied().map(Value::Int).collect()).unwrap()
+    };
+
+    let sum_of_squares = instance.get_func(&mut store, "sum-of-squares").unwrap();
+    assert_eq!(
+        Value::Int(15),
+        call_extref(&mut store, sum_of_squares, &[list(&[1, 2, 3])])
+    );
+    assert_eq!(
+        Value::Int(1),
+        call_extref(&mut store, sum_of_squares, &[list(&[])])
+    );
+
+    let squares = instance.get_func(&mut store, "squares").unwrap();
+    assert_eq!(
+        list(&[1, 4, 9]),
+        call_extref(&mut store, squares, &[list(&[1, 2, 3])])
+    );
+    assert_eq!(list(&[]), call_extref(&mut store, squares, &[list(&[])]));
+}
+
+#[test]
+fn test_fold_native_function_not_implemented() {
+    assert!(matches!(
+        generate("(define-read-only (sum (l (list 8 int))) (fold + l 0))").map_err(|err| err.kind),
+        Err(WasmGenerationError::NotImplemented)
+    ));
+}
+
+#[test]
+fn test_contract_call() {
+    let callee_id = QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("callee"),
+    );
+    let mut datastore = Datastore::new();
+    let callee = compile_stored(
+        &mut datastore,
+        &callee_id,
+        "(define-public (add (a int) (b int)) (ok (+ a b)))",
+    );
+    let caller = compile_stored(
+        &mut datastore,
+        &contract_id(),
+        "(define-public (add-one (a int)) (contract-call? .callee add a 1))",
+    );
+    assert_eq!(&[callee_id.clone()], caller.called_contracts());
+
+    let mut context = ClarityWasmContext::new();
+    context.set_contract_id(Some(contract_id()));
+    callee.prepare_context(&mut context, &callee_id).unwrap();
+    caller
+        .prepare_context(&mut context, &contract_id())
+        .unwrap();
+
+    let (instance, mut store) =
+        instantiate_generated(caller.module_bytes(), context, caller.constants());
+    let callee_instance =
+        instantiate_in_store(&mut store, callee.module_bytes(), callee.constants());
+    store
+        .data_mut()
+        .contracts_mut()
+        .register(callee_id, callee_instance);
+
+    // The response of the called function is the value of the `contract-call?`.
+    let add_one = instance.get_func(&mut store, "add-one").unwrap();
+    assert_eq!(
+        Value::okay(Value::Int(42)).unwrap(),
+        call_extref(&mut store, add_one, &[Value::Int(41)])
+    );
+
+    // The execution context is restored once the call completes.
+    assert_eq!(Some(&contract_id()), store.data().contract_id());
+}
+
+#[test]
+fn test_contract_call_with_data_vars() {
+    let callee_id = QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("callee"),
+    );
+    let mut datastore = Datastore::new();
+    let callee = compile_stored(
+        &mut datastore,
+        &callee_id,
+        "(define-data-var count int 1)
+         (define-public (bump (a int))
+           (begin (var-set count (+ (var-get count) a)) (ok (var-get count))))",
+    );
+    let caller = compile_stored(
+        &mut datastore,
+        &contract_id(),
+        "(define-data-var total int 10)
+         (define-public (bump-both (a int))
+           (begin (var-set total (+ (var-get total) a)) (contract-call? .callee bump a)))
+         (define-read-only (get-total) (var-get total))",
+    );
+
+    // Both modules reference their data variable by id 0.
+    let mut context = ClarityWasmContext::new();
+    context.set_contract_id(Some(contract_id()));
+    callee.prepare_context(&mut context, &callee_id).unwrap();
+    caller
+        .prepare_context(&mut context, &contract_id())
+        .unwrap();
+
+    let (instance, mut store) =
+        instantiate_generated(caller.module_bytes(), context, caller.constants());
+    let callee_instance =
+        instantiate_in_store(&mut store, callee.module_bytes(), callee.constants());
+    store
+        .data_mut()
+        .contracts_mut()
+        .register(callee_id.clone(), callee_instance);
+
+    // Each contract is initialized as the contract being executed.
+    store.data_mut().set_contract_id(Some(callee_id));
+    let top_level = callee_instance
+        .get_func(&mut store, TOP_LEVEL_FUNCTION)
+        .unwrap();
+    top_level.call(&mut store, &[], &mut []).unwrap();
+    store.data_mut().set_contract_id(Some(contract_id()));
+    let top_level = instance.get_func(&mut store, TOP_LEVEL_FUNCTION).unwrap();
+    top_level.call(&mut store, &[], &mut []).unwrap();
+
+    let bump_both = instance.get_func(&mut store, "bump-both").unwrap();
+    assert_eq!(
+        Value::okay(Value::Int(42)).unwrap(),
+        call_extref(&mut store, bump_both, &[Value::Int(41)])
+    );
+    let get_total = instance.get_func(&mut store, "get-total").unwrap();
+    assert_eq!(Value::Int(51), call_extref(&mut store, get_total, &[]));
+}
+
+#[test]
+fn test_contract_call_trait_parameter() {
+    let callee_id = QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("callee"),
+    );
+    let mut datastore = Datastore::new();
+    let callee = compile_stored(
+        &mut datastore,
+        &callee_id,
+        "(define-trait value-trait ((get-value (int) (response int int))))
+         (define-public (get-value (a int)) (ok (+ a 1)))",
+    );
+    let caller = compile_stored(
+        &mut datastore,
+        &contract_id(),
+        "(use-trait value-trait .callee.value-trait)
+         (define-public (call-get-value (t <value-trait>) (a int))
+           (contract-call? t get-value a))",
+    );
+    // The contract is only known at runtime.
+    assert!(caller.called_contracts().is_empty());
+
+    let mut context = ClarityWasmContext::new();
+    context.set_contract_id(Some(contract_id()));
+    callee.prepare_context(&mut context, &callee_id).unwrap();
+    caller
+        .prepare_context(&mut context, &contract_id())
+        .unwrap();
+
+    let (instance, mut store) =
+        instantiate_generated(caller.module_bytes(), context, caller.constants());
+    let callee_instance =
+        instantiate_in_store(&mut store, callee.module_bytes(), callee.constants());
+    store
+        .data_mut()
+        .contracts_mut()
+        .register(callee_id.clone(), callee_instance);
+
+    let call_get_value = instance.get_func(&mut store, "call-get-value").unwrap();
+    assert_eq!(
+        Value::okay(Value::Int(42)).unwrap(),
+        call_extref(
+            &mut store,
+            call_get_value,
+            &[
+                Value::Principal(PrincipalData::Contract(callee_id)),
+                Value::Int(41)
+            ]
+        )
+    );
+    assert_eq!(Some(&contract_id()), store.data().contract_id());
+}
+
+#[test]
+fn test_compilation_cache() {
+    let contract_src = "(define-read-only (double (a int)) (* a 2))";
+    let contract_analysis = analyze(contract_src);
+    let mut cache = CompilationCache::new(MemoryCacheBackend::new());
+
+    let compile_result = cache
+        .compile(contract_src, &contract_analysis, CodegenOptions::default())
+        .unwrap();
+    let cached = cache
+        .compile(contract_src, &contract_analysis, CodegenOptions::default())
+        .unwrap();
+    assert_eq!(compile_result.module_bytes(), cached.module_bytes());
+
+    // The options are part of the key.
+    let loop_options = CodegenOptions {
+        sequence_lowering: SequenceLowering::Loop,
+        ..Default::default()
+    };
+    assert_ne!(
+        CacheKey::new(contract_src, &contract_analysis, CodegenOptions::default()),
+        CacheKey::new(contract_src, &contract_analysis, loop_options)
+    );
+    cache
+        .compile(contract_src, &contract_analysis, loop_options)
+        .unwrap();
+
+    let mut config = Config::default();
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config).unwrap();
+    for _ in 0..2 {
+        let module = cache.load_module(&engine, &compile_result).unwrap();
+        assert!(module.get_export("double").is_some());
+    }
+
+    assert_eq!(
+        CacheStats {
+            compile_hits: 1,
+            compile_misses: 2,
+            precompile_hits: 1,
+            precompile_misses: 1,
+        },
+        cache.stats()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_disk_compilation_cache() {
+    let contract_src = "
+        (define-data-var counter int 1)
+        (define-read-only (get-counter) (+ (var-get counter) 2))";
+    let contract_analysis = analyze(contract_src);
+    let dir = std::env::temp_dir().join(format!("wasm-test-cache-{}", std::process::id()));
+
+    let mut config = Config::default();
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config).unwrap();
+
+    let secret = [7; 32];
+    let mut cache = CompilationCache::new(DiskCacheBackend::new(&dir, secret).unwrap());
+    let compile_result = cache
+        .compile(contract_src, &contract_analysis, CodegenOptions::default())
+        .unwrap();
+    cache.load_module(&engine, &compile_result).unwrap();
+
+    // A new cache over the same directory, e.g. once the node restarts, finds both
+    // the compile result and the precompiled artifact.
+    let mut cache = CompilationCache::new(DiskCacheBackend::new(&dir, secret).unwrap());
+    let cached = cache
+        .compile(contract_src, &contract_analysis, CodegenOptions::default())
+        .unwrap();
+    assert_eq!(compile_result.module_bytes(), cached.module_bytes());
+    assert_eq!(compile_result.names(), cached.names());
+    assert_eq!(compile_result.constants(), cached.constants());
+    assert_eq!(compile_result.data_vars(), cached.data_vars());
+    assert_eq!(
+        compile_result.contract_interface(),
+        cached.contract_interface()
+    );
+    assert_eq!(compile_result.source_map(), cached.source_map());
+    cache.load_module(&engine, &cached).unwrap();
+    assert_eq!(
+        CacheStats {
+            compile_hits: 1,
+            precompile_hits: 1,
+            ..Default::default()
+        },
+        cache.stats()
+    );
+
+    // Entries which were tampered with, or written under another secret key, are
+    // ignored.
+    let artifact_path = dir
+        .join(CacheKey::for_module(compile_result.module_bytes()).to_hex())
+        .with_extension("cwasm");
+    let mut artifact = std::fs::read(&artifact_path).unwrap();
+    let last = artifact.len() - 1;
+    artifact[last] ^= 1;
+    std::fs::write(&artifact_path, artifact).unwrap();
+    cache.load_module(&engine, &cached).unwrap();
+    assert_eq!(1, cache.stats().precompile_misses);
+
+    let mut cache = CompilationCache::new(DiskCacheBackend::new(&dir, [8; 32]).unwrap());
+    cache
+        .compile(contract_src, &contract_analysis, CodegenOptions::default())
+        .unwrap();
+    assert_eq!(1, cache.stats().compile_misses);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Generates the module for a contract defining a single private function and calls
+/// the function with the given arguments.
+fn call_private_function(contract_src: &str, args: &[Value]) -> Value {
+    let (instance, mut store) = instantiate_contract(contract_src);
+    let func = instance
+        .exports(&mut store)
+        .find(|export| export.name() != TOP_LEVEL_FUNCTION)
+        .and_then(|export| export.into_func())
+        .unwrap();
+    call_extref(&mut store, func, args)
+}
+
+/// Generates and instantiates the module for a contract, exporting its private
+/// functions under their names too, so that they can be called. The context is
+/// prepared and the contract initialized, as they would be by the host.
+fn instantiate_contract(contract_src: &str) -> (Instance, Store<ClarityWasmContext>) {
+    instantiate_with_convention(contract_src, CodegenConvention::Externref)
+}
+
+/// Like `instantiate_contract`, generating the module using the given convention.
+fn instantiate_with_convention(
+    contract_src: &str,
+    convention: CodegenConvention,
+) -> (Instance, Store<ClarityWasmContext>) {
+    instantiate_with_generator(contract_src, WasmGenerator::with_convention(convention))
+}
+
+/// Like `instantiate_contract`, generating the module using the given generator.
+fn instantiate_with_generator(
+    contract_src: &str,
+    generator: WasmGenerator,
+) -> (Instance, Store<ClarityWasmContext>) {
+    let mut generator = generate_with_generator(contract_src, generator).unwrap();
+
+    let private_functions = generator
+        .module
+        .funcs
+        .iter_local()
+        .filter(|(id, _)| generator.module.exports.get_exported_func(*id).is_none())
+        .filter_map(|(id, _)| Some((id, generator.module.funcs.get(id).name.clone()?)))
+        .collect::<Vec<_>>();
+    for (function_id, name) in private_functions {
+        generator.module.exports.add(&name, function_id);
+    }
+
+    let compile_result = generator.into_compile_result();
+    let mut context = ClarityWasmContext::new();
+    context.set_contract_id(Some(contract_id()));
+    compile_result
+        .prepare_context(&mut context, &contract_id())
+        .unwrap();
+
+    let (instance, mut store) = instantiate_generated(
+        compile_result.module_bytes(),
+        context,
+        compile_result.constants(),
+    );
+    if let Some(top_level) = instance.get_func(&mut store, TOP_LEVEL_FUNCTION) {
+        top_level.call(&mut store, &[], &mut []).unwrap();
+    }
+
+    (instance, store)
+}
+
+fn contract_id() -> QualifiedContractIdentifier {
+    QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("test"),
+    )
+}
+
+/// Analyzes the provided contract and generates its module, without finalizing it.
+fn generate(contract_src: &str) -> Result<WasmGenerator, GenerationError> {
+    generate_with_convention(contract_src, CodegenConvention::Externref)
+}
+
+/// Like `generate`, using the given convention.
+fn generate_with_convention(
+    contract_src: &str,
+    convention: CodegenConvention,
+) -> Result<WasmGenerator, GenerationError> {
+    generate_with_generator(contract_src, WasmGenerator::with_convention(convention))
+}
+
+/// Like `generate`, using the given generator.
+fn generate_with_generator(
+    contract_src: &str,
+    mut generator: WasmGenerator,
+) -> Result<WasmGenerator, GenerationError> {
+    generator.generate(analyze(contract_src))?;
+    Ok(generator)
+}
+
+/// Analyzes the provided contract.
+fn analyze(contract_src: &str) -> ContractAnalysis {
+    let mut datastore = Datastore::new();
+
+    analyze_contract(
+        contract_src,
+        &contract_id(),
+        LimitedCostTracker::new_free(),
+        ClarityVersion::Clarity2,
+        StacksEpochId::Epoch24,
+        &mut datastore,
+    )
+    .unwrap()
+    .contract_analysis
+}
+
+/// Analyzes and compiles the provided contract, storing its analysis so that the
+/// contracts analyzed after it can call it.
+fn compile_stored(
+    datastore: &mut Datastore,
+    contract_id: &QualifiedContractIdentifier,
+    contract_src: &str,
+) -> CompileResult {
+    let contract_analysis = analyze_contract(
+        contract_src,
+        contract_id,
+        LimitedCostTracker::new_free(),
+        ClarityVersion::Clarity2,
+        StacksEpochId::Epoch24,
+        datastore,
+    )
+    .unwrap()
+    .contract_analysis;
+
+    AnalysisDatabase::new(datastore)
+        .execute(|db| db.insert_contract(contract_id, &contract_analysis))
+        .unwrap();
+
+    compile(&contract_analysis).unwrap()
+}
+
+/// Instantiates a generated module with the given context, providing the host
+/// functions along with the cost tracker and constants table which it imports.
+fn instantiate_generated(
+    wasm: &[u8],
+    context: ClarityWasmContext,
+    constants: &[Value],
+) -> (Instance, Store<ClarityWasmContext>) {
+    let mut config = Config::default();
+    config.wasm_reference_types(true);
+
+    let engine = Engine::new(&config).unwrap();
+    let mut store = Store::new(&engine, context);
+
+    let instance = instantiate_in_store(&mut store, wasm, constants);
+    (instance, store)
+}
+
+/// Like `instantiate_generated`, instantiating the module in an existing store, e.g.
+/// so that contracts can call each other.
+fn instantiate_in_store(
+    store: &mut Store<ClarityWasmContext>,
+    wasm: &[u8],
+    constants: &[Value],
+) -> Instance {
+    let engine = store.engine().clone();
+    let module = Module::new(&engine, wasm).unwrap();
+
+    let mut linker = Linker::new(&engine);
+    let host_functions = get_all_functions(&mut *store);
+    link_host_functions(&mut linker, &*store, &host_functions).unwrap();
+
+    let cost_tracker = Global::new(
+        &mut *store,
+        GlobalType::new(ValType::ExternRef, Mutability::Const),
+        Val::ExternRef(None),
+    )
+    .unwrap();
+    linker
+        .define(&*store, "clarity", "__cost_tracker_ref", cost_tracker)
+        .unwrap();
+
+    let consts = Table::new(
+        &mut *store,
+        TableType::new(ValType::ExternRef, constants.len() as u32, None),
+        Val::ExternRef(None),
+    )
+    .unwrap();
+    for (index, constant) in constants.iter().enumerate() {
+        let constant = Val::ExternRef(Some(ExternRef::new(constant.clone())));
+        consts.set(&mut *store, index as u32, constant).unwrap();
+    }
+    linker
+        .define(&*store, "clarity", "__consts", consts)
+        .unwrap();
+
+    linker.instantiate(&mut *store, &module).unwrap()
+}
+
+/// Calls a generated function with the given arguments, returning its result.
+fn call_extref(store: &mut Store<ClarityWasmContext>, func: Func, args: &[Value]) -> Value {
+    let params = args
+        .iter()
+        .map(|arg| Val::ExternRef(Some(ExternRef::new(arg.clone()))))
+        .collect::<Vec<_>>();
+    let mut results = [Val::ExternRef(None)];
+    func.call(&mut *store, &params, &mut results).unwrap();
+
+    results[0]
+        .unwrap_externref()
+        .unwrap()
+        .data()
+        .downcast_ref::<Value>()
+        .unwrap()
+        .to_owned()
+}
@@ -1,7 +1,16 @@
+use crate::runtime::native_functions::{self, FuncMap};
+use crate::runtime::{BlockInfoProvider, RuntimeError};
 use crate::ClarityWasmContext;
-use clarity::vm::Value;
+use clarity::util::hash::hex_bytes;
+use clarity::vm::{
+    types::{
+        BlockInfoProperty, BurnBlockInfoProperty, PrincipalData, QualifiedContractIdentifier,
+        StandardPrincipalData, TupleData, TypeSignature,
+    },
+    ClarityVersion, Value,
+};
 use test_case::test_case;
-use wasmtime::{Config, Engine, ExternRef, Store, Val};
+use wasmtime::{Caller, Config, Engine, ExternRef, Func, Store, Val};
 
 #[test_case(Value::Int(1), Value::Int(2) => Value::Int(3))]
 #[test_case(Value::UInt(2), Value::UInt(3) => Value::UInt(5))]
@@ -26,8 +35,1034 @@ fn test_add_extref(a: Value, b: Value) -> Value {
         .to_owned()
 }
 
+#[test_case(Value::Int(1), Value::UInt(2) => RuntimeError::ArgumentTypeMismatch)]
+#[test_case(Value::Int(i128::MAX), Value::Int(1) => RuntimeError::ArithmeticOverflow)]
+#[test_case(Value::UInt(u128::MAX), Value::UInt(1) => RuntimeError::ArithmeticOverflow)]
+#[test_case(Value::Bool(true), Value::Bool(false) => RuntimeError::FunctionOnlySupportsIntegralValues)]
+fn test_add_extref_errors(a: Value, b: Value) -> RuntimeError {
+    let mut store = get_new_store();
+    let add_fn = crate::runtime::native_functions::define_add_extref(&mut store);
+    let params = &[
+        Val::ExternRef(Some(ExternRef::new(a))),
+        Val::ExternRef(Some(ExternRef::new(b))),
+    ];
+    let mut results = [Val::ExternRef(None)];
+    let err = add_fn
+        .call(store, params, &mut results)
+        .expect_err("Expected the function to fail");
+
+    *err.downcast_ref::<RuntimeError>()
+        .expect("Expected a RuntimeError")
+}
+
+#[test_case(Value::Int(5), Value::Int(7) => Ok(Value::Int(-2)))]
+#[test_case(Value::UInt(7), Value::UInt(5) => Ok(Value::UInt(2)))]
+#[test_case(Value::UInt(5), Value::UInt(7) => Err(RuntimeError::ArithmeticUnderflow))]
+#[test_case(Value::Int(i128::MIN), Value::Int(1) => Err(RuntimeError::ArithmeticUnderflow))]
+#[test_case(Value::Int(i128::MAX), Value::Int(-1) => Err(RuntimeError::ArithmeticOverflow))]
+fn test_sub_extref(a: Value, b: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let sub_fn = crate::runtime::native_functions::define_sub_extref(&mut store);
+    call_binary_extref(store, sub_fn, a, b)
+}
+
+#[test_case(Value::Int(7), Value::Int(2) => Ok(Value::Int(3)))]
+#[test_case(Value::Int(-7), Value::Int(2) => Ok(Value::Int(-3)))]
+#[test_case(Value::UInt(7), Value::UInt(0) => Err(RuntimeError::DivisionByZero))]
+#[test_case(Value::Int(i128::MIN), Value::Int(-1) => Err(RuntimeError::ArithmeticOverflow))]
+#[test_case(Value::Int(7), Value::UInt(0) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_div_extref(a: Value, b: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let div_fn = crate::runtime::native_functions::define_div_extref(&mut store);
+    call_binary_extref(store, div_fn, a, b)
+}
+
+#[test_case(Value::Int(7), Value::Int(2) => Ok(Value::Int(1)))]
+#[test_case(Value::Int(-7), Value::Int(2) => Ok(Value::Int(-1)))]
+#[test_case(Value::UInt(7), Value::UInt(0) => Err(RuntimeError::DivisionByZero))]
+fn test_mod_extref(a: Value, b: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let mod_fn = crate::runtime::native_functions::define_mod_extref(&mut store);
+    call_binary_extref(store, mod_fn, a, b)
+}
+
+#[test_case(Value::Int(2), Value::Int(1) => Ok(Value::Bool(true)))]
+#[test_case(Value::UInt(1), Value::UInt(2) => Ok(Value::Bool(false)))]
+#[test_case(Value::Int(1), Value::UInt(2) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_gt_extref(a: Value, b: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let gt_fn = crate::runtime::native_functions::define_gt_extref(&mut store);
+    call_binary_extref(store, gt_fn, a, b)
+}
+
+#[test_case(Value::Int(1), Value::Int(1) => Ok(Value::Bool(true)))]
+#[test_case(Value::buff_from(vec![1, 2]).unwrap(), Value::buff_from(vec![1, 2]).unwrap() => Ok(Value::Bool(true)))]
+#[test_case(Value::UInt(1), Value::UInt(2) => Ok(Value::Bool(false)))]
+fn test_is_eq_extref(a: Value, b: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let is_eq_fn = crate::runtime::native_functions::define_is_eq_extref(&mut store);
+    call_binary_extref(store, is_eq_fn, a, b)
+}
+
+#[test]
+fn test_sequence_primitives_extref() {
+    let mut store = get_new_store();
+    let len_fn = native_functions::define_len_extref(&mut store);
+    let element_at_fn = native_functions::define_element_at_extref(&mut store);
+    let slice_fn = native_functions::define_slice_extref(&mut store);
+    let index_of_fn = native_functions::define_index_of_extref(&mut store);
+
+    let seq = Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap();
+    let ascii = |s: &str| Value::string_ascii_from_bytes(s.as_bytes().to_vec()).unwrap();
+    let call = |store: &mut Store<ClarityWasmContext>, func: &Func, args: Vec<Value>| {
+        let params = args
+            .into_iter()
+            .map(|arg| Val::ExternRef(Some(ExternRef::new(arg))))
+            .collect::<Vec<_>>();
+        let mut results = [Val::ExternRef(None)];
+        func.call(store, &params, &mut results)
+            .expect("Failed to call function");
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .to_owned()
+    };
+
+    assert_eq!(Value::UInt(5), call(&mut store, &len_fn, vec![seq.clone()]));
+    assert_eq!(
+        Value::some(ascii("e")).unwrap(),
+        call(
+            &mut store,
+            &element_at_fn,
+            vec![seq.clone(), Value::UInt(1)]
+        )
+    );
+    assert_eq!(
+        Value::none(),
+        call(
+            &mut store,
+            &element_at_fn,
+            vec![seq.clone(), Value::UInt(5)]
+        )
+    );
+    assert_eq!(
+        Value::some(ascii("ell")).unwrap(),
+        call(
+            &mut store,
+            &slice_fn,
+            vec![seq.clone(), Value::UInt(1), Value::UInt(4)]
+        )
+    );
+    assert_eq!(
+        Value::none(),
+        call(
+            &mut store,
+            &slice_fn,
+            vec![seq.clone(), Value::UInt(4), Value::UInt(1)]
+        )
+    );
+    assert_eq!(
+        Value::some(Value::UInt(2)).unwrap(),
+        call(&mut store, &index_of_fn, vec![seq.clone(), ascii("l")])
+    );
+    assert_eq!(
+        Value::none(),
+        call(&mut store, &index_of_fn, vec![seq, ascii("z")])
+    );
+}
+
+#[test_case(
+    Value::list_from(vec![Value::Int(1)]).unwrap(),
+    Value::Int(2)
+    => Ok(Value::list_from(vec![Value::Int(1), Value::Int(2)]).unwrap())
+)]
+#[test_case(
+    Value::list_from(vec![Value::Int(1)]).unwrap(),
+    Value::UInt(2)
+    => Err(RuntimeError::ArgumentTypeMismatch)
+)]
+#[test_case(
+    Value::list_from(vec![Value::buff_from(vec![0; 600_000]).unwrap()]).unwrap(),
+    Value::buff_from(vec![0; 600_000]).unwrap()
+    => Err(RuntimeError::MaxLengthExceeded)
+)]
+fn test_append_extref(list: Value, element: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let append_fn = native_functions::define_append_extref(&mut store);
+    call_binary_extref(store, append_fn, list, element)
+}
+
+#[test_case(vec![] => Ok(Value::list_from(vec![]).unwrap()))]
+#[test_case(
+    vec![Value::Int(1), Value::Int(2), Value::Int(3)]
+    => Ok(Value::list_from(vec![Value::Int(1), Value::Int(2), Value::Int(3)]).unwrap())
+)]
+#[test_case(vec![Value::Int(1), Value::UInt(2)] => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_list_builder_extref(elements: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let list_push_fn = native_functions::define_list_push_extref(&mut store);
+    let list_build_fn = native_functions::define_list_build_extref(&mut store);
+
+    // The builder starts out as a null reference.
+    let mut builder = [Val::ExternRef(None)];
+    for element in elements {
+        let params = [
+            builder[0].clone(),
+            Val::ExternRef(Some(ExternRef::new(element))),
+        ];
+        list_push_fn
+            .call(&mut store, &params, &mut builder)
+            .expect("Failed to call function");
+    }
+
+    let mut results = [Val::ExternRef(None)];
+    match list_build_fn.call(&mut store, &builder, &mut results) {
+        Ok(()) => Ok(results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .to_owned()),
+        Err(e) => Err(*e
+            .downcast_ref::<RuntimeError>()
+            .expect("Expected a RuntimeError")),
+    }
+}
+
+#[test_case(
+    Value::buff_from(vec![1, 2]).unwrap(),
+    Value::buff_from(vec![3]).unwrap()
+    => Ok(Value::buff_from(vec![1, 2, 3]).unwrap())
+)]
+#[test_case(
+    Value::string_ascii_from_bytes(b"foo".to_vec()).unwrap(),
+    Value::string_ascii_from_bytes(b"bar".to_vec()).unwrap()
+    => Ok(Value::string_ascii_from_bytes(b"foobar".to_vec()).unwrap())
+)]
+#[test_case(
+    Value::buff_from(vec![0; 1024 * 1024]).unwrap(),
+    Value::buff_from(vec![0]).unwrap()
+    => Err(RuntimeError::MaxLengthExceeded)
+)]
+#[test_case(
+    Value::buff_from(vec![1]).unwrap(),
+    Value::string_ascii_from_bytes(b"a".to_vec()).unwrap()
+    => Err(RuntimeError::ArgumentTypeMismatch)
+)]
+#[test_case(
+    Value::list_from(vec![Value::buff_from(vec![0; 600_000]).unwrap()]).unwrap(),
+    Value::list_from(vec![Value::buff_from(vec![1; 600_000]).unwrap()]).unwrap()
+    => Err(RuntimeError::MaxLengthExceeded)
+)]
+fn test_concat_extref(a: Value, b: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let concat_fn = native_functions::define_concat_extref(&mut store);
+    call_binary_extref(store, concat_fn, a, b)
+}
+
+#[test_case(Value::string_ascii_from_bytes(b"-42".to_vec()).unwrap() => Value::some(Value::Int(-42)).unwrap())]
+#[test_case(Value::string_utf8_from_bytes("123".as_bytes().to_vec()).unwrap() => Value::some(Value::Int(123)).unwrap())]
+#[test_case(Value::string_ascii_from_bytes(b"abc".to_vec()).unwrap() => Value::none())]
+fn test_string_to_int_extref(value: Value) -> Value {
+    let mut store = get_new_store();
+    let string_to_int_fn = native_functions::define_string_to_int_extref(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    string_to_int_fn
+        .call(
+            &mut store,
+            &[Val::ExternRef(Some(ExternRef::new(value)))],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    results[0]
+        .unwrap_externref()
+        .unwrap()
+        .data()
+        .downcast_ref::<Value>()
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn test_string_to_uint_extref_rejects_negative() {
+    let mut store = get_new_store();
+    let string_to_uint_fn = native_functions::define_string_to_uint_extref(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    string_to_uint_fn
+        .call(
+            &mut store,
+            &[Val::ExternRef(Some(ExternRef::new(
+                Value::string_ascii_from_bytes(b"-1".to_vec()).unwrap(),
+            )))],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    assert_eq!(
+        Some(&Value::none()),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+}
+
+#[test_case(|store| native_functions::define_sha256_extref(store), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")]
+#[test_case(|store| native_functions::define_hash160_extref(store), "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb")]
+#[test_case(|store| native_functions::define_keccak256_extref(store), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")]
+fn test_hash_extref_empty_buffer(
+    define_fn: fn(&mut Store<ClarityWasmContext>) -> Func,
+    expected: &str,
+) {
+    let mut store = get_new_store();
+    let hash_fn = define_fn(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    hash_fn
+        .call(
+            &mut store,
+            &[Val::ExternRef(Some(ExternRef::new(
+                Value::buff_from(vec![]).unwrap(),
+            )))],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    assert_eq!(
+        Some(&Value::buff_from(hex_bytes(expected).unwrap()).unwrap()),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+}
+
+#[test]
+fn test_secp256k1_malformed_signatures() {
+    let mut store = get_new_store();
+    let recover_fn = native_functions::define_secp256k1_recover_extref(&mut store);
+    let verify_fn = native_functions::define_secp256k1_verify_extref(&mut store);
+
+    let message = Value::buff_from(vec![1; 32]).unwrap();
+    // A signature with an invalid recovery id.
+    let signature = Value::buff_from([vec![0; 64], vec![4]].concat()).unwrap();
+    let pubkey = Value::buff_from(vec![2; 33]).unwrap();
+
+    let mut results = [Val::ExternRef(None)];
+    recover_fn
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(message.clone()))),
+                Val::ExternRef(Some(ExternRef::new(signature.clone()))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    assert_eq!(
+        Some(&Value::error(Value::UInt(2)).unwrap()),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+
+    verify_fn
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(message))),
+                Val::ExternRef(Some(ExternRef::new(signature))),
+                Val::ExternRef(Some(ExternRef::new(pubkey))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    assert_eq!(
+        Some(&Value::Bool(false)),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+}
+
+#[test]
+fn test_principal_construct_and_destruct_extref() {
+    let mut store = get_new_store();
+    let construct_fn = native_functions::define_principal_construct_extref(&mut store);
+    let destruct_fn = native_functions::define_principal_destruct_extref(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    let mut call = |store: &mut Store<ClarityWasmContext>, func: &Func, params: &[Val]| {
+        func.call(store, params, &mut results)
+            .expect("Failed to call function");
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .to_owned()
+    };
+    let extref = |value: Value| Val::ExternRef(Some(ExternRef::new(value)));
+
+    // A testnet single-sig version on a testnet context.
+    let principal = Value::Principal(PrincipalData::Standard(StandardPrincipalData(26, [7; 20])));
+    let constructed = call(
+        &mut store,
+        &construct_fn,
+        &[
+            extref(Value::buff_from(vec![26]).unwrap()),
+            extref(Value::buff_from(vec![7; 20]).unwrap()),
+            Val::ExternRef(None),
+        ],
+    );
+    assert_eq!(Value::okay(principal.clone()).unwrap(), constructed);
+
+    let destructed = call(&mut store, &destruct_fn, &[extref(principal)]);
+    let tuple = destructed.expect_result_ok().expect_tuple();
+    assert_eq!(
+        &Value::buff_from(vec![26]).unwrap(),
+        tuple.get("version").unwrap()
+    );
+    assert_eq!(&Value::none(), tuple.get("name").unwrap());
+
+    // Hash bytes of the wrong length result in error code `u1`.
+    let invalid = call(
+        &mut store,
+        &construct_fn,
+        &[
+            extref(Value::buff_from(vec![26]).unwrap()),
+            extref(Value::buff_from(vec![7; 19]).unwrap()),
+            Val::ExternRef(None),
+        ],
+    );
+    let tuple = invalid.expect_result_err().expect_tuple();
+    assert_eq!(&Value::UInt(1), tuple.get("error_code").unwrap());
+
+    // A mainnet version on a testnet context results in error code `u0`.
+    let wrong_network = call(
+        &mut store,
+        &construct_fn,
+        &[
+            extref(Value::buff_from(vec![22]).unwrap()),
+            extref(Value::buff_from(vec![7; 20]).unwrap()),
+            Val::ExternRef(None),
+        ],
+    );
+    let tuple = wrong_network.expect_result_err().expect_tuple();
+    assert_eq!(&Value::UInt(0), tuple.get("error_code").unwrap());
+}
+
+#[test_case(Value::some(Value::Int(1)).unwrap() => Ok(Value::Int(1)))]
+#[test_case(Value::okay(Value::UInt(2)).unwrap() => Ok(Value::UInt(2)))]
+#[test_case(Value::none() => Err(RuntimeError::UnwrapFailed))]
+#[test_case(Value::error(Value::UInt(3)).unwrap() => Err(RuntimeError::UnwrapFailed))]
+#[test_case(Value::Int(1) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_unwrap_extref(value: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let unwrap_fn = native_functions::define_unwrap_extref(&mut store);
+    call_unary_extref(store, unwrap_fn, value)
+}
+
+#[test]
+fn test_tuple_get_and_merge_extref() {
+    let mut store = get_new_store();
+    let tuple_get_fn = native_functions::define_tuple_get_extref(&mut store);
+    let merge_fn = native_functions::define_merge_extref(&mut store);
+
+    let a_id = store.data_mut().names_mut().intern("a".into());
+    let b_id = store.data_mut().names_mut().intern("b".into());
+    assert_eq!(a_id, store.data_mut().names_mut().intern("a".into()));
+
+    let a = Value::Tuple(TupleData::from_data(vec![("a".into(), Value::Int(1))]).unwrap());
+    let b = Value::Tuple(TupleData::from_data(vec![("b".into(), Value::UInt(2))]).unwrap());
+
+    let mut results = [Val::ExternRef(None)];
+    merge_fn
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(a))),
+                Val::ExternRef(Some(ExternRef::new(b))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    let merged = results[0].unwrap_externref();
+
+    tuple_get_fn
+        .call(
+            &mut store,
+            &[Val::ExternRef(merged), Val::I32(b_id)],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    assert_eq!(
+        Some(&Value::UInt(2)),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+}
+
+#[test_case(Value::UInt(5) => Ok(Value::Int(5)))]
+#[test_case(Value::UInt(u128::MAX) => Err(RuntimeError::ArithmeticOverflow))]
+#[test_case(Value::Int(5) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_to_int_extref(value: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let to_int_fn = native_functions::define_to_int_extref(&mut store);
+    call_unary_extref(store, to_int_fn, value)
+}
+
+#[test_case(Value::Int(5) => Ok(Value::UInt(5)))]
+#[test_case(Value::Int(-1) => Err(RuntimeError::ArithmeticUnderflow))]
+fn test_to_uint_extref(value: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let to_uint_fn = native_functions::define_to_uint_extref(&mut store);
+    call_unary_extref(store, to_uint_fn, value)
+}
+
+#[test]
+fn test_to_uint_native_rejects_negative() {
+    let mut store = get_new_store();
+    let to_uint_fn = native_functions::define_to_uint_native(&mut store);
+    let mut results = [Val::I64(0), Val::I64(0)];
+
+    // -1 as a low/high i64 pair.
+    let err = to_uint_fn
+        .call(&mut store, &[Val::I64(-1), Val::I64(-1)], &mut results)
+        .expect_err("Expected the function to fail");
+    assert_eq!(
+        Some(&RuntimeError::ArithmeticUnderflow),
+        err.downcast_ref::<RuntimeError>()
+    );
+
+    to_uint_fn
+        .call(&mut store, &[Val::I64(42), Val::I64(0)], &mut results)
+        .expect("Failed to call function");
+    assert_eq!(42, results[0].unwrap_i64());
+    assert_eq!(0, results[1].unwrap_i64());
+}
+
+#[test_case(vec![0x01] => Ok(Value::Int(1)))]
+#[test_case(vec![0xff; 16] => Ok(Value::Int(-1)))]
+#[test_case(vec![0x00, 0x01] => Ok(Value::Int(256)))]
+#[test_case(vec![0; 17] => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_buff_to_int_le_extref(bytes: Vec<u8>) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let buff_to_int_fn = native_functions::define_buff_to_int_le_extref(&mut store);
+    call_unary_extref(store, buff_to_int_fn, Value::buff_from(bytes).unwrap())
+}
+
+#[test_case(vec![0x01] => Ok(Value::UInt(1)))]
+#[test_case(vec![0x01, 0x00] => Ok(Value::UInt(256)))]
+fn test_buff_to_uint_be_extref(bytes: Vec<u8>) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let buff_to_uint_fn = native_functions::define_buff_to_uint_be_extref(&mut store);
+    call_unary_extref(store, buff_to_uint_fn, Value::buff_from(bytes).unwrap())
+}
+
+#[test]
+fn test_map_functions_use_datastore() {
+    let mut store = get_new_store();
+    let map_get_fn = native_functions::define_map_get_extref(&mut store);
+    let map_set_fn = native_functions::define_map_set_extref(&mut store);
+    let map_insert_fn = native_functions::define_map_insert_extref(&mut store);
+    let map_delete_fn = native_functions::define_map_delete_extref(&mut store);
+
+    store
+        .data_mut()
+        .set_contract_id(Some(QualifiedContractIdentifier::transient()));
+    let map_id = store.data_mut().names_mut().intern("balances".into());
+    let key = Value::UInt(1);
+
+    let map_get = |store: &mut Store<ClarityWasmContext>| {
+        call_map_fn(store, map_get_fn, map_id, vec![key.clone()])
+    };
+
+    assert_eq!(Ok(Value::none()), map_get(&mut store));
+    assert_eq!(
+        Ok(Value::Bool(true)),
+        call_map_fn(
+            &mut store,
+            map_insert_fn,
+            map_id,
+            vec![key.clone(), Value::Int(10)]
+        )
+    );
+    assert_eq!(
+        Ok(Value::Bool(false)),
+        call_map_fn(
+            &mut store,
+            map_insert_fn,
+            map_id,
+            vec![key.clone(), Value::Int(20)]
+        )
+    );
+    assert_eq!(
+        Ok(Value::some(Value::Int(10)).unwrap()),
+        map_get(&mut store)
+    );
+
+    assert_eq!(
+        Ok(Value::Bool(true)),
+        call_map_fn(
+            &mut store,
+            map_set_fn,
+            map_id,
+            vec![key.clone(), Value::Int(20)]
+        )
+    );
+    assert_eq!(
+        Ok(Value::some(Value::Int(20)).unwrap()),
+        map_get(&mut store)
+    );
+
+    assert_eq!(
+        Ok(Value::Bool(true)),
+        call_map_fn(&mut store, map_delete_fn, map_id, vec![key.clone()])
+    );
+    assert_eq!(
+        Ok(Value::Bool(false)),
+        call_map_fn(&mut store, map_delete_fn, map_id, vec![key.clone()])
+    );
+    assert_eq!(Ok(Value::none()), map_get(&mut store));
+}
+
+#[test]
+fn test_map_get_extref_reuses_scratch_buffer() {
+    let mut store = get_new_store();
+    let map_get_fn = native_functions::define_map_get_extref(&mut store);
+    store
+        .data_mut()
+        .set_contract_id(Some(QualifiedContractIdentifier::transient()));
+    let map_id = store.data_mut().names_mut().intern("balances".into());
+
+    for _ in 0..3 {
+        assert_eq!(
+            Ok(Value::none()),
+            call_map_fn(&mut store, map_get_fn, map_id, vec![Value::UInt(1)])
+        );
+        assert_eq!(1, store.data().scratch.len());
+    }
+}
+
+#[test]
+fn test_map_get_extref_without_contract() {
+    let mut store = get_new_store();
+    let map_get_fn = native_functions::define_map_get_extref(&mut store);
+    let map_id = store.data_mut().names_mut().intern("balances".into());
+
+    assert_eq!(
+        Err(RuntimeError::ContractNotFound),
+        call_map_fn(&mut store, map_get_fn, map_id, vec![Value::UInt(1)])
+    );
+}
+
+#[test]
+fn test_var_set_and_var_get_extref() {
+    let mut store = get_new_store();
+    let var_get_fn = native_functions::define_var_get_extref(&mut store);
+    let var_set_fn = native_functions::define_var_set_extref(&mut store);
+
+    let contract_id = QualifiedContractIdentifier::transient();
+    store.data_mut().set_contract_id(Some(contract_id.clone()));
+    let var_id =
+        store
+            .data_mut()
+            .declare_data_var(contract_id, "counter".into(), TypeSignature::UIntType);
+
+    let mut results = [Val::ExternRef(None)];
+    var_set_fn
+        .call(
+            &mut store,
+            &[
+                Val::I32(var_id),
+                Val::ExternRef(Some(ExternRef::new(Value::UInt(5)))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    var_get_fn
+        .call(&mut store, &[Val::I32(var_id)], &mut results)
+        .expect("Failed to call function");
+
+    assert_eq!(
+        Some(&Value::UInt(5)),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+}
+
+#[test_case(false, Value::Int(5) => RuntimeError::ArgumentTypeMismatch ; "type not admitted")]
+#[test_case(true, Value::UInt(5) => RuntimeError::WriteInReadOnlyContext ; "read-only")]
+fn test_var_set_extref_errors(read_only: bool, value: Value) -> RuntimeError {
+    let mut store = get_new_store();
+    let var_set_fn = native_functions::define_var_set_extref(&mut store);
+
+    let contract_id = QualifiedContractIdentifier::transient();
+    store.data_mut().set_contract_id(Some(contract_id.clone()));
+    store.data_mut().set_read_only(read_only);
+    let var_id =
+        store
+            .data_mut()
+            .declare_data_var(contract_id, "counter".into(), TypeSignature::UIntType);
+
+    let mut results = [Val::ExternRef(None)];
+    let err = var_set_fn
+        .call(
+            &mut store,
+            &[
+                Val::I32(var_id),
+                Val::ExternRef(Some(ExternRef::new(value))),
+            ],
+            &mut results,
+        )
+        .expect_err("Expected var-set to fail");
+
+    *err.downcast_ref::<RuntimeError>()
+        .expect("Expected a RuntimeError")
+}
+
+#[test_case(1 => Value::some(Value::UInt(1_000)).unwrap() ; "known block")]
+#[test_case(5 => Value::none() ; "current block")]
+#[test_case(100 => Value::none() ; "future block")]
+fn test_get_block_info_extref(height: u128) -> Value {
+    let mut store = get_new_store_with_block_info();
+    let get_block_info_fn = native_functions::define_get_block_info_extref(&mut store);
+    let time_id = store.data_mut().names_mut().intern("time".into());
+
+    let mut results = [Val::ExternRef(None)];
+    get_block_info_fn
+        .call(
+            &mut store,
+            &[
+                Val::I32(time_id),
+                Val::ExternRef(Some(ExternRef::new(Value::UInt(height)))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    results[0]
+        .unwrap_externref()
+        .unwrap()
+        .data()
+        .downcast_ref::<Value>()
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn test_at_block_extref_switches_block_height() {
+    let mut store = get_new_store_with_block_info();
+    let at_block_fn = native_functions::define_at_block_extref(&mut store);
+    let block_height_fn = Func::wrap(
+        &mut store,
+        |caller: Caller<'_, ClarityWasmContext>| -> Option<ExternRef> {
+            let context = caller.data();
+            Some(ExternRef::new(Value::Bool(
+                context.block_height() == 2 && context.is_read_only(),
+            )))
+        },
+    );
+
+    let mut results = [Val::ExternRef(None)];
+    at_block_fn
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(Value::buff_from(vec![2; 32]).unwrap()))),
+                Val::FuncRef(Some(block_height_fn)),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    assert_eq!(
+        Some(&Value::Bool(true)),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+    assert_eq!(5, store.data().block_height());
+    assert!(!store.data().is_read_only());
+}
+
+#[test_case(Value::list_from(vec![Value::Int(1), Value::Int(2)]).unwrap() => Value::UInt(2) ; "list")]
+#[test_case(Value::buff_from(vec![1, 2, 3]).unwrap() => Value::UInt(3) ; "buffer")]
+#[test_case(Value::string_ascii_from_bytes(b"abcd".to_vec()).unwrap() => Value::UInt(4) ; "string-ascii")]
+#[test_case(Value::string_utf8_from_bytes("é€".as_bytes().to_vec()).unwrap() => Value::UInt(2) ; "string-utf8")]
+fn test_fold_extref_over_sequence(seq: Value) -> Value {
+    let mut store = get_new_store();
+    let fold_fn = native_functions::define_fold_extref(&mut store);
+
+    // Counts the elements of the sequence, verifying that each one is a Clarity value.
+    let count_fn = Func::wrap(
+        &mut store,
+        |element: Option<ExternRef>, acc: Option<ExternRef>| -> Option<ExternRef> {
+            assert!(element.unwrap().data().downcast_ref::<Value>().is_some());
+            match acc.unwrap().data().downcast_ref::<Value>() {
+                Some(Value::UInt(count)) => Some(ExternRef::new(Value::UInt(count + 1))),
+                _ => None,
+            }
+        },
+    );
+
+    let mut results = [Val::ExternRef(None)];
+    fold_fn
+        .call(
+            &mut store,
+            &[
+                Val::FuncRef(Some(count_fn)),
+                Val::ExternRef(Some(ExternRef::new(seq))),
+                Val::ExternRef(Some(ExternRef::new(Value::UInt(0)))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    results[0]
+        .unwrap_externref()
+        .unwrap()
+        .data()
+        .downcast_ref::<Value>()
+        .unwrap()
+        .to_owned()
+}
+
+#[test_case(i128::MAX, 1 => Err(RuntimeError::ArithmeticOverflow) ; "overflow")]
+#[test_case(i128::MIN, -1 => Err(RuntimeError::ArithmeticUnderflow) ; "underflow")]
+#[test_case(-2, 1 => Ok(-1) ; "signed")]
+fn test_add_native(a: i128, b: i128) -> Result<i128, RuntimeError> {
+    let mut store = get_new_store();
+    let add_fn = native_functions::define_add_native(&mut store);
+    let params = &[
+        Val::I64(a as i64),
+        Val::I64((a >> 64) as i64),
+        Val::I64(b as i64),
+        Val::I64((b >> 64) as i64),
+    ];
+    let mut results = [Val::I64(0), Val::I64(0)];
+
+    match add_fn.call(store, params, &mut results) {
+        Ok(()) => {
+            let low = results[0].unwrap_i64() as u64 as u128;
+            let high = results[1].unwrap_i64() as u64 as u128;
+            Ok((high << 64 | low) as i128)
+        }
+        Err(e) => Err(RuntimeError::from_error(&e).expect("Expected a RuntimeError")),
+    }
+}
+
+#[test_case(|store| native_functions::define_gt_native(store), -1, 1 => 0 ; "gt")]
+#[test_case(|store| native_functions::define_ge_native(store), 1, 1 => 1 ; "ge")]
+#[test_case(|store| native_functions::define_lt_native(store), i128::MIN, i128::MAX => 1 ; "lt")]
+#[test_case(|store| native_functions::define_le_native(store), 1 << 64, 1 => 0 ; "le")]
+#[test_case(|store| native_functions::define_is_eq_native(store), -1, -1 => 1 ; "is_eq")]
+fn test_comparison_native(
+    define_fn: fn(&mut Store<ClarityWasmContext>) -> Func,
+    a: i128,
+    b: i128,
+) -> i32 {
+    let mut store = get_new_store();
+    let compare_fn = define_fn(&mut store);
+    let params = &[
+        Val::I64(a as i64),
+        Val::I64((a >> 64) as i64),
+        Val::I64(b as i64),
+        Val::I64((b >> 64) as i64),
+    ];
+    let mut results = [Val::I32(-1)];
+    compare_fn
+        .call(store, params, &mut results)
+        .expect("Failed to call function");
+
+    results[0].unwrap_i32()
+}
+
+#[test]
+fn test_mul_extref_missing_argument() {
+    let mut store = get_new_store();
+    let mul_fn = crate::runtime::native_functions::define_mul_extref(&mut store);
+    let params = &[
+        Val::ExternRef(Some(ExternRef::new(Value::Int(2)))),
+        Val::ExternRef(None),
+    ];
+    let mut results = [Val::ExternRef(None)];
+    let err = mul_fn
+        .call(store, params, &mut results)
+        .expect_err("Expected the function to fail");
+
+    assert_eq!(
+        Some(&RuntimeError::FunctionArgumentRequired),
+        err.downcast_ref::<RuntimeError>()
+    );
+}
+
+#[test]
+fn test_print_extref_appends_event() {
+    let mut store = get_new_store();
+    let print_fn = crate::runtime::native_functions::define_print_extref(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    print_fn
+        .call(
+            &mut store,
+            &[Val::ExternRef(Some(ExternRef::new(Value::Int(42))))],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    let returned = results[0]
+        .unwrap_externref()
+        .unwrap()
+        .data()
+        .downcast_ref::<Value>()
+        .unwrap()
+        .to_owned();
+
+    assert_eq!(Value::Int(42), returned);
+    assert_eq!(vec![Value::Int(42)], store.data_mut().take_events());
+    assert!(store.data_mut().take_events().is_empty());
+}
+
+#[test]
+fn test_host_functions_honor_clarity_version() {
+    let mut store = get_new_store();
+    let func = crate::runtime::native_functions::define_print_extref(&mut store);
+    let func_map = FuncMap::new("print_extref", func).since(ClarityVersion::Clarity2);
+
+    assert!(!func_map.is_available_at(ClarityVersion::Clarity1));
+    assert!(func_map.is_available_at(ClarityVersion::Clarity2));
+
+    let clarity1_store = Store::new(
+        store.engine(),
+        ClarityWasmContext::new().with_clarity_version(ClarityVersion::Clarity1),
+    );
+    assert_eq!(
+        ClarityVersion::Clarity1,
+        clarity1_store.data().clarity_version()
+    );
+}
+
+#[test_case(ClarityVersion::Clarity1 => vec!["principal_of_extref", "principal_of_memory"])]
+#[test_case(ClarityVersion::Clarity2 => vec![
+    "principal_of_extref",
+    "principal_of_memory",
+    "principal_destruct_extref",
+    "principal_destruct_memory",
+    "principal_construct_extref",
+    "principal_construct_memory",
+])]
+fn test_principal_functions_availability(clarity_version: ClarityVersion) -> Vec<String> {
+    let engine = Engine::new(&Config::default()).expect("Failed to initialize Wasmtime Engine.");
+    let mut store = Store::new(
+        &engine,
+        ClarityWasmContext::new().with_clarity_version(clarity_version),
+    );
+
+    native_functions::get_all_functions(&mut store)
+        .into_iter()
+        .map(|f| f.name)
+        .filter(|name| name.starts_with("principal_"))
+        .collect()
+}
+
+#[test]
+fn test_get_tx_context_values() {
+    let mut store = get_new_store();
+    let sender = PrincipalData::Standard(StandardPrincipalData(26, [1; 20]));
+    store.data_mut().set_tx_sender(sender.clone());
+    store.data_mut().set_block_height(1234);
+
+    let tx_sender_fn = native_functions::define_get_tx_sender_extref(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    tx_sender_fn
+        .call(&mut store, &[], &mut results)
+        .expect("Failed to call function");
+    let tx_sender = results[0]
+        .unwrap_externref()
+        .unwrap()
+        .data()
+        .downcast_ref::<Value>()
+        .unwrap()
+        .to_owned();
+    assert_eq!(Value::Principal(sender), tx_sender);
+
+    let block_height_fn = native_functions::define_get_block_height_native(&mut store);
+    let mut results = [Val::I64(0), Val::I64(0)];
+    block_height_fn
+        .call(&mut store, &[], &mut results)
+        .expect("Failed to call function");
+    assert_eq!(Some(1234), results[0].i64());
+    assert_eq!(Some(0), results[1].i64());
+}
+
 /// Helper function. Initializes a clean new `Store` using defaults, but
 /// with WASM reference types enabled.
+#[test_case(Value::Bool(true) => Ok(Value::Bool(false)))]
+#[test_case(Value::Bool(false) => Ok(Value::Bool(true)))]
+#[test_case(Value::Int(1) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_not_extref(value: Value) -> Result<Value, RuntimeError> {
+    let mut store = get_new_store();
+    let func = native_functions::define_not_extref(&mut store);
+    call_unary_extref(store, func, value)
+}
+
+#[test_case(Value::Bool(true) => Ok(1))]
+#[test_case(Value::Bool(false) => Ok(0))]
+#[test_case(Value::Int(1) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_to_bool_extref(value: Value) -> Result<i32, RuntimeError> {
+    let mut store = get_new_store();
+    let func = native_functions::define_to_bool_extref(&mut store);
+    call_i32_extref(store, func, value)
+}
+
+#[test_case(Value::some(Value::Int(1)).unwrap() => Ok(1))]
+#[test_case(Value::none() => Ok(0))]
+#[test_case(Value::okay(Value::Int(1)).unwrap() => Ok(1))]
+#[test_case(Value::error(Value::Int(1)).unwrap() => Ok(0))]
+#[test_case(Value::Bool(true) => Err(RuntimeError::ArgumentTypeMismatch))]
+fn test_is_success_extref(value: Value) -> Result<i32, RuntimeError> {
+    let mut store = get_new_store();
+    let func = native_functions::define_is_success_extref(&mut store);
+    call_i32_extref(store, func, value)
+}
+
+/// Helper function. Calls a host function taking one `ExternRef` argument and
+/// returning an `i32`, recovering any `RuntimeError` it fails with.
+fn call_i32_extref(
+    store: Store<ClarityWasmContext>,
+    func: Func,
+    value: Value,
+) -> Result<i32, RuntimeError> {
+    let params = &[Val::ExternRef(Some(ExternRef::new(value)))];
+    let mut results = [Val::I32(0)];
+
+    match func.call(store, params, &mut results) {
+        Ok(()) => Ok(results[0].unwrap_i32()),
+        Err(e) => Err(*e
+            .downcast_ref::<RuntimeError>()
+            .expect("Expected a RuntimeError")),
+    }
+}
+
 fn get_new_store() -> Store<ClarityWasmContext> {
     let mut config = Config::default();
     config.wasm_reference_types(true);
@@ -35,3 +1070,114 @@ fn get_new_store() -> Store<ClarityWasmContext> {
     let context = ClarityWasmContext::new();
     Store::new(&engine, context)
 }
+
+/// Helper function. Calls a host function taking two `ExternRef` arguments and
+/// returning a single `ExternRef`, recovering any `RuntimeError` it fails with.
+fn call_binary_extref(
+    store: Store<ClarityWasmContext>,
+    func: Func,
+    a: Value,
+    b: Value,
+) -> Result<Value, RuntimeError> {
+    let params = &[
+        Val::ExternRef(Some(ExternRef::new(a))),
+        Val::ExternRef(Some(ExternRef::new(b))),
+    ];
+    let mut results = [Val::ExternRef(None)];
+
+    match func.call(store, params, &mut results) {
+        Ok(()) => Ok(results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .to_owned()),
+        Err(e) => Err(*e
+            .downcast_ref::<RuntimeError>()
+            .expect("Expected a RuntimeError")),
+    }
+}
+
+/// Helper function. Calls a host function taking one `ExternRef` argument and
+/// returning a single `ExternRef`, recovering any `RuntimeError` it fails with.
+fn call_unary_extref(
+    store: Store<ClarityWasmContext>,
+    func: Func,
+    value: Value,
+) -> Result<Value, RuntimeError> {
+    let params = &[Val::ExternRef(Some(ExternRef::new(value)))];
+    let mut results = [Val::ExternRef(None)];
+
+    match func.call(store, params, &mut results) {
+        Ok(()) => Ok(results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .to_owned()),
+        Err(e) => Err(*e
+            .downcast_ref::<RuntimeError>()
+            .expect("Expected a RuntimeError")),
+    }
+}
+
+/// Helper function. Calls one of the data map host functions, which take the map's
+/// interned name id followed by one or more `ExternRef` arguments.
+fn call_map_fn(
+    store: &mut Store<ClarityWasmContext>,
+    func: Func,
+    map_id: i32,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let mut params = vec![Val::I32(map_id)];
+    params.extend(
+        args.into_iter()
+            .map(|arg| Val::ExternRef(Some(ExternRef::new(arg)))),
+    );
+    let mut results = [Val::ExternRef(None)];
+
+    match func.call(store, &params, &mut results) {
+        Ok(()) => Ok(results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .to_owned()),
+        Err(e) => Err(*e
+            .downcast_ref::<RuntimeError>()
+            .expect("Expected a RuntimeError")),
+    }
+}
+
+/// Helper function. Creates a store at block height 5, whose blocks each have a
+/// `time` of their height times 1000 and an `id-header-hash` of 32 bytes of their
+/// height.
+fn get_new_store_with_block_info() -> Store<ClarityWasmContext> {
+    #[derive(Debug, Clone)]
+    struct StubBlockInfo;
+
+    impl BlockInfoProvider for StubBlockInfo {
+        fn get_block_info(&self, block_height: u32, property: BlockInfoProperty) -> Option<Value> {
+            match property {
+                BlockInfoProperty::Time => Some(Value::UInt(block_height as u128 * 1_000)),
+                _ => None,
+            }
+        }
+
+        fn get_burn_block_info(&self, _: u32, _: BurnBlockInfoProperty) -> Option<Value> {
+            None
+        }
+
+        fn get_block_height(&self, id_header_hash: &[u8]) -> Option<u32> {
+            id_header_hash.first().map(|height| *height as u32)
+        }
+    }
+
+    let mut store = get_new_store();
+    *store.data_mut() = ClarityWasmContext::new().with_block_info(StubBlockInfo);
+    store.data_mut().set_block_height(5);
+    store
+}
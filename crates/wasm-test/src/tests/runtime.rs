@@ -0,0 +1,931 @@
+use std::time::Duration;
+
+use crate::{
+    runtime::{
+        alloc_in_memory, call_with_deadline, native_functions, read_guest_panic,
+        read_value_from_memory, write_value_to_memory, BuddyAllocator, EpochTicker,
+        ExecutionDeadline, GuestPanic, GuestPanicReason, RuntimeBuilder, RuntimeError, ScratchPool,
+        WasmAllocator, WasmMemoryAllocator, PANIC_INFO_GLOBAL,
+    },
+    serialization::{serialize_clarity_value, serialize_packed_int_list, SerializationError},
+    ClarityWasmContext,
+};
+use clarity::util::hash::hex_bytes;
+use clarity::vm::{
+    types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData},
+    ContractName, Value,
+};
+use test_case::test_case;
+use walrus::{Module, ModuleConfig};
+use wasmtime::{Config, Engine, ExternRef, Memory, MemoryType, Store, Val};
+
+#[test]
+fn test_execution_deadline_interrupts_infinite_loop() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .with_host_functions(|_| Vec::new())
+        .with_epoch_interruption()
+        .instantiate(&generate_infinite_loop())
+        .expect("Failed to instantiate module");
+    let func = instance
+        .get_func(&mut store, "spin")
+        .expect("Failed to get fn");
+
+    let deadline = ExecutionDeadline::new(Duration::from_millis(1), 10);
+    let _ticker = EpochTicker::for_deadline(store.engine(), &deadline);
+
+    let err = call_with_deadline(&mut store, &deadline, &func, &[], &mut [])
+        .expect_err("Expected the call to time out");
+
+    assert_eq!(
+        Some(&RuntimeError::ExecutionTimedOut),
+        err.downcast_ref::<RuntimeError>()
+    );
+}
+
+#[test]
+fn test_host_functions_are_linked_by_name() {
+    let wasm_bytes = generate_out_of_order_imports();
+
+    RuntimeBuilder::new()
+        .instantiate(&wasm_bytes)
+        .expect("Failed to instantiate module with out-of-order imports");
+}
+
+#[test]
+fn test_contract_call_switches_contract_caller() {
+    let caller_id = QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("caller"),
+    );
+    let callee_id = QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("callee"),
+    );
+
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_whoami_contract())
+        .expect("Failed to instantiate module");
+    store
+        .data_mut()
+        .contracts_mut()
+        .register(callee_id.clone(), instance);
+    store.data_mut().set_contract_id(Some(caller_id.clone()));
+
+    let contract_call = native_functions::define_contract_call_extref(&mut store);
+    let mut results = [Val::ExternRef(None)];
+    contract_call
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(Value::Principal(
+                    PrincipalData::Contract(callee_id),
+                )))),
+                Val::ExternRef(Some(ExternRef::new(
+                    Value::string_ascii_from_bytes(b"whoami".to_vec()).unwrap(),
+                ))),
+                Val::ExternRef(Some(ExternRef::new(Value::list_from(vec![]).unwrap()))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    let result = results[0].unwrap_externref().unwrap();
+    assert_eq!(
+        Some(&Value::Principal(PrincipalData::Contract(
+            caller_id.clone()
+        ))),
+        result.data().downcast_ref::<Value>()
+    );
+    assert_eq!(Some(&caller_id), store.data().contract_id());
+}
+
+#[test]
+fn test_map_extref_over_list() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_sequence_contract())
+        .expect("Failed to instantiate module");
+    let func = instance
+        .get_func(&mut store, "map_double")
+        .expect("Failed to get fn");
+
+    let list = Value::list_from(vec![Value::Int(1), Value::Int(2), Value::Int(3)]).unwrap();
+    let mut results = [Val::ExternRef(None)];
+    func.call(
+        &mut store,
+        &[Val::ExternRef(Some(ExternRef::new(list)))],
+        &mut results,
+    )
+    .expect("Failed to call function");
+
+    let result = results[0].unwrap_externref().unwrap();
+    assert_eq!(
+        Some(&Value::list_from(vec![Value::Int(2), Value::Int(4), Value::Int(6)]).unwrap()),
+        result.data().downcast_ref::<Value>()
+    );
+}
+
+#[test]
+fn test_filter_extref_over_list() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_sequence_contract())
+        .expect("Failed to instantiate module");
+    let func = instance
+        .get_func(&mut store, "filter_positive")
+        .expect("Failed to get fn");
+
+    let list = Value::list_from(vec![
+        Value::Int(-1),
+        Value::Int(2),
+        Value::Int(0),
+        Value::Int(3),
+    ])
+    .unwrap();
+    let mut results = [Val::ExternRef(None)];
+    func.call(
+        &mut store,
+        &[Val::ExternRef(Some(ExternRef::new(list)))],
+        &mut results,
+    )
+    .expect("Failed to call function");
+
+    let result = results[0].unwrap_externref().unwrap();
+    assert_eq!(
+        Some(&Value::list_from(vec![Value::Int(2), Value::Int(3)]).unwrap()),
+        result.data().downcast_ref::<Value>()
+    );
+}
+
+#[test]
+fn test_secp256k1_through_linked_module() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_secp256k1_contract())
+        .expect("Failed to instantiate module");
+
+    let message = Value::buff_from(
+        hex_bytes("de5b9eb9e7c5592930eb2e30a01369c36586d872082ed8181ee83d2a0ec20f04").unwrap(),
+    )
+    .unwrap();
+    let signature = Value::buff_from(
+        hex_bytes(
+            "8738487ebe69b93d8e51583be8eee50bb4213fc49c767d329632730cc193b873554428fc936ca3569afc15f1c9365f6591d6251a89fee9c9ac661116824d3a1301",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let pubkey = Value::buff_from(
+        hex_bytes("03adb8de4bfb65db2cfd6120d55c6526ae9c52e675db7e47308636534ba7786110").unwrap(),
+    )
+    .unwrap();
+
+    let recover = instance
+        .get_func(&mut store, "recover")
+        .expect("Failed to get fn");
+    let mut results = [Val::ExternRef(None)];
+    recover
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(message.clone()))),
+                Val::ExternRef(Some(ExternRef::new(signature.clone()))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    assert_eq!(
+        Some(&Value::okay(pubkey.clone()).unwrap()),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+
+    let verify = instance
+        .get_func(&mut store, "verify")
+        .expect("Failed to get fn");
+    verify
+        .call(
+            &mut store,
+            &[
+                Val::ExternRef(Some(ExternRef::new(message.clone()))),
+                Val::ExternRef(Some(ExternRef::new(signature.clone()))),
+                Val::ExternRef(Some(ExternRef::new(pubkey.clone()))),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+    assert_eq!(
+        Some(&Value::Bool(true)),
+        results[0]
+            .unwrap_externref()
+            .unwrap()
+            .data()
+            .downcast_ref::<Value>()
+    );
+
+    // The memory convention variant recovers the same public key.
+    let memory = instance
+        .get_memory(&mut store, "vm_mem")
+        .expect("Failed to get memory");
+    let mut params = Vec::new();
+    let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+    for value in [&message, &signature] {
+        let ptr = write_value_to_memory(&mut store, memory, &mut *alloc, value)
+            .expect("Failed to write value to memory");
+        params.extend([Val::I32(ptr.offset), Val::I32(ptr.len)]);
+    }
+    store.data_mut().alloc = alloc;
+
+    let recover_memory = instance
+        .get_func(&mut store, "recover_memory")
+        .expect("Failed to get fn");
+    let mut results = [Val::I32(0), Val::I32(0), Val::I32(0)];
+    recover_memory
+        .call(&mut store, &params, &mut results)
+        .expect("Failed to call function");
+    assert_eq!(0, results[0].unwrap_i32());
+    assert_eq!(
+        Value::okay(pubkey).unwrap(),
+        read_value_from_memory(
+            &store,
+            memory,
+            results[1].unwrap_i32(),
+            results[2].unwrap_i32()
+        )
+        .expect("Failed to read value from memory")
+    );
+}
+
+#[test]
+fn test_drop_ptrs_memory() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .with_context(ClarityWasmContext::new().with_allocator(BuddyAllocator::new()))
+        .instantiate(&generate_drop_ptrs_contract())
+        .expect("Failed to instantiate module");
+    let memory = instance
+        .get_memory(&mut store, "vm_mem")
+        .expect("Failed to get memory");
+
+    let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+    let a = write_value_to_memory(&mut store, memory, &mut *alloc, &Value::Int(1))
+        .expect("Failed to write value to memory");
+    let b = write_value_to_memory(&mut store, memory, &mut *alloc, &Value::Int(2))
+        .expect("Failed to write value to memory");
+    // `a` is listed twice, but can only be freed once.
+    let ptrs = [a, b, a]
+        .iter()
+        .flat_map(|ptr| [ptr.offset.to_le_bytes(), ptr.len.to_le_bytes()].concat())
+        .collect::<Vec<_>>();
+    let ptrs_ptr = alloc_in_memory(&mut store, memory, &mut *alloc, ptrs.len())
+        .expect("Failed to allocate memory");
+    memory
+        .write(&mut store, ptrs_ptr.offset as usize, &ptrs)
+        .expect("Failed to write pointers");
+    store.data_mut().alloc = alloc;
+
+    let drop_ptrs = instance
+        .get_func(&mut store, "drop_ptrs")
+        .expect("Failed to get fn");
+    let mut results = [Val::I32(0), Val::I32(0), Val::I32(0)];
+    drop_ptrs
+        .call(
+            &mut store,
+            &[Val::I32(ptrs_ptr.offset), Val::I32(3)],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    assert_eq!(
+        (0, ptrs_ptr.offset, 2),
+        (
+            results[0].unwrap_i32(),
+            results[1].unwrap_i32(),
+            results[2].unwrap_i32()
+        )
+    );
+    // Only the pointers themselves are still allocated.
+    assert_eq!(1, store.data().alloc_stats().live_allocations);
+
+    // Reading pointers from outside of the memory fails.
+    let end = memory.data_size(&store) as i32;
+    drop_ptrs
+        .call(&mut store, &[Val::I32(end), Val::I32(1)], &mut results)
+        .expect("Failed to call function");
+    assert_eq!(
+        RuntimeError::IndexOutOfRange as i32,
+        results[0].unwrap_i32()
+    );
+}
+
+#[test_case(false ; "regular list")]
+#[test_case(true ; "packed list")]
+fn test_fold_memory_over_int_list(packed: bool) {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_fold_memory_contract())
+        .expect("Failed to instantiate module");
+    let memory = instance
+        .get_memory(&mut store, "vm_mem")
+        .expect("Failed to get memory");
+
+    let list = Value::list_from((1..=4).map(Value::Int).collect()).unwrap();
+    let mut alloc = std::mem::take(&mut store.data_mut().alloc);
+    let seq_ptr = if packed {
+        let bytes = serialize_packed_int_list(&list).expect("Failed to serialize value");
+        let ptr = alloc_in_memory(&mut store, memory, &mut *alloc, bytes.len())
+            .expect("Failed to allocate memory");
+        memory
+            .write(&mut store, ptr.offset as usize, &bytes)
+            .expect("Failed to write value");
+        ptr
+    } else {
+        write_value_to_memory(&mut store, memory, &mut *alloc, &list)
+            .expect("Failed to write value to memory")
+    };
+    let init_ptr = write_value_to_memory(&mut store, memory, &mut *alloc, &Value::Int(10))
+        .expect("Failed to write value to memory");
+    store.data_mut().alloc = alloc;
+
+    let fold_add = instance
+        .get_func(&mut store, "fold_add")
+        .expect("Failed to get fn");
+    let mut results = [Val::I32(0), Val::I32(0), Val::I32(0)];
+    fold_add
+        .call(
+            &mut store,
+            &[
+                Val::I32(seq_ptr.offset),
+                Val::I32(seq_ptr.len),
+                Val::I32(init_ptr.offset),
+                Val::I32(init_ptr.len),
+            ],
+            &mut results,
+        )
+        .expect("Failed to call function");
+
+    assert_eq!(0, results[0].unwrap_i32());
+    assert_eq!(
+        Value::Int(20),
+        read_value_from_memory(
+            &store,
+            memory,
+            results[1].unwrap_i32(),
+            results[2].unwrap_i32()
+        )
+        .expect("Failed to read value from memory")
+    );
+}
+
+#[test]
+fn test_traps_are_not_runtime_errors() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_unreachable())
+        .expect("Failed to instantiate module");
+    let func = instance
+        .get_func(&mut store, "trap")
+        .expect("Failed to get fn");
+
+    let err = func
+        .call(&mut store, &[], &mut [])
+        .expect_err("Expected the call to trap");
+
+    assert_eq!(None, RuntimeError::from_error(&err));
+}
+
+#[test]
+fn test_read_guest_panic_after_trap() {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&generate_guest_panic(1, "assertion failed at src/lib.rs:1"))
+        .expect("Failed to instantiate module");
+    let func = instance
+        .get_func(&mut store, "trap")
+        .expect("Failed to get fn");
+
+    func.call(&mut store, &[], &mut [])
+        .expect_err("Expected the call to trap");
+
+    assert_eq!(
+        Some(GuestPanic {
+            reason: GuestPanicReason::Panic,
+            message: "assertion failed at src/lib.rs:1".to_string(),
+        }),
+        read_guest_panic(&mut store, &instance)
+    );
+}
+
+#[test_case(generate_guest_panic(0, "") ; "nothing recorded")]
+#[test_case(generate_unreachable() ; "no panic record")]
+fn test_read_guest_panic_without_failure(wasm: Vec<u8>) {
+    let (instance, mut store) = RuntimeBuilder::new()
+        .instantiate(&wasm)
+        .expect("Failed to instantiate module");
+
+    assert_eq!(None, read_guest_panic(&mut store, &instance));
+}
+
+/// Helper function. Generates a module exporting a `spin` function which
+/// never returns.
+fn generate_infinite_loop() -> Vec<u8> {
+    use walrus::FunctionBuilder;
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let mut spin = FunctionBuilder::new(&mut module.types, &[], &[]);
+    spin.func_body().loop_(None, |body| {
+        let id = body.id();
+        body.br(id);
+    });
+    let spin_id = spin.finish(vec![], &mut module.funcs);
+    module.exports.add("spin", spin_id);
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting a `trap` function which
+/// immediately traps.
+fn generate_unreachable() -> Vec<u8> {
+    use walrus::FunctionBuilder;
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let mut trap = FunctionBuilder::new(&mut module.types, &[], &[]);
+    trap.func_body().unreachable();
+    let trap_id = trap.finish(vec![], &mut module.funcs);
+    module.exports.add("trap", trap_id);
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting a `trap` function which
+/// immediately traps, along with a panic record (as written by `wasm-stdlib`)
+/// holding the given reason and message.
+fn generate_guest_panic(reason: i32, message: &str) -> Vec<u8> {
+    use walrus::{ActiveData, ActiveDataLocation, DataKind, InitExpr, ValType};
+
+    const PANIC_RECORD_ADDRESS: i32 = 16;
+
+    let mut module = Module::from_buffer(&generate_unreachable()).unwrap();
+    let memory = module.memories.add_local(false, 1, None);
+    module.exports.add("memory", memory);
+
+    let mut record = reason.to_le_bytes().to_vec();
+    record.extend_from_slice(&(message.len() as i32).to_le_bytes());
+    record.extend_from_slice(message.as_bytes());
+    module.data.add(
+        DataKind::Active(ActiveData {
+            memory,
+            location: ActiveDataLocation::Absolute(PANIC_RECORD_ADDRESS as u32),
+        }),
+        record,
+    );
+
+    let panic_info = module.globals.add_local(
+        ValType::I32,
+        false,
+        InitExpr::Value(walrus::ir::Value::I32(PANIC_RECORD_ADDRESS)),
+    );
+    module.exports.add(PANIC_INFO_GLOBAL, panic_info);
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module which imports a subset of the host functions
+/// in a different order than they are defined in `get_all_functions`.
+fn generate_out_of_order_imports() -> Vec<u8> {
+    use walrus::ValType;
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let print_memory_ty = module.types.add(
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let add_extref_ty = module.types.add(
+        &[ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    module.add_import_func("clarity", "print_memory", print_memory_ty);
+    module.add_import_func("clarity", "add_extref", add_extref_ty);
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting a `whoami` function which returns
+/// the current `contract-caller`.
+fn generate_whoami_contract() -> Vec<u8> {
+    use walrus::{FunctionBuilder, ValType};
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let get_contract_caller_ty = module.types.add(&[], &[ValType::Externref]);
+    let (get_contract_caller, _) = module.add_import_func(
+        "clarity",
+        "get_contract_caller_extref",
+        get_contract_caller_ty,
+    );
+
+    let mut whoami = FunctionBuilder::new(&mut module.types, &[], &[ValType::Externref]);
+    whoami.func_body().call(get_contract_caller);
+    let whoami_id = whoami.finish(vec![], &mut module.funcs);
+    module.exports.add("whoami", whoami_id);
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting a `map_double` function which
+/// maps each element of the provided sequence to itself added to itself, and a
+/// `filter_positive` function which retains only the elements greater than zero.
+fn generate_sequence_contract() -> Vec<u8> {
+    use walrus::{FunctionBuilder, ValType};
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let add_extref_ty = module.types.add(
+        &[ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    let (add_extref, _) = module.add_import_func("clarity", "add_extref", add_extref_ty);
+    let map_extref_ty = module.types.add(
+        &[ValType::Funcref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    let (map_extref, _) = module.add_import_func("clarity", "map_extref", map_extref_ty);
+    let (sub_extref, _) = module.add_import_func("clarity", "sub_extref", add_extref_ty);
+    let (gt_extref, _) = module.add_import_func("clarity", "gt_extref", add_extref_ty);
+    let (filter_extref, _) = module.add_import_func("clarity", "filter_extref", map_extref_ty);
+
+    let mut double = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::Externref],
+        &[ValType::Externref],
+    );
+    let x = module.locals.add(ValType::Externref);
+    double
+        .func_body()
+        .local_get(x)
+        .local_get(x)
+        .call(add_extref);
+    let double_id = double.finish(vec![x], &mut module.funcs);
+
+    let mut map_double = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::Externref],
+        &[ValType::Externref],
+    );
+    let seq = module.locals.add(ValType::Externref);
+    map_double
+        .func_body()
+        .ref_func(double_id)
+        .local_get(seq)
+        .call(map_extref);
+    let map_double_id = map_double.finish(vec![seq], &mut module.funcs);
+    module.exports.add("map_double", map_double_id);
+
+    // `(> x (- x x))`, i.e. `(> x 0)` without needing a constant.
+    let mut is_positive = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::Externref],
+        &[ValType::Externref],
+    );
+    let x = module.locals.add(ValType::Externref);
+    is_positive
+        .func_body()
+        .local_get(x)
+        .local_get(x)
+        .local_get(x)
+        .call(sub_extref)
+        .call(gt_extref);
+    let is_positive_id = is_positive.finish(vec![x], &mut module.funcs);
+
+    let mut filter_positive = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::Externref],
+        &[ValType::Externref],
+    );
+    let seq = module.locals.add(ValType::Externref);
+    filter_positive
+        .func_body()
+        .ref_func(is_positive_id)
+        .local_get(seq)
+        .call(filter_extref);
+    let filter_positive_id = filter_positive.finish(vec![seq], &mut module.funcs);
+    module.exports.add("filter_positive", filter_positive_id);
+
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting `recover` and `verify` functions
+/// which call the `secp256k1` host functions, and a `recover_memory` function which
+/// calls the memory convention variant on values in its `vm_mem` memory.
+fn generate_secp256k1_contract() -> Vec<u8> {
+    use walrus::{FunctionBuilder, ValType};
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let memory = module.memories.add_local(false, 1, None);
+    module.exports.add("vm_mem", memory);
+
+    let recover_ty = module.types.add(
+        &[ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    let (recover_extref, _) =
+        module.add_import_func("clarity", "secp256k1_recover_extref", recover_ty);
+    let verify_ty = module.types.add(
+        &[ValType::Externref, ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    let (verify_extref, _) =
+        module.add_import_func("clarity", "secp256k1_verify_extref", verify_ty);
+    let recover_memory_ty = module.types.add(
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let (recover_memory, _) =
+        module.add_import_func("clarity", "secp256k1_recover_memory", recover_memory_ty);
+
+    let mut recover = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    let message = module.locals.add(ValType::Externref);
+    let signature = module.locals.add(ValType::Externref);
+    recover
+        .func_body()
+        .local_get(message)
+        .local_get(signature)
+        .call(recover_extref);
+    let recover_id = recover.finish(vec![message, signature], &mut module.funcs);
+    module.exports.add("recover", recover_id);
+
+    let mut verify = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::Externref, ValType::Externref, ValType::Externref],
+        &[ValType::Externref],
+    );
+    let message = module.locals.add(ValType::Externref);
+    let signature = module.locals.add(ValType::Externref);
+    let pubkey = module.locals.add(ValType::Externref);
+    verify
+        .func_body()
+        .local_get(message)
+        .local_get(signature)
+        .local_get(pubkey)
+        .call(verify_extref);
+    let verify_id = verify.finish(vec![message, signature, pubkey], &mut module.funcs);
+    module.exports.add("verify", verify_id);
+
+    let mut recover_in_memory = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let params = (0..4)
+        .map(|_| module.locals.add(ValType::I32))
+        .collect::<Vec<_>>();
+    let mut body = recover_in_memory.func_body();
+    for param in &params {
+        body.local_get(*param);
+    }
+    body.call(recover_memory);
+    let recover_in_memory_id = recover_in_memory.finish(params, &mut module.funcs);
+    module.exports.add("recover_memory", recover_in_memory_id);
+
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting its `vm_mem` memory and a
+/// `fold_add` function which sums the elements of a sequence onto an initial value
+/// using the `fold_memory` host function.
+fn generate_fold_memory_contract() -> Vec<u8> {
+    use walrus::{FunctionBuilder, ValType};
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let memory = module.memories.add_local(false, 1, None);
+    module.exports.add("vm_mem", memory);
+
+    let memory_binary_ty = module.types.add(
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let (add_memory, _) = module.add_import_func("clarity", "add_memory", memory_binary_ty);
+    let fold_memory_ty = module.types.add(
+        &[
+            ValType::Funcref,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+        ],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let (fold_memory, _) = module.add_import_func("clarity", "fold_memory", fold_memory_ty);
+
+    let mut add = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let add_params = (0..4)
+        .map(|_| module.locals.add(ValType::I32))
+        .collect::<Vec<_>>();
+    let mut body = add.func_body();
+    for param in &add_params {
+        body.local_get(*param);
+    }
+    body.call(add_memory);
+    let add_id = add.finish(add_params, &mut module.funcs);
+
+    let mut fold_add = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let fold_params = (0..4)
+        .map(|_| module.locals.add(ValType::I32))
+        .collect::<Vec<_>>();
+    let mut body = fold_add.func_body();
+    body.ref_func(add_id);
+    for param in &fold_params {
+        body.local_get(*param);
+    }
+    body.call(fold_memory);
+    let fold_add_id = fold_add.finish(fold_params, &mut module.funcs);
+    module.exports.add("fold_add", fold_add_id);
+
+    module.emit_wasm()
+}
+
+/// Helper function. Generates a module exporting its `vm_mem` memory and a
+/// `drop_ptrs` function which calls the `drop_ptrs_memory` host function.
+fn generate_drop_ptrs_contract() -> Vec<u8> {
+    use walrus::{FunctionBuilder, ValType};
+
+    let mut module = Module::with_config(ModuleConfig::new());
+    let memory = module.memories.add_local(false, 1, None);
+    module.exports.add("vm_mem", memory);
+
+    let drop_ptrs_ty = module.types.add(
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let (drop_ptrs_memory, _) = module.add_import_func("clarity", "drop_ptrs_memory", drop_ptrs_ty);
+
+    let mut drop_ptrs = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32],
+        &[ValType::I32, ValType::I32, ValType::I32],
+    );
+    let ptr_offset = module.locals.add(ValType::I32);
+    let count = module.locals.add(ValType::I32);
+    drop_ptrs
+        .func_body()
+        .local_get(ptr_offset)
+        .local_get(count)
+        .call(drop_ptrs_memory);
+    let drop_ptrs_id = drop_ptrs.finish(vec![ptr_offset, count], &mut module.funcs);
+    module.exports.add("drop_ptrs", drop_ptrs_id);
+
+    module.emit_wasm()
+}
+
+#[test]
+fn test_write_value_to_memory() {
+    let (mut store, memory) = get_new_store_with_memory();
+    let mut alloc = WasmAllocator::new();
+    let a = Value::Int(5);
+    let b = Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap();
+
+    let a_ptr = write_value_to_memory(&mut store, memory, &mut alloc, &a)
+        .expect("Failed to write value to memory");
+    let b_ptr = write_value_to_memory(&mut store, memory, &mut alloc, &b)
+        .expect("Failed to write value to memory");
+
+    // The values are written back-to-back, in exactly-sized regions.
+    let a_bytes = serialize_clarity_value(&a).unwrap();
+    let b_bytes = serialize_clarity_value(&b).unwrap();
+    assert_eq!((0, a_bytes.len() as i32), (a_ptr.offset, a_ptr.len));
+    assert_eq!(
+        (a_bytes.len() as i32, b_bytes.len() as i32),
+        (b_ptr.offset, b_ptr.len)
+    );
+
+    let data = memory.data(&store);
+    assert_eq!(&a_bytes[..], &data[..a_bytes.len()]);
+    assert_eq!(
+        &b_bytes[..],
+        &data[a_bytes.len()..a_bytes.len() + b_bytes.len()]
+    );
+}
+
+#[test]
+fn test_write_value_to_memory_grows_memory() {
+    let (mut store, memory) = get_new_store_with_memory();
+    let mut alloc = WasmAllocator::new();
+    // Fill all but the last few bytes of the (single page) memory.
+    alloc.alloc_for_size(65_536 - 4);
+
+    let ptr = write_value_to_memory(&mut store, memory, &mut alloc, &Value::UInt(1))
+        .expect("Failed to write value to memory");
+
+    assert_eq!(2, memory.size(&store));
+    let read = read_value_from_memory(&store, memory, ptr.offset, ptr.len)
+        .expect("Failed to read value from memory");
+    assert_eq!(Value::UInt(1), read);
+}
+
+#[test_case(Box::new(WasmAllocator::new().with_max_memory_pages(1)), None ; "allocator maximum")]
+#[test_case(Box::new(BuddyAllocator::new().with_max_memory_pages(1)), None ; "buddy allocator maximum")]
+#[test_case(Box::new(WasmAllocator::new()), Some(1) ; "memory type maximum")]
+fn test_write_value_to_memory_out_of_bounds(
+    mut alloc: Box<dyn WasmMemoryAllocator>,
+    maximum: Option<u32>,
+) {
+    let engine = Engine::new(&Config::default()).expect("Failed to initialize Wasmtime Engine.");
+    let mut store = Store::new(&engine, ClarityWasmContext::new());
+    let memory =
+        Memory::new(&mut store, MemoryType::new(1, maximum)).expect("Failed to create memory");
+    // Fill all but the last few bytes of the (single page) memory.
+    alloc.alloc_for_size(65_536 - 4);
+    let live_bytes = alloc.stats().live_bytes();
+
+    let result = write_value_to_memory(&mut store, memory, &mut alloc, &Value::UInt(1));
+
+    assert!(matches!(result, Err(SerializationError::BufferTooSmall)));
+    assert_eq!(1, memory.size(&store));
+    // The region reserved for the value is released again.
+    assert_eq!(live_bytes, alloc.stats().live_bytes());
+}
+
+#[test]
+fn test_write_value_to_memory_with_guards() {
+    let (mut store, memory) = get_new_store_with_memory();
+    let mut alloc = GuardedAllocator::new(WasmAllocator::new());
+
+    let ptr = write_value_to_memory(&mut store, memory, &mut alloc, &Value::UInt(1))
+        .expect("Failed to write value to memory");
+
+    let data = memory.data(&store);
+    let end = (ptr.offset + ptr.len) as usize;
+    assert_eq!(8, ptr.offset);
+    assert_eq!([0xCA; 8], data[..8]);
+    assert_eq!([0xCA; 8], data[end..end + 8]);
+    assert!(alloc.check_canaries(data).is_ok());
+}
+
+#[test]
+fn test_read_value_from_memory() {
+    let (mut store, memory) = get_new_store_with_memory();
+    let mut alloc = WasmAllocator::new();
+    let value = Value::list_from(vec![Value::UInt(1), Value::UInt(2)]).unwrap();
+    let ptr = write_value_to_memory(&mut store, memory, &mut alloc, &value).unwrap();
+
+    let read = read_value_from_memory(&store, memory, ptr.offset, ptr.len)
+        .expect("Failed to read value from memory");
+
+    assert_eq!(value, read);
+}
+
+#[test_case(-1, 4 ; "negative offset")]
+#[test_case(0, -1 ; "negative length")]
+#[test_case(65_530, 19 ; "past the end of memory")]
+#[test_case(i32::MAX, i32::MAX ; "overflowing range")]
+fn test_read_value_from_memory_out_of_bounds(offset: i32, len: i32) {
+    let (store, memory) = get_new_store_with_memory();
+
+    assert!(matches!(
+        read_value_from_memory(&store, memory, offset, len),
+        Err(SerializationError::IndexOutOfRange)
+    ));
+}
+
+#[test]
+fn test_with_scratch_reuses_buffers() {
+    let mut context = ClarityWasmContext::new();
+
+    let first = context.with_scratch(|_, buffer| {
+        assert!(buffer.is_empty());
+        buffer.extend_from_slice(&[1, 2, 3]);
+        buffer.as_ptr()
+    });
+    let second = context.with_scratch(|_, buffer| {
+        assert!(buffer.is_empty());
+        buffer.as_ptr()
+    });
+    assert_eq!(first, second);
+
+    // Nested calls get their own buffers, which are both pooled afterwards.
+    context.with_scratch(|context, outer| {
+        context.with_scratch(|_, inner| assert_ne!(outer.as_ptr(), inner.as_ptr()))
+    });
+    assert_eq!(2, context.scratch.len());
+}
+
+#[test]
+fn test_scratch_pool_drops_oversized_buffers() {
+    let mut pool = ScratchPool::new();
+    pool.give_back(Vec::with_capacity(1024 * 1024));
+    assert!(pool.is_empty());
+
+    let buffer = pool.take();
+    pool.give_back(buffer);
+    assert_eq!(1, pool.len());
+}
+
+/// Helper function. Creates a new store along with a single page of memory.
+fn get_new_store_with_memory() -> (Store<ClarityWasmContext>, Memory) {
+    let engine = Engine::new(&Config::default()).expect("Failed to initialize Wasmtime Engine.");
+    let mut store = Store::new(&engine, ClarityWasmContext::new());
+    let memory =
+        Memory::new(&mut store, MemoryType::new(1, None)).expect("Failed to create memory");
+    (store, memory)
+}
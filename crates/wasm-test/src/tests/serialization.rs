@@ -1,11 +1,25 @@
-use crate::serialization::{deserialize_clarity_value, serialize_clarity_value};
+use crate::serialization::{
+    describe, deserialize_clarity_seq_to_ptrs, deserialize_clarity_value,
+    deserialize_clarity_value_borrowed, deserialize_clarity_value_typed,
+    deserialize_clarity_value_with, deserialize_clarity_value_with_context,
+    deserialize_clarity_value_with_limits, deserialize_packed_int_list, explain,
+    from_consensus_bytes, get_list_element, get_tuple_field, serialize_clarity_value,
+    serialize_clarity_value_into, serialize_clarity_value_with, serialize_consensus,
+    serialize_packed_int_list, serialized_size, to_consensus_bytes, unpack_int_list,
+    unpack_uint_list, ClaritySeqIterator, ClarityValueRef, Codec, DeserializationLimits,
+    SerializationError, TypeIndicator, LENGTH_ESCAPE,
+};
 use clarity::vm::{
     types::{
-        ASCIIData, BuffData, CharType, ListData, ListTypeData, OptionalData, ResponseData,
-        SequenceData, TypeSignature, UTF8Data,
+        signatures::CallableSubtype, ASCIIData, BuffData, BufferLength, CallableData, CharType,
+        ListData, ListTypeData, OptionalData, PrincipalData, QualifiedContractIdentifier,
+        ResponseData, SequenceData, SequenceSubtype, StandardPrincipalData, StringSubtype,
+        StringUTF8Length, TraitIdentifier, TupleData, TupleTypeSignature, TypeSignature, UTF8Data,
     },
-    Value,
+    ContractName, Value,
 };
+use std::borrow::Cow;
+use test_case::test_case;
 
 #[test]
 fn test_serialize_uint() {
@@ -187,27 +201,42 @@ fn test_serialize_ascii_string() {
     assert_eq!(data, &val);
 }
 
-#[test]
-fn test_serialize_utf8_string() {
-    let str = "hello world!";
-    let mut data = Vec::<Vec<u8>>::new();
-    for chunk in str.as_bytes().chunks(4) {
-        data.push(vec![chunk[0], chunk[1], chunk[2], chunk[3]]);
-    }
-    let value = Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data { data })));
+#[test_case("hello world!" ; "ascii only")]
+#[test_case("héllo wörld" ; "two-byte characters")]
+#[test_case("こんにちは世界" ; "three-byte characters")]
+#[test_case("hi 👋🏽!" ; "four-byte characters and modifiers")]
+#[test_case("" ; "empty")]
+fn test_serialize_utf8_string(str: &str) {
+    let value = Value::string_utf8_from_bytes(str.as_bytes().to_vec()).unwrap();
 
     let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    assert_eq!(str.as_bytes(), &serialized[3..]);
 
-    println!("serialized: {:?}", serialized);
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
 
-    assert!(
-        serialized.len() > 0,
-        "Expected serialized bytes to be longer than 0"
-    );
+    // Each character is one Unicode scalar value, as in Clarity.
+    let chars = match deserialized {
+        Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data { data }))) => data,
+        _ => panic!("Expected a string-utf8"),
+    };
+    assert_eq!(str.chars().count(), chars.len());
+}
 
-    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+#[test_case(vec![b"hell".to_vec(), b"o".to_vec()] ; "several characters in one element")]
+#[test_case(vec![vec![0xC3], vec![0xA9]] ; "character split across elements")]
+#[test_case(vec![vec![]] ; "empty element")]
+fn test_serialize_non_canonical_utf8_string_fails(data: Vec<Vec<u8>>) {
+    let value = Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data { data })));
 
-    assert_eq!(value, deserialized);
+    assert_eq!(
+        Err(SerializationError::FailedToConvertBytesToUtf8),
+        serialize_clarity_value(&value)
+    );
+    assert_eq!(
+        Err(SerializationError::FailedToConvertBytesToUtf8),
+        serialize_clarity_value_into(&value, &mut [0; 64])
+    );
 }
 
 #[test]
@@ -237,3 +266,1163 @@ fn test_serialize_list_of_ints() {
 
     assert_eq!(value, deserialized);
 }
+
+#[test_case(
+    ListTypeData::new_list(TypeSignature::UIntType, 10).unwrap(),
+    vec![Value::UInt(1), Value::UInt(2)]
+    ; "below max length"
+)]
+#[test_case(ListTypeData::new_list(TypeSignature::NoType, 0).unwrap(), vec![] ; "empty")]
+#[test_case(ListTypeData::new_list(TypeSignature::BoolType, 3).unwrap(), vec![] ; "empty with type")]
+#[test_case(
+    ListTypeData::new_list(TypeSignature::new_option(uint_list_type(3)).unwrap(), 2).unwrap(),
+    vec![Value::some(Value::list_from(vec![Value::UInt(1)]).unwrap()).unwrap()]
+    ; "nested list"
+)]
+#[test_case(
+    ListTypeData::new_list(point_tuple_type(), 4).unwrap(),
+    vec![Value::Tuple(TupleData::from_data(vec![("x".into(), Value::Int(1)), ("y".into(), Value::Int(-1))]).unwrap())]
+    ; "tuple"
+)]
+#[test_case(
+    ListTypeData::new_list(TypeSignature::PrincipalType, 2).unwrap(),
+    vec![Value::Principal(PrincipalData::Standard(StandardPrincipalData::transient()))]
+    ; "principal"
+)]
+fn test_serialize_list_preserves_type(type_signature: ListTypeData, data: Vec<Value>) {
+    let value = Value::Sequence(SequenceData::List(ListData {
+        data,
+        type_signature,
+    }));
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    assert_eq!(serialized.len(), serialized_size(&value));
+    let mut buffer = vec![0; serialized.len()];
+    serialize_clarity_value_into(&value, &mut buffer).expect("Failed to serialize value");
+    assert_eq!(serialized, buffer);
+
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
+    let borrowed =
+        deserialize_clarity_value_borrowed(&serialized).expect("Failed to deserialize value");
+    assert_eq!(Ok(value), borrowed.into_value());
+}
+
+#[test_case(
+    ListTypeData::new_list(TypeSignature::UIntType, 1).unwrap(),
+    vec![Value::UInt(1), Value::UInt(2)]
+    ; "longer than max length"
+)]
+#[test_case(
+    ListTypeData::new_list(TypeSignature::IntType, 2).unwrap(),
+    vec![Value::UInt(1)]
+    ; "item not admitted"
+)]
+fn test_deserialize_list_not_matching_type_fails(type_signature: ListTypeData, data: Vec<Value>) {
+    // Lists can only be built this way by constructing `ListData` directly.
+    let value = Value::Sequence(SequenceData::List(ListData {
+        data,
+        type_signature,
+    }));
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    assert_eq!(
+        Err(SerializationError::TypeMismatch),
+        deserialize_clarity_value(&serialized)
+    );
+    assert!(deserialize_clarity_value_borrowed(&serialized)
+        .and_then(ClarityValueRef::into_value)
+        .is_err());
+}
+
+#[test]
+fn test_serialize_standard_principal() {
+    let value = Value::Principal(PrincipalData::Standard(StandardPrincipalData::transient()));
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_serialize_contract_principal() {
+    let value = Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier::new(
+        StandardPrincipalData::transient(),
+        ContractName::from("my-contract"),
+    )));
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_serialize_tuple() {
+    let value = Value::Tuple(
+        TupleData::from_data(vec![
+            ("b".into(), Value::UInt(2)),
+            ("a".into(), Value::Int(-1)),
+            (
+                "name".into(),
+                Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap(),
+            ),
+        ])
+        .unwrap(),
+    );
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_serialize_nested_tuple() {
+    let inner = Value::Tuple(
+        TupleData::from_data(vec![
+            ("x".into(), Value::some(Value::Bool(true)).unwrap()),
+            (
+                "y".into(),
+                Value::list_from(vec![Value::UInt(1), Value::UInt(2)]).unwrap(),
+            ),
+        ])
+        .unwrap(),
+    );
+    let value = Value::Tuple(
+        TupleData::from_data(vec![("inner".into(), inner), ("z".into(), Value::Int(3))]).unwrap(),
+    );
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_serialize_tuple_is_independent_of_field_order() {
+    let a = Value::Tuple(
+        TupleData::from_data(vec![
+            ("a".into(), Value::Int(1)),
+            ("b".into(), Value::Int(2)),
+        ])
+        .unwrap(),
+    );
+    let b = Value::Tuple(
+        TupleData::from_data(vec![
+            ("b".into(), Value::Int(2)),
+            ("a".into(), Value::Int(1)),
+        ])
+        .unwrap(),
+    );
+
+    assert_eq!(
+        serialize_clarity_value(&a).expect("Failed to serialize value"),
+        serialize_clarity_value(&b).expect("Failed to serialize value")
+    );
+}
+
+#[test]
+fn test_serialize_small_value_uses_u16_header() {
+    let serialized = serialize_clarity_value(&Value::UInt(1)).expect("Failed to serialize value");
+
+    assert_eq!(&[1, 16, 0], &serialized[0..3]);
+    assert_eq!(19, serialized.len());
+}
+
+#[test]
+fn test_serialize_large_buffer() {
+    // One byte too large for a u16 length indicator.
+    let value = Value::buff_from(vec![0xab; LENGTH_ESCAPE as usize]).unwrap();
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    assert_eq!(&LENGTH_ESCAPE.to_le_bytes(), &serialized[1..3]);
+
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_serialize_large_list() {
+    let value = Value::list_from(vec![Value::Bool(true); 70_000]).unwrap();
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_deserialize_borrowed_list_of_strings() {
+    let value = Value::list_from(vec![
+        Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap(),
+        Value::string_ascii_from_bytes(b"world".to_vec()).unwrap(),
+    ])
+    .unwrap();
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let borrowed =
+        deserialize_clarity_value_borrowed(&serialized).expect("Failed to deserialize value");
+    match &borrowed {
+        ClarityValueRef::List(items, _) => {
+            assert_eq!(2, items.len());
+            assert!(matches!(
+                &items[0],
+                ClarityValueRef::AsciiString(Cow::Borrowed(b"hello"))
+            ));
+        }
+        _ => panic!("Expected a list"),
+    }
+
+    assert_eq!(
+        value,
+        borrowed.into_value().expect("Failed to convert value")
+    );
+}
+
+#[test]
+fn test_deserialize_borrowed_matches_owned() {
+    let value = Value::Tuple(
+        TupleData::from_data(vec![
+            ("buff".into(), Value::buff_from(vec![1, 2, 3]).unwrap()),
+            (
+                "utf8".into(),
+                Value::string_utf8_from_bytes("héllo".as_bytes().to_vec()).unwrap(),
+            ),
+            (
+                "resp".into(),
+                Value::okay(Value::some(Value::UInt(1)).unwrap()).unwrap(),
+            ),
+            (
+                "principal".into(),
+                Value::Principal(PrincipalData::Standard(StandardPrincipalData::transient())),
+            ),
+        ])
+        .unwrap(),
+    );
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let borrowed =
+        deserialize_clarity_value_borrowed(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(
+        deserialize_clarity_value(&serialized).expect("Failed to deserialize value"),
+        borrowed.into_value().expect("Failed to convert value")
+    );
+}
+
+#[test]
+fn test_seq_iterator_yields_elements() {
+    let value = Value::list_from(vec![Value::Int(1), Value::Int(-2), Value::Int(3)]).unwrap();
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let elements = ClaritySeqIterator::new(&serialized)
+        .expect("Failed to create iterator")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to iterate over list");
+
+    assert_eq!(3, elements.len());
+    for (element, expected) in elements.iter().zip([1, -2, 3]) {
+        assert_eq!(TypeIndicator::Int, element.type_indicator);
+        assert_eq!(
+            element.bytes,
+            &serialized
+                [element.ptr.offset as usize..(element.ptr.offset + element.ptr.len) as usize]
+        );
+        assert_eq!(Value::Int(expected), element.value().unwrap());
+    }
+}
+
+#[test]
+fn test_seq_iterator_stops_on_truncated_list() {
+    let value = Value::list_from(vec![Value::UInt(1), Value::UInt(2)]).unwrap();
+    let mut serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    // Claim a third element which isn't there.
+    serialized[3] = 3;
+
+    let results = ClaritySeqIterator::new(&serialized)
+        .expect("Failed to create iterator")
+        .collect::<Vec<_>>();
+
+    assert_eq!(3, results.len());
+    assert!(results[0].is_ok() && results[1].is_ok());
+    assert!(results[2].is_err());
+}
+
+/// Helper function. Gets the bytes at the given location of the buffer.
+fn slice_at(buffer: &[u8], ptr: crate::Ptr) -> &[u8] {
+    &buffer[ptr.offset as usize..(ptr.offset + ptr.len) as usize]
+}
+
+#[test]
+fn test_get_list_element() {
+    let items = vec![
+        Value::Int(1),
+        Value::string_ascii_from_bytes(b"two".to_vec()).unwrap(),
+        Value::some(Value::UInt(3)).unwrap(),
+    ];
+    let value = Value::list_from(items.clone()).unwrap();
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    for (index, item) in items.iter().enumerate() {
+        let ptr = get_list_element(&serialized, index)
+            .expect("Failed to get list element")
+            .expect("Expected an element");
+        assert_eq!(
+            Ok(item.clone()),
+            deserialize_clarity_value(slice_at(&serialized, ptr))
+        );
+    }
+
+    assert!(matches!(get_list_element(&serialized, 3), Ok(None)));
+}
+
+#[test]
+fn test_get_tuple_field() {
+    let inner = Value::Tuple(
+        TupleData::from_data(vec![("z".into(), Value::buff_from(vec![1, 2, 3]).unwrap())]).unwrap(),
+    );
+    let value = Value::Tuple(
+        TupleData::from_data(vec![
+            ("a".into(), Value::Bool(true)),
+            ("nested".into(), inner.clone()),
+        ])
+        .unwrap(),
+    );
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let a = get_tuple_field(&serialized, "a")
+        .expect("Failed to get tuple field")
+        .expect("Expected a field");
+    assert_eq!(
+        Ok(Value::Bool(true)),
+        deserialize_clarity_value(slice_at(&serialized, a))
+    );
+
+    // Locations are relative to the buffer they were read from, so they compose.
+    let nested = get_tuple_field(&serialized, "nested")
+        .expect("Failed to get tuple field")
+        .expect("Expected a field");
+    let nested_bytes = slice_at(&serialized, nested);
+    assert_eq!(Ok(inner), deserialize_clarity_value(nested_bytes));
+    let z = get_tuple_field(nested_bytes, "z")
+        .expect("Failed to get tuple field")
+        .expect("Expected a field");
+    assert_eq!(
+        Ok(Value::buff_from(vec![1, 2, 3]).unwrap()),
+        deserialize_clarity_value(slice_at(nested_bytes, z))
+    );
+
+    assert!(matches!(get_tuple_field(&serialized, "b"), Ok(None)));
+}
+
+#[test]
+fn test_partial_access_rejects_other_types() {
+    let list = serialize_clarity_value(&Value::list_from(vec![Value::Int(1)]).unwrap()).unwrap();
+    let int = serialize_clarity_value(&Value::Int(1)).unwrap();
+
+    assert!(matches!(
+        get_tuple_field(&list, "a"),
+        Err(SerializationError::TypeNotAllowed {
+            received: TypeIndicator::List
+        })
+    ));
+    assert!(matches!(
+        get_list_element(&int, 0),
+        Err(SerializationError::TypeNotAllowed {
+            received: TypeIndicator::Int
+        })
+    ));
+}
+
+#[test]
+fn test_seq_iterator_rejects_non_lists() {
+    let serialized = serialize_clarity_value(&Value::UInt(1)).expect("Failed to serialize value");
+
+    assert!(ClaritySeqIterator::new(&serialized).is_err());
+}
+
+#[test_case(Value::Int(1) => "0000000000000000000000000000000001" ; "int")]
+#[test_case(Value::UInt(1) => "0100000000000000000000000000000001" ; "uint")]
+#[test_case(Value::Bool(true) => "03" ; "true")]
+#[test_case(Value::Bool(false) => "04" ; "false")]
+#[test_case(Value::none() => "09" ; "none")]
+#[test_case(Value::some(Value::Bool(true)).unwrap() => "0a03" ; "some")]
+#[test_case(Value::okay(Value::Bool(true)).unwrap() => "0703" ; "ok")]
+#[test_case(Value::error(Value::Bool(false)).unwrap() => "0804" ; "err")]
+#[test_case(Value::buff_from(vec![0xde, 0xad]).unwrap() => "0200000002dead" ; "buffer")]
+#[test_case(Value::string_ascii_from_bytes(b"hi".to_vec()).unwrap() => "0d000000026869" ; "string-ascii")]
+#[test_case(Value::string_utf8_from_bytes("é".as_bytes().to_vec()).unwrap() => "0e00000002c3a9" ; "string-utf8")]
+#[test_case(Value::list_from(vec![Value::Bool(true), Value::Bool(false)]).unwrap() => "0b000000020304" ; "list")]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![("b".into(), Value::Bool(true)), ("a".into(), Value::none())]).unwrap())
+    => "0c00000002016109016203" ; "tuple"
+)]
+fn test_serialize_consensus(value: Value) -> String {
+    let serialized =
+        serialize_clarity_value_with(&value, Codec::Consensus).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value_with(&serialized, Codec::Consensus)
+        .expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
+
+    serialized.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn test_serialize_consensus_contract_principal() {
+    let value = Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier::new(
+        StandardPrincipalData(26, [1; 20]),
+        ContractName::from("foo"),
+    )));
+
+    let serialized =
+        serialize_clarity_value_with(&value, Codec::Consensus).expect("Failed to serialize value");
+    assert_eq!(0x06, serialized[0]);
+    assert_eq!(26, serialized[1]);
+    assert_eq!(&[3, b'f', b'o', b'o'], &serialized[22..]);
+
+    let deserialized = deserialize_clarity_value_with(&serialized, Codec::Consensus)
+        .expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_deserialize_consensus_rejects_truncated_and_trailing_bytes() {
+    let serialized = serialize_clarity_value_with(&Value::UInt(1), Codec::Consensus)
+        .expect("Failed to serialize value");
+
+    assert!(deserialize_clarity_value_with(&serialized[..10], Codec::Consensus).is_err());
+
+    let mut trailing = serialized.clone();
+    trailing.push(0);
+    assert!(deserialize_clarity_value_with(&trailing, Codec::Consensus).is_err());
+}
+
+#[test_case(Value::Int(-2) ; "int")]
+#[test_case(Value::UInt(u128::MAX) ; "uint")]
+#[test_case(Value::Bool(false) ; "bool")]
+#[test_case(Value::some(Value::none()).unwrap() ; "nested optional")]
+#[test_case(Value::error(Value::UInt(1)).unwrap() ; "err")]
+#[test_case(Value::buff_from(vec![7; 70_000]).unwrap() ; "buffer with escaped length")]
+#[test_case(Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap() ; "string-ascii")]
+#[test_case(Value::string_utf8_from_bytes("héllo".as_bytes().to_vec()).unwrap() ; "string-utf8")]
+#[test_case(Value::list_from(vec![Value::Int(1), Value::Int(-1)]).unwrap() ; "list")]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![("b".into(), Value::Bool(true)), ("a".into(), Value::none())]).unwrap())
+    ; "tuple"
+)]
+#[test_case(Value::Principal(PrincipalData::Standard(StandardPrincipalData(26, [1; 20]))) ; "standard principal")]
+#[test_case(Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier::transient())) ; "contract principal")]
+fn test_transcode_consensus(value: Value) {
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let consensus = serialize_consensus(&value).expect("Failed to serialize value");
+
+    assert_eq!(
+        consensus,
+        to_consensus_bytes(&serialized).expect("Failed to transcode value")
+    );
+    assert_eq!(
+        serialized,
+        from_consensus_bytes(&consensus).expect("Failed to transcode value")
+    );
+}
+
+#[test]
+fn test_to_consensus_bytes_callable_contract() {
+    let value = Value::CallableContract(CallableData {
+        contract_identifier: QualifiedContractIdentifier::transient(),
+        trait_identifier: None,
+    });
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    assert_eq!(
+        serialize_consensus(&value).unwrap(),
+        to_consensus_bytes(&serialized).expect("Failed to transcode value")
+    );
+}
+
+#[test]
+fn test_to_consensus_bytes_packed_int_list() {
+    let value = Value::list_from(vec![Value::UInt(1), Value::UInt(2)]).unwrap();
+    let packed = serialize_packed_int_list(&value).expect("Failed to serialize value");
+
+    assert_eq!(
+        serialize_consensus(&value).unwrap(),
+        to_consensus_bytes(&packed).expect("Failed to transcode value")
+    );
+}
+
+#[test]
+fn test_to_consensus_bytes_sorts_tuple_fields() {
+    let unsorted = with_header(
+        TypeIndicator::Tuple as u8,
+        &[
+            [2, 0, 1, b'b'].as_slice(),
+            with_header(3, &[1]).as_slice(),
+            &[1, b'a'],
+            with_header(3, &[0]).as_slice(),
+        ]
+        .concat(),
+    );
+    let value = Value::Tuple(
+        TupleData::from_data(vec![
+            ("a".into(), Value::Bool(false)),
+            ("b".into(), Value::Bool(true)),
+        ])
+        .unwrap(),
+    );
+
+    assert_eq!(
+        serialize_consensus(&value).unwrap(),
+        to_consensus_bytes(&unsorted).expect("Failed to transcode value")
+    );
+}
+
+#[test_case(vec![] ; "empty buffer")]
+#[test_case(vec![0x00, 1, 2] ; "truncated int")]
+#[test_case(vec![0x03, 0x03] ; "trailing bytes")]
+#[test_case(vec![0x0a] ; "some without value")]
+#[test_case(vec![0x0b, 0, 0, 0, 2, 0x03] ; "list with missing item")]
+#[test_case(vec![0x0d, 0, 0, 0, 1, 0xFF] ; "non-ascii string")]
+#[test_case(vec![0x42] ; "unknown prefix")]
+fn test_from_consensus_bytes_malformed_input_fails(buffer: Vec<u8>) {
+    assert!(from_consensus_bytes(&buffer).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialized_value_json_roundtrip() {
+    use crate::serialization::SerializedValue;
+
+    let value = Value::some(Value::UInt(1)).unwrap();
+    let serialized = SerializedValue::from_value(&value).expect("Failed to serialize value");
+
+    let json = serde_json::to_string(&serialized).expect("Failed to write JSON");
+    assert_eq!(
+        format!("\"{}\"", clarity::util::hash::to_hex(serialized.as_bytes())),
+        json
+    );
+
+    let deserialized: SerializedValue = serde_json::from_str(&json).expect("Failed to read JSON");
+    assert_eq!(serialized, deserialized);
+    assert_eq!(
+        Ok(TypeIndicator::Optional),
+        deserialized.type_indicator().map_err(|_| ())
+    );
+    assert_eq!(
+        value,
+        deserialized
+            .to_value()
+            .expect("Failed to deserialize value")
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_ptr_and_type_indicator_serde() {
+    let json = serde_json::to_string(&(crate::Ptr::new(8, 19), TypeIndicator::UInt))
+        .expect("Failed to write JSON");
+    assert_eq!(r#"[{"offset":8,"len":19},"UInt"]"#, json);
+
+    let (ptr, ty): (crate::Ptr, TypeIndicator) =
+        serde_json::from_str(&json).expect("Failed to read JSON");
+    assert_eq!((8, 19), (ptr.offset, ptr.len));
+    assert_eq!(TypeIndicator::UInt, ty);
+}
+
+#[test_case(Value::UInt(1) ; "uint")]
+#[test_case(Value::okay(Value::some(Value::Int(-1)).unwrap()).unwrap() ; "nested")]
+#[test_case(Value::buff_from(vec![7; LENGTH_ESCAPE as usize + 1]).unwrap() ; "large buffer")]
+#[test_case(
+    Value::list_from(vec![Value::buff_from(vec![1; 40_000]).unwrap(); 2]).unwrap()
+    ; "list with a large body"
+)]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![
+        ("a".into(), Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier::transient()))),
+        ("b".into(), Value::string_utf8_from_bytes("€".as_bytes().to_vec()).unwrap()),
+    ]).unwrap())
+    ; "tuple"
+)]
+fn test_serialize_into_matches_serialize(value: Value) {
+    let expected = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let mut buffer = vec![0u8; expected.len() + 8];
+    let written =
+        serialize_clarity_value_into(&value, &mut buffer).expect("Failed to serialize value");
+
+    assert_eq!(expected.len(), written);
+    assert_eq!(expected, buffer[..written]);
+}
+
+#[test]
+fn test_serialize_into_buffer_too_small() {
+    let value = Value::buff_from(vec![1, 2, 3]).unwrap();
+    let mut buffer = [0u8; 5];
+
+    assert!(serialize_clarity_value_into(&value, &mut buffer).is_err());
+}
+
+#[test_case(Value::Bool(false) ; "bool")]
+#[test_case(Value::none() ; "none")]
+#[test_case(Value::error(Value::UInt(3)).unwrap() ; "response")]
+#[test_case(Value::list_from(vec![]).unwrap() ; "empty list")]
+#[test_case(Value::string_utf8_from_bytes("a€b".as_bytes().to_vec()).unwrap() ; "utf8 string")]
+#[test_case(Value::buff_from(vec![7; LENGTH_ESCAPE as usize - 1]).unwrap() ; "largest short buffer")]
+#[test_case(Value::buff_from(vec![7; LENGTH_ESCAPE as usize]).unwrap() ; "smallest escaped buffer")]
+#[test_case(
+    Value::list_from(vec![Value::buff_from(vec![1; 40_000]).unwrap(); 2]).unwrap()
+    ; "list with a large body"
+)]
+#[test_case(
+    Value::Principal(PrincipalData::Standard(StandardPrincipalData::transient()))
+    ; "standard principal"
+)]
+#[test_case(
+    Value::CallableContract(CallableData {
+        contract_identifier: QualifiedContractIdentifier::transient(),
+        trait_identifier: Some(TraitIdentifier::new(
+            StandardPrincipalData::transient(),
+            "my-contract".into(),
+            "my-trait".into(),
+        )),
+    })
+    ; "callable contract"
+)]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![
+        ("a".into(), Value::Int(-1)),
+        ("bb".into(), Value::some(Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap()).unwrap()),
+    ]).unwrap())
+    ; "tuple"
+)]
+fn test_serialized_size_matches_serialize(value: Value) {
+    let expected = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    assert_eq!(expected.len(), serialized_size(&value));
+}
+
+fn uint_list_type(max_len: u32) -> TypeSignature {
+    TypeSignature::SequenceType(SequenceSubtype::ListType(
+        ListTypeData::new_list(TypeSignature::UIntType, max_len).unwrap(),
+    ))
+}
+
+fn point_tuple_type() -> TypeSignature {
+    TypeSignature::TupleType(
+        TupleTypeSignature::try_from(vec![
+            ("x".into(), TypeSignature::IntType),
+            ("y".into(), TypeSignature::IntType),
+        ])
+        .unwrap(),
+    )
+}
+
+#[test_case(Value::UInt(1) => TypeSignature::UIntType ; "uint")]
+#[test_case(Value::Bool(false) => TypeSignature::BoolType ; "bool")]
+#[test_case(
+    Value::none() => TypeSignature::OptionalType(Box::new(TypeSignature::NoType))
+    ; "none"
+)]
+#[test_case(
+    Value::okay(Value::Int(1)).unwrap()
+    => TypeSignature::ResponseType(Box::new((TypeSignature::IntType, TypeSignature::NoType)))
+    ; "ok"
+)]
+#[test_case(
+    Value::string_ascii_from_bytes(b"hello".to_vec()).unwrap()
+    => TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(
+        BufferLength::try_from(5u32).unwrap()
+    )))
+    ; "ascii string"
+)]
+#[test_case(
+    Value::string_utf8_from_bytes("héllo 👋".as_bytes().to_vec()).unwrap()
+    => TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(
+        StringUTF8Length::try_from(7u32).unwrap()
+    )))
+    ; "utf8 string counts chars"
+)]
+#[test_case(
+    Value::Sequence(SequenceData::List(ListData {
+        data: vec![Value::UInt(1)],
+        type_signature: ListTypeData::new_list(TypeSignature::UIntType, 10).unwrap(),
+    }))
+    => uint_list_type(10)
+    ; "list with declared type"
+)]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![("x".into(), Value::Int(1)), ("y".into(), Value::Int(-1))]).unwrap())
+    => point_tuple_type()
+    ; "tuple"
+)]
+#[test_case(
+    Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier::transient()))
+    => TypeSignature::PrincipalType
+    ; "contract principal"
+)]
+#[test_case(
+    Value::CallableContract(CallableData {
+        contract_identifier: QualifiedContractIdentifier::transient(),
+        trait_identifier: None,
+    })
+    => TypeSignature::CallableType(CallableSubtype::Principal(QualifiedContractIdentifier::transient()))
+    ; "callable contract"
+)]
+fn test_describe(value: Value) -> TypeSignature {
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let type_signature = describe(&serialized).expect("Failed to describe value");
+    assert!(type_signature.admits(&value));
+    type_signature
+}
+
+#[test]
+fn test_describe_rejects_list_longer_than_its_type() {
+    let value = Value::Sequence(SequenceData::List(ListData {
+        data: vec![Value::UInt(1), Value::UInt(2)],
+        type_signature: ListTypeData::new_list(TypeSignature::UIntType, 1).unwrap(),
+    }));
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    assert_eq!(Err(SerializationError::TypeMismatch), describe(&serialized));
+}
+
+#[test_case(Value::UInt(1), TypeSignature::UIntType ; "uint")]
+#[test_case(
+    Value::list_from(vec![Value::UInt(1), Value::UInt(2)]).unwrap(), uint_list_type(2)
+    ; "list at max length"
+)]
+#[test_case(
+    Value::some(Value::list_from(vec![Value::UInt(1)]).unwrap()).unwrap(),
+    TypeSignature::new_option(uint_list_type(2)).unwrap()
+    ; "optional list"
+)]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![("x".into(), Value::Int(1)), ("y".into(), Value::Int(-1))]).unwrap()),
+    point_tuple_type()
+    ; "tuple"
+)]
+fn test_deserialize_typed_accepts_matching_value(value: Value, type_signature: TypeSignature) {
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let deserialized = deserialize_clarity_value_typed(&serialized, &type_signature)
+        .expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+#[test_case(Value::Int(1), TypeSignature::UIntType ; "wrong type")]
+#[test_case(
+    Value::list_from(vec![Value::UInt(1), Value::UInt(2), Value::UInt(3)]).unwrap(), uint_list_type(2)
+    ; "list too long"
+)]
+#[test_case(
+    Value::list_from(vec![Value::Int(1)]).unwrap(), uint_list_type(2)
+    ; "wrong element type"
+)]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![("x".into(), Value::Int(1))]).unwrap()),
+    point_tuple_type()
+    ; "missing tuple field"
+)]
+#[test_case(
+    Value::Tuple(TupleData::from_data(vec![("x".into(), Value::Int(1)), ("y".into(), Value::UInt(1))]).unwrap()),
+    point_tuple_type()
+    ; "wrong tuple field type"
+)]
+fn test_deserialize_typed_rejects_mismatched_value(value: Value, type_signature: TypeSignature) {
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    assert!(matches!(
+        deserialize_clarity_value_typed(&serialized, &type_signature),
+        Err(SerializationError::TypeMismatch)
+    ));
+}
+
+/// Prefixes the provided body with a (short) header for the given type indicator.
+fn with_header(type_indicator: u8, body: &[u8]) -> Vec<u8> {
+    let mut buffer = vec![type_indicator];
+    buffer.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    buffer.extend_from_slice(body);
+    buffer
+}
+
+#[test_case(vec![] ; "empty buffer")]
+#[test_case(vec![1] ; "missing length indicator")]
+#[test_case(vec![1, 0] ; "truncated length indicator")]
+#[test_case(vec![8, 0xFF, 0xFF, 1] ; "truncated escaped length indicator")]
+#[test_case(vec![0, 0, 0] ; "zero type indicator")]
+#[test_case(vec![99, 0, 0] ; "unknown type indicator")]
+#[test_case(vec![1, 16, 0] ; "uint without body")]
+#[test_case(with_header(2, &[1; 15]) ; "int too short")]
+#[test_case(with_header(3, &[]) ; "bool without body")]
+#[test_case(with_header(3, &[2]) ; "bool out of range")]
+#[test_case(with_header(3, &[1, 1]) ; "bool too long")]
+#[test_case(with_header(4, &[]) ; "optional without indicator")]
+#[test_case(with_header(4, &[1]) ; "some without value")]
+#[test_case(with_header(5, &[]) ; "response without indicator")]
+#[test_case(with_header(5, &[1, 3, 1]) ; "response with truncated value")]
+#[test_case(with_header(7, &[0xC3]) ; "invalid utf8")]
+#[test_case(with_header(10, &[0; 5]) ; "truncated standard principal")]
+#[test_case(with_header(11, &[0; 21]) ; "contract principal without name")]
+#[test_case(with_header(11, &[[0; 21].as_slice(), &[10, 0, b'a']].concat()) ; "contract principal with truncated name")]
+#[test_case(with_header(12, &[0; 3]) ; "truncated callable contract")]
+#[test_case(with_header(12, &[[0; 21].as_slice(), &[1, 0, b'a']].concat()) ; "callable contract without trait indicator")]
+#[test_case(with_header(12, &[[0; 21].as_slice(), &[1, 0, b'a', 1, 0]].concat()) ; "callable contract with truncated trait")]
+#[test_case(with_header(9, &[]) ; "list without length")]
+#[test_case(with_header(9, &[0xFE, 0xFF]) ; "list with huge length and no items")]
+#[test_case(with_header(9, &[[2, 0].as_slice(), &with_header(3, &[1])].concat()) ; "list with missing item")]
+#[test_case(with_header(9, &[1, 0, 1, 16, 0, 1]) ; "list item longer than list")]
+#[test_case(with_header(13, &[1, 0, 5, b'a']) ; "tuple with truncated field name")]
+#[test_case(with_header(13, &[1, 0, 1, b'a']) ; "tuple field without value")]
+#[test_case(with_header(13, &[2, 0, 1, b'a', 3, 1, 0, 1]) ; "tuple with missing field")]
+fn test_deserialize_malformed_input_fails(buffer: Vec<u8>) {
+    assert!(deserialize_clarity_value(&buffer).is_err());
+    assert!(deserialize_clarity_value_borrowed(&buffer).is_err());
+    assert!(describe(&buffer).is_err());
+    assert!(explain(&buffer).contains("error:"));
+    // These only need to fail gracefully, as they do not accept all types.
+    let _ = deserialize_clarity_seq_to_ptrs(&buffer);
+    let _ = ClaritySeqIterator::new(&buffer).map(|iter| iter.collect::<Vec<_>>());
+}
+
+#[test]
+fn test_deserialize_truncated_values_fails() {
+    let value = Value::list_from(vec![
+        Value::Tuple(
+            TupleData::from_data(vec![
+                ("a".into(), Value::some(Value::UInt(1)).unwrap()),
+                (
+                    "b".into(),
+                    Value::Principal(PrincipalData::Contract(
+                        QualifiedContractIdentifier::transient(),
+                    )),
+                ),
+            ])
+            .unwrap(),
+        );
+        2
+    ])
+    .unwrap();
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    // Truncate the body at every position, fixing up the outer header so that the
+    // truncation is only detected when the inner values are read.
+    for len in 3..serialized.len() {
+        let buffer = with_header(serialized[0], &serialized[3..len]);
+        assert!(
+            deserialize_clarity_value(&buffer).is_err(),
+            "Expected truncation at {len} to fail"
+        );
+        assert!(deserialize_clarity_value_borrowed(&buffer).is_err());
+        assert!(explain(&buffer).contains("error:"));
+    }
+}
+
+#[test_case(None ; "without trait")]
+#[test_case(Some("my-trait") ; "with trait")]
+fn test_serialize_deserialize_callable_contract(trait_name: Option<&str>) {
+    let contract_identifier = QualifiedContractIdentifier::transient();
+    let value = Value::CallableContract(CallableData {
+        trait_identifier: trait_name.map(|name| {
+            TraitIdentifier::new(
+                StandardPrincipalData::transient(),
+                contract_identifier.name.clone(),
+                name.into(),
+            )
+        }),
+        contract_identifier,
+    });
+
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+
+    assert_eq!(value, deserialized);
+}
+
+fn nested_optional(depth: usize) -> Value {
+    (1..depth).fold(Value::UInt(1), |value, _| Value::some(value).unwrap())
+}
+
+#[test]
+fn test_deserialize_default_limits_admit_clarity_values() {
+    let value = nested_optional(DeserializationLimits::default().max_depth);
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let deserialized = deserialize_clarity_value(&serialized).expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_deserialize_max_depth_exceeded() {
+    let limits = DeserializationLimits {
+        max_depth: 3,
+        ..Default::default()
+    };
+
+    let serialized = serialize_clarity_value(&nested_optional(3)).unwrap();
+    assert!(deserialize_clarity_value_with_limits(&serialized, &limits).is_ok());
+
+    let serialized = serialize_clarity_value(&nested_optional(4)).unwrap();
+    assert!(matches!(
+        deserialize_clarity_value_with_limits(&serialized, &limits),
+        Err(SerializationError::MaxDepthExceeded)
+    ));
+}
+
+#[test]
+fn test_deserialize_max_depth_exceeded_by_hostile_buffer() {
+    // Deeply nested `some`s which Clarity would never produce, which must not
+    // exhaust the stack.
+    let mut buffer = with_header(3, &[1]);
+    for _i in 0..1_000 {
+        buffer = with_header(4, &[[1].as_slice(), &buffer].concat());
+    }
+
+    assert!(matches!(
+        deserialize_clarity_value(&buffer),
+        Err(SerializationError::MaxDepthExceeded)
+    ));
+}
+
+#[test_case(Value::list_from(vec![Value::Bool(true); 5]).unwrap() ; "list")]
+#[test_case(
+    Value::Tuple(TupleData::from_data(
+        ["a", "b", "c", "d", "e"].iter().map(|name| ((*name).into(), Value::Bool(true))).collect()
+    ).unwrap())
+    ; "tuple"
+)]
+fn test_deserialize_max_elements_exceeded(value: Value) {
+    let serialized = serialize_clarity_value(&value).unwrap();
+
+    let limits = DeserializationLimits {
+        max_elements: 5,
+        ..Default::default()
+    };
+    assert!(deserialize_clarity_value_with_limits(&serialized, &limits).is_ok());
+
+    let limits = DeserializationLimits {
+        max_elements: 4,
+        ..Default::default()
+    };
+    assert!(matches!(
+        deserialize_clarity_value_with_limits(&serialized, &limits),
+        Err(SerializationError::MaxElementsExceeded)
+    ));
+}
+
+#[test]
+fn test_deserialize_max_bytes_exceeded() {
+    let serialized = serialize_clarity_value(&Value::buff_from(vec![1; 100]).unwrap()).unwrap();
+
+    let limits = DeserializationLimits {
+        max_bytes: serialized.len(),
+        ..Default::default()
+    };
+    assert!(deserialize_clarity_value_with_limits(&serialized, &limits).is_ok());
+
+    let limits = DeserializationLimits {
+        max_bytes: serialized.len() - 1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        deserialize_clarity_value_with_limits(&serialized, &limits),
+        Err(SerializationError::MaxBytesExceeded)
+    ));
+}
+
+#[cfg(feature = "testing")]
+proptest::proptest! {
+    #[test]
+    fn test_roundtrip_arbitrary_values(value in crate::serialization::testing::arb_value()) {
+        crate::serialization::testing::assert_roundtrip(&value);
+    }
+}
+
+#[test_case(Value::list_from((1..=5).map(Value::Int).collect()).unwrap() ; "ints")]
+#[test_case(Value::list_from(vec![Value::Int(i128::MIN), Value::Int(i128::MAX)]).unwrap() ; "extreme ints")]
+#[test_case(Value::list_from((1..=5).map(Value::UInt).collect()).unwrap() ; "uints")]
+#[test_case(Value::list_from((0..5_000).map(Value::UInt).collect()).unwrap() ; "escaped length")]
+fn test_serialize_deserialize_packed_int_list(value: Value) {
+    let packed = serialize_packed_int_list(&value).expect("Failed to serialize value");
+    let regular = serialize_clarity_value(&value).expect("Failed to serialize value");
+    assert!(packed.len() < regular.len());
+
+    let deserialized = deserialize_packed_int_list(&packed).expect("Failed to deserialize value");
+    assert_eq!(value, deserialized);
+}
+
+#[test]
+fn test_unpack_packed_int_lists() {
+    let ints = Value::list_from(vec![Value::Int(-1), Value::Int(2)]).unwrap();
+    let packed = serialize_packed_int_list(&ints).unwrap();
+    let mut out = Vec::new();
+    unpack_int_list(&packed, &mut out).expect("Failed to unpack ints");
+    assert_eq!(vec![-1, 2], out);
+    assert!(unpack_uint_list(&packed, &mut Vec::new()).is_err());
+
+    let uints = Value::list_from(vec![Value::UInt(1), Value::UInt(u128::MAX)]).unwrap();
+    let packed = serialize_packed_int_list(&uints).unwrap();
+    let mut out = Vec::new();
+    unpack_uint_list(&packed, &mut out).expect("Failed to unpack uints");
+    assert_eq!(vec![1, u128::MAX], out);
+    assert!(unpack_int_list(&packed, &mut Vec::new()).is_err());
+}
+
+#[test_case(Value::Int(1) ; "not a list")]
+#[test_case(Value::list_from(vec![Value::Bool(true)]).unwrap() ; "list of bools")]
+#[test_case(Value::list_from(vec![]).unwrap() ; "untyped empty list")]
+fn test_serialize_packed_int_list_rejects_other_values(value: Value) {
+    assert!(matches!(
+        serialize_packed_int_list(&value),
+        Err(SerializationError::TypeNotAllowed { .. })
+    ));
+}
+
+#[test_case(vec![] ; "empty buffer")]
+#[test_case(with_header(2, &[0; 16]) ; "regular int")]
+#[test_case(with_header(0x83, &[0; 16]) ; "packed bools")]
+#[test_case(with_header(0x82, &[0; 15]) ; "partial element")]
+#[test_case(vec![0x82, 32, 0, 1, 2] ; "truncated body")]
+fn test_deserialize_malformed_packed_int_list_fails(buffer: Vec<u8>) {
+    assert!(deserialize_packed_int_list(&buffer).is_err());
+}
+
+#[test]
+fn test_serialization_error_display() {
+    assert_eq!(
+        "invalid type indicator 99",
+        SerializationError::InvalidTypeIndicator(99).to_string()
+    );
+    assert_eq!(
+        "invalid buffer length: expected 16 bytes, received 3",
+        SerializationError::InvalidBufferLength {
+            expected: 16,
+            received: 3
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_deserialize_with_context_reports_nested_location() {
+    // A list containing a tuple whose `b` field is `(some <invalid>)`.
+    let invalid = with_header(99, &[]);
+    let some = with_header(4, &[[1].as_slice(), &invalid].concat());
+    let tuple = with_header(
+        13,
+        &[
+            [2, 0].as_slice(),
+            &[1, b'a'],
+            &with_header(3, &[1]),
+            &[1, b'b'],
+            &some,
+        ]
+        .concat(),
+    );
+    let list = with_header(
+        9,
+        &[
+            [2, 0].as_slice(),
+            // The list's type, `(list 2 (tuple (a bool)))`.
+            &[10, 0, 2, 0, 0, 0, 13, 1, 0, 1, b'a', 3],
+            &with_header(13, &[1, 0, 1, b'a', 3, 1, 0, 1]),
+            &tuple,
+        ]
+        .concat(),
+    );
+
+    let err = deserialize_clarity_value_with_context(&list).unwrap_err();
+
+    assert_eq!(SerializationError::InvalidTypeIndicator(99), err.error);
+    assert_eq!("[1].b.some", err.path);
+    assert_eq!(list.len() - invalid.len(), err.offset);
+    assert_eq!(&invalid[..], &list[err.offset..]);
+    assert_eq!(
+        "invalid type indicator 99 (at byte 45, value[1].b.some)",
+        err.to_string()
+    );
+    assert!(matches!(
+        deserialize_clarity_value(&list),
+        Err(SerializationError::InvalidTypeIndicator(99))
+    ));
+}
+
+#[test]
+fn test_deserialize_with_context_outermost_value() {
+    let err = deserialize_clarity_value_with_context(&[]).unwrap_err();
+
+    assert_eq!(
+        SerializationError::AttemtToDeserializeZeroLengthBuffer,
+        err.error
+    );
+    assert_eq!(0, err.offset);
+    assert_eq!("", err.path);
+}
+
+/// Helper function. Collapses the whitespace in each line of an `explain()` dump,
+/// so that tests don't depend on its column widths.
+fn explain_lines(buffer: &[u8]) -> Vec<String> {
+    explain(buffer)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+#[test]
+fn test_explain_annotates_nested_values() {
+    let value = Value::Tuple(
+        TupleData::from_data(vec![("a".into(), Value::some(Value::Bool(true)).unwrap())]).unwrap(),
+    );
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    assert_eq!(
+        vec![
+            "0000 0d tuple",
+            "0001 0c 00 length: 12",
+            "0003 01 00 count: 1",
+            "0005 01 61 field: a",
+            "0007 04 optional",
+            "0008 05 00 length: 5",
+            "000a 01 some",
+            "000b 03 bool",
+            "000c 01 00 length: 1",
+            "000e 01 value: true",
+        ],
+        explain_lines(&serialized)
+    );
+}
+
+#[test]
+fn test_explain_reports_trailing_bytes() {
+    let mut serialized =
+        serialize_clarity_value(&Value::Int(-1)).expect("Failed to serialize value");
+    serialized.extend_from_slice(&[0xAB, 0xCD]);
+
+    let lines = explain_lines(&serialized);
+
+    assert_eq!(
+        "0003 ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff value: -1",
+        lines[2]
+    );
+    assert_eq!(
+        "0013 ab cd error: length indicator does not match the buffer length",
+        lines[3]
+    );
+}
+
+#[test]
+fn test_explain_truncates_long_fields() {
+    let value = Value::string_ascii_from_bytes(b"hello, explained world".to_vec()).unwrap();
+    let serialized = serialize_clarity_value(&value).expect("Failed to serialize value");
+
+    let lines = explain_lines(&serialized);
+
+    assert_eq!(
+        "0003 68 65 6c 6c 6f 2c 20 65 78 70 6c 61 69 6e 65 64 .. value: \"hello, explained world\"",
+        lines[2]
+    );
+}
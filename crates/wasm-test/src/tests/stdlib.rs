@@ -0,0 +1,194 @@
+use crate::stdlib::{link::link_bytes_into, StdlibExports, StdlibLinkError};
+use wasmtime::{Engine, Linker, Module, Store};
+
+#[test]
+fn test_stdlib_exports_are_found_by_either_name() {
+    let exports = StdlibExports::new();
+
+    for export in exports.iter() {
+        assert_eq!(export.name.replace('-', "_"), export.alias);
+        assert_eq!(Some(*export), exports.get(export.name));
+        assert_eq!(Some(*export), exports.get(export.alias));
+    }
+    assert_eq!(None, exports.get("add-unt128"));
+}
+
+#[test]
+fn test_link_into_module() {
+    use walrus::{FunctionBuilder, ModuleConfig, ValType};
+
+    // The imported function shifts the indices of the module's own functions.
+    let mut module = walrus::Module::with_config(ModuleConfig::new());
+    let noop_ty = module.types.add(&[], &[]);
+    module.add_import_func("host", "noop", noop_ty);
+
+    let linked =
+        link_bytes_into(&mut module, &generate_stdlib()).expect("Failed to link wasm-stdlib");
+    let add_id = linked.function("add_int128").expect("Failed to get fn");
+    assert_eq!(Some(add_id), linked.function("add-int128"));
+    assert_eq!(None, linked.function("sub-int128"));
+
+    let mut test = FunctionBuilder::new(
+        &mut module.types,
+        &[],
+        &[ValType::I32, ValType::I64, ValType::I64],
+    );
+    test.func_body()
+        .i64_const(5)
+        .i64_const(7)
+        .i64_const(3)
+        .i64_const(0)
+        .call(add_id);
+    let test_id = test.finish(vec![], &mut module.funcs);
+    module.exports.add("test", test_id);
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, module.emit_wasm()).expect("Failed to compile module");
+    let mut store = Store::new(&engine, ());
+    let mut linker = Linker::new(&engine);
+    linker
+        .func_wrap("host", "noop", || {})
+        .expect("Failed to define fn");
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .expect("Failed to instantiate module");
+
+    let func = instance
+        .get_typed_func::<(), (i32, i64, i64)>(&mut store, "test")
+        .expect("Failed to get fn");
+    // double(5) + 42 (from the data segment) + 3 (counted in a loop), and the high
+    // bits of `a` as those of `b` are zero.
+    assert_eq!(
+        (0, 55, 7),
+        func.call(&mut store, ()).expect("Failed to call fn")
+    );
+
+    assert!(instance.get_memory(&mut store, "memory").is_some());
+    assert!(instance.get_global(&mut store, "panic-info").is_some());
+}
+
+#[test]
+fn test_link_into_module_with_memory() {
+    let mut module = walrus::Module::with_config(walrus::ModuleConfig::new());
+    module.memories.add_local(false, 1, None);
+
+    assert!(matches!(
+        link_bytes_into(&mut module, &generate_stdlib()),
+        Err(StdlibLinkError::Unsupported(_))
+    ));
+}
+
+/// Helper function. Generates a stand-in for the `wasm-stdlib` module, exporting an
+/// `add-int128` function which uses each kind of item that can be linked: a memory
+/// with a data segment, a global, a table of functions and nested blocks.
+fn generate_stdlib() -> Vec<u8> {
+    use walrus::{
+        ir::{BinaryOp, LoadKind, MemArg, UnaryOp, Value},
+        ActiveData, ActiveDataLocation, DataKind, ElementKind, ExportItem, FunctionBuilder,
+        InitExpr, ModuleConfig, ValType,
+    };
+
+    let mut module = walrus::Module::with_config(ModuleConfig::new());
+    let memory_id = module.memories.add_local(false, 1, None);
+    module.exports.add("memory", ExportItem::Memory(memory_id));
+    module.data.add(
+        DataKind::Active(ActiveData {
+            memory: memory_id,
+            location: ActiveDataLocation::Absolute(16),
+        }),
+        vec![42, 0, 0, 0],
+    );
+    let panic_info_id =
+        module
+            .globals
+            .add_local(ValType::I32, true, InitExpr::Value(Value::I32(1024)));
+    module
+        .exports
+        .add("panic-info", ExportItem::Global(panic_info_id));
+
+    // `double(x)`, which is only called indirectly.
+    let mut double = FunctionBuilder::new(&mut module.types, &[ValType::I64], &[ValType::I64]);
+    let x = module.locals.add(ValType::I64);
+    double
+        .func_body()
+        .local_get(x)
+        .local_get(x)
+        .binop(BinaryOp::I64Add);
+    let double_id = double.finish(vec![x], &mut module.funcs);
+    let double_ty = module.types.add(&[ValType::I64], &[ValType::I64]);
+    let table_id = module.tables.add_local(1, Some(1), ValType::Funcref);
+    module.elements.add(
+        ElementKind::Active {
+            table: table_id,
+            offset: InitExpr::Value(Value::I32(0)),
+        },
+        ValType::Funcref,
+        vec![Some(double_id)],
+    );
+
+    // `add-int128` returns `double(a_lo) + 42 + b_lo` as the low bits and either `a_hi`
+    // or `panic-info` (if `b_hi` is non-zero) as the high bits.
+    let mut add = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I64, ValType::I64, ValType::I64, ValType::I64],
+        &[ValType::I32, ValType::I64, ValType::I64],
+    );
+    let a_lo = module.locals.add(ValType::I64);
+    let a_hi = module.locals.add(ValType::I64);
+    let b_lo = module.locals.add(ValType::I64);
+    let b_hi = module.locals.add(ValType::I64);
+    let sum = module.locals.add(ValType::I64);
+    let mut body = add.func_body();
+    body.local_get(a_lo)
+        .i32_const(0)
+        .call_indirect(double_ty, table_id)
+        .i32_const(16)
+        .load(
+            memory_id,
+            LoadKind::I32 { atomic: false },
+            MemArg {
+                align: 4,
+                offset: 0,
+            },
+        )
+        .unop(UnaryOp::I64ExtendUI32)
+        .binop(BinaryOp::I64Add)
+        .local_set(sum);
+    body.block(None, |block| {
+        let block_id = block.id();
+        block.loop_(None, |loop_| {
+            let loop_id = loop_.id();
+            loop_
+                .local_get(b_lo)
+                .unop(UnaryOp::I64Eqz)
+                .br_if(block_id)
+                .local_get(sum)
+                .i64_const(1)
+                .binop(BinaryOp::I64Add)
+                .local_set(sum)
+                .local_get(b_lo)
+                .i64_const(1)
+                .binop(BinaryOp::I64Sub)
+                .local_set(b_lo)
+                .br(loop_id);
+        });
+    });
+    body.i32_const(0)
+        .local_get(sum)
+        .local_get(b_hi)
+        .i64_const(0)
+        .binop(BinaryOp::I64Ne)
+        .if_else(
+            ValType::I64,
+            |then| {
+                then.global_get(panic_info_id).unop(UnaryOp::I64ExtendUI32);
+            },
+            |else_| {
+                else_.local_get(a_hi);
+            },
+        );
+    let add_id = add.finish(vec![a_lo, a_hi, b_lo, b_hi], &mut module.funcs);
+    module.exports.add("add-int128", add_id);
+
+    module.emit_wasm()
+}
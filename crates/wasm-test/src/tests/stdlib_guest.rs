@@ -0,0 +1,310 @@
+use clarity::vm::{
+    functions::arithmetic::{native_div, native_mod},
+    Value,
+};
+use primitive_types::{U256, U512};
+use test_case::test_case;
+use wasmtime::{Engine, Instance, Linker, Module, Store};
+
+use crate::stdlib::{StdlibExports, STDLIB_MODULE_NAME, STDLIB_WASM};
+
+/// The error code returned by the guest for a division by zero.
+const ERR_DIVISION_BY_ZERO: i32 = 12;
+
+/// Instantiates the embedded `wasm-stdlib` module.
+fn instantiate_stdlib() -> (Instance, Store<()>) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, STDLIB_WASM).expect("Failed to compile wasm-stdlib");
+
+    let mut store = Store::new(&engine, ());
+    let instance =
+        Instance::new(&mut store, &module, &[]).expect("Failed to instantiate wasm-stdlib");
+    (instance, store)
+}
+
+/// Calls the given signed 128-bit arithmetic function of the guest, returning its
+/// error code and result.
+fn call_int128_op(
+    instance: &Instance,
+    store: &mut Store<()>,
+    name: &str,
+    a: i128,
+    b: i128,
+) -> (i32, i128) {
+    let func = instance
+        .get_typed_func::<(i64, i64, i64, i64), (i32, i64, i64)>(&mut *store, name)
+        .expect("Failed to get fn");
+
+    let (err, lo, hi) = func
+        .call(
+            &mut *store,
+            (a as i64, (a >> 64) as i64, b as i64, (b >> 64) as i64),
+        )
+        .expect("Failed to call fn");
+    (err, ((lo as u64) as i128) | ((hi as i128) << 64))
+}
+
+/// Asserts that the guest's result for `a` and `b` matches that of evaluating the
+/// Clarity function. Both must fail on the same operands, and division by zero must
+/// be reported as such.
+fn assert_conforms(
+    instance: &Instance,
+    store: &mut Store<()>,
+    name: &str,
+    clarity_op: fn(Value, Value) -> clarity::vm::errors::InterpreterResult<Value>,
+    a: i128,
+    b: i128,
+) {
+    let (err, result) = call_int128_op(instance, store, name, a, b);
+    match clarity_op(Value::Int(a), Value::Int(b)) {
+        Ok(expected) => {
+            assert_eq!((0, expected), (err, Value::Int(result)), "{name} {a} {b}")
+        }
+        Err(_) => {
+            assert_ne!(0, err, "{name} {a} {b}");
+            if b == 0 {
+                assert_eq!(ERR_DIVISION_BY_ZERO, err, "{name} {a} {b}");
+            }
+        }
+    }
+}
+
+fn clarity_div(a: Value, b: Value) -> clarity::vm::errors::InterpreterResult<Value> {
+    native_div(vec![a, b])
+}
+
+#[test_case(7, 2 ; "positive")]
+#[test_case(-7, 2 ; "negative dividend")]
+#[test_case(7, -2 ; "negative divisor")]
+#[test_case(-7, -2 ; "negative operands")]
+#[test_case(7, 0 ; "division by zero")]
+#[test_case(i128::MIN, -1 ; "min by minus one")]
+#[test_case(i128::MIN, 1 ; "min by one")]
+#[test_case(i128::MAX, -1 ; "max by minus one")]
+#[test_case(i128::MIN, i128::MAX ; "min by max")]
+fn test_int128_division_conforms_to_clarity(a: i128, b: i128) {
+    let (instance, mut store) = instantiate_stdlib();
+    assert_conforms(&instance, &mut store, "div-int128", clarity_div, a, b);
+    assert_conforms(&instance, &mut store, "mod-int128", native_mod, a, b);
+}
+
+#[cfg(feature = "testing")]
+proptest::proptest! {
+    #[test]
+    fn test_int128_division_conforms_to_clarity_randomized(
+        a in proptest::prelude::any::<i128>(),
+        b in proptest::prop_oneof![
+            proptest::prelude::any::<i128>(),
+            -1_000i128..1_000,
+        ],
+    ) {
+        let (instance, mut store) = instantiate_stdlib();
+        assert_conforms(&instance, &mut store, "div-int128", clarity_div, a, b);
+        assert_conforms(&instance, &mut store, "mod-int128", native_mod, a, b);
+    }
+}
+
+#[test_case(2, 10, (0, 1024) ; "positive")]
+#[test_case(-2, 3, (0, -8) ; "negative base")]
+#[test_case(7, 0, (0, 1) ; "zero exponent")]
+#[test_case(2, 127, (6, 0) ; "overflow")]
+#[test_case(-3, 81, (11, 0) ; "underflow")]
+#[test_case(2, -1, (21, 0) ; "negative exponent")]
+#[test_case(1, 1 << 32, (21, 0) ; "exponent out of range")]
+fn test_pow_int128(a: i128, b: i128, expected: (i32, i128)) {
+    let (instance, mut store) = instantiate_stdlib();
+    assert_eq!(
+        expected,
+        call_int128_op(&instance, &mut store, "pow-int128", a, b)
+    );
+}
+
+/// Calls the given 256-bit addition or subtraction of the guest, returning its carry
+/// (or borrow) and result.
+fn call_uint256_op(
+    instance: &Instance,
+    store: &mut Store<()>,
+    name: &str,
+    a: U256,
+    b: U256,
+) -> (i32, U256) {
+    let func = instance
+        .get_typed_func::<(i64, i64, i64, i64, i64, i64, i64, i64), (i32, i64, i64, i64, i64)>(
+            &mut *store,
+            name,
+        )
+        .expect("Failed to get fn");
+
+    let (carry, r0, r1, r2, r3) = func
+        .call(
+            &mut *store,
+            (
+                a.0[0] as i64,
+                a.0[1] as i64,
+                a.0[2] as i64,
+                a.0[3] as i64,
+                b.0[0] as i64,
+                b.0[1] as i64,
+                b.0[2] as i64,
+                b.0[3] as i64,
+            ),
+        )
+        .expect("Failed to call fn");
+    (carry, U256([r0 as u64, r1 as u64, r2 as u64, r3 as u64]))
+}
+
+/// Calls the given wide multiplication of 128-bit operands of the guest.
+fn call_mul_wide_128(
+    instance: &Instance,
+    store: &mut Store<()>,
+    name: &str,
+    a: u128,
+    b: u128,
+) -> U256 {
+    let func = instance
+        .get_typed_func::<(i64, i64, i64, i64), (i64, i64, i64, i64)>(&mut *store, name)
+        .expect("Failed to get fn");
+
+    let (p0, p1, p2, p3) = func
+        .call(
+            &mut *store,
+            (a as i64, (a >> 64) as i64, b as i64, (b >> 64) as i64),
+        )
+        .expect("Failed to call fn");
+    U256([p0 as u64, p1 as u64, p2 as u64, p3 as u64])
+}
+
+/// Calls the given wide multiplication of 256-bit operands of the guest.
+fn call_mul_wide_256(
+    instance: &Instance,
+    store: &mut Store<()>,
+    name: &str,
+    a: U256,
+    b: U256,
+) -> U512 {
+    let func = instance
+        .get_typed_func::<
+            (i64, i64, i64, i64, i64, i64, i64, i64),
+            (i64, i64, i64, i64, i64, i64, i64, i64),
+        >(&mut *store, name)
+        .expect("Failed to get fn");
+
+    let (p0, p1, p2, p3, p4, p5, p6, p7) = func
+        .call(
+            &mut *store,
+            (
+                a.0[0] as i64,
+                a.0[1] as i64,
+                a.0[2] as i64,
+                a.0[3] as i64,
+                b.0[0] as i64,
+                b.0[1] as i64,
+                b.0[2] as i64,
+                b.0[3] as i64,
+            ),
+        )
+        .expect("Failed to call fn");
+    U512([p0, p1, p2, p3, p4, p5, p6, p7].map(|limb| limb as u64))
+}
+
+/// Computes the full product of `a` and `b` as signed (two's complement) integers,
+/// as `primitive-types` only has unsigned ones.
+fn signed_full_mul(a: U256, b: U256) -> U512 {
+    let abs = |value: U256| {
+        if value.bit(255) {
+            (!value).overflowing_add(U256::one()).0
+        } else {
+            value
+        }
+    };
+
+    let product = abs(a).full_mul(abs(b));
+    if a.bit(255) != b.bit(255) {
+        (!product).overflowing_add(U512::one()).0
+    } else {
+        product
+    }
+}
+
+/// Asserts that the guest's 256-bit building blocks match `primitive-types` for `a`
+/// and `b`, and its 128-bit wide multiplications for their low 128 bits.
+fn assert_wide_arithmetic_conforms(instance: &Instance, store: &mut Store<()>, a: U256, b: U256) {
+    let (sum, carry) = a.overflowing_add(b);
+    assert_eq!(
+        (carry as i32, sum),
+        call_uint256_op(instance, store, "add-uint256", a, b),
+        "{a} + {b}"
+    );
+    let (diff, borrow) = a.overflowing_sub(b);
+    assert_eq!(
+        (borrow as i32, diff),
+        call_uint256_op(instance, store, "sub-uint256", a, b),
+        "{a} - {b}"
+    );
+    assert_eq!(
+        a.full_mul(b),
+        call_mul_wide_256(instance, store, "mul-wide-uint256", a, b),
+        "{a} * {b}"
+    );
+    assert_eq!(
+        signed_full_mul(a, b),
+        call_mul_wide_256(instance, store, "mul-wide-int256", a, b),
+        "{a} * {b} (signed)"
+    );
+
+    let (a, b) = (a.low_u128(), b.low_u128());
+    assert_eq!(
+        U256::from(a) * U256::from(b),
+        call_mul_wide_128(instance, store, "mul-wide-uint128", a, b),
+        "{a} * {b}"
+    );
+    let (a, b) = (a as i128, b as i128);
+    let product = U256::from(a.unsigned_abs()) * U256::from(b.unsigned_abs());
+    let expected = if (a < 0) != (b < 0) {
+        (!product).overflowing_add(U256::one()).0
+    } else {
+        product
+    };
+    assert_eq!(
+        expected,
+        call_mul_wide_128(instance, store, "mul-wide-int128", a as u128, b as u128),
+        "{a} * {b} (signed)"
+    );
+}
+
+#[test_case(U256::zero(), U256::zero() ; "zeros")]
+#[test_case(U256::MAX, U256::one() ; "carry")]
+#[test_case(U256::zero(), U256::one() ; "borrow")]
+#[test_case(U256::MAX, U256::MAX ; "max")]
+#[test_case(U256::one() << 255, U256::MAX ; "min by minus one")]
+#[test_case(U256::one() << 255, U256::one() << 255 ; "min by min")]
+#[test_case(U256::from(u128::MAX), U256::from(i128::MIN as u128) ; "128-bit limits")]
+fn test_wide_arithmetic_conforms(a: U256, b: U256) {
+    let (instance, mut store) = instantiate_stdlib();
+    assert_wide_arithmetic_conforms(&instance, &mut store, a, b);
+}
+
+#[cfg(feature = "testing")]
+proptest::proptest! {
+    #[test]
+    fn test_wide_arithmetic_conforms_randomized(
+        a in proptest::prelude::any::<[u64; 4]>(),
+        b in proptest::prelude::any::<[u64; 4]>(),
+    ) {
+        let (instance, mut store) = instantiate_stdlib();
+        assert_wide_arithmetic_conforms(&instance, &mut store, U256(a), U256(b));
+    }
+}
+
+#[test]
+fn test_stdlib_exports_link() {
+    let (instance, mut store) = instantiate_stdlib();
+    let mut linker = Linker::new(store.engine());
+    StdlibExports::new()
+        .link(&mut linker, &mut store, &instance)
+        .expect("Failed to link wasm-stdlib");
+
+    for name in ["add-int128", "add_int128", "alloc"] {
+        assert!(linker.get(&mut store, STDLIB_MODULE_NAME, name).is_some());
+    }
+}